@@ -0,0 +1,167 @@
+//! Send/receive path benchmarks on python-can's virtual bus.
+//!
+//! `cargo bench --bench send_recv` prints results. With
+//! `-- --save-baseline <file>` results are written to `<file>`; with
+//! `-- --baseline <file>` they're compared against it and the run fails if
+//! anything regressed by more than 10%.
+
+use std::{
+    collections::BTreeMap,
+    sync::mpsc::channel,
+    time::{Duration, Instant},
+};
+
+use pycanrs::{CanId, PyCanBusType, PyCanInterface, PyCanMessage};
+use pyo3::{types::IntoPyDict, IntoPy, Python};
+
+const ITERATIONS: u32 = 2000;
+const REGRESSION_THRESHOLD: f64 = 1.10;
+
+fn open(channel: &str) -> PyCanInterface {
+    PyCanInterface::new(PyCanBusType::Virtual {
+        channel: channel.into(),
+    })
+    .expect("python-can virtual bus should open")
+}
+
+/// Time for one `send()`.
+fn send_throughput() -> Duration {
+    let tx = open("bench-send");
+    let id = CanId::Standard(0x123);
+
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        tx.send(id, &i.to_le_bytes());
+    }
+    start.elapsed() / ITERATIONS
+}
+
+/// Time from `send()` on one interface to the rx callback on another.
+fn callback_latency() -> Duration {
+    let tx = open("bench-latency");
+    let rx = open("bench-latency");
+    let (seen_tx, seen) = channel();
+
+    rx.register_rx_callback(move |_| seen_tx.send(Instant::now()).unwrap(), |_| {})
+        .unwrap();
+
+    let mut total = Duration::ZERO;
+    for i in 0..ITERATIONS {
+        let sent = Instant::now();
+        tx.send(CanId::Standard(0x123), &i.to_le_bytes());
+        total += seen.recv().unwrap() - sent;
+    }
+    total / ITERATIONS
+}
+
+/// Per-frame time to deliver a burst of frames to `callbacks` rx
+/// callbacks, as on a busy bus with several consumers.
+fn burst_delivery(batched: bool, callbacks: usize) -> Duration {
+    let bus = format!(
+        "bench-burst-{callbacks}{}",
+        if batched { "-batched" } else { "" }
+    );
+    let tx = open(&bus);
+    let rx = PyCanInterface::builder(PyCanBusType::Virtual {
+        channel: bus.clone(),
+    })
+    .batched_delivery(batched)
+    .build()
+    .unwrap();
+
+    let (seen_tx, seen) = channel();
+    for _ in 0..callbacks {
+        let seen_tx = seen_tx.clone();
+        rx.register_rx_callback(move |_| seen_tx.send(()).unwrap(), |_| {})
+            .unwrap();
+    }
+
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        tx.send(CanId::Standard(0x123), &i.to_le_bytes());
+    }
+    for _ in 0..ITERATIONS as usize * callbacks {
+        seen.recv().unwrap();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+/// Time to extract a PyCanMessage from a python-can Message.
+fn extraction() -> Duration {
+    Python::with_gil(|py| {
+        let can = py.import("can").unwrap();
+        let kwargs = [
+            ("arbitration_id", 0x123.into_py(py)),
+            ("data", vec![1u8, 2, 3, 4, 5, 6, 7, 8].into_py(py)),
+        ]
+        .into_py_dict(py);
+        let msg = can.call_method("Message", (), Some(kwargs)).unwrap();
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            let _: PyCanMessage = msg.extract().unwrap();
+        }
+        start.elapsed() / ITERATIONS
+    })
+}
+
+fn parse_results(s: &str) -> BTreeMap<String, f64> {
+    s.lines()
+        .filter_map(|l| l.split_once('='))
+        .filter_map(|(k, v)| Some((k.trim().into(), v.trim().parse().ok()?)))
+        .collect()
+}
+
+fn main() {
+    let results: BTreeMap<String, f64> = [
+        ("send_ns", send_throughput()),
+        ("callback_latency_ns", callback_latency()),
+        ("burst_delivery_ns", burst_delivery(false, 1)),
+        ("burst_delivery_batched_ns", burst_delivery(true, 1)),
+        ("busy_delivery_ns", burst_delivery(false, 4)),
+        ("busy_delivery_batched_ns", burst_delivery(true, 4)),
+        ("extraction_ns", extraction()),
+    ]
+    .into_iter()
+    .map(|(k, d)| (k.to_string(), d.as_nanos() as f64))
+    .collect();
+
+    for (name, ns) in &results {
+        println!("{name:<24} {ns:>12.0} ns/iter");
+    }
+    for name in ["burst_delivery", "busy_delivery"] {
+        let speedup = results[&format!("{name}_ns")] / results[&format!("{name}_batched_ns")];
+        println!("{name:<24} {speedup:>12.2}x batched");
+    }
+
+    let args: Vec<String> = std::env::args().collect();
+    let flag = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    if let Some(path) = flag("--save-baseline") {
+        let out: String = results.iter().map(|(k, v)| format!("{k}={v}\n")).collect();
+        std::fs::write(path, out).expect("should be able to write baseline");
+    }
+
+    if let Some(path) = flag("--baseline") {
+        let baseline =
+            parse_results(&std::fs::read_to_string(path).expect("baseline should exist"));
+        let mut regressed = false;
+
+        for (name, ns) in &results {
+            if let Some(base) = baseline.get(name) {
+                let ratio = ns / base;
+                println!("{name:<24} {:>+11.1}% vs baseline", (ratio - 1.0) * 100.0);
+                regressed |= ratio > REGRESSION_THRESHOLD;
+            }
+        }
+
+        if regressed {
+            eprintln!("performance regressed by more than 10%");
+            std::process::exit(1);
+        }
+    }
+}
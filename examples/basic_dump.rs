@@ -1,4 +1,7 @@
-use std::time::Duration;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
@@ -25,12 +28,22 @@ struct Args {
     /// Useful for `cantools decode`.
     #[clap(short)]
     compat: bool,
+    /// Print one JSON object per frame, for piping into `jq`.
+    #[clap(long)]
+    json: bool,
+    /// Also send frames read from stdin, one per line as candump text or
+    /// JSON, and exit once stdin ends.
+    #[clap(long)]
+    stdin: bool,
+    /// With `--stdin`, send frames as far apart as their timestamps say.
+    #[clap(long, requires = "stdin")]
+    paced: bool,
 }
 
 pub fn main() -> Result<()> {
     let args = Args::parse();
 
-    let can = PyCanInterface::new(match &args.bus {
+    let bustype = match &args.bus {
         Bus::Slcan {
             serial_port,
             bitrate,
@@ -47,7 +60,7 @@ pub fn main() -> Result<()> {
             host: host.clone(),
             port: *port,
         },
-    })?;
+    };
 
     let iface_name = if args.compat {
         match args.bus {
@@ -65,29 +78,41 @@ pub fn main() -> Result<()> {
         }
     };
 
-    let cb = move |msg: &PyCanMessage| {
-        let mut data = String::new();
-        for byte in msg.data.as_ref().unwrap() {
-            data += &format!("{:02X} ", byte);
-        }
-        let data = data.trim();
-
-        println!(
-            "  {iface_name}  {:08X}   [{}]  {data}",
-            msg.arbitration_id,
-            msg.dlc.unwrap()
-        );
-    };
+    let can = Arc::new(PyCanInterface::builder(bustype).name(iface_name).build()?);
 
     let err_cb = |err: &_| {
         eprintln!("{err}");
         std::process::exit(-1);
     };
-    can.register_rx_callback(cb, err_cb)?;
+    if args.json {
+        let out = Mutex::new(NdjsonWriter::stdout());
+        let cb = move |msg: &PyCanMessage| {
+            if out.lock().unwrap().write(msg).is_err() {
+                // e.g. the other end of the pipe went away
+                std::process::exit(0);
+            }
+        };
+        can.register_rx_callback(cb, err_cb)?;
+    } else {
+        let fmt = FrameFormatter::new(FrameStyle::Candump)
+            .color(!args.compat && FrameFormatter::stdout_is_color());
+        let cb = move |msg: &PyCanMessage| println!("{}", fmt.format(msg));
+        can.register_rx_callback(cb, err_cb)?;
+    }
 
     // handle Ctrl-c
     ctrlc::set_handler(|| std::process::exit(0))?;
 
+    if args.stdin {
+        let frames = TextReader::stdin();
+        if args.paced {
+            frames.paced().pump(can)?;
+        } else {
+            frames.pump(can)?;
+        }
+        return Ok(());
+    }
+
     loop {
         std::thread::sleep(Duration::from_secs(1));
     }
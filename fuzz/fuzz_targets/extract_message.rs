@@ -0,0 +1,78 @@
+//! Arbitrary Message-like Python objects through the extraction path.
+//! Extraction may fail, but must never panic.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use pyo3::{PyObject, Python, ToPyObject};
+use pycanrs::PyCanMessage;
+
+/// Attributes python-can messages have had across releases.
+const ATTRS: &[&str] = &[
+    "arbitration_id",
+    "data",
+    "dlc",
+    "is_fd",
+    "is_extended_id",
+    "extended_id",
+    "id_type",
+    "is_error_frame",
+    "timestamp",
+    "is_remote_frame",
+    "bitrate_switch",
+    "error_state_indicator",
+    "is_rx",
+];
+
+#[derive(Arbitrary, Debug)]
+enum Value {
+    None,
+    Bool(bool),
+    Int(i64),
+    BigInt(i128),
+    Float(f64),
+    Bytes(Vec<u8>),
+    ByteList(Vec<i16>),
+    Str(String),
+}
+
+impl Value {
+    fn to_object(&self, py: Python) -> PyObject {
+        match self {
+            Self::None => py.None(),
+            Self::Bool(v) => v.to_object(py),
+            Self::Int(v) => v.to_object(py),
+            Self::BigInt(v) => v.to_object(py),
+            Self::Float(v) => v.to_object(py),
+            Self::Bytes(v) => pyo3::types::PyBytes::new(py, v).to_object(py),
+            Self::ByteList(v) => v.to_object(py),
+            Self::Str(v) => v.to_object(py),
+        }
+    }
+}
+
+/// Which attributes are set, and to what; unset ones are missing entirely.
+#[derive(Arbitrary, Debug)]
+struct Message(Vec<(u8, Value)>);
+
+fuzz_target!(|input: Message| {
+    Python::with_gil(|py| {
+        let Ok(msg) = py
+            .import("types")
+            .and_then(|types| types.getattr("SimpleNamespace")?.call0())
+        else {
+            return;
+        };
+
+        for (attr, value) in &input.0 {
+            let name = ATTRS[usize::from(*attr) % ATTRS.len()];
+            let _ = msg.setattr(name, value.to_object(py));
+        }
+
+        if let Ok(msg) = msg.extract::<PyCanMessage>() {
+            let _ = msg.to_string();
+            let _ = msg.data_length();
+        }
+    });
+});
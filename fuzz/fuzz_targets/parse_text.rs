@@ -0,0 +1,13 @@
+//! Arbitrary text through every parser that takes user or adapter input.
+//! Parsing may fail, but must never panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pycanrs::{parse_hex, CanId, PyCanBusType};
+
+fuzz_target!(|input: &str| {
+    let _ = parse_hex(input);
+    let _ = input.parse::<CanId>();
+    let _ = input.parse::<PyCanBusType>();
+});
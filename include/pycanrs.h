@@ -0,0 +1,78 @@
+/*
+ * C API for pycanrs. Mirrors src/capi.rs; keep the two in step.
+ *
+ * Build the library with the `capi` feature, e.g.:
+ *
+ *     cargo rustc --release --lib --features capi --crate-type cdylib
+ *
+ * The library embeds Python, so python-can must be importable at runtime.
+ *
+ * Functions report failure with a NULL or negative return, after which
+ * pycanrs_last_error() describes what went wrong.
+ */
+
+#ifndef PYCANRS_H
+#define PYCANRS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An open bus. */
+typedef struct PycanrsBus PycanrsBus;
+
+/* A received frame, as passed to callbacks. */
+typedef struct PycanrsFrame {
+    uint32_t id;
+    bool extended;
+    bool is_rx;
+    bool is_error_frame;
+    bool is_remote_frame;
+    bool is_fd;
+    /* Payload length in bytes. Only the first `len` bytes of `data` are
+     * valid. */
+    uint8_t len;
+    uint8_t data[64];
+    /* Seconds, from the backend's clock. NaN if the backend gave none. */
+    double timestamp;
+} PycanrsFrame;
+
+/* Called on the notifier thread. `frame` is only valid during the call. */
+typedef void (*PycanrsRxCallback)(const PycanrsFrame *frame, void *user_data);
+
+/* Describes the last error on the calling thread. Valid until the next
+ * call into the API on that thread. Never NULL. */
+const char *pycanrs_last_error(void);
+
+/* Open the bus described by a URI, e.g. "socketcan://can0". Returns NULL
+ * on failure. */
+PycanrsBus *pycanrs_open(const char *bus);
+
+/* Send a frame, blocking until the backend accepts it. Returns 0 on
+ * success, -1 on failure. */
+int pycanrs_send(const PycanrsBus *bus, uint32_t id, bool extended,
+                 const uint8_t *data, size_t len);
+
+/* Call `callback` with every received frame, error frames included,
+ * passing `user_data` through. Returns a handle for
+ * pycanrs_remove_callback(), or -1 on failure. */
+int64_t pycanrs_register_callback(const PycanrsBus *bus,
+                                  PycanrsRxCallback callback,
+                                  void *user_data);
+
+/* Stop calling a callback. Must not be called from within a callback.
+ * Returns 0 on success, -1 if `handle` isn't registered on `bus`. */
+int pycanrs_remove_callback(const PycanrsBus *bus, int64_t handle);
+
+/* Close a bus, stopping its callbacks. NULL is ignored. */
+void pycanrs_close(PycanrsBus *bus);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PYCANRS_H */
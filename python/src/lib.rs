@@ -0,0 +1,10 @@
+//! The `pycanrs` Python extension module. See `src/python.rs` in the
+//! parent crate.
+
+use pyo3::prelude::*;
+
+#[pymodule]
+#[pyo3(name = "pycanrs")]
+fn pycanrs_python(py: Python, m: &PyModule) -> PyResult<()> {
+    pycanrs::python::init(py, m)
+}
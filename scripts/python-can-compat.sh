@@ -0,0 +1,23 @@
+#!/bin/sh
+# Run tests/python_can_compat.rs against each pinned python-can release,
+# each installed into its own virtualenv under target/.
+#
+#   scripts/python-can-compat.sh            # every pinned release
+#   scripts/python-can-compat.sh 4.3.1      # just these
+
+set -eu
+
+[ $# -gt 0 ] || set -- 3.3.4 4.0.0 4.3.1
+
+cd "$(dirname "$0")/.."
+for version in "$@"; do
+    venv="target/python-can-$version"
+    if [ ! -x "$venv/bin/python" ]; then
+        python3 -m venv "$venv"
+        "$venv/bin/python" -m pip install --quiet "python-can==$version"
+    fi
+    site=$("$venv/bin/python" -c "import sysconfig; print(sysconfig.get_paths()['purelib'])")
+
+    echo "python-can $version"
+    PYTHONPATH="$site" cargo test --quiet --test python_can_compat
+done
@@ -0,0 +1,249 @@
+//! Learning a bus's normal traffic and flagging deviations from it.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{CanId, EventHub, ListenerId, PyCanError, PyCanEvent, PyCanInterface, PyCanMessage};
+
+/// A deviation from the learned baseline, reported as
+/// [`PyCanEvent::Anomaly`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Anomaly {
+    /// An ID that wasn't seen while learning. Reported once per ID.
+    NewId { id: CanId },
+    /// The ID's smoothed period moved more than the tolerance away from
+    /// its baseline. Reported again only after the period has come back.
+    PeriodChanged {
+        id: CanId,
+        baseline: Duration,
+        observed: Duration,
+    },
+    /// A payload length that wasn't seen on this ID while learning.
+    LengthChanged { id: CanId, len: usize },
+}
+
+#[derive(Clone, Debug)]
+pub struct AnomalyConfig {
+    /// How long to learn the baseline for. One too long to time means
+    /// learning never ends.
+    pub learn: Duration,
+    /// Relative period change reported, e.g. 0.2 for 20%.
+    pub period_tolerance: f64,
+    /// Weight of each new interval in the smoothed period.
+    pub smoothing: f64,
+    /// IDs seen fewer times than this while learning get no period
+    /// baseline, since one or two intervals say little.
+    pub min_samples: u64,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            learn: Duration::from_secs(10),
+            period_tolerance: 0.2,
+            smoothing: 0.1,
+            min_samples: 5,
+        }
+    }
+}
+
+#[derive(Default)]
+struct IdState {
+    frames: u64,
+    last: Option<f64>,
+    /// Sum and count of intervals while learning, for the baseline mean.
+    learned_total: f64,
+    learned_intervals: u64,
+    baseline: Option<f64>,
+    lengths: BTreeSet<usize>,
+    smoothed: Option<f64>,
+    deviating: bool,
+}
+
+struct Monitor {
+    config: AnomalyConfig,
+    events: EventHub,
+    learn_start: Instant,
+    /// None if learning doesn't end.
+    learn_until: Option<Instant>,
+    learning: bool,
+    ids: HashMap<CanId, IdState>,
+    anomalies: u64,
+}
+
+impl Monitor {
+    fn observe(&mut self, msg: &PyCanMessage, now: Instant) {
+        if self.learning && self.learn_until.is_some_and(|until| now >= until) {
+            self.finish_learning();
+        }
+
+        // Backend timestamps where there are some, for accurate periods.
+        // Frames with a non-finite one don't count towards periods.
+        let time = match msg.timestamp {
+            Some(time) => Some(time).filter(|t| t.is_finite()),
+            None => Some((now - self.learn_start).as_secs_f64()),
+        };
+        let len = msg.data.as_ref().map_or(0, Vec::len);
+        let id = msg.arbitration_id;
+
+        let mut found = Vec::new();
+        let learning = self.learning;
+        let config = &self.config;
+        let state = self.ids.entry(id).or_insert_with(|| {
+            if !learning {
+                found.push(Anomaly::NewId { id });
+            }
+            IdState::default()
+        });
+
+        let interval = time.and_then(|time| {
+            let last = state.last.replace(time)?;
+            Some((time - last).max(0.0)).filter(|i| i.is_finite())
+        });
+        state.frames += 1;
+
+        if learning {
+            state.lengths.insert(len);
+            if let Some(interval) = interval {
+                state.learned_total += interval;
+                state.learned_intervals += 1;
+            }
+        } else {
+            if state.lengths.insert(len) && state.frames > 1 {
+                found.push(Anomaly::LengthChanged { id, len });
+            }
+
+            if let (Some(baseline), Some(interval)) = (state.baseline, interval) {
+                let smoothed = state
+                    .smoothed
+                    .map_or(baseline, |s| s + config.smoothing * (interval - s));
+                state.smoothed = Some(smoothed);
+
+                let deviating = (smoothed - baseline).abs() > baseline * config.period_tolerance;
+                if deviating && !state.deviating {
+                    found.push(Anomaly::PeriodChanged {
+                        id,
+                        baseline: secs(baseline),
+                        observed: secs(smoothed),
+                    });
+                }
+                state.deviating = deviating;
+            }
+        }
+
+        for anomaly in found {
+            self.anomalies += 1;
+            self.events.emit(PyCanEvent::Anomaly(anomaly));
+        }
+    }
+
+    fn finish_learning(&mut self) {
+        self.learning = false;
+        for state in self.ids.values_mut() {
+            if state.frames >= self.config.min_samples.max(2) && state.learned_intervals > 0 {
+                let mean = state.learned_total / state.learned_intervals as f64;
+                state.baseline = (mean > 0.0 && mean.is_finite()).then_some(mean);
+            }
+        }
+    }
+}
+
+/// Saturates periods too long for a Duration, or zero if NaN, as a
+/// degenerate smoothing factor can produce.
+fn secs(secs: f64) -> Duration {
+    if secs.is_nan() {
+        return Duration::ZERO;
+    }
+    Duration::try_from_secs_f64(secs.max(0.0)).unwrap_or(Duration::MAX)
+}
+
+/// Learns which IDs a bus carries, at what periods and with which payload
+/// lengths, then reports deviations as [`PyCanEvent::Anomaly`] events,
+/// e.g. for intrusion detection demos or catching regressions on a
+/// bench. Stops on drop.
+///
+/// Periods are checked as frames arrive, so an ID that stops altogether
+/// isn't reported.
+pub struct AnomalyMonitor {
+    iface: Arc<PyCanInterface>,
+    listener: ListenerId,
+    monitor: Arc<Mutex<Monitor>>,
+}
+
+impl AnomalyMonitor {
+    pub fn start(iface: &Arc<PyCanInterface>, config: AnomalyConfig) -> Result<Self, PyCanError> {
+        let learn_start = Instant::now();
+        let monitor = Arc::new(Mutex::new(Monitor {
+            learn_start,
+            learn_until: learn_start.checked_add(config.learn),
+            config,
+            events: iface.events.clone(),
+            learning: true,
+            ids: HashMap::new(),
+            anomalies: 0,
+        }));
+
+        let listener = {
+            let monitor = monitor.clone();
+            iface.register_rx_callback(
+                move |msg| {
+                    if msg.is_rx {
+                        monitor.lock().unwrap().observe(msg, Instant::now());
+                    }
+                },
+                |_| {},
+            )?
+        };
+
+        Ok(Self {
+            iface: iface.clone(),
+            listener,
+            monitor,
+        })
+    }
+
+    /// Whether the baseline is still being learned.
+    pub fn is_learning(&self) -> bool {
+        let monitor = self.monitor.lock().unwrap();
+        monitor.learning
+            && monitor
+                .learn_until
+                .is_none_or(|until| Instant::now() < until)
+    }
+
+    /// Anomalies reported so far.
+    pub fn anomalies(&self) -> u64 {
+        self.monitor.lock().unwrap().anomalies
+    }
+
+    /// IDs in the baseline, with their learned periods where they have
+    /// one.
+    pub fn baseline(&self) -> Vec<(CanId, Option<Duration>)> {
+        let monitor = self.monitor.lock().unwrap();
+        let mut ids: Vec<_> = monitor
+            .ids
+            .iter()
+            .map(|(id, s)| (*id, s.baseline.map(secs)))
+            .collect();
+        ids.sort_by_key(|(id, _)| *id);
+        ids
+    }
+
+    /// Forget the baseline and learn a new one for `config.learn`.
+    pub fn relearn(&self) {
+        let mut monitor = self.monitor.lock().unwrap();
+        monitor.learning = true;
+        monitor.learn_start = Instant::now();
+        monitor.learn_until = monitor.learn_start.checked_add(monitor.config.learn);
+        monitor.ids.clear();
+    }
+}
+
+impl Drop for AnomalyMonitor {
+    fn drop(&mut self) {
+        let _ = self.iface.remove_listener(self.listener);
+    }
+}
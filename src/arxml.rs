@@ -0,0 +1,251 @@
+//! AUTOSAR ARXML database loading, for the CAN communication extract.
+//!
+//! Frames are found through their CAN frame triggerings, and signals
+//! through the I-signal I-PDUs mapped into each frame. Linear and text
+//! table compu methods are supported. Multiplexed, container and secured
+//! PDUs, and signal groups, are skipped.
+
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use crate::{
+    decode::{read_xml, DatabaseError},
+    xml::Element,
+    ByteOrder, CanId, Decoder, MessageDef, Signal,
+};
+
+/// Elements by their absolute SHORT-NAME path, e.g. `/Pkg/Frames/Status`.
+struct Index<'a>(HashMap<String, &'a Element>);
+
+impl<'a> Index<'a> {
+    fn new(root: &'a Element) -> Self {
+        let mut index = Self(HashMap::new());
+        index.add(root, String::new());
+        index
+    }
+
+    fn add(&mut self, el: &'a Element, path: String) {
+        let path = match el.text_at(&["SHORT-NAME"]) {
+            Some(name) => {
+                let path = format!("{path}/{name}");
+                self.0.insert(path.clone(), el);
+                path
+            }
+            None => path,
+        };
+        for child in &el.children {
+            self.add(child, path.clone());
+        }
+    }
+
+    /// The element a `*-REF` child of `el` points to.
+    fn follow(&self, el: &Element, reference: &str) -> Option<&'a Element> {
+        self.0.get(el.text_at(&[reference])?).copied()
+    }
+
+    /// Follow the first `reference` found anywhere below `el`.
+    fn follow_nested(&self, el: &Element, reference: &str) -> Option<&'a Element> {
+        let mut refs = Vec::new();
+        el.descendants(reference, &mut refs);
+        self.0.get(refs.first()?.text.trim()).copied()
+    }
+}
+
+fn short_name(el: &Element) -> &str {
+    el.text_at(&["SHORT-NAME"]).unwrap_or_default()
+}
+
+fn description(el: &Element) -> Option<String> {
+    let mut texts = Vec::new();
+    el.child("DESC")?.descendants("L-2", &mut texts);
+    texts.first().map(|t| t.text.trim().into())
+}
+
+fn number<T: std::str::FromStr>(el: &Element, path: &[&str]) -> Option<T> {
+    el.text_at(path)?.parse().ok()
+}
+
+/// AUTOSAR integers may be written in hex.
+fn integer(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Apply a compu method's scaling and value table to `signal`.
+fn apply_compu_method(signal: &mut Signal, method: &Element, index: &Index) {
+    let scales = method
+        .find(&["COMPU-INTERNAL-TO-PHYS", "COMPU-SCALES"])
+        .into_iter()
+        .flat_map(|s| s.children("COMPU-SCALE"));
+
+    for scale in scales {
+        if let Some(coeffs) = scale.child("COMPU-RATIONAL-COEFFS") {
+            let numerator: Vec<f64> = coeffs
+                .find(&["COMPU-NUMERATOR"])
+                .into_iter()
+                .flat_map(|n| n.children("V"))
+                .filter_map(|v| v.text.trim().parse().ok())
+                .collect();
+            let denominator: f64 = number(coeffs, &["COMPU-DENOMINATOR", "V"]).unwrap_or(1.0);
+            if let [offset, factor, ..] = numerator[..] {
+                signal.offset = offset / denominator;
+                signal.factor = factor / denominator;
+            }
+        } else if let Some(label) = scale.text_at(&["COMPU-CONST", "VT"]) {
+            if let Some(value) = number(scale, &["LOWER-LIMIT"]) {
+                signal.values.insert(value, label.into());
+            }
+        }
+    }
+
+    if let Some(unit) = index.follow(method, "UNIT-REF") {
+        signal.unit = unit
+            .text_at(&["DISPLAY-NAME"])
+            .unwrap_or(short_name(unit))
+            .into();
+    }
+}
+
+/// An I-signal mapped into a PDU at `pdu_offset` bits into the frame.
+fn parse_signal(mapping: &Element, pdu_offset: u32, index: &Index) -> Option<Signal> {
+    let isignal = index.follow(mapping, "I-SIGNAL-REF")?;
+    let start: u32 = number(mapping, &["START-POSITION"])?;
+    let length = number(isignal, &["LENGTH"])?;
+    let byte_order = match mapping.text_at(&["PACKING-BYTE-ORDER"]) {
+        Some("MOST-SIGNIFICANT-BYTE-FIRST") => ByteOrder::BigEndian,
+        _ => ByteOrder::LittleEndian,
+    };
+
+    let mut signal = Signal {
+        byte_order,
+        comment: description(isignal),
+        ..Signal::new(short_name(isignal), pdu_offset.checked_add(start)?, length)
+    };
+
+    // Representation is on the I-signal, or else on its system signal
+    let system_signal = index.follow(isignal, "SYSTEM-SIGNAL-REF");
+    let compu_method = index
+        .follow_nested(isignal, "COMPU-METHOD-REF")
+        .or_else(|| index.follow_nested(system_signal?, "COMPU-METHOD-REF"));
+    if let Some(method) = compu_method {
+        apply_compu_method(&mut signal, method, index);
+    }
+
+    if let Some(base_type) = index.follow_nested(isignal, "BASE-TYPE-REF") {
+        signal.signed = base_type.text_at(&["BASE-TYPE-ENCODING"]) == Some("2C");
+    }
+    if signal.comment.is_none() {
+        signal.comment = system_signal.and_then(description);
+    }
+
+    Some(signal)
+}
+
+/// The period of a PDU's cyclic transmission mode, if it has one.
+fn pdu_cycle_time(pdu: &Element) -> Result<Option<Duration>, DatabaseError> {
+    let mut timings = Vec::new();
+    pdu.descendants("CYCLIC-TIMING", &mut timings);
+    let Some(seconds) = timings
+        .first()
+        .and_then(|timing| number::<f64>(timing, &["TIME-PERIOD", "VALUE"]))
+    else {
+        return Ok(None);
+    };
+    (seconds > 0.0)
+        .then(|| Duration::try_from_secs_f64(seconds))
+        .transpose()
+        .map_err(|_| {
+            DatabaseError::Invalid(format!(
+                "PDU {} has an out of range cycle time of {seconds:e} s",
+                short_name(pdu)
+            ))
+        })
+}
+
+fn parse_frame(triggering: &Element, index: &Index) -> Result<Option<MessageDef>, DatabaseError> {
+    let Some(frame) = index.follow(triggering, "FRAME-REF") else {
+        return Ok(None);
+    };
+    let name = short_name(frame);
+
+    let raw = triggering
+        .text_at(&["IDENTIFIER"])
+        .and_then(integer)
+        .ok_or_else(|| {
+            DatabaseError::Invalid(format!("frame {name} without a valid identifier"))
+        })?;
+    let extended = triggering.text_at(&["CAN-ADDRESSING-MODE"]) == Some("EXTENDED");
+    let id = CanId::new(raw as u32, extended)
+        .map_err(|e| DatabaseError::Invalid(format!("frame {name}: {e}")))?;
+
+    let mut signals = Vec::new();
+    let mut cycle_time = None;
+    let mappings = frame
+        .find(&["PDU-TO-FRAME-MAPPINGS"])
+        .into_iter()
+        .flat_map(|m| m.children("PDU-TO-FRAME-MAPPING"));
+    for mapping in mappings {
+        let Some(pdu) = index.follow(mapping, "PDU-REF") else {
+            continue;
+        };
+        if pdu.name != "I-SIGNAL-I-PDU" {
+            continue;
+        }
+        let pdu_offset = number(mapping, &["START-POSITION"]).unwrap_or(0);
+        if cycle_time.is_none() {
+            cycle_time = pdu_cycle_time(pdu)?;
+        }
+
+        let isignals = pdu
+            .find(&["I-SIGNAL-TO-PDU-MAPPINGS"])
+            .into_iter()
+            .flat_map(|m| m.children("I-SIGNAL-TO-I-PDU-MAPPING"));
+        signals.extend(isignals.filter_map(|m| parse_signal(m, pdu_offset, index)));
+    }
+
+    Ok(Some(MessageDef {
+        id,
+        name: name.into(),
+        length: number(frame, &["FRAME-LENGTH"]).unwrap_or(8),
+        cycle_time,
+        signals,
+        comment: description(frame),
+    }))
+}
+
+impl Decoder {
+    /// Parse an ARXML database. Every CAN frame triggering in the file is
+    /// loaded; if the same ID is triggered on several clusters, the last
+    /// one wins.
+    pub fn from_arxml(text: &str) -> Result<Self, DatabaseError> {
+        let root = crate::xml::parse(text).map_err(DatabaseError::Xml)?;
+        Self::arxml(&root)
+    }
+
+    /// Read and parse an ARXML file.
+    pub fn load_arxml(path: impl AsRef<Path>) -> Result<Self, DatabaseError> {
+        Self::arxml(&read_xml(path.as_ref())?)
+    }
+
+    fn arxml(root: &Element) -> Result<Self, DatabaseError> {
+        if root.name != "AUTOSAR" {
+            return Err(DatabaseError::Invalid(format!(
+                "expected <AUTOSAR>, got <{}>",
+                root.name
+            )));
+        }
+
+        let index = Index::new(root);
+        let mut triggerings = Vec::new();
+        root.descendants("CAN-FRAME-TRIGGERING", &mut triggerings);
+
+        let mut decoder = Self::new();
+        for triggering in triggerings {
+            if let Some(message) = parse_frame(triggering, &index)? {
+                decoder.add(message);
+            }
+        }
+        Ok(decoder)
+    }
+}
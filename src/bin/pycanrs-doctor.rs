@@ -0,0 +1,11 @@
+//! Print pycanrs' environment diagnostics. Exits non-zero if a required
+//! dependency is missing.
+
+fn main() {
+    let report = pycanrs::doctor();
+    print!("{report}");
+
+    if !report.ok() {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,415 @@
+//! Fanning received frames out to independent consumers, each with its
+//! own bounded queue, so one slow consumer can't hold up the others.
+//!
+//! ```ignore
+//! let frames = bus.broadcast(1024, LagPolicy::DropOldest)?;
+//! let logger = frames.subscribe();
+//! let decoder = frames.subscribe_with(64, LagPolicy::DropNewest);
+//! std::thread::spawn(move || loop {
+//!     match logger.recv() {
+//!         Ok(msg) => log(&msg),
+//!         Err(BroadcastError::Lagged(n)) => eprintln!("missed {n} frames"),
+//!         Err(BroadcastError::ReceiveFailed(e)) => eprintln!("{e}"),
+//!         Err(_) => break,
+//!     }
+//! });
+//! ```
+//!
+//! A subscriber can also be rate limited per ID, e.g. for a display that
+//! only needs the latest value of a 100 Hz sensor frame now and then:
+//!
+//! ```ignore
+//! let display = frames.subscribe().max_rate(Filter::new(0x300, 0x700), 10.0);
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Condvar, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+use crate::{describe_py_err, CanId, Filter, ListenerId, PyCanError, PyCanInterface, PyCanMessage};
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum BroadcastError {
+    /// Frames were discarded because the subscriber fell behind. Frames
+    /// after the gap are still delivered.
+    #[error("Subscriber fell behind and missed {0} frames")]
+    Lagged(u64),
+    /// The subscriber fell behind with [`LagPolicy::Disconnect`], and
+    /// everything queued before that has been received.
+    #[error("Subscriber was disconnected for falling behind")]
+    Disconnected,
+    /// python-can's notifier raised, or a received frame couldn't be
+    /// extracted. Delivered in place among the frames; later frames are
+    /// still received.
+    #[error("Failed to receive :: `{0}`")]
+    ReceiveFailed(String),
+    /// The [`Broadcast`] was dropped, and everything queued has been
+    /// received.
+    #[error("Broadcast closed")]
+    Closed,
+    #[error("No frame available")]
+    Empty,
+    #[error("Timed out waiting for a frame")]
+    Timeout,
+}
+
+/// What to do with a frame for a subscriber whose queue is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Discard the oldest queued frame to make room, as
+    /// `tokio::sync::broadcast` does.
+    #[default]
+    DropOldest,
+    /// Discard the new frame, keeping what's queued.
+    DropNewest,
+    /// Stop delivering to the subscriber. It receives what's queued,
+    /// then [`BroadcastError::Disconnected`].
+    Disconnect,
+}
+
+#[derive(Default)]
+struct Queue {
+    /// Frames, or receive errors, each with the number missed just
+    /// before it.
+    frames: VecDeque<(u64, Result<PyCanMessage, String>)>,
+    /// Frames missed since the last one queued.
+    missed_at_end: u64,
+    missed: u64,
+    disconnected: bool,
+    closed: bool,
+    /// Minimum time between frames of each ID matching the filter, from
+    /// [`Subscriber::max_rate`]. None if only the first is delivered.
+    limits: Vec<(Filter, Option<Duration>)>,
+    throttled: HashMap<CanId, Throttle>,
+}
+
+/// A rate-limited ID.
+struct Throttle {
+    period: Option<Duration>,
+    /// When the next frame can be delivered, or None if never.
+    next: Option<Instant>,
+    /// The latest frame that arrived too soon, delivered at `next`.
+    held: Option<PyCanMessage>,
+}
+
+impl Queue {
+    /// When the next held frame is due.
+    fn next_due(&self) -> Option<Instant> {
+        self.throttled
+            .values()
+            .filter(|t| t.held.is_some())
+            .filter_map(|t| t.next)
+            .min()
+    }
+}
+
+struct Slot {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    capacity: usize,
+    policy: LagPolicy,
+}
+
+impl Slot {
+    fn push(&self, msg: &PyCanMessage) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.disconnected {
+            return;
+        }
+
+        let now = Instant::now();
+        self.release(&mut queue, Some(now));
+        let id = msg.arbitration_id;
+        let period = queue
+            .limits
+            .iter()
+            .find(|(filter, _)| filter.matches(id))
+            .map(|&(_, period)| period);
+        if let Some(period) = period {
+            let throttle = queue.throttled.entry(id).or_insert(Throttle {
+                period,
+                next: Some(now),
+                held: None,
+            });
+            if throttle.next.is_none_or(|next| now < next) {
+                throttle.held = Some(msg.clone());
+                // Waiting receivers need to wake when it's due
+                self.ready.notify_all();
+                return;
+            }
+            throttle.next = period.and_then(|period| now.checked_add(period));
+        }
+        self.enqueue(&mut queue, Ok(msg.clone()));
+    }
+
+    /// Queue a receive error in place among the frames.
+    fn push_error(&self, err: String) {
+        let mut queue = self.queue.lock().unwrap();
+        self.release(&mut queue, Some(Instant::now()));
+        self.enqueue(&mut queue, Err(err));
+    }
+
+    /// Queue held frames due by `now`, or all of them if None.
+    fn release(&self, queue: &mut Queue, now: Option<Instant>) {
+        let mut due: Vec<_> = queue
+            .throttled
+            .values_mut()
+            .filter(|t| now.is_none_or(|now| t.next.is_some_and(|next| next <= now)))
+            .filter_map(|t| {
+                let msg = t.held.take()?;
+                let released = t.next;
+                t.next = released
+                    .zip(t.period)
+                    .and_then(|(at, period)| at.max(now.unwrap_or(at)).checked_add(period));
+                Some((released, msg))
+            })
+            .collect();
+        due.sort_by_key(|&(released, _)| released);
+        for (_, msg) in due {
+            self.enqueue(queue, Ok(msg));
+        }
+    }
+
+    fn enqueue(&self, queue: &mut Queue, msg: Result<PyCanMessage, String>) {
+        if queue.disconnected {
+            return;
+        }
+
+        if queue.frames.len() >= self.capacity {
+            queue.missed += 1;
+            match self.policy {
+                LagPolicy::DropOldest => {
+                    let missed = queue.frames.pop_front().map_or(0, |(missed, _)| missed);
+                    match queue.frames.front_mut() {
+                        Some((before, _)) => *before += missed + 1,
+                        None => queue.missed_at_end += missed + 1,
+                    }
+                }
+                LagPolicy::DropNewest => {
+                    queue.missed_at_end += 1;
+                    return;
+                }
+                LagPolicy::Disconnect => {
+                    queue.disconnected = true;
+                    self.ready.notify_all();
+                    return;
+                }
+            }
+        }
+
+        let missed = std::mem::take(&mut queue.missed_at_end);
+        queue.frames.push_back((missed, msg));
+        self.ready.notify_one();
+    }
+
+    /// Held frames are delivered straight away, so the latest values
+    /// aren't lost.
+    fn close(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        self.release(&mut queue, None);
+        queue.closed = true;
+        self.ready.notify_all();
+    }
+
+    /// Missed frames are reported once, in the place they were missed.
+    fn take(&self, queue: &mut Queue) -> Result<PyCanMessage, BroadcastError> {
+        self.release(queue, Some(Instant::now()));
+        if let Some((missed, _)) = queue.frames.front_mut() {
+            if *missed > 0 {
+                return Err(BroadcastError::Lagged(std::mem::take(missed)));
+            }
+        }
+        match queue.frames.pop_front() {
+            Some((_, msg)) => msg.map_err(BroadcastError::ReceiveFailed),
+            None if queue.disconnected => Err(BroadcastError::Disconnected),
+            None if queue.closed => Err(BroadcastError::Closed),
+            None => Err(BroadcastError::Empty),
+        }
+    }
+}
+
+type Slots = Mutex<Vec<Weak<Slot>>>;
+
+/// Received frames, fanned out to any number of [`Subscriber`]s. Created
+/// by [`PyCanInterface::broadcast`]. Stops receiving on drop, after which
+/// subscribers get [`BroadcastError::Closed`] once they've caught up.
+pub struct Broadcast {
+    iface: Arc<PyCanInterface>,
+    listener: ListenerId,
+    slots: Arc<Slots>,
+    capacity: usize,
+    policy: LagPolicy,
+}
+
+impl Broadcast {
+    /// A new subscriber with the broadcast's capacity and policy. It
+    /// receives frames from now on.
+    pub fn subscribe(&self) -> Subscriber {
+        self.subscribe_with(self.capacity, self.policy)
+    }
+
+    /// A new subscriber queueing up to `capacity` frames, at least 1.
+    pub fn subscribe_with(&self, capacity: usize, policy: LagPolicy) -> Subscriber {
+        let slot = Arc::new(Slot {
+            queue: Mutex::default(),
+            ready: Condvar::new(),
+            capacity: capacity.max(1),
+            policy,
+        });
+        let mut slots = self.slots.lock().unwrap();
+        slots.retain(|s| s.strong_count() > 0);
+        slots.push(Arc::downgrade(&slot));
+        Subscriber { slot }
+    }
+
+    /// Subscribers still receiving frames.
+    pub fn subscribers(&self) -> usize {
+        self.slots
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|s| !s.queue.lock().unwrap().disconnected)
+            .count()
+    }
+}
+
+impl Drop for Broadcast {
+    fn drop(&mut self) {
+        let _ = self.iface.remove_listener(self.listener);
+        for slot in self.slots.lock().unwrap().iter().filter_map(Weak::upgrade) {
+            slot.close();
+        }
+    }
+}
+
+/// One consumer of a [`Broadcast`]. Dropping it unsubscribes.
+pub struct Subscriber {
+    slot: Arc<Slot>,
+}
+
+impl Subscriber {
+    /// Deliver at most `hz` frames per second of each ID matching
+    /// `filter`. A frame arriving too soon is held back and delivered once
+    /// the ID's next slot comes up, unless a newer one replaces it first.
+    /// Replaced frames aren't counted as missed.
+    ///
+    /// If several filters match an ID, the first added applies. A rate of
+    /// zero or less, or one too low to time, delivers only an ID's first
+    /// frame until the broadcast closes, when the latest held one is
+    /// delivered too.
+    pub fn max_rate(self, filter: Filter, hz: f64) -> Self {
+        let period = Duration::try_from_secs_f64(1.0 / hz)
+            .ok()
+            .filter(|_| hz > 0.0);
+        self.slot
+            .queue
+            .lock()
+            .unwrap()
+            .limits
+            .push((filter, period));
+        self
+    }
+
+    /// The next frame, waiting for one if none is queued.
+    pub fn recv(&self) -> Result<PyCanMessage, BroadcastError> {
+        let mut queue = self.slot.queue.lock().unwrap();
+        loop {
+            match self.slot.take(&mut queue) {
+                Err(BroadcastError::Empty) => {
+                    queue = match queue.next_due() {
+                        Some(due) => {
+                            let wait = due.saturating_duration_since(Instant::now());
+                            self.slot.ready.wait_timeout(queue, wait).unwrap().0
+                        }
+                        None => self.slot.ready.wait(queue).unwrap(),
+                    }
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Like [`Self::recv`], but gives up after `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<PyCanMessage, BroadcastError> {
+        // A timeout too long to represent waits forever
+        let Some(deadline) = Instant::now().checked_add(timeout) else {
+            return self.recv();
+        };
+        let mut queue = self.slot.queue.lock().unwrap();
+        loop {
+            match self.slot.take(&mut queue) {
+                Err(BroadcastError::Empty) => {
+                    let Some(wait) = deadline.checked_duration_since(Instant::now()) else {
+                        return Err(BroadcastError::Timeout);
+                    };
+                    let due = queue
+                        .next_due()
+                        .map(|due| due.saturating_duration_since(Instant::now()));
+                    let wait = due.map_or(wait, |due| due.min(wait));
+                    queue = self.slot.ready.wait_timeout(queue, wait).unwrap().0;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// The next frame if one is queued, without waiting.
+    pub fn try_recv(&self) -> Result<PyCanMessage, BroadcastError> {
+        self.slot.take(&mut self.slot.queue.lock().unwrap())
+    }
+
+    /// Frames queued and not yet received.
+    pub fn pending(&self) -> usize {
+        self.slot.queue.lock().unwrap().frames.len()
+    }
+
+    /// Frames this subscriber has missed by falling behind, in total.
+    pub fn missed(&self) -> u64 {
+        self.slot.queue.lock().unwrap().missed
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.slot.queue.lock().unwrap().disconnected
+    }
+}
+
+impl PyCanInterface {
+    /// Fan received frames, including error frames, out to subscribers,
+    /// each queueing up to `capacity` frames and handling falling behind
+    /// with `policy` unless subscribed with other settings.
+    pub fn broadcast(
+        self: &Arc<Self>,
+        capacity: usize,
+        policy: LagPolicy,
+    ) -> Result<Broadcast, PyCanError> {
+        let slots = Arc::new(Slots::default());
+        let rx_slots = slots.clone();
+        let error_slots = slots.clone();
+        let listener = self.register_rx_callback_all(
+            move |msg| {
+                for slot in rx_slots.lock().unwrap().iter().filter_map(Weak::upgrade) {
+                    slot.push(msg);
+                }
+            },
+            move |err| {
+                let err = describe_py_err(err);
+                for slot in error_slots.lock().unwrap().iter().filter_map(Weak::upgrade) {
+                    slot.push_error(err.clone());
+                }
+            },
+        )?;
+
+        Ok(Broadcast {
+            iface: self.clone(),
+            listener,
+            slots,
+            capacity: capacity.max(1),
+            policy,
+        })
+    }
+}
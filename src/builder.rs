@@ -0,0 +1,186 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::{Decoder, DlcPolicy, PyCanBusType, PyCanError, PyCanInterface};
+
+/// Configures and opens a [`PyCanInterface`].
+#[derive(Clone, Debug)]
+pub struct PyCanInterfaceBuilder {
+    pub(crate) kind: PyCanBusType,
+    pub(crate) name: Option<String>,
+    pub(crate) notifier_timeout: Duration,
+    pub(crate) tx_confirmations: bool,
+    pub(crate) batched_delivery: bool,
+    pub(crate) callback_workers: usize,
+    pub(crate) max_batch: usize,
+    pub(crate) drift_correction: bool,
+    pub(crate) dlc_policy: Option<DlcPolicy>,
+    pub(crate) last_frame_cache: bool,
+    pub(crate) cycle_stats: bool,
+    pub(crate) usb_serial: Option<String>,
+    pub(crate) libusb_path: Option<PathBuf>,
+    pub(crate) decoder: Option<Decoder>,
+    pub(crate) tx_gap: Option<Duration>,
+    pub(crate) gil_budget: Option<Duration>,
+    #[cfg(feature = "trace")]
+    pub(crate) tracer: Option<crate::FrameTracer>,
+}
+
+impl PyCanInterfaceBuilder {
+    pub fn new(kind: PyCanBusType) -> Self {
+        Self {
+            kind,
+            name: None,
+            notifier_timeout: Duration::from_secs(1),
+            tx_confirmations: false,
+            batched_delivery: false,
+            callback_workers: 0,
+            max_batch: 256,
+            drift_correction: false,
+            dlc_policy: None,
+            last_frame_cache: false,
+            cycle_stats: false,
+            usb_serial: None,
+            libusb_path: None,
+            decoder: None,
+            tx_gap: None,
+            gil_budget: None,
+            #[cfg(feature = "trace")]
+            tracer: None,
+        }
+    }
+
+    /// Human-readable label for the interface, e.g. `powertrain`. Attached
+    /// to received messages and events. Defaults to the bus channel.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// How long python-can's notifier blocks in each `recv` call before
+    /// checking whether it should stop. Longer timeouts mean fewer wakeups
+    /// on a quiet bus, at the cost of slower shutdown. Defaults to 1s, as
+    /// in python-can.
+    pub fn notifier_timeout(mut self, timeout: Duration) -> Self {
+        self.notifier_timeout = timeout;
+        self
+    }
+
+    /// Ask the backend to echo transmitted frames (python-can's
+    /// `receive_own_messages`) and report them as
+    /// [`crate::PyCanEvent::TxConfirmation`] events. Backends that don't
+    /// support echoes never confirm. Echoed frames are also delivered to rx
+    /// callbacks, with `is_rx` false.
+    pub fn tx_confirmations(mut self, enabled: bool) -> Self {
+        self.tx_confirmations = enabled;
+        self
+    }
+
+    /// Deliver received frames to Rust callbacks in batches: a Rust thread
+    /// drains what python-can has queued, up to [`Self::max_batch`], under
+    /// one GIL acquisition, then runs callbacks with the GIL released. This raises throughput on
+    /// busy buses, at the cost of up to 100ms extra latency at shutdown.
+    pub fn batched_delivery(mut self, enabled: bool) -> Self {
+        self.batched_delivery = enabled;
+        self
+    }
+
+    /// With batched delivery, take at most `frames` frames from python-can
+    /// per GIL acquisition, delivering them before taking more, so a
+    /// backlog doesn't hold up the frames at its front. Defaults to 256.
+    pub fn max_batch(mut self, frames: usize) -> Self {
+        self.max_batch = frames.max(1);
+        self
+    }
+
+    /// Run Rust rx callbacks on `workers` threads, so a slow callback
+    /// doesn't delay extraction of the frames behind it. By default frames
+    /// with the same ID stay in order, but frames with different IDs may be
+    /// delivered out of order; see [`crate::DeliveryOrder`] and
+    /// `register_rx_callback_ordered`. Implies batched delivery.
+    pub fn callback_workers(mut self, workers: usize) -> Self {
+        self.callback_workers = workers;
+        self.batched_delivery |= workers > 0;
+        self
+    }
+
+    /// Estimate the offset between the backend's clock and ours and set
+    /// `corrected_timestamp` on received frames. Meant for socketcand, whose
+    /// timestamps come from the remote host.
+    pub fn drift_correction(mut self, enabled: bool) -> Self {
+        self.drift_correction = enabled;
+        self
+    }
+
+    /// Check received payloads against their DLC and pad, truncate or
+    /// drop mismatched frames. Off by default, in which case frames are
+    /// delivered as the backend reports them.
+    pub fn dlc_policy(mut self, policy: DlcPolicy) -> Self {
+        self.dlc_policy = Some(policy);
+        self
+    }
+
+    /// Keep the last data frame received for each ID, for
+    /// [`PyCanInterface::last_frame`].
+    pub fn last_frame_cache(mut self, enabled: bool) -> Self {
+        self.last_frame_cache = enabled;
+        self
+    }
+
+    /// Measure the cycle time of each received ID, for
+    /// [`PyCanInterface::cycle_stats`].
+    pub fn cycle_stats(mut self, enabled: bool) -> Self {
+        self.cycle_stats = enabled;
+        self
+    }
+
+    /// Open the slcan or gs_usb adapter with this USB serial number,
+    /// wherever it's plugged in. The port or USB address in the bus type is
+    /// replaced with the adapter's current one when the interface is opened.
+    pub fn usb_serial(mut self, serial: impl Into<String>) -> Self {
+        self.usb_serial = Some(serial.into());
+        self
+    }
+
+    /// libusb library for pyusb to use with gs_usb adapters. On macOS,
+    /// Homebrew and MacPorts locations are tried if this isn't set.
+    pub fn libusb_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.libusb_path = Some(path.into());
+        self
+    }
+
+    /// Signal definitions, for [`PyCanInterface::subscribe_signal`].
+    pub fn decoder(mut self, decoder: Decoder) -> Self {
+        self.decoder = Some(decoder);
+        self
+    }
+
+    /// Leave at least `gap` between frames sent on this interface, for
+    /// adapters (some slcan ones in particular) that drop frames sent back
+    /// to back. Sends wait out the rest of the gap; `try_send` fails with
+    /// `TxQueueFull` instead. Periodic tasks run by the backend aren't
+    /// paced. The native `SlcanBus` has its own `tx_gap`.
+    pub fn tx_gap(mut self, gap: Duration) -> Self {
+        self.tx_gap = Some(gap);
+        self
+    }
+
+    /// Emit [`crate::PyCanEvent::GilBudgetExceeded`] whenever sending,
+    /// receiving or an rx callback holds the GIL for longer than `budget`.
+    /// Hold times are measured either way; see
+    /// [`PyCanInterface::gil_stats`].
+    pub fn gil_budget(mut self, budget: Duration) -> Self {
+        self.gil_budget = Some(budget);
+        self
+    }
+
+    /// Log every frame sent and received with `tracer`.
+    #[cfg(feature = "trace")]
+    pub fn frame_trace(mut self, tracer: crate::FrameTracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    pub fn build(self) -> Result<PyCanInterface, PyCanError> {
+        PyCanInterface::open(self)
+    }
+}
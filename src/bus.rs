@@ -0,0 +1,27 @@
+//! A minimal frame I/O interface shared by python-can interfaces and the
+//! native drivers, so code that only moves frames can use either.
+
+use std::time::Duration;
+
+use pyo3::Python;
+
+use crate::{describe_py_err, PyCanError, PyCanInterface, PyCanMessage};
+
+pub trait CanBus: Send + Sync {
+    fn send_frame(&self, msg: &PyCanMessage) -> Result<(), PyCanError>;
+
+    /// Wait up to `timeout` for a frame; None waits forever.
+    fn recv_frame(&self, timeout: Option<Duration>) -> Result<Option<PyCanMessage>, PyCanError>;
+}
+
+impl CanBus for PyCanInterface {
+    fn send_frame(&self, msg: &PyCanMessage) -> Result<(), PyCanError> {
+        let data = msg.data.as_deref().unwrap_or_default();
+        Python::with_gil(|py| self.send_once(py, msg.arbitration_id, data, None))
+            .map_err(|e| PyCanError::FailedToSend(describe_py_err(&e)))
+    }
+
+    fn recv_frame(&self, timeout: Option<Duration>) -> Result<Option<PyCanMessage>, PyCanError> {
+        Python::with_gil(|py| self.recv_prepared(py, timeout))
+    }
+}
@@ -0,0 +1,34 @@
+//! Most recent frame received for each ID.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{CanId, PyCanInterface, PyCanMessage};
+
+#[derive(Clone, Default)]
+pub(crate) struct LastFrames(Arc<RwLock<HashMap<CanId, PyCanMessage>>>);
+
+impl LastFrames {
+    pub(crate) fn record(&self, msg: &PyCanMessage) {
+        self.0
+            .write()
+            .unwrap()
+            .insert(msg.arbitration_id, msg.clone());
+    }
+}
+
+impl PyCanInterface {
+    /// The last data frame received with `id`. Always None unless the
+    /// cache was enabled with [`crate::PyCanInterfaceBuilder::last_frame_cache`].
+    pub fn last_frame(&self, id: CanId) -> Option<PyCanMessage> {
+        self.last_frames
+            .as_ref()?
+            .0
+            .read()
+            .unwrap()
+            .get(&id)
+            .cloned()
+    }
+}
@@ -0,0 +1,251 @@
+//! C API for driving buses from other languages. Declared in
+//! `include/pycanrs.h`, which must be kept in step with this file.
+//!
+//! Functions report failure with a NULL or negative return, and
+//! [`pycanrs_last_error`] then describes what went wrong.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{CanId, ListenerId, PyCanBusType, PyCanError, PyCanInterface, PyCanMessage};
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_error(err: impl ToString) {
+    let msg = CString::new(err.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = msg);
+}
+
+/// An open bus. Opaque to C.
+pub struct PycanrsBus {
+    iface: PyCanInterface,
+    /// Listeners registered through the C API, by the handle C was given.
+    listeners: Mutex<HashMap<i64, ListenerId>>,
+    next_handle: AtomicI64,
+}
+
+/// A received frame, as passed to callbacks.
+#[repr(C)]
+pub struct PycanrsFrame {
+    pub id: u32,
+    pub extended: bool,
+    pub is_rx: bool,
+    pub is_error_frame: bool,
+    pub is_remote_frame: bool,
+    pub is_fd: bool,
+    /// Payload length in bytes. Only the first `len` bytes of `data` are
+    /// valid.
+    pub len: u8,
+    pub data: [u8; 64],
+    /// Seconds, from the backend's clock. NaN if the backend gave none.
+    pub timestamp: f64,
+}
+
+impl From<&PyCanMessage> for PycanrsFrame {
+    fn from(msg: &PyCanMessage) -> Self {
+        let payload = msg.data.as_deref().unwrap_or_default();
+        let len = payload.len().min(64);
+        let mut data = [0; 64];
+        data[..len].copy_from_slice(&payload[..len]);
+        Self {
+            id: msg.arbitration_id.raw(),
+            extended: msg.arbitration_id.is_extended(),
+            is_rx: msg.is_rx,
+            is_error_frame: msg.is_error_frame,
+            is_remote_frame: msg.is_remote_frame,
+            is_fd: msg.is_fd,
+            len: len as u8,
+            data,
+            timestamp: msg.timestamp.unwrap_or(f64::NAN),
+        }
+    }
+}
+
+pub type PycanrsRxCallback = extern "C" fn(frame: *const PycanrsFrame, user_data: *mut c_void);
+
+/// The caller's `user_data`, which it promises can be used from the
+/// notifier thread.
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    // A method rather than `.0`, so closures capture the whole wrapper
+    fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// Describes the last error on the calling thread. Valid until the next
+/// call into the API on that thread. Never NULL.
+#[no_mangle]
+pub extern "C" fn pycanrs_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Open the bus described by the URI `bus`, e.g. `socketcan://can0`.
+/// Returns NULL on failure.
+///
+/// # Safety
+///
+/// `bus` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn pycanrs_open(bus: *const c_char) -> *mut PycanrsBus {
+    if bus.is_null() {
+        set_error("bus is NULL");
+        return std::ptr::null_mut();
+    }
+    let opened = CStr::from_ptr(bus)
+        .to_str()
+        .map_err(|e| e.to_string())
+        .and_then(|bus| bus.parse::<PyCanBusType>().map_err(|e| e.to_string()))
+        .and_then(|kind| PyCanInterface::new(kind).map_err(|e| e.to_string()));
+
+    match opened {
+        Ok(iface) => Box::into_raw(Box::new(PycanrsBus {
+            iface,
+            listeners: Mutex::default(),
+            next_handle: AtomicI64::new(1),
+        })),
+        Err(e) => {
+            set_error(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Send a frame, blocking until the backend accepts it. Returns 0 on
+/// success, -1 on failure.
+///
+/// # Safety
+///
+/// `bus` must come from [`pycanrs_open`] and not have been closed, and
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pycanrs_send(
+    bus: *const PycanrsBus,
+    id: u32,
+    extended: bool,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    let Some(bus) = bus.as_ref() else {
+        set_error("bus is NULL");
+        return -1;
+    };
+    let data = match (data.is_null(), len) {
+        (_, 0) => &[][..],
+        (true, _) => {
+            set_error("data is NULL");
+            return -1;
+        }
+        (false, len) => std::slice::from_raw_parts(data, len),
+    };
+
+    let sent = CanId::new(id, extended)
+        .map_err(|e| PyCanError::FailedToSend(e.to_string()))
+        .and_then(|id| bus.iface.send_checked(id, data));
+
+    match sent {
+        Ok(_) => 0,
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+/// Call `callback` with every received frame, error frames included,
+/// passing `user_data` through. Callbacks run on the notifier thread, and
+/// the frame is only valid for the duration of the call. Returns a handle
+/// for [`pycanrs_remove_callback`], or -1 on failure.
+///
+/// # Safety
+///
+/// `bus` must come from [`pycanrs_open`] and not have been closed.
+/// `user_data` must be safe to use from another thread.
+#[no_mangle]
+pub unsafe extern "C" fn pycanrs_register_callback(
+    bus: *const PycanrsBus,
+    callback: Option<PycanrsRxCallback>,
+    user_data: *mut c_void,
+) -> i64 {
+    let Some(bus) = bus.as_ref() else {
+        set_error("bus is NULL");
+        return -1;
+    };
+    let Some(callback) = callback else {
+        set_error("callback is NULL");
+        return -1;
+    };
+
+    let user_data = UserData(user_data);
+    let registered = bus.iface.register_rx_callback_all(
+        move |msg| {
+            let frame = PycanrsFrame::from(msg);
+            callback(&frame, user_data.get());
+        },
+        |_| {},
+    );
+
+    match registered {
+        Ok(listener) => {
+            let handle = bus.next_handle.fetch_add(1, Ordering::Relaxed);
+            bus.listeners.lock().unwrap().insert(handle, listener);
+            handle
+        }
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+/// Stop calling a callback. Must not be called from within a callback.
+/// Returns 0 on success, -1 if `handle` isn't registered on `bus`.
+///
+/// # Safety
+///
+/// `bus` must come from [`pycanrs_open`] and not have been closed.
+#[no_mangle]
+pub unsafe extern "C" fn pycanrs_remove_callback(bus: *const PycanrsBus, handle: i64) -> c_int {
+    let Some(bus) = bus.as_ref() else {
+        set_error("bus is NULL");
+        return -1;
+    };
+    let Some(listener) = bus.listeners.lock().unwrap().remove(&handle) else {
+        set_error(format!("no callback with handle {handle}"));
+        return -1;
+    };
+
+    match bus.iface.remove_listener(listener) {
+        Ok(()) => 0,
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+/// Close a bus, stopping its callbacks. NULL is ignored.
+///
+/// # Safety
+///
+/// `bus` must come from [`pycanrs_open`] and not have been closed, and
+/// must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pycanrs_close(bus: *mut PycanrsBus) {
+    if !bus.is_null() {
+        drop(Box::from_raw(bus));
+    }
+}
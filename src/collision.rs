@@ -0,0 +1,135 @@
+//! Checking the IDs an application will transmit against live traffic.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{CanId, Decoder, PyCanError, PyCanEvent, PyCanInterface};
+
+/// The messages an application intends to transmit.
+#[derive(Clone, Debug, Default)]
+pub struct TxPlan {
+    entries: Vec<(CanId, String)>,
+}
+
+impl TxPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Plan to send `id`, described as `name` in reports.
+    pub fn add(mut self, id: CanId, name: impl Into<String>) -> Self {
+        self.entries.push((id, name.into()));
+        self
+    }
+
+    /// Plan to send the messages called `names` in `decoder`.
+    pub fn from_decoder(decoder: &Decoder, names: &[&str]) -> Result<Self, PyCanError> {
+        names.iter().try_fold(Self::new(), |plan, name| {
+            let def = decoder
+                .messages()
+                .find(|m| m.name == *name)
+                .ok_or_else(|| PyCanError::UnknownMessage((*name).into()))?;
+            Ok(plan.add(def.id, &def.name))
+        })
+    }
+}
+
+/// A planned ID that something else on the bus already sends.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IdCollision {
+    pub id: CanId,
+    /// Name of the planned message.
+    pub name: String,
+    /// Frames seen with this ID during the learn period.
+    pub frames: u64,
+    /// Timestamp of the first of them.
+    pub first_seen: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CollisionReport {
+    pub collisions: Vec<IdCollision>,
+    /// IDs planned more than once, with the names they were planned as.
+    pub duplicates: Vec<(CanId, Vec<String>)>,
+    /// Distinct IDs seen on the bus during the learn period.
+    pub observed_ids: usize,
+}
+
+impl CollisionReport {
+    /// Whether it's safe to start transmitting.
+    pub fn is_clear(&self) -> bool {
+        self.collisions.is_empty() && self.duplicates.is_empty()
+    }
+}
+
+impl PyCanInterface {
+    /// Listen to the bus for `learn` and report planned IDs that other
+    /// nodes already send, and IDs planned twice. Each collision is also
+    /// emitted as a [`PyCanEvent::IdCollision`] warning. Frames this
+    /// interface sent itself aren't counted.
+    pub fn check_tx_ids(
+        &self,
+        plan: &TxPlan,
+        learn: Duration,
+    ) -> Result<CollisionReport, PyCanError> {
+        let seen = Arc::new(Mutex::new(HashMap::<CanId, (u64, Option<f64>)>::new()));
+
+        let listener = {
+            let seen = seen.clone();
+            self.register_rx_callback(
+                move |msg| {
+                    if !msg.is_rx {
+                        return;
+                    }
+                    let mut seen = seen.lock().unwrap();
+                    let (frames, first) = seen.entry(msg.arbitration_id).or_default();
+                    *frames += 1;
+                    if first.is_none() {
+                        *first = msg.timestamp;
+                    }
+                },
+                |_| {},
+            )?
+        };
+        std::thread::sleep(learn);
+        self.remove_listener(listener)?;
+
+        let seen = std::mem::take(&mut *seen.lock().unwrap());
+
+        let mut planned = BTreeMap::<CanId, Vec<String>>::new();
+        for (id, name) in &plan.entries {
+            planned.entry(*id).or_default().push(name.clone());
+        }
+
+        let collisions: Vec<_> = planned
+            .iter()
+            .filter_map(|(id, names)| {
+                let (frames, first_seen) = seen.get(id)?;
+                Some(IdCollision {
+                    id: *id,
+                    name: names[0].clone(),
+                    frames: *frames,
+                    first_seen: *first_seen,
+                })
+            })
+            .collect();
+        for collision in &collisions {
+            self.events.emit(PyCanEvent::IdCollision {
+                id: collision.id,
+                frames: collision.frames,
+            });
+        }
+
+        Ok(CollisionReport {
+            collisions,
+            duplicates: planned
+                .into_iter()
+                .filter(|(_, names)| names.len() > 1)
+                .collect(),
+            observed_ids: seen.len(),
+        })
+    }
+}
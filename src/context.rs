@@ -0,0 +1,45 @@
+//! Free-form annotations carried along with a message.
+
+use std::{any::Any, collections::HashMap, fmt::Debug, sync::Arc};
+
+/// Values attached to a [`crate::PyCanMessage`] by the layers it passes
+/// through, keyed by name. Values are shared, so cloning a message doesn't
+/// copy them.
+#[derive(Clone, Default)]
+pub struct Context {
+    values: HashMap<String, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Context {
+    pub fn insert<T: Any + Send + Sync>(&mut self, key: impl Into<String>, value: T) {
+        self.values.insert(key.into(), Arc::new(value));
+    }
+
+    /// The value under `key`, if there is one and it's a `T`.
+    pub fn get<T: Any>(&self, key: &str) -> Option<&T> {
+        self.values.get(key)?.downcast_ref()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
+    /// Returns whether `key` was present.
+    pub fn remove(&mut self, key: &str) -> bool {
+        self.values.remove(key).is_some()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl Debug for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.keys()).finish()
+    }
+}
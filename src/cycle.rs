@@ -0,0 +1,167 @@
+//! Observed cycle times for each received ID.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{CanId, PyCanInterface, PyCanMessage};
+
+/// Intervals between consecutive frames of one ID.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CycleStats {
+    pub id: CanId,
+    pub frames: u64,
+    pub min: Duration,
+    pub mean: Duration,
+    pub max: Duration,
+    /// Standard deviation of the intervals.
+    pub jitter: Duration,
+}
+
+#[derive(Default)]
+struct Cycle {
+    frames: u64,
+    intervals: u64,
+    /// Time of the last frame with a finite timestamp.
+    last: Option<f64>,
+    min: f64,
+    max: f64,
+    /// Running mean and sum of squared deviations (Welford).
+    mean: f64,
+    m2: f64,
+}
+
+impl Cycle {
+    fn record(&mut self, time: f64) {
+        self.frames += 1;
+        if !time.is_finite() {
+            return;
+        }
+        let Some(last) = self.last.replace(time) else {
+            return;
+        };
+        let interval = (time - last).max(0.0);
+        if !interval.is_finite() {
+            return;
+        }
+
+        self.intervals += 1;
+        let n = self.intervals as f64;
+        if self.intervals == 1 {
+            self.min = interval;
+            self.max = interval;
+        } else {
+            self.min = self.min.min(interval);
+            self.max = self.max.max(interval);
+        }
+        let delta = interval - self.mean;
+        self.mean += delta / n;
+        self.m2 += delta * (interval - self.mean);
+    }
+
+    fn stats(&self, id: CanId) -> CycleStats {
+        let variance = if self.intervals > 1 {
+            self.m2 / self.intervals as f64
+        } else {
+            0.0
+        };
+        CycleStats {
+            id,
+            frames: self.frames,
+            min: secs(self.min),
+            mean: secs(self.mean),
+            max: secs(self.max),
+            jitter: secs(variance.sqrt()),
+        }
+    }
+}
+
+/// Saturates intervals too long for a Duration, which a pair of far-apart
+/// timestamps can produce.
+fn secs(secs: f64) -> Duration {
+    if secs.is_nan() {
+        return Duration::ZERO;
+    }
+    Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX)
+}
+
+#[derive(Clone)]
+pub(crate) struct CycleTimes {
+    ids: Arc<Mutex<HashMap<CanId, Cycle>>>,
+    start: Instant,
+}
+
+impl Default for CycleTimes {
+    fn default() -> Self {
+        Self {
+            ids: Arc::default(),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl CycleTimes {
+    pub(crate) fn record(&self, msg: &PyCanMessage) {
+        if !msg.is_rx {
+            return;
+        }
+        // Backend timestamps where there are some, since callbacks run late
+        let time = msg
+            .timestamp
+            .unwrap_or_else(|| self.start.elapsed().as_secs_f64());
+        self.ids
+            .lock()
+            .unwrap()
+            .entry(msg.arbitration_id)
+            .or_default()
+            .record(time);
+    }
+
+    pub(crate) fn get(&self, id: CanId) -> Option<CycleStats> {
+        self.ids.lock().unwrap().get(&id).map(|c| c.stats(id))
+    }
+
+    /// Stats of every ID, in ID order.
+    pub(crate) fn all(&self) -> Vec<CycleStats> {
+        let mut stats: Vec<_> = self
+            .ids
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, c)| c.stats(*id))
+            .collect();
+        stats.sort_by_key(|s| s.id);
+        stats
+    }
+
+    pub(crate) fn clear(&self) {
+        self.ids.lock().unwrap().clear();
+    }
+}
+
+impl PyCanInterface {
+    /// Cycle time of `id` since it was first received or stats were last
+    /// reset. Always None unless enabled with
+    /// [`crate::PyCanInterfaceBuilder::cycle_stats`].
+    pub fn cycle_stats(&self, id: CanId) -> Option<CycleStats> {
+        self.cycle_times.as_ref()?.get(id)
+    }
+
+    /// Cycle times of every ID received, in ID order.
+    pub fn all_cycle_stats(&self) -> Vec<CycleStats> {
+        self.cycle_times
+            .as_ref()
+            .map(CycleTimes::all)
+            .unwrap_or_default()
+    }
+
+    /// Forget the cycle times measured so far, e.g. at the start of a
+    /// test step.
+    pub fn reset_cycle_stats(&self) {
+        if let Some(cycles) = &self.cycle_times {
+            cycles.clear();
+        }
+    }
+}
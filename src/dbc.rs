@@ -0,0 +1,420 @@
+//! DBC database parsing, so signals can be decoded without cantools.
+//!
+//! Messages (`BO_`), signals (`SG_`) including multiplexing, comments
+//! (`CM_`), value descriptions (`VAL_`) and message cycle times
+//! (`GenMsgCycleTime`) are read. Everything else, e.g. other attributes
+//! and environment variables, is skipped.
+
+use std::{path::Path, time::Duration};
+
+use thiserror::Error;
+
+use crate::{ByteOrder, CanId, Decoder, MessageDef, Multiplexing, Signal};
+
+#[derive(Debug, Error)]
+pub enum DbcError {
+    #[error("Failed to read DBC file :: `{0}`")]
+    Io(String),
+    #[error("DBC syntax error on line {0} :: `{1}`")]
+    Syntax(usize, String),
+}
+
+/// Set on `BO_` IDs of extended frames.
+const EXTENDED_FLAG: u32 = 0x8000_0000;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Punct(char),
+}
+
+struct Tokens {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+fn tokenize(text: &str) -> Result<Tokens, DbcError> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '"' => {
+                let start = line;
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => s.extend(chars.next()),
+                        Some(c) => {
+                            line += usize::from(c == '\n');
+                            s.push(c);
+                        }
+                        None => return Err(DbcError::Syntax(start, "unterminated string".into())),
+                    }
+                }
+                tokens.push((start, Token::Str(s)));
+            }
+            c if c.is_alphanumeric() || "_.-+".contains(c) => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || "_.-+".contains(c)) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push((line, Token::Word(word)));
+            }
+            c => {
+                tokens.push((line, Token::Punct(c)));
+                chars.next();
+            }
+        }
+    }
+
+    Ok(Tokens { tokens, pos: 0 })
+}
+
+impl Tokens {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    /// Line of the next token, or of the last one at the end of input.
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos.min(self.tokens.len().saturating_sub(1)))
+            .map_or(1, |(line, _)| *line)
+    }
+
+    fn error(&self, reason: impl Into<String>) -> DbcError {
+        DbcError::Syntax(self.line(), reason.into())
+    }
+
+    fn next(&mut self) -> Result<Token, DbcError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .map(|(_, t)| t.clone())
+            .ok_or_else(|| self.error("unexpected end of file"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn word(&mut self) -> Result<String, DbcError> {
+        match self.next()? {
+            Token::Word(w) => Ok(w),
+            t => Err(self.error(format!("expected a name or number, got {t:?}"))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, DbcError> {
+        match self.next()? {
+            Token::Str(s) => Ok(s),
+            t => Err(self.error(format!("expected a string, got {t:?}"))),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, DbcError> {
+        let word = self.word()?;
+        word.parse()
+            .map_err(|_| self.error(format!("invalid number `{word}`")))
+    }
+
+    /// A number that must be finite, so e.g. `1e400` isn't read as inf.
+    fn float(&mut self) -> Result<f64, DbcError> {
+        let word = self.word()?;
+        word.parse()
+            .ok()
+            .filter(|value: &f64| value.is_finite())
+            .ok_or_else(|| self.error(format!("invalid number `{word}`")))
+    }
+
+    fn punct(&mut self, expected: char) -> Result<(), DbcError> {
+        match self.next()? {
+            Token::Punct(c) if c == expected => Ok(()),
+            t => Err(self.error(format!("expected `{expected}`, got {t:?}"))),
+        }
+    }
+
+    /// Skip the rest of the current statement, up to and including `;`.
+    fn skip_statement(&mut self) {
+        while let Some((_, token)) = self.tokens.get(self.pos) {
+            self.pos += 1;
+            if *token == Token::Punct(';') {
+                return;
+            }
+        }
+    }
+
+    /// Skip the remaining tokens on `line`.
+    fn skip_line(&mut self, line: usize) {
+        while self.tokens.get(self.pos).is_some_and(|(l, _)| *l == line) {
+            self.pos += 1;
+        }
+    }
+}
+
+/// A message ID, or None for IDs that aren't valid CAN IDs, like the
+/// `VECTOR__INDEPENDENT_SIG_MSG` placeholder some tools write.
+fn message_id(tokens: &mut Tokens) -> Result<Option<CanId>, DbcError> {
+    let raw: u32 = tokens.number()?;
+    let extended = raw & EXTENDED_FLAG != 0;
+    Ok(CanId::new(raw & !EXTENDED_FLAG, extended).ok())
+}
+
+/// `BO_ <id> <name>: <dlc> <sender>`
+fn parse_message(tokens: &mut Tokens) -> Result<Option<MessageDef>, DbcError> {
+    let line = tokens.line();
+    let id = message_id(tokens)?;
+    let name = tokens.word()?;
+    tokens.punct(':')?;
+    let length = tokens.number()?;
+    tokens.skip_line(line);
+
+    Ok(id.map(|id| MessageDef {
+        id,
+        name,
+        length,
+        cycle_time: None,
+        signals: Vec::new(),
+        comment: None,
+    }))
+}
+
+/// `SG_ <name> [M|m<n>] : <start>|<len>@<order><sign> (<factor>,<offset>)
+/// [<min>|<max>] "<unit>" <receivers>`
+fn parse_signal(tokens: &mut Tokens) -> Result<Signal, DbcError> {
+    let line = tokens.line();
+    let name = tokens.word()?;
+
+    let multiplexing = match tokens.peek() {
+        Some(Token::Word(w)) if w == "M" => Multiplexing::Multiplexor,
+        // `m<n>M` marks extended multiplexing; only the `m<n>` part is used
+        Some(Token::Word(w)) if w.starts_with('m') => {
+            let value = w[1..].trim_end_matches('M');
+            Multiplexing::Multiplexed(
+                value
+                    .parse()
+                    .map_err(|_| tokens.error(format!("invalid multiplexer `{w}`")))?,
+            )
+        }
+        _ => Multiplexing::None,
+    };
+    if multiplexing != Multiplexing::None {
+        tokens.next()?;
+    }
+
+    tokens.punct(':')?;
+    let start_bit = tokens.number()?;
+    tokens.punct('|')?;
+    let length = tokens.number()?;
+    tokens.punct('@')?;
+
+    let layout = tokens.word()?;
+    let (byte_order, signed) = match layout.as_str() {
+        "1+" => (ByteOrder::LittleEndian, false),
+        "1-" => (ByteOrder::LittleEndian, true),
+        "0+" => (ByteOrder::BigEndian, false),
+        "0-" => (ByteOrder::BigEndian, true),
+        _ => return Err(tokens.error(format!("invalid signal layout `@{layout}`"))),
+    };
+
+    tokens.punct('(')?;
+    let factor = tokens.float()?;
+    tokens.punct(',')?;
+    let offset = tokens.float()?;
+    tokens.punct(')')?;
+    tokens.punct('[')?;
+    tokens.float()?;
+    tokens.punct('|')?;
+    tokens.float()?;
+    tokens.punct(']')?;
+    let unit = tokens.string()?;
+    tokens.skip_line(line);
+
+    Ok(Signal {
+        byte_order,
+        signed,
+        factor,
+        offset,
+        unit,
+        multiplexing,
+        ..Signal::new(name, start_bit, length)
+    })
+}
+
+fn find_signal<'a>(
+    messages: &'a mut [MessageDef],
+    id: Option<CanId>,
+    name: &str,
+) -> Option<&'a mut Signal> {
+    messages
+        .iter_mut()
+        .find(|m| Some(m.id) == id)?
+        .signals
+        .iter_mut()
+        .find(|s| s.name == name)
+}
+
+/// `CM_ [BO_ <id> | SG_ <id> <signal> | BU_ <node> | EV_ <var>] "<text>";`
+fn parse_comment(tokens: &mut Tokens, messages: &mut [MessageDef]) -> Result<(), DbcError> {
+    match tokens.peek() {
+        Some(Token::Word(w)) if w == "BO_" => {
+            tokens.next()?;
+            let id = message_id(tokens)?;
+            let text = tokens.string()?;
+            if let Some(message) = messages.iter_mut().find(|m| Some(m.id) == id) {
+                message.comment = Some(text);
+            }
+        }
+        Some(Token::Word(w)) if w == "SG_" => {
+            tokens.next()?;
+            let id = message_id(tokens)?;
+            let name = tokens.word()?;
+            let text = tokens.string()?;
+            if let Some(signal) = find_signal(messages, id, &name) {
+                signal.comment = Some(text);
+            }
+        }
+        _ => {}
+    }
+    tokens.skip_statement();
+    Ok(())
+}
+
+/// `BA_ "<attribute>" [BO_ <id> | ...] <value>;`. Only message cycle times
+/// are used.
+fn parse_attribute(tokens: &mut Tokens, messages: &mut [MessageDef]) -> Result<(), DbcError> {
+    if tokens.peek() == Some(&Token::Str("GenMsgCycleTime".into())) {
+        tokens.next()?;
+        if tokens.peek() == Some(&Token::Word("BO_".into())) {
+            tokens.next()?;
+            let id = message_id(tokens)?;
+            let ms = tokens.float()?;
+            let cycle_time = (ms > 0.0)
+                .then(|| Duration::try_from_secs_f64(ms / 1000.0))
+                .transpose()
+                .map_err(|_| tokens.error(format!("cycle time {ms:e} ms is out of range")))?;
+            if let Some(message) = messages.iter_mut().find(|m| Some(m.id) == id) {
+                message.cycle_time = cycle_time;
+            }
+        }
+    }
+    tokens.skip_statement();
+    Ok(())
+}
+
+/// `VAL_ <id> <signal> <value> "<label>" ... ;`
+fn parse_values(tokens: &mut Tokens, messages: &mut [MessageDef]) -> Result<(), DbcError> {
+    // `VAL_ <env var> ...` describes an environment variable
+    if !matches!(tokens.peek(), Some(Token::Word(w)) if w.starts_with(|c: char| c.is_ascii_digit()))
+    {
+        tokens.skip_statement();
+        return Ok(());
+    }
+
+    let id = message_id(tokens)?;
+    let name = tokens.word()?;
+    let mut values = Vec::new();
+    while tokens.peek() != Some(&Token::Punct(';')) {
+        let value: i64 = tokens.number()?;
+        values.push((value, tokens.string()?));
+    }
+    tokens.next()?;
+
+    if let Some(signal) = find_signal(messages, id, &name) {
+        signal.values.extend(values);
+    }
+    Ok(())
+}
+
+impl Decoder {
+    /// Parse a DBC database.
+    pub fn from_dbc(text: &str) -> Result<Self, DbcError> {
+        let mut tokens = tokenize(text)?;
+        let mut messages: Vec<MessageDef> = Vec::new();
+        // Whether `SG_` lines belong to the last message
+        let mut in_message = false;
+
+        while let Some(token) = tokens.peek().cloned() {
+            let line = tokens.line();
+            tokens.next()?;
+
+            let Token::Word(keyword) = token else {
+                return Err(tokens.error(format!("unexpected {token:?}")));
+            };
+
+            match keyword.as_str() {
+                "BO_" => {
+                    let message = parse_message(&mut tokens)?;
+                    in_message = message.is_some();
+                    messages.extend(message);
+                    continue;
+                }
+                "SG_" => {
+                    let signal = parse_signal(&mut tokens)?;
+                    if in_message {
+                        let message = messages.last_mut().unwrap();
+                        if signal.bytes_needed().is_none_or(|n| n > message.length) {
+                            return Err(DbcError::Syntax(
+                                line,
+                                format!(
+                                    "signal {} doesn't fit in the {} bytes of {}",
+                                    signal.name, message.length, message.name
+                                ),
+                            ));
+                        }
+                        message.signals.push(signal);
+                    }
+                    continue;
+                }
+                "CM_" => parse_comment(&mut tokens, &mut messages)?,
+                "VAL_" => parse_values(&mut tokens, &mut messages)?,
+                "BA_" => parse_attribute(&mut tokens, &mut messages)?,
+                "VERSION" | "BS_" | "BU_" => tokens.skip_line(line),
+                // The new-symbols list runs until the bit timing section
+                "NS_" => {
+                    while tokens
+                        .peek()
+                        .is_some_and(|t| *t != Token::Word("BS_".into()))
+                    {
+                        tokens.next()?;
+                    }
+                }
+                _ => tokens.skip_statement(),
+            }
+            in_message = false;
+        }
+
+        let mut decoder = Self::new();
+        for message in messages {
+            decoder.add(message);
+        }
+        Ok(decoder)
+    }
+
+    /// Read and parse a DBC file.
+    pub fn load_dbc(path: impl AsRef<Path>) -> Result<Self, DbcError> {
+        let path = path.as_ref();
+        let text =
+            std::fs::read(path).map_err(|e| DbcError::Io(format!("{}: {e}", path.display())))?;
+        // DBC files are often Latin-1 rather than UTF-8
+        let text = match String::from_utf8(text) {
+            Ok(text) => text,
+            Err(e) => e.into_bytes().iter().map(|&b| char::from(b)).collect(),
+        };
+        Self::from_dbc(&text)
+    }
+}
@@ -0,0 +1,415 @@
+//! Signal decoding, with DBC semantics for bit layout and multiplexing.
+//!
+//! A multiplexed message has one multiplexor signal (`M` in a DBC) whose
+//! value selects which of its multiplexed signals (`m<n>`) are present in
+//! a given frame. Signals that aren't multiplexed are always present.
+
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::Path,
+    time::Duration,
+};
+
+use pyo3::PyErr;
+use thiserror::Error;
+
+use crate::{CanId, ListenerId, PyCanError, PyCanInterface, PyCanMessage};
+
+/// Errors loading a KCD or ARXML database. DBC files have [`crate::DbcError`].
+#[derive(Debug, Error)]
+pub enum DatabaseError {
+    #[error("Failed to read database file :: `{0}`")]
+    Io(String),
+    #[error("Malformed XML :: `{0}`")]
+    Xml(String),
+    #[error("Invalid database :: `{0}`")]
+    Invalid(String),
+}
+
+/// Read an XML database file.
+pub(crate) fn read_xml(path: &Path) -> Result<crate::xml::Element, DatabaseError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| DatabaseError::Io(format!("{}: {e}", path.display())))?;
+    crate::xml::parse(&text).map_err(DatabaseError::Xml)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Intel: `start_bit` is the least significant bit.
+    LittleEndian,
+    /// Motorola: `start_bit` is the most significant bit, in DBC bit
+    /// numbering.
+    BigEndian,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Multiplexing {
+    #[default]
+    None,
+    /// Selects which multiplexed signals are present.
+    Multiplexor,
+    /// Only present when the multiplexor has this value.
+    Multiplexed(u64),
+}
+
+#[derive(Clone, Debug)]
+pub struct Signal {
+    pub name: String,
+    pub start_bit: u32,
+    pub length: u32,
+    pub byte_order: ByteOrder,
+    pub signed: bool,
+    pub factor: f64,
+    pub offset: f64,
+    pub unit: String,
+    pub multiplexing: Multiplexing,
+    /// Labels for raw values, e.g. `0 => "Off"`. Keys of signed signals
+    /// are the sign-extended raw value.
+    pub values: BTreeMap<i64, String>,
+    pub comment: Option<String>,
+}
+
+impl Signal {
+    /// An unsigned little-endian signal with no scaling.
+    pub fn new(name: impl Into<String>, start_bit: u32, length: u32) -> Self {
+        Self {
+            name: name.into(),
+            start_bit,
+            length,
+            byte_order: ByteOrder::LittleEndian,
+            signed: false,
+            factor: 1.0,
+            offset: 0.0,
+            unit: String::new(),
+            multiplexing: Multiplexing::None,
+            values: BTreeMap::new(),
+            comment: None,
+        }
+    }
+
+    /// Positions of the signal's bits in the payload, least significant
+    /// first, or None if they run past the largest bit position.
+    fn bit_positions(&self) -> Option<Vec<u32>> {
+        match self.byte_order {
+            ByteOrder::LittleEndian => {
+                let end = self.start_bit.checked_add(self.length)?;
+                Some((self.start_bit..end).collect())
+            }
+            ByteOrder::BigEndian => {
+                // Walk from the MSB down, moving to the next byte's MSB at
+                // each byte boundary
+                let mut positions = Vec::with_capacity(self.length as usize);
+                let mut pos = self.start_bit;
+                for i in 0..self.length {
+                    positions.push(pos);
+                    if i + 1 < self.length {
+                        pos = if pos.is_multiple_of(8) {
+                            pos.checked_add(15)?
+                        } else {
+                            pos - 1
+                        };
+                    }
+                }
+                positions.reverse();
+                Some(positions)
+            }
+        }
+    }
+
+    /// How many payload bytes the signal spans, or None if it's empty,
+    /// longer than 64 bits, or its bits can't be laid out.
+    pub(crate) fn bytes_needed(&self) -> Option<usize> {
+        if self.length == 0 || self.length > 64 {
+            return None;
+        }
+        let last = self.bit_positions()?.into_iter().max()?;
+        Some(last as usize / 8 + 1)
+    }
+
+    /// The raw value, or None if the signal doesn't fit in `data`.
+    pub fn raw(&self, data: &[u8]) -> Option<u64> {
+        if self.length == 0 || self.length > 64 {
+            return None;
+        }
+
+        self.bit_positions()?
+            .into_iter()
+            .enumerate()
+            .try_fold(0, |value, (i, pos)| {
+                let byte = data.get(pos as usize / 8)?;
+                Some(value | u64::from(byte >> (pos % 8) & 1) << i)
+            })
+    }
+
+    /// Write the raw value for physical `value` into `data`, rounded and
+    /// clamped to what the signal can hold. Returns None, leaving `data`
+    /// unchanged, if the signal doesn't fit in `data`.
+    pub fn encode(&self, value: f64, data: &mut [u8]) -> Option<()> {
+        if self.length == 0 || self.length > 64 {
+            return None;
+        }
+        let positions = self.bit_positions()?;
+        if positions.iter().any(|&pos| pos as usize / 8 >= data.len()) {
+            return None;
+        }
+
+        let (min, max) = if self.signed {
+            (
+                -(1i128 << (self.length - 1)),
+                (1i128 << (self.length - 1)) - 1,
+            )
+        } else {
+            (0, (1i128 << self.length) - 1)
+        };
+        let raw = (((value - self.offset) / self.factor).round() as i128).clamp(min, max) as u64;
+
+        for (i, pos) in positions.into_iter().enumerate() {
+            let (byte, mask) = (pos as usize / 8, 1 << (pos % 8));
+            if raw >> i & 1 == 1 {
+                data[byte] |= mask;
+            } else {
+                data[byte] &= !mask;
+            }
+        }
+        Some(())
+    }
+
+    /// The raw value, sign-extended if the signal is signed.
+    fn raw_signed(&self, data: &[u8]) -> Option<i128> {
+        let raw = self.raw(data)?;
+        Some(
+            if self.signed && self.length < 64 && raw >> (self.length - 1) & 1 == 1 {
+                (raw | u64::MAX << self.length) as i64 as i128
+            } else if self.signed {
+                raw as i64 as i128
+            } else {
+                raw as i128
+            },
+        )
+    }
+
+    /// The scaled physical value, or None if the signal doesn't fit in
+    /// `data`.
+    pub fn decode(&self, data: &[u8]) -> Option<f64> {
+        Some(self.raw_signed(data)? as f64 * self.factor + self.offset)
+    }
+
+    /// The value table label for the value in `data`, if it has one.
+    pub fn label(&self, data: &[u8]) -> Option<&str> {
+        let raw = i64::try_from(self.raw_signed(data)?).ok()?;
+        self.values.get(&raw).map(String::as_str)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MessageDef {
+    pub id: CanId,
+    pub name: String,
+    /// Payload length in bytes.
+    pub length: usize,
+    /// How often the message is sent, if it's cyclic.
+    pub cycle_time: Option<Duration>,
+    pub signals: Vec<Signal>,
+    pub comment: Option<String>,
+}
+
+impl MessageDef {
+    /// Decode the signals present in `data`: every plain signal, the
+    /// multiplexor, and the multiplexed signals it selects. Signals that
+    /// don't fit in `data` are left out.
+    pub fn decode<'a>(&'a self, data: &[u8]) -> Vec<(&'a str, f64)> {
+        self.present(data)
+            .filter_map(|s| Some((s.name.as_str(), s.decode(data)?)))
+            .collect()
+    }
+
+    /// The signals present in `data`, given its multiplexor value.
+    fn present<'a>(&'a self, data: &[u8]) -> impl Iterator<Item = &'a Signal> {
+        let mux = self
+            .signals
+            .iter()
+            .find(|s| s.multiplexing == Multiplexing::Multiplexor)
+            .and_then(|s| s.raw(data));
+
+        self.signals.iter().filter(move |s| match s.multiplexing {
+            Multiplexing::None | Multiplexing::Multiplexor => true,
+            Multiplexing::Multiplexed(value) => mux == Some(value),
+        })
+    }
+}
+
+/// Message definitions by ID.
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    messages: HashMap<CanId, MessageDef>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, message: MessageDef) {
+        self.messages.insert(message.id, message);
+    }
+
+    pub fn message(&self, id: CanId) -> Option<&MessageDef> {
+        self.messages.get(&id)
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = &MessageDef> {
+        self.messages.values()
+    }
+
+    /// The signals in `msg`, or None if its ID isn't defined.
+    pub fn decode<'a>(&'a self, msg: &PyCanMessage) -> Option<Vec<(&'a str, f64)>> {
+        let def = self.messages.get(&msg.arbitration_id)?;
+        Some(def.decode(msg.data.as_deref().unwrap_or_default()))
+    }
+
+    /// `msg` for printing with its message name and signal values.
+    pub fn decorate<'a>(&'a self, msg: &'a PyCanMessage) -> DecoratedMessage<'a> {
+        DecoratedMessage {
+            msg,
+            def: self.messages.get(&msg.arbitration_id),
+        }
+    }
+}
+
+/// Signals shown by [`DecoratedMessage`] before the rest are elided,
+/// unless formatted with `{:#}`.
+const DECORATED_SIGNALS: usize = 6;
+
+/// A frame that displays as its usual [`Display`] output followed by its
+/// message name and signal values, e.g.
+/// `... | EngineData: EngineSpeed=1250 rpm, Gear=Drive`. Value table
+/// labels are shown instead of numbers where there is one. Frames with no
+/// definition display as usual.
+pub struct DecoratedMessage<'a> {
+    msg: &'a PyCanMessage,
+    def: Option<&'a MessageDef>,
+}
+
+impl Display for DecoratedMessage<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)?;
+        let Some(def) = self.def.filter(|_| !self.msg.is_error_frame) else {
+            return Ok(());
+        };
+
+        write!(f, " | {}", def.name)?;
+        let data = self.msg.data.as_deref().unwrap_or_default();
+        let limit = if f.alternate() {
+            usize::MAX
+        } else {
+            DECORATED_SIGNALS
+        };
+        let mut signals = def
+            .present(data)
+            .filter(|s| s.raw(data).is_some())
+            .peekable();
+        for (i, signal) in signals.by_ref().take(limit).enumerate() {
+            write!(f, "{}{}=", if i == 0 { ": " } else { ", " }, signal.name)?;
+            match (signal.label(data), signal.decode(data)) {
+                (Some(label), _) => write!(f, "{label}")?,
+                (None, Some(value)) if signal.unit.is_empty() => write!(f, "{value}")?,
+                (None, Some(value)) => write!(f, "{value} {}", signal.unit)?,
+                (None, None) => {}
+            }
+        }
+        if signals.peek().is_some() {
+            write!(f, ", ...")?;
+        }
+        Ok(())
+    }
+}
+
+impl PyCanInterface {
+    /// The signal definitions set with
+    /// [`crate::PyCanInterfaceBuilder::decoder`].
+    pub fn decoder(&self) -> Option<&Decoder> {
+        self.decoder.as_deref()
+    }
+
+    /// Register a callback for changes in the physical value of the signal
+    /// `name`. It's called with the first value received, then whenever
+    /// the value moves more than `deadband` away from the last one
+    /// reported. Frames where a multiplexed signal isn't present are
+    /// ignored.
+    ///
+    /// A signal name used by several messages must be qualified with its
+    /// message, as `Message.signal`; otherwise this fails with
+    /// [`PyCanError::AmbiguousSignal`].
+    pub fn subscribe_signal<R, E>(
+        &self,
+        name: &str,
+        deadband: f64,
+        on_change: R,
+        on_error: E,
+    ) -> Result<ListenerId, PyCanError>
+    where
+        R: Fn(f64, &PyCanMessage) + Send + 'static,
+        E: Fn(&PyErr) + Send + 'static,
+    {
+        let (message, signal_name) = match name.split_once('.') {
+            Some((message, signal)) => (Some(message), signal),
+            None => (None, name),
+        };
+        let mut found = self
+            .decoder
+            .iter()
+            .flat_map(|d| d.messages())
+            .filter(|m| message.is_none_or(|message| m.name == message))
+            .filter_map(|m| Some((m, m.signals.iter().find(|s| s.name == signal_name)?)));
+        let (def, signal) = found
+            .next()
+            .ok_or_else(|| PyCanError::UnknownSignal(name.into()))?;
+        if let Some((other, _)) = found.next() {
+            return Err(PyCanError::AmbiguousSignal(format!(
+                "{name} is in {} and {}",
+                def.name, other.name
+            )));
+        }
+        let id = def.id;
+
+        let mux = match signal.multiplexing {
+            Multiplexing::Multiplexed(value) => def
+                .signals
+                .iter()
+                .find(|s| s.multiplexing == Multiplexing::Multiplexor)
+                .cloned()
+                .map(|mux| (mux, value)),
+            _ => None,
+        };
+        let signal = signal.clone();
+        let last = Cell::new(None);
+
+        self.register_rx_callback(
+            move |msg| {
+                if msg.arbitration_id != id {
+                    return;
+                }
+                let data = msg.data.as_deref().unwrap_or_default();
+                if let Some((mux, value)) = &mux {
+                    if mux.raw(data) != Some(*value) {
+                        return;
+                    }
+                }
+
+                let Some(value) = signal.decode(data) else {
+                    return;
+                };
+                if last
+                    .get()
+                    .is_none_or(|last: f64| (value - last).abs() > deadband)
+                {
+                    last.set(Some(value));
+                    on_change(value, msg);
+                }
+            },
+            on_error,
+        )
+    }
+}
@@ -0,0 +1,250 @@
+//! Frames sent at a deadline, from a per-interface timing wheel.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use pyo3::Python;
+
+use crate::{describe_py_err, CanId, PyCanError, PyCanEvent, PyCanInterface};
+
+/// Wheel resolution. Frames go out within a tick of their deadline.
+const TICK: Duration = Duration::from_millis(1);
+
+/// Slots in the wheel. Deadlines further out than this many ticks wrap
+/// around and wait for later turns.
+const SLOTS: usize = 512;
+
+struct Entry {
+    key: u64,
+    /// Tick the frame is due in.
+    tick: u64,
+    deadline: Instant,
+    id: CanId,
+    data: Vec<u8>,
+}
+
+struct Wheel {
+    slots: Vec<Vec<Entry>>,
+    /// Last tick whose slot has been processed.
+    processed: u64,
+    len: usize,
+    stopped: bool,
+}
+
+/// Hashed timing wheel of pending frames, drained by one worker thread.
+pub(crate) struct TxWheel {
+    epoch: Instant,
+    wheel: Mutex<Wheel>,
+    wake: Condvar,
+    next_key: AtomicU64,
+}
+
+impl TxWheel {
+    /// Start the worker, which sends due frames on `iface` until the
+    /// wheel is stopped.
+    pub(crate) fn start(iface: Weak<PyCanInterface>) -> Arc<Self> {
+        let wheel = Arc::new(Self {
+            epoch: Instant::now(),
+            wheel: Mutex::new(Wheel {
+                slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+                processed: 0,
+                len: 0,
+                stopped: false,
+            }),
+            wake: Condvar::new(),
+            next_key: AtomicU64::new(0),
+        });
+
+        let worker = wheel.clone();
+        std::thread::spawn(move || worker.run(&iface));
+        wheel
+    }
+
+    /// Stop the worker. Frames still pending are dropped.
+    pub(crate) fn stop(&self) {
+        self.wheel.lock().unwrap().stopped = true;
+        self.wake.notify_all();
+    }
+
+    /// First tick at or after `at`.
+    fn tick_of(&self, at: Instant) -> u64 {
+        let elapsed = at.saturating_duration_since(self.epoch);
+        elapsed.as_nanos().div_ceil(TICK.as_nanos()) as u64
+    }
+
+    /// Latest tick that has started.
+    fn current_tick(&self) -> u64 {
+        (self.epoch.elapsed().as_nanos() / TICK.as_nanos()) as u64
+    }
+
+    fn instant_of(&self, tick: u64) -> Instant {
+        self.epoch + Duration::from_nanos(tick * TICK.as_nanos() as u64)
+    }
+
+    fn insert(self: &Arc<Self>, at: Instant, id: CanId, data: &[u8]) -> DeferredTx {
+        let key = self.next_key.fetch_add(1, Ordering::Relaxed);
+        let mut wheel = self.wheel.lock().unwrap();
+        // Deadlines already passed go out on the next tick
+        let tick = self.tick_of(at).max(wheel.processed + 1);
+        wheel.slots[tick as usize % SLOTS].push(Entry {
+            key,
+            tick,
+            deadline: at,
+            id,
+            data: data.to_vec(),
+        });
+        wheel.len += 1;
+        drop(wheel);
+        self.wake.notify_all();
+
+        DeferredTx {
+            key,
+            tick,
+            wheel: Arc::downgrade(self),
+        }
+    }
+
+    fn cancel(&self, key: u64, tick: u64) -> bool {
+        let mut wheel = self.wheel.lock().unwrap();
+        let slot = &mut wheel.slots[tick as usize % SLOTS];
+        let Some(idx) = slot.iter().position(|e| e.key == key) else {
+            return false;
+        };
+        slot.swap_remove(idx);
+        wheel.len -= 1;
+        true
+    }
+
+    /// The next tick with anything in its slot, if any.
+    fn next_busy_tick(wheel: &Wheel) -> Option<u64> {
+        (wheel.processed + 1..=wheel.processed + SLOTS as u64)
+            .find(|tick| !wheel.slots[*tick as usize % SLOTS].is_empty())
+    }
+
+    /// Wait for the next busy tick and take every frame due by then.
+    fn next_due(&self) -> Option<Vec<Entry>> {
+        let mut wheel = self.wheel.lock().unwrap();
+        loop {
+            if wheel.stopped {
+                return None;
+            }
+
+            let now = self.current_tick();
+            if wheel.len > 0 && now > wheel.processed {
+                let mut due = Vec::new();
+                // Past a full turn, every slot has been visited once
+                let first = wheel.processed + 1;
+                for tick in first.max(now.saturating_sub(SLOTS as u64 - 1))..=now {
+                    let slot = &mut wheel.slots[tick as usize % SLOTS];
+                    let mut i = 0;
+                    while i < slot.len() {
+                        if slot[i].tick <= now {
+                            due.push(slot.swap_remove(i));
+                        } else {
+                            i += 1;
+                        }
+                    }
+                }
+                wheel.processed = now;
+                wheel.len -= due.len();
+                if !due.is_empty() {
+                    due.sort_by_key(|e| (e.deadline, e.key));
+                    return Some(due);
+                }
+                continue;
+            }
+
+            wheel.processed = wheel.processed.max(now);
+            wheel = match Self::next_busy_tick(&wheel) {
+                Some(tick) => {
+                    let timeout = self
+                        .instant_of(tick)
+                        .saturating_duration_since(Instant::now());
+                    self.wake.wait_timeout(wheel, timeout).unwrap().0
+                }
+                None => self.wake.wait(wheel).unwrap(),
+            };
+        }
+    }
+
+    fn run(&self, iface: &Weak<PyCanInterface>) {
+        while let Some(due) = self.next_due() {
+            let Some(iface) = iface.upgrade() else {
+                return;
+            };
+            for entry in due {
+                iface.send_deferred(entry.id, &entry.data);
+            }
+        }
+    }
+}
+
+/// A frame waiting to be sent by [`PyCanInterface::send_at`]. Dropping
+/// the handle doesn't cancel it.
+#[derive(Debug)]
+pub struct DeferredTx {
+    key: u64,
+    tick: u64,
+    wheel: Weak<TxWheel>,
+}
+
+impl DeferredTx {
+    /// Don't send the frame after all. Returns false if it has already
+    /// been sent.
+    pub fn cancel(self) -> bool {
+        self.wheel
+            .upgrade()
+            .is_some_and(|wheel| wheel.cancel(self.key, self.tick))
+    }
+}
+
+impl PyCanInterface {
+    /// Send a frame at `at` instead of now, e.g. a diagnostic response
+    /// due within a protocol deadline. Frames are sent from one
+    /// background thread per interface, within about a millisecond of
+    /// their deadline, in deadline order. Send failures are reported as
+    /// [`PyCanEvent::DeferredTxFailed`].
+    pub fn send_at(self: &Arc<Self>, id: CanId, data: &[u8], at: Instant) -> DeferredTx {
+        let wheel = self
+            .tx_wheel
+            .get_or_init(|| TxWheel::start(Arc::downgrade(self)));
+        wheel.insert(at, id, data)
+    }
+
+    /// Send a frame once `delay` has passed. Fails if `delay` is too long
+    /// to schedule.
+    pub fn send_after(
+        self: &Arc<Self>,
+        id: CanId,
+        data: &[u8],
+        delay: Duration,
+    ) -> Result<DeferredTx, PyCanError> {
+        let at = Instant::now()
+            .checked_add(delay)
+            .ok_or_else(|| PyCanError::InvalidDuration(format!("send delay of {delay:?}")))?;
+        Ok(self.send_at(id, data, at))
+    }
+
+    fn send_deferred(&self, id: CanId, data: &[u8]) {
+        let Some((id, data)) = self.intercept_tx(id, data) else {
+            return;
+        };
+        let token = self.tx_token(id, &data);
+        let res = Python::with_gil(|py| {
+            self.send_once(py, id, &data, None)
+                .map_err(|e| describe_py_err(&e))
+        });
+        if let Err(reason) = res {
+            if let Some(pending) = &self.pending_tx {
+                pending.cancel(token);
+            }
+            self.events
+                .emit(PyCanEvent::DeferredTxFailed { id, reason });
+        }
+    }
+}
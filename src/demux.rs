@@ -0,0 +1,106 @@
+//! Split incoming traffic into per-node streams.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+};
+
+use pyo3::PyErr;
+
+use crate::{CanId, ListenerId, PyCanError, PyCanInterface, PyCanMessage};
+
+/// The bits of an arbitration ID that identify the sending node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdField {
+    pub shift: u32,
+    pub width: u32,
+}
+
+impl IdField {
+    pub fn new(shift: u32, width: u32) -> Self {
+        Self { shift, width }
+    }
+
+    /// Low byte of the ID, e.g. the J1939 source address.
+    pub fn low_byte() -> Self {
+        Self::new(0, 8)
+    }
+
+    pub fn extract(&self, id: CanId) -> u32 {
+        let mask = 1u32.checked_shl(self.width).map_or(u32::MAX, |m| m - 1);
+        id.raw().checked_shr(self.shift).unwrap_or(0) & mask
+    }
+}
+
+type Streams = HashMap<u32, Sender<PyCanMessage>>;
+
+/// Per-node streams of received frames, created by [`PyCanInterface::demux`].
+pub struct Demuxer {
+    listener: ListenerId,
+    streams: Arc<Mutex<Streams>>,
+    new_nodes: Receiver<(u32, Receiver<PyCanMessage>)>,
+}
+
+impl Demuxer {
+    /// The listener feeding this demuxer; remove it to stop demultiplexing.
+    pub fn listener_id(&self) -> ListenerId {
+        self.listener
+    }
+
+    /// Stream of frames from `node`. Replaces any existing stream for it.
+    pub fn node(&self, node: u32) -> Receiver<PyCanMessage> {
+        let (tx, rx) = channel();
+        self.streams.lock().unwrap().insert(node, tx);
+        rx
+    }
+
+    /// Streams for nodes seen for the first time, which weren't requested
+    /// with `node` beforehand.
+    pub fn new_nodes(&self) -> &Receiver<(u32, Receiver<PyCanMessage>)> {
+        &self.new_nodes
+    }
+}
+
+impl PyCanInterface {
+    /// Demultiplex received frames by the node number in `field` of their
+    /// IDs. Streams whose receiver is dropped are discarded, and recreated
+    /// (as a new node) if that node sends again.
+    pub fn demux<E>(&self, field: IdField, on_error: E) -> Result<Demuxer, PyCanError>
+    where
+        E: Fn(&PyErr) + Send + 'static,
+    {
+        let streams = Arc::new(Mutex::new(Streams::new()));
+        let (new_tx, new_nodes) = channel();
+
+        let rx_streams = streams.clone();
+        let listener = self.register_rx_callback(
+            move |msg| {
+                let node = field.extract(msg.arbitration_id);
+                let mut streams = rx_streams.lock().unwrap();
+
+                if let Some(tx) = streams.get(&node) {
+                    if tx.send(msg.clone()).is_ok() {
+                        return;
+                    }
+                    streams.remove(&node);
+                }
+
+                let (tx, rx) = channel();
+                if new_tx.send((node, rx)).is_ok() {
+                    let _ = tx.send(msg.clone());
+                    streams.insert(node, tx);
+                }
+            },
+            on_error,
+        )?;
+
+        Ok(Demuxer {
+            listener,
+            streams,
+            new_nodes,
+        })
+    }
+}
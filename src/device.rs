@@ -0,0 +1,196 @@
+//! Declaring a simulated ECU's TX table and RX handlers in one place.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    CanId, Filter, ListenerId, PyCanError, PyCanInterface, PyCanMessage, TaskGroup, TxToken,
+};
+
+type Handler = Box<dyn Fn(&Device, &PyCanMessage) + Send + Sync>;
+
+/// Declares what a [`Device`] sends and how it reacts to what it
+/// receives.
+///
+/// ```ignore
+/// let ecu = Device::builder()
+///     .periodic("status", CanId::Standard(0x100), &[0; 8], Duration::from_millis(10))
+///     .event("ack", CanId::Standard(0x101), &[0x01])
+///     .on_rx(Filter::id(CanId::Standard(0x200)), |ecu, msg| {
+///         let _ = ecu.send_event("ack");
+///     })
+///     .start(&iface)?;
+/// ```
+#[derive(Default)]
+pub struct DeviceBuilder {
+    periodic: Vec<(String, CanId, Vec<u8>, Duration)>,
+    events: Vec<(String, CanId, Vec<u8>)>,
+    handlers: Vec<(Filter, Handler)>,
+}
+
+impl DeviceBuilder {
+    /// Send `data` on `id` every `period` while the device runs.
+    pub fn periodic(mut self, name: &str, id: CanId, data: &[u8], period: Duration) -> Self {
+        self.periodic.push((name.into(), id, data.to_vec(), period));
+        self
+    }
+
+    /// A message sent on demand with [`Device::send_event`], starting out
+    /// with `data` as its payload.
+    pub fn event(mut self, name: &str, id: CanId, data: &[u8]) -> Self {
+        self.events.push((name.into(), id, data.to_vec()));
+        self
+    }
+
+    /// Call `handler` for every received frame matching `filter`. A frame
+    /// matching several filters goes to each of their handlers, in the
+    /// order they were added.
+    pub fn on_rx<H>(mut self, filter: Filter, handler: H) -> Self
+    where
+        H: Fn(&Device, &PyCanMessage) + Send + Sync + 'static,
+    {
+        self.handlers.push((filter, Box::new(handler)));
+        self
+    }
+
+    /// Start the periodic messages and handlers on `iface`.
+    pub fn start(self, iface: &Arc<PyCanInterface>) -> Result<Device, PyCanError> {
+        let mut tasks = TaskGroup::new();
+        for (name, id, data, period) in &self.periodic {
+            tasks.add(iface, name, *id, data, *period)?;
+        }
+
+        let inner = Arc::new(DeviceInner {
+            iface: iface.clone(),
+            tasks: Mutex::new(tasks),
+            events: self
+                .events
+                .into_iter()
+                .map(|(name, id, data)| (name, (id, Mutex::new(data))))
+                .collect(),
+        });
+
+        let mut guard = None;
+        if !self.handlers.is_empty() {
+            // Handlers get a device without the guard, so the listener
+            // is only ever removed from outside its own callback
+            let device = Device {
+                inner: inner.clone(),
+                _guard: None,
+            };
+            let handlers = self.handlers;
+            let listener = iface.register_rx_callback(
+                move |msg| {
+                    for (filter, handler) in &handlers {
+                        if filter.matches(msg.arbitration_id) {
+                            handler(&device, msg);
+                        }
+                    }
+                },
+                |_| {},
+            )?;
+            guard = Some(Arc::new(ListenerGuard {
+                iface: iface.clone(),
+                listener,
+            }));
+        }
+
+        Ok(Device {
+            inner,
+            _guard: guard,
+        })
+    }
+}
+
+struct DeviceInner {
+    iface: Arc<PyCanInterface>,
+    tasks: Mutex<TaskGroup>,
+    /// Event messages by name, with their current payloads.
+    events: HashMap<String, (CanId, Mutex<Vec<u8>>)>,
+}
+
+/// Removes the handlers' listener on drop.
+struct ListenerGuard {
+    iface: Arc<PyCanInterface>,
+    listener: ListenerId,
+}
+
+impl Drop for ListenerGuard {
+    fn drop(&mut self) {
+        let _ = self.iface.remove_listener(self.listener);
+    }
+}
+
+/// A simulated ECU started from a [`DeviceBuilder`]. Clones share the same
+/// device, which stops once every clone is dropped. Clones of the device
+/// passed to handlers don't keep it running.
+#[derive(Clone)]
+pub struct Device {
+    inner: Arc<DeviceInner>,
+    _guard: Option<Arc<ListenerGuard>>,
+}
+
+impl Device {
+    pub fn builder() -> DeviceBuilder {
+        DeviceBuilder::default()
+    }
+
+    pub fn iface(&self) -> &Arc<PyCanInterface> {
+        &self.inner.iface
+    }
+
+    /// Send the event message `name` with its current payload.
+    pub fn send_event(&self, name: &str) -> Result<TxToken, PyCanError> {
+        let (id, data) = self.event(name)?;
+        let data = data.lock().unwrap().clone();
+        self.send(*id, &data)
+    }
+
+    /// Send the event message `name` with `data`, which becomes its
+    /// current payload.
+    pub fn send_event_with(&self, name: &str, data: &[u8]) -> Result<TxToken, PyCanError> {
+        let (id, current) = self.event(name)?;
+        *current.lock().unwrap() = data.to_vec();
+        self.send(*id, data)
+    }
+
+    /// Change the payload of the periodic or event message `name`.
+    /// Periodic messages pick it up from their next cycle.
+    pub fn update(&self, name: &str, data: &[u8]) -> Result<(), PyCanError> {
+        if let Some((_, current)) = self.inner.events.get(name) {
+            *current.lock().unwrap() = data.to_vec();
+            return Ok(());
+        }
+        self.inner.tasks.lock().unwrap().update(&[(name, data)])
+    }
+
+    /// Start or stop the periodic message `name`.
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> Result<(), PyCanError> {
+        self.inner.tasks.lock().unwrap().set_enabled(name, enabled)
+    }
+
+    /// Stop every periodic message, e.g. to simulate the ECU going
+    /// silent. Handlers keep running.
+    pub fn stop_all(&self) -> Result<(), PyCanError> {
+        self.inner.tasks.lock().unwrap().stop_all()
+    }
+
+    /// Restart every enabled periodic message.
+    pub fn start_all(&self) -> Result<(), PyCanError> {
+        self.inner.tasks.lock().unwrap().start_all()
+    }
+
+    fn event(&self, name: &str) -> Result<&(CanId, Mutex<Vec<u8>>), PyCanError> {
+        self.inner
+            .events
+            .get(name)
+            .ok_or_else(|| PyCanError::UnknownMessage(name.into()))
+    }
+
+    fn send(&self, id: CanId, data: &[u8]) -> Result<TxToken, PyCanError> {
+        self.inner.iface.send_checked(id, data)
+    }
+}
@@ -0,0 +1,284 @@
+//! Batched delivery of received frames.
+//!
+//! Instead of python-can's notifier calling into Rust once per frame, a
+//! single `can.BufferedReader` collects frames and a Rust thread drains
+//! what's queued, up to a batch limit, in one GIL acquisition, then runs
+//! the callbacks with the GIL released. Optionally, callbacks run on a pool of worker threads
+//! so a slow callback doesn't hold up draining.
+//!
+//! With a worker pool, each subscription picks its own ordering guarantee
+//! with [`DeliveryOrder`]. Without one, every callback runs on the drain
+//! thread and sees frames in receive order.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Sender},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use pyo3::{
+    types::{IntoPyDict, PyCFunction, PyDict, PyTuple},
+    Py, PyAny, PyErr, Python,
+};
+
+use crate::{
+    describe_py_err, extraction_error, notifier_exception, Frames, GilOp, Inbound, ListenerId,
+    PyCanError, PyCanEvent, PyCanInterface, PyCanMessage,
+};
+
+/// How long the drain thread waits for a frame before checking whether
+/// the interface is still alive.
+const POLL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Order in which a subscription's callback sees received frames when
+/// callbacks run on worker threads (see
+/// [`crate::PyCanInterfaceBuilder::callback_workers`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeliveryOrder {
+    /// Every frame in receive order. All global subscriptions share one
+    /// thread, separate from the worker pool.
+    Global,
+    /// Frames with the same ID in receive order; frames with different
+    /// IDs may be reordered.
+    #[default]
+    PerId,
+    /// No ordering. Frames are spread across workers round-robin and the
+    /// callback may run concurrently with itself.
+    Unordered,
+}
+
+type RxCallback = Box<dyn Fn(&PyCanMessage) + Send + Sync>;
+type ErrorCallback = Box<dyn Fn(&PyErr) + Send>;
+
+struct Subscriber {
+    id: ListenerId,
+    frames: Frames,
+    order: DeliveryOrder,
+    on_rx: RxCallback,
+    on_error: Mutex<ErrorCallback>,
+}
+
+/// Rust callbacks fed by the drain thread. The list is copied on write
+/// and callbacks run on a snapshot, so a callback can register or remove
+/// listeners, including itself. A removed callback may still see the
+/// frame being delivered.
+#[derive(Clone, Default)]
+pub(crate) struct Dispatcher {
+    subscribers: Arc<RwLock<Arc<Vec<Arc<Subscriber>>>>>,
+}
+
+impl Dispatcher {
+    pub(crate) fn add<R, E>(
+        &self,
+        frames: Frames,
+        order: DeliveryOrder,
+        on_rx: R,
+        on_error: E,
+    ) -> ListenerId
+    where
+        R: Fn(&PyCanMessage) + Send + Sync + 'static,
+        E: Fn(&PyErr) + Send + 'static,
+    {
+        let id = ListenerId::next();
+        let subscriber = Arc::new(Subscriber {
+            id,
+            frames,
+            order,
+            on_rx: Box::new(on_rx),
+            on_error: Mutex::new(Box::new(on_error)),
+        });
+        let mut subscribers = self.subscribers.write().unwrap();
+        let mut list = Vec::clone(&subscribers);
+        list.push(subscriber);
+        *subscribers = Arc::new(list);
+        id
+    }
+
+    /// Returns whether `id` was subscribed.
+    pub(crate) fn remove(&self, id: ListenerId) -> bool {
+        let mut subscribers = self.subscribers.write().unwrap();
+        if !subscribers.iter().any(|s| s.id == id) {
+            return false;
+        }
+        *subscribers = Arc::new(subscribers.iter().filter(|s| s.id != id).cloned().collect());
+        true
+    }
+
+    /// The current subscribers, for running callbacks without the lock.
+    fn snapshot(&self) -> Arc<Vec<Arc<Subscriber>>> {
+        self.subscribers.read().unwrap().clone()
+    }
+
+    /// Run the callbacks of subscribers with the given ordering, or of
+    /// every subscriber if `order` is None.
+    fn deliver(&self, msg: &PyCanMessage, order: Option<DeliveryOrder>) {
+        self.snapshot()
+            .iter()
+            .filter(|s| order.is_none_or(|order| s.order == order))
+            .filter(|s| s.frames.accepts(msg))
+            .for_each(|s| (s.on_rx)(msg));
+    }
+
+    fn error(&self, err: &PyErr) {
+        self.snapshot()
+            .iter()
+            .for_each(|s| (s.on_error.lock().unwrap())(err));
+    }
+
+    fn has_subscribers(&self, order: DeliveryOrder) -> bool {
+        self.snapshot().iter().any(|s| s.order == order)
+    }
+
+    fn spawn_lane(&self) -> Sender<(PyCanMessage, DeliveryOrder)> {
+        let (tx, rx) = channel::<(PyCanMessage, DeliveryOrder)>();
+        let dispatcher = self.clone();
+        std::thread::spawn(move || {
+            rx.iter()
+                .for_each(|(msg, order)| dispatcher.deliver(&msg, Some(order)))
+        });
+        tx
+    }
+
+    /// Deliver batches from the drain thread until the interface is dropped,
+    /// inline or on `workers` worker threads plus one thread for
+    /// [`DeliveryOrder::Global`] subscriptions.
+    fn run(&self, workers: usize, alive: &AtomicBool, mut next_batch: impl FnMut() -> Batch) {
+        let pool: Vec<_> = (0..workers).map(|_| self.spawn_lane()).collect();
+        let global = (workers > 0).then(|| self.spawn_lane());
+        let mut next_worker = 0;
+
+        while alive.load(Ordering::Relaxed) {
+            let batch = next_batch();
+
+            let Some(global) = &global else {
+                for item in batch {
+                    match item {
+                        Ok(msg) => self.deliver(&msg, None),
+                        Err(err) => self.error(&err),
+                    }
+                }
+                continue;
+            };
+
+            // Checked once per batch, so a subscription added mid-batch
+            // starts receiving from the next one.
+            let routes = [
+                DeliveryOrder::Global,
+                DeliveryOrder::PerId,
+                DeliveryOrder::Unordered,
+            ]
+            .map(|order| self.has_subscribers(order));
+
+            for item in batch {
+                let msg = match item {
+                    Ok(msg) => msg,
+                    Err(err) => {
+                        self.error(&err);
+                        continue;
+                    }
+                };
+
+                if routes[0] {
+                    let _ = global.send((msg.clone(), DeliveryOrder::Global));
+                }
+                if routes[1] {
+                    let worker = msg.arbitration_id.raw() as usize % pool.len();
+                    let _ = pool[worker].send((msg.clone(), DeliveryOrder::PerId));
+                }
+                if routes[2] {
+                    next_worker = (next_worker + 1) % pool.len();
+                    let _ = pool[next_worker].send((msg, DeliveryOrder::Unordered));
+                }
+            }
+        }
+    }
+}
+
+type Batch = Vec<Result<PyCanMessage, PyErr>>;
+
+/// Take up to `max_batch` frames queued in `reader`, waiting up to
+/// POLL_TIMEOUT for the first.
+fn drain(py: Python, reader: &Py<PyAny>, inbound: &Inbound, max_batch: usize) -> Batch {
+    let mut batch = Vec::new();
+    let mut timeout = POLL_TIMEOUT.as_secs_f64();
+    // Waiting for the first frame releases the GIL, so timing starts once
+    // there's one
+    let mut start = None;
+
+    for _ in 0..max_batch {
+        let obj = match reader.call_method1(py, "get_message", (timeout,)) {
+            Ok(obj) if obj.is_none(py) => break,
+            Ok(obj) => obj,
+            Err(e) => {
+                batch.push(Err(e));
+                break;
+            }
+        };
+        start.get_or_insert_with(Instant::now);
+
+        let obj = obj.as_ref(py);
+        match obj.extract::<PyCanMessage>() {
+            Ok(msg) => batch.extend(inbound.prepare(msg).map(Ok)),
+            Err(e) => batch.push(Err(extraction_error(Some(obj), e))),
+        }
+        timeout = 0.0;
+    }
+
+    if let Some(start) = start {
+        inbound.gil.record(GilOp::Recv, start.elapsed());
+    }
+    batch
+}
+
+impl PyCanInterface {
+    /// Add the BufferedReader to the notifier and start draining it.
+    pub(crate) fn start_dispatcher(
+        &self,
+        dispatcher: &Dispatcher,
+        workers: usize,
+        max_batch: usize,
+    ) -> Result<(), PyCanError> {
+        let events = self.events.clone();
+        let on_error = dispatcher.clone();
+
+        let reader = Python::with_gil(|py| -> Result<Py<PyAny>, PyCanError> {
+            let error_shim = PyCFunction::new_closure(
+                py,
+                None,
+                None,
+                move |args: &PyTuple, _kwargs: Option<&PyDict>| {
+                    let err = notifier_exception(args);
+                    events.emit(PyCanEvent::NotifierError(err.to_string()));
+                    on_error.error(&err);
+                },
+            )
+            .map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))?;
+
+            let methods = [("on_error", error_shim)].into_py_dict(py);
+            let reader = self
+                .make_listener(py, "BufferedReader", methods)
+                .map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))?;
+
+            self.notifier
+                .call_method1(py, "add_listener", (reader,))
+                .map_err(|e| PyCanError::FailedToAddListener(describe_py_err(&e)))?;
+
+            Ok(reader.into())
+        })?;
+
+        let dispatcher = dispatcher.clone();
+        let alive = self.alive.clone();
+        let inbound = self.inbound();
+
+        std::thread::spawn(move || {
+            dispatcher.run(workers, &alive, || {
+                Python::with_gil(|py| drain(py, &reader, &inbound, max_batch))
+            })
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Environment diagnostics: is everything pycanrs needs installed?
+
+use std::fmt::Display;
+
+use pyo3::{PyResult, Python};
+
+/// The outcome of one check.
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    /// Version found, or why the check failed.
+    pub detail: String,
+    /// What to do about a failure.
+    pub remedy: Option<&'static str>,
+    /// Whether pycanrs can work at all without this.
+    pub required: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// Whether every required check passed.
+    pub fn ok(&self) -> bool {
+        self.checks.iter().all(|c| c.ok || !c.required)
+    }
+}
+
+impl Display for DoctorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for check in &self.checks {
+            let status = match (check.ok, check.required) {
+                (true, _) => "ok",
+                (false, true) => "FAIL",
+                (false, false) => "missing",
+            };
+            writeln!(f, "[{status:>7}] {}: {}", check.name, check.detail)?;
+            if let (false, Some(remedy)) = (check.ok, check.remedy) {
+                writeln!(f, "          -> {remedy}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Python modules to look for: (check name, module, required, remedy).
+const MODULES: &[(&str, &str, bool, &str)] = &[
+    ("python-can", "can", true, "pip install python-can"),
+    ("pyserial (slcan)", "serial", false, "pip install pyserial"),
+    ("pyusb (gs_usb)", "usb", false, "pip install pyusb"),
+    ("gs_usb", "gs_usb", false, "pip install gs_usb"),
+    ("isotp", "isotp", false, "pip install can-isotp"),
+    ("cantools", "cantools", false, "pip install cantools"),
+];
+
+fn module_version(py: Python, module: &str) -> PyResult<String> {
+    let module = py.import(module)?;
+    Ok(module
+        .getattr("__version__")
+        .and_then(|v| v.extract())
+        .unwrap_or_else(|_| "installed".into()))
+}
+
+fn check(name: &'static str, required: bool, remedy: &'static str, res: PyResult<String>) -> Check {
+    let ok = res.is_ok();
+    Check {
+        name,
+        ok,
+        detail: res.unwrap_or_else(|e| e.to_string()),
+        remedy: (!ok).then_some(remedy),
+        required,
+    }
+}
+
+/// Check for Python, python-can, optional packages and backend drivers.
+pub fn doctor() -> DoctorReport {
+    Python::with_gil(|py| {
+        let mut checks = vec![check(
+            "python",
+            true,
+            "install Python 3 and make sure pycanrs was built against it",
+            py.import("sys")
+                .and_then(|sys| sys.getattr("version"))
+                .and_then(|v| v.extract()),
+        )];
+
+        checks.extend(MODULES.iter().map(|&(name, module, required, remedy)| {
+            check(name, required, remedy, module_version(py, module))
+        }));
+
+        if cfg!(target_os = "linux") {
+            checks.push(Check {
+                name: "socketcan",
+                ok: std::path::Path::new("/proc/net/can").exists(),
+                detail: "kernel CAN support (/proc/net/can)".into(),
+                remedy: Some("load the can and can_raw kernel modules: modprobe can_raw"),
+                required: false,
+            });
+        }
+
+        let libusb = py
+            .import("usb.backend.libusb1")
+            .and_then(|libusb1| libusb1.call_method0("get_backend"))
+            .map(|backend| !backend.is_none());
+        checks.push(Check {
+            name: "libusb (gs_usb)",
+            ok: matches!(libusb, Ok(true)),
+            detail: match libusb {
+                Ok(true) => "found".into(),
+                Ok(false) => "pyusb couldn't load libusb".into(),
+                Err(e) => e.to_string(),
+            },
+            remedy: Some(
+                "install libusb (e.g. brew install libusb), or pass its path to \
+                 PyCanInterfaceBuilder::libusb_path",
+            ),
+            required: false,
+        });
+
+        if cfg!(windows) {
+            checks.push(check(
+                "Vector XL driver",
+                false,
+                "install the Vector XL Driver Library",
+                py.import("can.interfaces.vector.xldriver")
+                    .map(|_| "found".into()),
+            ));
+        }
+
+        DoctorReport { checks }
+    })
+}
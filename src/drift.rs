@@ -0,0 +1,61 @@
+//! Rebasing remote timestamps onto the local clock.
+//!
+//! socketcand stamps frames on the remote host, whose clock drifts
+//! relative to ours. Each received frame gives a sample of
+//! `local - remote`, which is the clock offset plus the (variable)
+//! network delay. Since delay is never negative, the smallest samples are
+//! the best estimate of the offset: the estimate drops immediately to a
+//! smaller sample and creeps slowly towards larger ones, so it follows
+//! drift without chasing delay spikes.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{PyCanInterface, PyCanMessage};
+
+/// Weight given to a sample above the current estimate.
+const RISE_GAIN: f64 = 0.001;
+
+#[derive(Clone, Default)]
+pub(crate) struct DriftEstimator {
+    offset: Arc<Mutex<Option<f64>>>,
+}
+
+impl DriftEstimator {
+    /// Feed the frame's timestamp to the estimator and set its
+    /// `corrected_timestamp`. Frames without a timestamp are left alone.
+    pub(crate) fn correct(&self, msg: &mut PyCanMessage) {
+        let Some(remote) = msg.timestamp else {
+            return;
+        };
+
+        let local = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let sample = local - remote;
+
+        let mut offset = self.offset.lock().unwrap();
+        let estimate = match *offset {
+            Some(estimate) if sample > estimate => estimate + RISE_GAIN * (sample - estimate),
+            _ => sample,
+        };
+        *offset = Some(estimate);
+
+        msg.corrected_timestamp = Some(remote + estimate);
+    }
+
+    fn offset(&self) -> Option<f64> {
+        *self.offset.lock().unwrap()
+    }
+}
+
+impl PyCanInterface {
+    /// Current estimate of `local - remote` clock offset in seconds, if
+    /// drift correction is enabled and a timestamped frame has been received.
+    pub fn clock_offset(&self) -> Option<f64> {
+        self.drift.as_ref().and_then(DriftEstimator::offset)
+    }
+}
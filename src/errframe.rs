@@ -0,0 +1,294 @@
+//! Decoding SocketCAN error frames, which carry the error class in the ID
+//! and details in the payload (see `linux/can/error.h`).
+
+use std::fmt::{self, Display};
+
+use crate::{BusState, PyCanMessage};
+
+const ERR_TX_TIMEOUT: u32 = 0x001;
+const ERR_LOSTARB: u32 = 0x002;
+const ERR_CRTL: u32 = 0x004;
+const ERR_PROT: u32 = 0x008;
+const ERR_TRX: u32 = 0x010;
+const ERR_ACK: u32 = 0x020;
+const ERR_BUSOFF: u32 = 0x040;
+const ERR_BUSERROR: u32 = 0x080;
+const ERR_RESTARTED: u32 = 0x100;
+const ERR_CNT: u32 = 0x200;
+
+/// Controller status, from byte 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ControllerProblem {
+    RxOverflow,
+    TxOverflow,
+    /// Receive error counter reached the warning level.
+    RxWarning,
+    TxWarning,
+    /// Receive error counter reached the error-passive level.
+    RxPassive,
+    TxPassive,
+    /// Back to error-active.
+    Active,
+}
+
+const CONTROLLER_PROBLEMS: [(u8, ControllerProblem); 7] = [
+    (0x01, ControllerProblem::RxOverflow),
+    (0x02, ControllerProblem::TxOverflow),
+    (0x04, ControllerProblem::RxWarning),
+    (0x08, ControllerProblem::TxWarning),
+    (0x10, ControllerProblem::RxPassive),
+    (0x20, ControllerProblem::TxPassive),
+    (0x40, ControllerProblem::Active),
+];
+
+/// Kind of protocol violation, from byte 2.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProtocolError {
+    Bit,
+    Form,
+    Stuff,
+    /// Couldn't send a dominant bit.
+    Bit0,
+    /// Couldn't send a recessive bit.
+    Bit1,
+    Overload,
+    /// Active error announcement.
+    ActiveErrorFlag,
+    /// The error occurred while transmitting.
+    OnTransmit,
+}
+
+const PROTOCOL_ERRORS: [(u8, ProtocolError); 8] = [
+    (0x01, ProtocolError::Bit),
+    (0x02, ProtocolError::Form),
+    (0x04, ProtocolError::Stuff),
+    (0x08, ProtocolError::Bit0),
+    (0x10, ProtocolError::Bit1),
+    (0x20, ProtocolError::Overload),
+    (0x40, ProtocolError::ActiveErrorFlag),
+    (0x80, ProtocolError::OnTransmit),
+];
+
+/// Where in the frame a protocol violation was detected, from byte 3.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProtocolLocation {
+    Unspecified,
+    StartOfFrame,
+    /// ID bits 28-21, or 10-3 of a standard ID.
+    Id28To21,
+    /// ID bits 20-18, or 2-0 of a standard ID.
+    Id20To18,
+    SubstituteRtr,
+    IdExtension,
+    Id17To13,
+    Id12To5,
+    Id4To0,
+    Rtr,
+    Reserved1,
+    Reserved0,
+    Dlc,
+    Data,
+    CrcSequence,
+    CrcDelimiter,
+    AckSlot,
+    AckDelimiter,
+    EndOfFrame,
+    Intermission,
+    Other(u8),
+}
+
+impl ProtocolLocation {
+    fn from_code(code: u8) -> Self {
+        match code {
+            0x00 => Self::Unspecified,
+            0x03 => Self::StartOfFrame,
+            0x02 => Self::Id28To21,
+            0x06 => Self::Id20To18,
+            0x04 => Self::SubstituteRtr,
+            0x05 => Self::IdExtension,
+            0x07 => Self::Id17To13,
+            0x0f => Self::Id12To5,
+            0x0e => Self::Id4To0,
+            0x0c => Self::Rtr,
+            0x0d => Self::Reserved1,
+            0x09 => Self::Reserved0,
+            0x0b => Self::Dlc,
+            0x0a => Self::Data,
+            0x08 => Self::CrcSequence,
+            0x18 => Self::CrcDelimiter,
+            0x19 => Self::AckSlot,
+            0x1b => Self::AckDelimiter,
+            0x1a => Self::EndOfFrame,
+            0x12 => Self::Intermission,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolViolation {
+    pub errors: Vec<ProtocolError>,
+    pub location: ProtocolLocation,
+}
+
+/// State of one bus wire, from byte 4.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WireStatus {
+    Unspecified,
+    NoWire,
+    ShortToBattery,
+    ShortToVcc,
+    ShortToGround,
+    /// CAN_L shorted to CAN_H.
+    ShortToCanH,
+    Other(u8),
+}
+
+impl WireStatus {
+    fn from_nibble(nibble: u8) -> Self {
+        match nibble {
+            0x0 => Self::Unspecified,
+            0x4 => Self::NoWire,
+            0x5 => Self::ShortToBattery,
+            0x6 => Self::ShortToVcc,
+            0x7 => Self::ShortToGround,
+            0x8 => Self::ShortToCanH,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransceiverStatus {
+    pub can_h: WireStatus,
+    pub can_l: WireStatus,
+}
+
+/// A decoded SocketCAN error frame. Detail fields are only set if the
+/// frame's error class says they're valid.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SocketcanError {
+    pub tx_timeout: bool,
+    /// Bit arbitration was lost in, or 0 if unknown.
+    pub lost_arbitration: Option<u8>,
+    pub controller: Vec<ControllerProblem>,
+    pub protocol: Option<ProtocolViolation>,
+    pub transceiver: Option<TransceiverStatus>,
+    /// Nothing acknowledged a transmitted frame.
+    pub no_ack: bool,
+    pub bus_off: bool,
+    pub bus_error: bool,
+    /// The controller was restarted after bus-off.
+    pub restarted: bool,
+    /// TX and RX error counters.
+    pub error_counters: Option<(u8, u8)>,
+}
+
+impl SocketcanError {
+    /// Decode `msg`, or None if it isn't an error frame.
+    pub fn decode(msg: &PyCanMessage) -> Option<Self> {
+        if !msg.is_error_frame {
+            return None;
+        }
+        let class = msg.arbitration_id.raw();
+        let data = msg.data.as_deref().unwrap_or_default();
+        let byte = |i: usize| data.get(i).copied().unwrap_or_default();
+        let set = |flag: u32| class & flag != 0;
+
+        Some(Self {
+            tx_timeout: set(ERR_TX_TIMEOUT),
+            lost_arbitration: set(ERR_LOSTARB).then(|| byte(0)),
+            controller: if set(ERR_CRTL) {
+                CONTROLLER_PROBLEMS
+                    .iter()
+                    .filter(|(bit, _)| byte(1) & bit != 0)
+                    .map(|(_, problem)| *problem)
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            protocol: set(ERR_PROT).then(|| ProtocolViolation {
+                errors: PROTOCOL_ERRORS
+                    .iter()
+                    .filter(|(bit, _)| byte(2) & bit != 0)
+                    .map(|(_, error)| *error)
+                    .collect(),
+                location: ProtocolLocation::from_code(byte(3)),
+            }),
+            transceiver: set(ERR_TRX).then(|| TransceiverStatus {
+                can_h: WireStatus::from_nibble(byte(4) & 0x0f),
+                can_l: WireStatus::from_nibble(byte(4) >> 4),
+            }),
+            no_ack: set(ERR_ACK),
+            bus_off: set(ERR_BUSOFF),
+            bus_error: set(ERR_BUSERROR),
+            restarted: set(ERR_RESTARTED),
+            error_counters: set(ERR_CNT).then(|| (byte(6), byte(7))),
+        })
+    }
+
+    /// The bus state this error reports a change to, if any.
+    pub fn bus_state(&self) -> Option<BusState> {
+        let passive = [ControllerProblem::RxPassive, ControllerProblem::TxPassive];
+        if self.bus_off {
+            Some(BusState::Error)
+        } else if self.controller.iter().any(|p| passive.contains(p)) {
+            Some(BusState::Passive)
+        } else if self.restarted || self.controller.contains(&ControllerProblem::Active) {
+            Some(BusState::Active)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for SocketcanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.tx_timeout {
+            parts.push("TX timeout".to_string());
+        }
+        if let Some(bit) = self.lost_arbitration {
+            parts.push(format!("lost arbitration at bit {bit}"));
+        }
+        if !self.controller.is_empty() {
+            parts.push(format!("controller {:?}", self.controller));
+        }
+        if let Some(prot) = &self.protocol {
+            parts.push(format!("protocol {:?} at {:?}", prot.errors, prot.location));
+        }
+        if let Some(trx) = &self.transceiver {
+            parts.push(format!("CAN_H {:?}, CAN_L {:?}", trx.can_h, trx.can_l));
+        }
+        if self.no_ack {
+            parts.push("no ACK".to_string());
+        }
+        if self.bus_off {
+            parts.push("bus-off".to_string());
+        }
+        if self.bus_error {
+            parts.push("bus error".to_string());
+        }
+        if self.restarted {
+            parts.push("restarted".to_string());
+        }
+        if let Some((tx, rx)) = self.error_counters {
+            parts.push(format!("TEC {tx}, REC {rx}"));
+        }
+
+        if parts.is_empty() {
+            write!(f, "unspecified error")
+        } else {
+            write!(f, "{}", parts.join("; "))
+        }
+    }
+}
+
+impl PyCanMessage {
+    /// Decode this frame as a SocketCAN error frame. None if it isn't an
+    /// error frame. Other backends' error frames may not follow SocketCAN's
+    /// layout.
+    pub fn socketcan_error(&self) -> Option<SocketcanError> {
+        SocketcanError::decode(self)
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{Anomaly, BusState, CanId, GilOp, TxToken};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+/// Identifies a listener registered on a [`crate::PyCanInterface`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ListenerId(u64);
+
+impl ListenerId {
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Lifecycle events reported by an interface.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum PyCanEvent {
+    BusOpened,
+    BusClosed,
+    NotifierError(String),
+    /// The notifier's reader thread exited, so no more frames will be
+    /// received. Carries the exception that killed it, if known.
+    NotifierDied(String),
+    ListenerAdded(ListenerId),
+    ListenerRemoved(ListenerId),
+    ReconnectAttempt {
+        attempt: u32,
+    },
+    /// The USB adapter was unplugged. See [`crate::HotplugWatcher`].
+    AdapterDetached,
+    /// The USB adapter was plugged back in and the interface reopened.
+    AdapterAttached,
+    /// The bus moved between error-active/passive or bus-off.
+    BusStateChanged {
+        old: BusState,
+        new: BusState,
+    },
+    /// The backend echoed a frame sent with this token.
+    TxConfirmation {
+        token: TxToken,
+        id: CanId,
+    },
+    /// A periodic task stopped without being asked to.
+    PeriodicTaskStopped {
+        id: CanId,
+    },
+    /// A supervised periodic task was restarted.
+    PeriodicTaskRestarted {
+        id: CanId,
+        attempt: u32,
+    },
+    /// Restarting a supervised periodic task failed. It's tried again
+    /// after the backoff.
+    PeriodicTaskRestartFailed {
+        id: CanId,
+        attempt: u32,
+        reason: String,
+    },
+    /// A received frame's payload length didn't match its DLC. `dlc` is
+    /// the code as received, `len` the payload length after applying the
+    /// [`crate::DlcPolicy`], and `rejected` whether the frame was dropped.
+    DlcMismatch {
+        id: CanId,
+        dlc: u8,
+        len: usize,
+        rejected: bool,
+    },
+    /// Another node already sends an ID this application plans to
+    /// transmit. See [`crate::PyCanInterface::check_tx_ids`].
+    IdCollision {
+        id: CanId,
+        frames: u64,
+    },
+    /// A frame scheduled with `send_at` couldn't be sent.
+    DeferredTxFailed {
+        id: CanId,
+        reason: String,
+    },
+    /// Traffic deviated from the baseline an
+    /// [`crate::AnomalyMonitor`] learned.
+    Anomaly(Anomaly),
+    /// Nothing was received for the [`crate::SleepMonitor`]'s timeout, so
+    /// the network is taken to be asleep.
+    SleepDetected {
+        silent_for: Duration,
+    },
+    /// Traffic resumed after [`PyCanEvent::SleepDetected`].
+    WakeDetected,
+    /// An operation held the GIL for longer than the budget set with
+    /// [`crate::PyCanInterfaceBuilder::gil_budget`].
+    GilBudgetExceeded {
+        op: GilOp,
+        held: Duration,
+    },
+}
+
+/// Receives lifecycle events. `iface` is the name of the interface
+/// the event originated from.
+pub trait EventSink: Send + Sync {
+    fn on_event(&self, iface: &str, event: &PyCanEvent);
+}
+
+static GLOBAL_SINK: RwLock<Option<Arc<dyn EventSink>>> = RwLock::new(None);
+
+/// Install a sink that receives events from every interface in the process.
+pub fn set_global_event_sink(sink: Arc<dyn EventSink>) {
+    *GLOBAL_SINK.write().unwrap() = Some(sink);
+}
+
+/// Remove the process-wide sink, if any.
+pub fn clear_global_event_sink() {
+    *GLOBAL_SINK.write().unwrap() = None;
+}
+
+/// Per-interface event dispatch. Cheap to clone so it can be moved into
+/// listener shims.
+#[derive(Clone)]
+pub(crate) struct EventHub {
+    iface: Arc<str>,
+    sink: Arc<RwLock<Option<Arc<dyn EventSink>>>>,
+}
+
+impl EventHub {
+    pub(crate) fn new(iface: Arc<str>) -> Self {
+        Self {
+            iface,
+            sink: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub(crate) fn set_sink(&self, sink: Option<Arc<dyn EventSink>>) {
+        *self.sink.write().unwrap() = sink;
+    }
+
+    pub(crate) fn emit(&self, event: PyCanEvent) {
+        if let Some(sink) = self.sink.read().unwrap().as_ref() {
+            sink.on_event(&self.iface, &event);
+        }
+
+        if let Some(sink) = GLOBAL_SINK.read().unwrap().as_ref() {
+            sink.on_event(&self.iface, &event);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! ID filters, with the same semantics as python-can's `can_filters`.
+
+use crate::{id::EXTENDED_ID_MAX, CanId};
+
+/// Matches IDs where `id & can_mask == can_id & can_mask`. If `extended`
+/// is set, the ID's format must match it too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Filter {
+    pub can_id: u32,
+    pub can_mask: u32,
+    pub extended: Option<bool>,
+}
+
+impl Filter {
+    pub fn new(can_id: u32, can_mask: u32) -> Self {
+        Self {
+            can_id,
+            can_mask,
+            extended: None,
+        }
+    }
+
+    /// Matches exactly `id`, including its format.
+    pub fn id(id: CanId) -> Self {
+        Self {
+            can_id: id.raw(),
+            can_mask: EXTENDED_ID_MAX,
+            extended: Some(id.is_extended()),
+        }
+    }
+
+    /// Only match standard (`false`) or extended (`true`) IDs.
+    pub fn extended(mut self, extended: bool) -> Self {
+        self.extended = Some(extended);
+        self
+    }
+
+    pub fn matches(&self, id: CanId) -> bool {
+        self.extended.is_none_or(|ext| ext == id.is_extended())
+            && id.raw() & self.can_mask == self.can_id & self.can_mask
+    }
+}
@@ -0,0 +1,212 @@
+//! Terminal formatting of frames, candump-style or as a table.
+
+use std::{cell::Cell, io::IsTerminal};
+
+use crate::PyCanMessage;
+
+/// ANSI colors frames are given by ID. Error frames are always red.
+const PALETTE: [u8; 12] = [32, 33, 34, 35, 36, 92, 93, 94, 95, 96, 37, 90];
+const ERROR_COLOR: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrameStyle {
+    /// As `candump` prints them, e.g. `  can0  123   [2]  DE AD`.
+    #[default]
+    Candump,
+    /// Fixed-width columns, with flags spelled out. See
+    /// [`FrameFormatter::header`].
+    Table,
+}
+
+/// Formats frames one per line for printing, e.g. in a dump tool.
+///
+/// The delta-time column is the time since the previous frame this
+/// formatter formatted, so use one formatter per output stream.
+///
+/// ```ignore
+/// let fmt = FrameFormatter::new(FrameStyle::Table).delta_time(true).color(FrameFormatter::stdout_is_color());
+/// println!("{}", fmt.header());
+/// iface.register_rx_callback(move |msg| println!("{}", fmt.format(msg)), |_| {})?;
+/// ```
+#[derive(Debug, Default)]
+pub struct FrameFormatter {
+    style: FrameStyle,
+    color: bool,
+    timestamps: bool,
+    delta_time: bool,
+    iface_width: usize,
+    /// Timestamp of the last frame formatted.
+    last: Cell<Option<f64>>,
+}
+
+impl FrameFormatter {
+    pub fn new(style: FrameStyle) -> Self {
+        Self {
+            style,
+            ..Self::default()
+        }
+    }
+
+    /// Color each frame by its ID with ANSI escapes.
+    pub fn color(mut self, enabled: bool) -> Self {
+        self.color = enabled;
+        self
+    }
+
+    /// Show each frame's timestamp.
+    pub fn timestamps(mut self, enabled: bool) -> Self {
+        self.timestamps = enabled;
+        self
+    }
+
+    /// Show the time since the previous frame.
+    pub fn delta_time(mut self, enabled: bool) -> Self {
+        self.delta_time = enabled;
+        self
+    }
+
+    /// Pad interface names to `width`, so columns line up across buses.
+    pub fn iface_width(mut self, width: usize) -> Self {
+        self.iface_width = width;
+        self
+    }
+
+    /// Whether stdout is a terminal and `NO_COLOR` isn't set.
+    pub fn stdout_is_color() -> bool {
+        std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+    }
+
+    /// Column headings for [`FrameStyle::Table`]. Empty for candump
+    /// style, which has none.
+    pub fn header(&self) -> String {
+        if self.style == FrameStyle::Candump {
+            return String::new();
+        }
+
+        let mut line = String::new();
+        if self.timestamps {
+            line += &format!("{:>17} ", "Time");
+        }
+        if self.delta_time {
+            line += &format!("{:>10} ", "Delta");
+        }
+        line += &format!(
+            "{:<w$} {:>8} {:<5} {:>3}  Data",
+            "Iface",
+            "ID",
+            "Flags",
+            "Len",
+            w = self.iface_width.max(5)
+        );
+        line
+    }
+
+    /// One line for `msg`, without a trailing newline.
+    pub fn format(&self, msg: &PyCanMessage) -> String {
+        let delta = match (msg.timestamp, self.last.get()) {
+            (Some(now), Some(last)) => (now - last).max(0.0),
+            _ => 0.0,
+        };
+        if msg.timestamp.is_some() {
+            self.last.set(msg.timestamp);
+        }
+
+        let line = match self.style {
+            FrameStyle::Candump => self.candump(msg, delta),
+            FrameStyle::Table => self.table(msg, delta),
+        };
+        if !self.color {
+            return line;
+        }
+
+        let color = if msg.is_error_frame {
+            ERROR_COLOR.to_string()
+        } else {
+            let idx = msg.arbitration_id.raw() as usize % PALETTE.len();
+            format!("\x1b[{}m", PALETTE[idx])
+        };
+        format!("{color}{line}{RESET}")
+    }
+
+    fn candump(&self, msg: &PyCanMessage, delta: f64) -> String {
+        let mut line = String::new();
+        if self.timestamps {
+            line += &format!(" ({:.6})", msg.timestamp.unwrap_or_default());
+        }
+        if self.delta_time {
+            line += &format!(" ({delta:010.6})");
+        }
+
+        let iface = msg.iface_name.as_deref().unwrap_or_default();
+        let len = payload(msg).len();
+        line += &format!("  {iface:>w$}  ", w = self.iface_width);
+        if msg.is_error_frame {
+            line += &format!("{:08X}   [{len}]  {}  ERRORFRAME", error_id(msg), hex(msg));
+        } else if msg.is_remote_frame {
+            line += &format!(
+                "{}   [{}]  remote request",
+                msg.arbitration_id,
+                msg.dlc.unwrap_or_default()
+            );
+        } else if msg.is_fd {
+            line += &format!("{}  [{len:02}]  {}", msg.arbitration_id, hex(msg));
+        } else {
+            line += &format!("{}   [{len}]  {}", msg.arbitration_id, hex(msg));
+        }
+        line.trim_end().to_string()
+    }
+
+    fn table(&self, msg: &PyCanMessage, delta: f64) -> String {
+        let mut line = String::new();
+        if self.timestamps {
+            line += &format!("{:>17.6} ", msg.timestamp.unwrap_or_default());
+        }
+        if self.delta_time {
+            line += &format!("{delta:>10.6} ");
+        }
+
+        let flags: String = [
+            (msg.arbitration_id.is_extended(), 'X'),
+            (msg.is_remote_frame, 'R'),
+            (msg.is_error_frame, 'E'),
+            (msg.is_fd, 'F'),
+            (msg.bitrate_switch, 'B'),
+        ]
+        .iter()
+        .map(|&(set, flag)| if set { flag } else { '-' })
+        .collect();
+        let len = if msg.is_remote_frame {
+            usize::from(msg.dlc.unwrap_or_default())
+        } else {
+            payload(msg).len()
+        };
+
+        line += &format!(
+            "{:<w$} {:>8} {flags} {len:>3}  {}",
+            msg.iface_name.as_deref().unwrap_or_default(),
+            msg.arbitration_id.to_string(),
+            hex(msg),
+            w = self.iface_width.max(5)
+        );
+        line.trim_end().to_string()
+    }
+}
+
+fn payload(msg: &PyCanMessage) -> &[u8] {
+    msg.data.as_deref().unwrap_or_default()
+}
+
+/// Payload bytes in hex, space separated.
+fn hex(msg: &PyCanMessage) -> String {
+    payload(msg)
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// SocketCAN reports error frames with the error flag in the ID.
+fn error_id(msg: &PyCanMessage) -> u32 {
+    0x2000_0000 | msg.arbitration_id.raw()
+}
@@ -0,0 +1,106 @@
+//! Terse frame construction for tests and examples.
+
+use thiserror::Error;
+
+use crate::id::CanIdError;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum HexError {
+    #[error("Invalid hex payload `{0}` - expected pairs of hex digits")]
+    InvalidFormat(String),
+    #[error("Hex payload `{0}` is longer than 64 bytes")]
+    TooLong(String),
+}
+
+/// Why [`try_can_frame!`](crate::try_can_frame) couldn't build a frame.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FrameError {
+    #[error(transparent)]
+    Id(#[from] CanIdError),
+    #[error(transparent)]
+    Payload(#[from] HexError),
+}
+
+/// Parse a payload written as hex, e.g. `"DEADBEEF"`, `"de ad be ef"` or
+/// `"DE:AD:BE:EF"`.
+pub fn parse_hex(s: &str) -> Result<Vec<u8>, HexError> {
+    let digits: Vec<u8> = s
+        .bytes()
+        .filter(|b| !matches!(b, b' ' | b':' | b'.' | b'-'))
+        .collect();
+
+    if !digits.len().is_multiple_of(2) || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return Err(HexError::InvalidFormat(s.into()));
+    }
+    if digits.len() > 128 {
+        return Err(HexError::TooLong(s.into()));
+    }
+
+    Ok(digits
+        .chunks(2)
+        .map(|pair| {
+            // Both digits were checked above
+            let pair = std::str::from_utf8(pair).unwrap();
+            u8::from_str_radix(pair, 16).unwrap()
+        })
+        .collect())
+}
+
+/// Build a [`crate::PyCanMessage`] data frame, panicking on an invalid ID
+/// or payload. The payload is a byte array or a hex string:
+///
+/// ```ignore
+/// let a = can_frame!(0x123, [0x01, 0x02]);
+/// let b = can_frame!(0x18FEF100, "01 02", extended);
+/// ```
+#[cfg(feature = "panicking-api")]
+#[macro_export]
+macro_rules! can_frame {
+    ($id:expr, [$($byte:expr),* $(,)?]) => {
+        $crate::can_frame!(@build $id, false, &[$($byte),*])
+    };
+    ($id:expr, [$($byte:expr),* $(,)?], extended) => {
+        $crate::can_frame!(@build $id, true, &[$($byte),*])
+    };
+    ($id:expr, $hex:literal) => {
+        $crate::can_frame!(@build $id, false, &$crate::frame::parse_hex($hex).expect("can_frame! payload"))
+    };
+    ($id:expr, $hex:literal, extended) => {
+        $crate::can_frame!(@build $id, true, &$crate::frame::parse_hex($hex).expect("can_frame! payload"))
+    };
+    (@build $id:expr, $extended:expr, $data:expr) => {
+        $crate::PyCanMessage::new(
+            $crate::CanId::new($id, $extended).expect("can_frame! ID"),
+            $data,
+        )
+    };
+}
+
+/// Like [`can_frame!`], but evaluates to a `Result<PyCanMessage,
+/// FrameError>` instead of panicking.
+///
+/// ```ignore
+/// let a = try_can_frame!(0x123, "01 02")?;
+/// ```
+#[macro_export]
+macro_rules! try_can_frame {
+    ($id:expr, [$($byte:expr),* $(,)?]) => {
+        $crate::try_can_frame!(@build $id, false, Ok::<_, $crate::frame::HexError>(vec![$($byte),*]))
+    };
+    ($id:expr, [$($byte:expr),* $(,)?], extended) => {
+        $crate::try_can_frame!(@build $id, true, Ok::<_, $crate::frame::HexError>(vec![$($byte),*]))
+    };
+    ($id:expr, $hex:literal) => {
+        $crate::try_can_frame!(@build $id, false, $crate::frame::parse_hex($hex))
+    };
+    ($id:expr, $hex:literal, extended) => {
+        $crate::try_can_frame!(@build $id, true, $crate::frame::parse_hex($hex))
+    };
+    (@build $id:expr, $extended:expr, $data:expr) => {
+        (|| -> ::std::result::Result<$crate::PyCanMessage, $crate::frame::FrameError> {
+            let id = $crate::CanId::new($id, $extended)?;
+            let data: ::std::vec::Vec<u8> = $data?;
+            Ok($crate::PyCanMessage::new(id, &data))
+        })()
+    };
+}
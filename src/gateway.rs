@@ -0,0 +1,530 @@
+//! Forwarding between two interfaces through a set of rules, e.g. to
+//! translate between an OEM CAN matrix and an in-house one.
+//!
+//! GatewayRules are read from an INI-style file. Each received frame is checked
+//! against the rules in order, and the first one that matches decides
+//! what's sent on; frames no rule matches are dropped:
+//!
+//! ```text
+//! # Pass diagnostics through unchanged
+//! [rule diag]
+//! match = 700:700
+//!
+//! # Move a status frame to another ID, clearing the top nibble of byte 1
+//! [rule status]
+//! match = 18FF0001
+//! remap = 321
+//! byte 1 &= 0x0F
+//! rate = 100ms
+//!
+//! # Decode with the source interface's database and re-encode into a
+//! # message from the destination's
+//! [rule speed]
+//! match = 1A0
+//! translate = VehicleSpeed
+//! signal Speed_kph = WheelSpeed * 0.036
+//! signal Valid = 1
+//! ```
+//!
+//! `match` takes an ID in candump notation, optionally with a mask
+//! (`id:mask`). `remap` replaces the ID, `byte N` sets (`=`), masks
+//! (`&=`), or sets bits in (`|=`, `^=`) a payload byte, and `rate` sends
+//! at most one frame per interval. `translate` builds a new payload for
+//! the named destination message, from expressions over the source
+//! message's signals using `+ - * /` and parentheses; signals not given
+//! keep the value last sent. Transformations apply in the order
+//! translate, remap, bytes, rate.
+
+use std::{
+    cell::RefCell,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+use crate::{CanId, Filter, ListenerId, MessageDef, PyCanError, PyCanInterface, PyCanMessage};
+
+#[derive(Debug, Error)]
+pub enum GatewayError {
+    #[error("Failed to read gateway rules :: `{0}`")]
+    Io(String),
+    #[error("Gateway rules syntax error on line {0} :: `{1}`")]
+    Syntax(usize, String),
+    #[error("Gateway rule refers to an unknown message or signal :: `{0}`")]
+    Database(String),
+    #[error(transparent)]
+    Interface(#[from] PyCanError),
+}
+
+/// Arithmetic over the source message's signals.
+#[derive(Clone, Debug)]
+enum Expr {
+    Number(f64),
+    Signal(String),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn parse(s: &str) -> Result<Self, String> {
+        let mut parser = ExprParser {
+            chars: s.chars().filter(|c| !c.is_whitespace()).collect(),
+            pos: 0,
+        };
+        let expr = parser.sum()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(c) => Err(format!("unexpected `{c}` in `{s}`")),
+        }
+    }
+
+    /// None if a signal isn't present.
+    fn eval(&self, signals: &[(&str, f64)]) -> Option<f64> {
+        Some(match self {
+            Self::Number(n) => *n,
+            Self::Signal(name) => signals.iter().find(|(n, _)| n == name)?.1,
+            Self::Neg(e) => -e.eval(signals)?,
+            Self::Binary(op, a, b) => {
+                let (a, b) = (a.eval(signals)?, b.eval(signals)?);
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    _ => a / b,
+                }
+            }
+        })
+    }
+
+    fn signals<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Self::Number(_) => {}
+            Self::Signal(name) => out.push(name),
+            Self::Neg(e) => e.signals(out),
+            Self::Binary(_, a, b) => {
+                a.signals(out);
+                b.signals(out);
+            }
+        }
+    }
+}
+
+struct ExprParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[char],
+        operand: fn(&mut Self) -> Result<Expr, String>,
+    ) -> Result<Expr, String> {
+        let mut expr = operand(self)?;
+        while let Some(op) = self.peek().filter(|c| ops.contains(c)) {
+            self.pos += 1;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(operand(self)?));
+        }
+        Ok(expr)
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        self.binary(&['+', '-'], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        self.binary(&['*', '/'], Self::operand)
+    }
+
+    fn operand(&mut self) -> Result<Expr, String> {
+        let start = self.pos;
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.operand()?)))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.sum()?;
+                if self.peek() != Some(')') {
+                    return Err("unclosed `(`".into());
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || c == '.')
+                {
+                    self.pos += 1;
+                }
+                let s: String = self.chars[start..self.pos].iter().collect();
+                number(&s).map(Expr::Number)
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                    self.pos += 1;
+                }
+                Ok(Expr::Signal(self.chars[start..self.pos].iter().collect()))
+            }
+            Some(c) => Err(format!("unexpected `{c}`")),
+            None => Err("expression ends early".into()),
+        }
+    }
+}
+
+/// A decimal number, or an integer in `0x` hex.
+fn number(s: &str) -> Result<f64, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).map(|n| n as f64).ok(),
+        None => s.parse().ok(),
+    }
+    .ok_or_else(|| format!("invalid number `{s}`"))
+}
+
+pub(crate) fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (value, unit) = s
+        .find(|c: char| c.is_alphabetic())
+        .map(|i| s.split_at(i))
+        .ok_or_else(|| format!("duration `{s}` has no unit"))?;
+    let value: f64 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid duration `{s}`"))?;
+    let seconds = match unit {
+        "us" => value / 1e6,
+        "ms" => value / 1e3,
+        "s" => value,
+        _ => return Err(format!("unknown unit in `{s}`")),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("invalid duration `{s}`: {e}"))
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ByteOp {
+    Set,
+    And,
+    Or,
+    Xor,
+}
+
+#[derive(Clone, Debug)]
+struct Translation {
+    message: String,
+    signals: Vec<(String, Expr)>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    name: String,
+    /// Where the rule's section starts.
+    line: usize,
+    filter: Option<Filter>,
+    translate: Option<Translation>,
+    remap: Option<CanId>,
+    bytes: Vec<(usize, ByteOp, u8)>,
+    rate: Option<Duration>,
+}
+
+impl Rule {
+    fn new(name: &str, line: usize) -> Self {
+        Self {
+            name: name.into(),
+            line,
+            filter: None,
+            translate: None,
+            remap: None,
+            bytes: Vec::new(),
+            rate: None,
+        }
+    }
+
+    /// Apply one `key = value` line.
+    fn set(&mut self, key: &str, op: Option<ByteOp>, value: &str) -> Result<(), String> {
+        let plain = |op: Option<ByteOp>| match op {
+            None | Some(ByteOp::Set) => Ok(()),
+            Some(_) => Err(format!("`{key}` only takes `=`")),
+        };
+
+        match key.split_once(char::is_whitespace) {
+            Some(("byte", index)) => {
+                let index = index
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid byte index `{index}`"))?;
+                let byte = number(value)?;
+                if !(0.0..=255.0).contains(&byte) || byte.fract() != 0.0 {
+                    return Err(format!("`{value}` isn't a byte"));
+                }
+                self.bytes
+                    .push((index, op.unwrap_or(ByteOp::Set), byte as u8));
+            }
+            Some(("signal", name)) => {
+                plain(op)?;
+                let translate = self
+                    .translate
+                    .as_mut()
+                    .ok_or("`signal` before `translate`")?;
+                translate
+                    .signals
+                    .push((name.trim().into(), Expr::parse(value)?));
+            }
+            _ => {
+                plain(op)?;
+                match key {
+                    "match" => self.filter = Some(parse_match(value)?),
+                    "remap" => {
+                        self.remap = Some(value.parse().map_err(|e| format!("{e}"))?);
+                    }
+                    "rate" => self.rate = Some(parse_duration(value)?),
+                    "translate" => {
+                        self.translate = Some(Translation {
+                            message: value.into(),
+                            signals: Vec::new(),
+                        });
+                    }
+                    _ => return Err(format!("unknown key `{key}`")),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `id` or `id:mask`, in candump notation.
+pub(crate) fn parse_match(s: &str) -> Result<Filter, String> {
+    let (id, mask) = match s.split_once(':') {
+        Some((id, mask)) => (id, Some(mask)),
+        None => (s, None),
+    };
+    let id: CanId = id.parse().map_err(|e| format!("{e}"))?;
+    Ok(match mask {
+        None => Filter::id(id),
+        Some(mask) => {
+            let mask =
+                u32::from_str_radix(mask, 16).map_err(|_| format!("invalid mask `{mask}`"))?;
+            Filter::new(id.raw(), mask).extended(id.is_extended())
+        }
+    })
+}
+
+/// An ordered list of gateway rules.
+#[derive(Clone, Debug, Default)]
+pub struct GatewayRules {
+    rules: Vec<Rule>,
+}
+
+impl GatewayRules {
+    pub fn parse(text: &str) -> Result<Self, GatewayError> {
+        let mut rules: Vec<Rule> = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let error = |e: String| GatewayError::Syntax(i + 1, e);
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(section) = line.strip_prefix('[') {
+                let name = section
+                    .strip_suffix(']')
+                    .and_then(|s| s.trim().strip_prefix("rule"))
+                    .ok_or_else(|| error(format!("expected `[rule <name>]`, got `{line}`")))?;
+                rules.push(Rule::new(name.trim(), i + 1));
+                continue;
+            }
+
+            let rule = rules
+                .last_mut()
+                .ok_or_else(|| error("setting outside a `[rule]` section".into()))?;
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected `key = value`, got `{line}`")))?;
+            let (key, op) = match key.trim_end().char_indices().last() {
+                Some((i, '&')) => (&key[..i], Some(ByteOp::And)),
+                Some((i, '|')) => (&key[..i], Some(ByteOp::Or)),
+                Some((i, '^')) => (&key[..i], Some(ByteOp::Xor)),
+                _ => (key, None),
+            };
+            rule.set(key.trim(), op, value.trim()).map_err(error)?;
+        }
+
+        if let Some(rule) = rules.iter().find(|r| r.filter.is_none()) {
+            return Err(GatewayError::Syntax(
+                rule.line,
+                format!("rule `{}` has no `match`", rule.name),
+            ));
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GatewayError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| GatewayError::Io(format!("{}: {e}", path.display())))?;
+        Self::parse(&text)
+    }
+}
+
+/// A rule with its translation resolved against the databases.
+struct Active {
+    rule: Rule,
+    translate: Option<(MessageDef, Vec<u8>)>,
+    last_sent: Option<Instant>,
+}
+
+impl Active {
+    fn new(rule: Rule, src: &PyCanInterface, dst: &PyCanInterface) -> Result<Self, GatewayError> {
+        let Some(translate) = &rule.translate else {
+            return Ok(Self {
+                rule,
+                translate: None,
+                last_sent: None,
+            });
+        };
+        let unknown = |what: String| GatewayError::Database(format!("{}: {what}", rule.name));
+
+        let def = dst
+            .decoder()
+            .and_then(|d| d.messages().find(|m| m.name == translate.message))
+            .ok_or_else(|| {
+                unknown(format!(
+                    "no message {} on {}",
+                    translate.message,
+                    dst.name()
+                ))
+            })?
+            .clone();
+        for (name, expr) in &translate.signals {
+            if !def.signals.iter().any(|s| s.name == *name) {
+                return Err(unknown(format!("no signal {}.{name}", def.name)));
+            }
+            let mut inputs = Vec::new();
+            expr.signals(&mut inputs);
+            for input in inputs {
+                let known = src
+                    .decoder()
+                    .into_iter()
+                    .flat_map(|d| d.messages())
+                    .filter(|m| rule.filter.is_none_or(|f| f.matches(m.id)))
+                    .any(|m| m.signals.iter().any(|s| s.name == input));
+                if !known {
+                    return Err(unknown(format!("no signal {input} on {}", src.name())));
+                }
+            }
+        }
+
+        let data = vec![0; def.length];
+        Ok(Self {
+            rule,
+            translate: Some((def, data)),
+            last_sent: None,
+        })
+    }
+
+    /// The frame to send for `msg`, if any.
+    fn apply(&mut self, src: &PyCanInterface, msg: &PyCanMessage) -> Option<(CanId, Vec<u8>)> {
+        let mut id = msg.arbitration_id;
+        let mut data = msg.data.clone().unwrap_or_default();
+
+        if let (Some(translate), Some((def, last))) = (&self.rule.translate, &mut self.translate) {
+            let inputs = src.decoder()?.decode(msg)?;
+            for (name, expr) in &translate.signals {
+                let signal = def.signals.iter().find(|s| s.name == *name)?;
+                if let Some(value) = expr.eval(&inputs) {
+                    signal.encode(value, last)?;
+                }
+            }
+            id = def.id;
+            data.clone_from(last);
+        }
+
+        if let Some(remap) = self.rule.remap {
+            id = remap;
+        }
+
+        for &(index, op, value) in &self.rule.bytes {
+            let Some(byte) = data.get_mut(index) else {
+                continue;
+            };
+            match op {
+                ByteOp::Set => *byte = value,
+                ByteOp::And => *byte &= value,
+                ByteOp::Or => *byte |= value,
+                ByteOp::Xor => *byte ^= value,
+            }
+        }
+
+        if let Some(rate) = self.rule.rate {
+            let now = Instant::now();
+            if self.last_sent.is_some_and(|last| now - last < rate) {
+                return None;
+            }
+            self.last_sent = Some(now);
+        }
+
+        Some((id, data))
+    }
+}
+
+/// Forwards frames received on one interface to another through a set
+/// of [`GatewayRules`]. Stops on drop. For both directions, start two gateways.
+pub struct Gateway {
+    src: Arc<PyCanInterface>,
+    listener: ListenerId,
+}
+
+impl Gateway {
+    /// Start forwarding from `src` to `dst`. Translations use the decoders
+    /// set on each interface's builder, and fail here if a message or
+    /// signal they name isn't defined.
+    pub fn start(
+        src: Arc<PyCanInterface>,
+        dst: Arc<PyCanInterface>,
+        rules: GatewayRules,
+    ) -> Result<Self, GatewayError> {
+        let active = rules
+            .rules
+            .into_iter()
+            .map(|rule| Active::new(rule, &src, &dst))
+            .collect::<Result<Vec<_>, _>>()?;
+        let active = RefCell::new(active);
+
+        // Holding `src` in its own callback would keep it alive forever
+        let weak_src = Arc::downgrade(&src);
+        let listener = src.register_rx_callback(
+            move |msg| {
+                let Some(src) = weak_src.upgrade() else {
+                    return;
+                };
+                let mut active = active.borrow_mut();
+                let Some(rule) = active
+                    .iter_mut()
+                    .find(|a| a.rule.filter.is_some_and(|f| msg.matches(&f)))
+                else {
+                    return;
+                };
+                if let Some((id, data)) = rule.apply(&src, msg) {
+                    // Like a real gateway, drop what can't be forwarded
+                    let _ = dst.send_checked(id, &data);
+                }
+            },
+            |_| {},
+        )?;
+
+        Ok(Self { src, listener })
+    }
+}
+
+impl Drop for Gateway {
+    fn drop(&mut self) {
+        let _ = self.src.remove_listener(self.listener);
+    }
+}
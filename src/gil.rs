@@ -0,0 +1,164 @@
+//! How long the interface holds the GIL, so a slow backend starving other
+//! Python threads shows up.
+//!
+//! Times are measured around the work done with the GIL held. Waiting for
+//! a frame to arrive releases the GIL and isn't counted, but a send is
+//! timed as a whole, so one blocked on a full TX queue counts in full even
+//! if the backend released the GIL while it waited.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{EventHub, PyCanEvent, PyCanInterface};
+
+/// Operations whose GIL hold time is measured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GilOp {
+    /// Building a frame and handing it to python-can's `send`.
+    Send,
+    /// Converting received frames, including draining batches with
+    /// batched delivery.
+    Recv,
+    /// Rx callbacks run from python-can's notifier, including the Rust
+    /// callbacks they call. Callbacks run by batched delivery don't hold
+    /// the GIL and aren't counted.
+    Callback,
+}
+
+/// GIL hold times for one [`GilOp`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GilHold {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+    /// Holds longer than the budget set with
+    /// [`crate::PyCanInterfaceBuilder::gil_budget`].
+    pub over_budget: u64,
+}
+
+impl GilHold {
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            n => Duration::from_nanos((self.total.as_nanos() / u128::from(n)) as u64),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GilStats {
+    pub send: GilHold,
+    pub recv: GilHold,
+    pub callback: GilHold,
+}
+
+#[derive(Default)]
+struct Counters {
+    count: AtomicU64,
+    total_ns: AtomicU64,
+    max_ns: AtomicU64,
+    over_budget: AtomicU64,
+}
+
+impl Counters {
+    fn hold(&self) -> GilHold {
+        GilHold {
+            count: self.count.load(Ordering::Relaxed),
+            total: Duration::from_nanos(self.total_ns.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(self.max_ns.load(Ordering::Relaxed)),
+            over_budget: self.over_budget.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        for counter in [&self.count, &self.total_ns, &self.max_ns, &self.over_budget] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+struct Meter {
+    send: Counters,
+    recv: Counters,
+    callback: Counters,
+    budget: Option<Duration>,
+    events: EventHub,
+}
+
+/// An interface's GIL measurements. Cheap to clone into listener shims.
+#[derive(Clone)]
+pub(crate) struct GilMeter(Arc<Meter>);
+
+impl GilMeter {
+    pub(crate) fn new(budget: Option<Duration>, events: EventHub) -> Self {
+        Self(Arc::new(Meter {
+            send: Counters::default(),
+            recv: Counters::default(),
+            callback: Counters::default(),
+            budget,
+            events,
+        }))
+    }
+
+    fn counters(&self, op: GilOp) -> &Counters {
+        match op {
+            GilOp::Send => &self.0.send,
+            GilOp::Recv => &self.0.recv,
+            GilOp::Callback => &self.0.callback,
+        }
+    }
+
+    /// Run `f`, which holds the GIL, and record how long it took.
+    pub(crate) fn time<T>(&self, op: GilOp, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let res = f();
+        self.record(op, start.elapsed());
+        res
+    }
+
+    pub(crate) fn record(&self, op: GilOp, held: Duration) {
+        let counters = self.counters(op);
+        let ns = u64::try_from(held.as_nanos()).unwrap_or(u64::MAX);
+        counters.count.fetch_add(1, Ordering::Relaxed);
+        counters.total_ns.fetch_add(ns, Ordering::Relaxed);
+        counters.max_ns.fetch_max(ns, Ordering::Relaxed);
+
+        if self.0.budget.is_some_and(|budget| held > budget) {
+            counters.over_budget.fetch_add(1, Ordering::Relaxed);
+            self.0
+                .events
+                .emit(PyCanEvent::GilBudgetExceeded { op, held });
+        }
+    }
+
+    pub(crate) fn stats(&self) -> GilStats {
+        GilStats {
+            send: self.0.send.hold(),
+            recv: self.0.recv.hold(),
+            callback: self.0.callback.hold(),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        self.0.send.reset();
+        self.0.recv.reset();
+        self.0.callback.reset();
+    }
+}
+
+impl PyCanInterface {
+    /// How long operations on this interface have held the GIL since it
+    /// was opened or the stats were last reset.
+    pub fn gil_stats(&self) -> GilStats {
+        self.gil.stats()
+    }
+
+    pub fn reset_gil_stats(&self) {
+        self.gil.reset();
+    }
+}
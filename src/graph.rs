@@ -0,0 +1,390 @@
+//! Running graphs of frame sources, middlewares and sinks.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use pyo3::Python;
+
+use crate::{FrameSink, FrameSource, PipeError, PyCanMessage};
+
+/// How long a source thread waits for a frame before checking whether
+/// the pipeline has been dropped.
+const POLL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A node in a [`Pipeline`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// Frame counts for one node since the pipeline was built.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NodeStats {
+    /// Frames that reached the node while it was running.
+    pub frames_in: u64,
+    /// Frames passed on, or written for sinks.
+    pub frames_out: u64,
+    /// Frames that reached the node while it was stopped, or that a
+    /// middleware dropped.
+    pub dropped: u64,
+    /// Read errors for sources, write errors for sinks.
+    pub errors: u64,
+}
+
+type Middleware = Box<dyn FnMut(PyCanMessage) -> Option<PyCanMessage> + Send>;
+
+enum Kind {
+    Source,
+    Middleware(Mutex<Middleware>),
+    Sink(Mutex<Box<dyn FrameSink>>),
+}
+
+#[derive(Default)]
+struct Counters {
+    frames_in: AtomicU64,
+    frames_out: AtomicU64,
+    dropped: AtomicU64,
+    errors: AtomicU64,
+}
+
+struct Node {
+    name: String,
+    kind: Kind,
+    children: Vec<NodeId>,
+    running: AtomicBool,
+    counters: Counters,
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+}
+
+impl Graph {
+    /// Pass a frame into `id` and on down the graph.
+    fn push(&self, id: NodeId, msg: PyCanMessage) {
+        let node = &self.nodes[id.0];
+        let count = |c: &AtomicU64| c.fetch_add(1, Ordering::Relaxed);
+
+        if !node.running.load(Ordering::Relaxed) {
+            count(&node.counters.dropped);
+            return;
+        }
+        count(&node.counters.frames_in);
+
+        let msg = match &node.kind {
+            Kind::Source => msg,
+            Kind::Middleware(f) => match (f.lock().unwrap())(msg) {
+                Some(msg) => msg,
+                None => {
+                    count(&node.counters.dropped);
+                    return;
+                }
+            },
+            Kind::Sink(sink) => {
+                match sink.lock().unwrap().write_frame(&msg) {
+                    Ok(()) => count(&node.counters.frames_out),
+                    Err(_) => count(&node.counters.errors),
+                };
+                return;
+            }
+        };
+
+        count(&node.counters.frames_out);
+        if let Some((last, rest)) = node.children.split_last() {
+            for child in rest {
+                self.push(*child, msg.clone());
+            }
+            self.push(*last, msg);
+        }
+    }
+
+    /// Whether `to` can be reached from `from`.
+    fn reaches(&self, from: NodeId, to: NodeId) -> bool {
+        from == to
+            || self.nodes[from.0]
+                .children
+                .iter()
+                .any(|child| self.reaches(*child, to))
+    }
+}
+
+/// Wires frame sources through middlewares into sinks, and runs them,
+/// e.g. as the core of a gateway appliance.
+///
+/// Each source is read on its own thread, and its frames are pushed
+/// through everything downstream of it on that thread. Nodes can be
+/// added, connected, stopped and restarted while the pipeline runs; a
+/// stopped node drops the frames that reach it, so everything behind it
+/// stops too.
+///
+/// ```ignore
+/// let pipeline = Pipeline::new();
+/// let can0 = pipeline.source("can0", can0.frame_source()?);
+/// let pt = pipeline.middleware("powertrain", can0, |msg| (msg.arbitration_id.raw() < 0x200).then_some(msg))?;
+/// pipeline.sink("can1", pt, can1.clone())?;
+/// let log = pipeline.sink("log", can0, TrcWriter::create("can0.trc")?)?;
+/// pipeline.start();
+///
+/// pipeline.stop_node(log);
+/// println!("{:?}", pipeline.stats(pt));
+/// ```
+pub struct Pipeline {
+    graph: Arc<RwLock<Graph>>,
+    /// Sources not yet being read.
+    pending: Mutex<Vec<(NodeId, Box<dyn FrameSource>)>>,
+    started: AtomicBool,
+    /// Cleared to stop the source threads.
+    alive: Arc<AtomicBool>,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self {
+            graph: Arc::default(),
+            pending: Mutex::default(),
+            started: AtomicBool::new(false),
+            alive: Arc::new(AtomicBool::new(true)),
+            threads: Mutex::default(),
+        }
+    }
+
+    fn add(&self, name: &str, kind: Kind, upstream: Option<NodeId>) -> Result<NodeId, PipeError> {
+        let mut graph = self.graph.write().unwrap();
+        let id = NodeId(graph.nodes.len());
+        if let Some(upstream) = upstream {
+            check_upstream(&graph, upstream)?;
+        }
+
+        graph.nodes.push(Node {
+            name: name.into(),
+            kind,
+            children: Vec::new(),
+            running: AtomicBool::new(true),
+            counters: Counters::default(),
+        });
+        if let Some(upstream) = upstream {
+            graph.nodes[upstream.0].children.push(id);
+        }
+        Ok(id)
+    }
+
+    /// Add a source. It's read from as soon as the pipeline is started,
+    /// or straight away if it already has been.
+    pub fn source(&self, name: &str, source: impl FrameSource + 'static) -> NodeId {
+        let id = self
+            .add(name, Kind::Source, None)
+            .expect("sources have no upstream");
+        self.pending.lock().unwrap().push((id, Box::new(source)));
+        if self.started.load(Ordering::Relaxed) {
+            self.spawn_pending();
+        }
+        id
+    }
+
+    /// Add a middleware fed by `upstream`, which rewrites each frame or
+    /// drops it by returning None.
+    pub fn middleware<F>(&self, name: &str, upstream: NodeId, f: F) -> Result<NodeId, PipeError>
+    where
+        F: FnMut(PyCanMessage) -> Option<PyCanMessage> + Send + 'static,
+    {
+        self.add(
+            name,
+            Kind::Middleware(Mutex::new(Box::new(f))),
+            Some(upstream),
+        )
+    }
+
+    /// Add a sink fed by `upstream`.
+    pub fn sink(
+        &self,
+        name: &str,
+        upstream: NodeId,
+        sink: impl FrameSink + 'static,
+    ) -> Result<NodeId, PipeError> {
+        self.add(name, Kind::Sink(Mutex::new(Box::new(sink))), Some(upstream))
+    }
+
+    /// Also feed `to` from `from`, e.g. to merge two sources into one
+    /// sink. Fails if `from` is a sink, `to` is a source, or the link
+    /// would make a loop.
+    pub fn connect(&self, from: NodeId, to: NodeId) -> Result<(), PipeError> {
+        let mut graph = self.graph.write().unwrap();
+        check_upstream(&graph, from)?;
+        match graph.nodes.get(to.0).map(|n| &n.kind) {
+            None => return Err(PipeError::InvalidGraph(format!("no node {}", to.0))),
+            Some(Kind::Source) => {
+                return Err(PipeError::InvalidGraph(format!(
+                    "{} is a source",
+                    graph.nodes[to.0].name
+                )))
+            }
+            Some(_) => {}
+        }
+        if graph.reaches(to, from) {
+            return Err(PipeError::InvalidGraph(format!(
+                "{} -> {} would make a loop",
+                graph.nodes[from.0].name, graph.nodes[to.0].name
+            )));
+        }
+
+        let children = &mut graph.nodes[from.0].children;
+        if !children.contains(&to) {
+            children.push(to);
+        }
+        Ok(())
+    }
+
+    /// Start reading every source.
+    pub fn start(&self) {
+        self.started.store(true, Ordering::Relaxed);
+        self.spawn_pending();
+    }
+
+    fn spawn_pending(&self) {
+        let mut threads = self.threads.lock().unwrap();
+        for (id, mut source) in self.pending.lock().unwrap().drain(..) {
+            let graph = self.graph.clone();
+            let alive = self.alive.clone();
+            threads.push(std::thread::spawn(move || {
+                while let Some(frame) = source.next_frame_timeout(POLL_TIMEOUT) {
+                    if !alive.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let graph = graph.read().unwrap();
+                    match frame {
+                        Ok(msg) => graph.push(id, msg),
+                        Err(PipeError::Timeout) => {}
+                        Err(_) => {
+                            graph.nodes[id.0]
+                                .counters
+                                .errors
+                                .fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+            }));
+        }
+    }
+
+    /// Stop passing frames through `id` and everything fed only by it.
+    /// A stopped source is still read, so an interface's receive queue
+    /// doesn't back up, but its frames are dropped.
+    pub fn stop_node(&self, id: NodeId) {
+        if let Some(node) = self.graph.read().unwrap().nodes.get(id.0) {
+            node.running.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Resume a node stopped with [`Self::stop_node`].
+    pub fn start_node(&self, id: NodeId) {
+        if let Some(node) = self.graph.read().unwrap().nodes.get(id.0) {
+            node.running.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_running(&self, id: NodeId) -> bool {
+        self.graph
+            .read()
+            .unwrap()
+            .nodes
+            .get(id.0)
+            .is_some_and(|n| n.running.load(Ordering::Relaxed))
+    }
+
+    /// Look a node up by name.
+    pub fn node(&self, name: &str) -> Option<NodeId> {
+        self.graph
+            .read()
+            .unwrap()
+            .nodes
+            .iter()
+            .position(|n| n.name == name)
+            .map(NodeId)
+    }
+
+    pub fn stats(&self, id: NodeId) -> Option<NodeStats> {
+        let graph = self.graph.read().unwrap();
+        let counters = &graph.nodes.get(id.0)?.counters;
+        let get = |c: &AtomicU64| c.load(Ordering::Relaxed);
+        Some(NodeStats {
+            frames_in: get(&counters.frames_in),
+            frames_out: get(&counters.frames_out),
+            dropped: get(&counters.dropped),
+            errors: get(&counters.errors),
+        })
+    }
+
+    /// Stats for every node, by name.
+    pub fn all_stats(&self) -> HashMap<String, NodeStats> {
+        let names: Vec<_> = {
+            let graph = self.graph.read().unwrap();
+            graph.nodes.iter().map(|n| n.name.clone()).collect()
+        };
+        names
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, name)| Some((name, self.stats(NodeId(i))?)))
+            .collect()
+    }
+
+    /// Flush every sink. Reports the first error.
+    pub fn flush(&self) -> Result<(), PipeError> {
+        let graph = self.graph.read().unwrap();
+        let mut res = Ok(());
+        for node in &graph.nodes {
+            if let Kind::Sink(sink) = &node.kind {
+                let flushed = sink.lock().unwrap().flush_frames();
+                if res.is_ok() {
+                    res = flushed;
+                }
+            }
+        }
+        res
+    }
+}
+
+fn check_upstream(graph: &Graph, id: NodeId) -> Result<(), PipeError> {
+    match graph.nodes.get(id.0) {
+        None => Err(PipeError::InvalidGraph(format!("no node {}", id.0))),
+        Some(Node {
+            kind: Kind::Sink(_),
+            name,
+            ..
+        }) => Err(PipeError::InvalidGraph(format!("{name} is a sink"))),
+        Some(_) => Ok(()),
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stops the source threads and waits for them, then flushes the sinks.
+/// A source that doesn't implement
+/// [`FrameSource::next_frame_timeout`] holds this up until it next yields
+/// a frame or ends.
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.alive.store(false, Ordering::Relaxed);
+        let threads = std::mem::take(&mut *self.threads.lock().unwrap());
+        // Sinks such as interfaces need the GIL, so don't hold it while
+        // waiting
+        Python::with_gil(|py| {
+            py.allow_threads(|| {
+                for thread in threads {
+                    let _ = thread.join();
+                }
+            })
+        });
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,135 @@
+//! gs_usb/candleLight device access, through the USB control requests
+//! defined by the Linux gs_usb driver.
+
+use pyo3::{Py, PyAny, PyResult, Python};
+
+use crate::{describe_py_err, PyCanBusType, PyCanError, PyCanInterface};
+
+/// `USB_DIR_IN | USB_TYPE_VENDOR | USB_RECIP_INTERFACE`
+const REQ_TYPE_IN: u8 = 0xC1;
+/// `USB_DIR_OUT | USB_TYPE_VENDOR | USB_RECIP_INTERFACE`
+const REQ_TYPE_OUT: u8 = 0x41;
+
+const BREQ_DEVICE_CONFIG: u8 = 5;
+const BREQ_SET_TERMINATION: u8 = 12;
+const BREQ_GET_TERMINATION: u8 = 13;
+const BREQ_GET_STATE: u8 = 14;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GsusbDeviceInfo {
+    /// Number of CAN channels on the device.
+    pub channels: u16,
+    pub firmware_version: u32,
+    pub hardware_version: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GsusbErrorCounters {
+    /// Controller state, as `enum can_state` in the Linux kernel
+    /// (0 = error-active ... 3 = bus-off).
+    pub state: u32,
+    pub rx_errors: u32,
+    pub tx_errors: u32,
+}
+
+/// Extra functionality for gs_usb interfaces. Every method fails with
+/// [`PyCanError::Unsupported`] on other bus types.
+///
+/// Termination and error counter requests need firmware that implements
+/// them (recent candleLight builds do).
+pub trait GsusbExt {
+    fn gsusb_device_info(&self) -> Result<GsusbDeviceInfo, PyCanError>;
+    fn gsusb_error_counters(&self) -> Result<GsusbErrorCounters, PyCanError>;
+    fn gsusb_termination(&self) -> Result<bool, PyCanError>;
+    fn gsusb_set_termination(&self, enabled: bool) -> Result<(), PyCanError>;
+}
+
+fn le_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+impl PyCanInterface {
+    /// The gs_usb channel number, or Unsupported if this isn't a gs_usb bus.
+    fn gsusb_channel(&self) -> Result<u16, PyCanError> {
+        let PyCanBusType::Gsusb { usb_channel, .. } = &self.bustype else {
+            return Err(PyCanError::Unsupported(format!(
+                "gs_usb requests on {}",
+                self.bustype
+            )));
+        };
+
+        Ok(usb_channel.parse().unwrap_or(0))
+    }
+
+    /// The pyusb device underlying python-can's GsUsbBus.
+    fn gsusb_device(&self, py: Python) -> PyResult<Py<PyAny>> {
+        self.iface.getattr(py, "gs_usb")?.getattr(py, "gs_usb")
+    }
+
+    fn gsusb_read(&self, request: u8, len: usize) -> Result<Vec<u8>, PyCanError> {
+        let channel = self.gsusb_channel()?;
+
+        Python::with_gil(|py| -> PyResult<Vec<u8>> {
+            self.gsusb_device(py)?
+                .call_method1(py, "ctrl_transfer", (REQ_TYPE_IN, request, channel, 0, len))?
+                .extract(py)
+        })
+        .map_err(|e| PyCanError::BackendRequestFailed(describe_py_err(&e)))
+        .and_then(|buf| {
+            if buf.len() < len {
+                return Err(PyCanError::BackendRequestFailed(format!(
+                    "short gs_usb response to request {request}: {buf:02X?}"
+                )));
+            }
+            Ok(buf)
+        })
+    }
+
+    fn gsusb_write(&self, request: u8, data: &[u8]) -> Result<(), PyCanError> {
+        let channel = self.gsusb_channel()?;
+
+        Python::with_gil(|py| -> PyResult<()> {
+            self.gsusb_device(py)?.call_method1(
+                py,
+                "ctrl_transfer",
+                (REQ_TYPE_OUT, request, channel, 0, data.to_vec()),
+            )?;
+            Ok(())
+        })
+        .map_err(|e| PyCanError::BackendRequestFailed(describe_py_err(&e)))
+    }
+}
+
+impl GsusbExt for PyCanInterface {
+    fn gsusb_device_info(&self) -> Result<GsusbDeviceInfo, PyCanError> {
+        // struct gs_device_config { u8 reserved[3]; u8 icount; u32 sw_version; u32 hw_version; }
+        let buf = self.gsusb_read(BREQ_DEVICE_CONFIG, 12)?;
+
+        Ok(GsusbDeviceInfo {
+            // icount is the highest channel index
+            channels: u16::from(buf[3]) + 1,
+            firmware_version: le_u32(&buf, 4),
+            hardware_version: le_u32(&buf, 8),
+        })
+    }
+
+    fn gsusb_error_counters(&self) -> Result<GsusbErrorCounters, PyCanError> {
+        // struct gs_device_state { u32 state; u32 rxerr; u32 txerr; }
+        let buf = self.gsusb_read(BREQ_GET_STATE, 12)?;
+
+        Ok(GsusbErrorCounters {
+            state: le_u32(&buf, 0),
+            rx_errors: le_u32(&buf, 4),
+            tx_errors: le_u32(&buf, 8),
+        })
+    }
+
+    fn gsusb_termination(&self) -> Result<bool, PyCanError> {
+        let buf = self.gsusb_read(BREQ_GET_TERMINATION, 4)?;
+        Ok(le_u32(&buf, 0) != 0)
+    }
+
+    fn gsusb_set_termination(&self, enabled: bool) -> Result<(), PyCanError> {
+        self.gsusb_write(BREQ_SET_TERMINATION, &u32::from(enabled).to_le_bytes())
+    }
+}
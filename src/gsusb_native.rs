@@ -0,0 +1,386 @@
+//! Native gs_usb/candleLight driver.
+//!
+//! Talks to the adapter through Linux usbdevfs (`/dev/bus/usb`), so it
+//! needs neither python-can nor pyusb and libusb. The kernel's gs_usb
+//! driver is detached from the interface while the bus is open. Linux
+//! only.
+
+use std::{
+    fs::{File, OpenOptions},
+    os::fd::AsRawFd,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{message::len_to_dlc, CanBus, CanId, PyCanBusType, PyCanError, PyCanMessage};
+
+/// `USB_DIR_IN | USB_TYPE_VENDOR | USB_RECIP_INTERFACE`
+const REQ_TYPE_IN: u8 = 0xC1;
+/// `USB_DIR_OUT | USB_TYPE_VENDOR | USB_RECIP_INTERFACE`
+const REQ_TYPE_OUT: u8 = 0x41;
+
+const BREQ_HOST_FORMAT: u8 = 0;
+const BREQ_BITTIMING: u8 = 1;
+const BREQ_MODE: u8 = 2;
+const BREQ_BT_CONST: u8 = 4;
+
+const MODE_RESET: u32 = 0;
+const MODE_START: u32 = 1;
+
+const ENDPOINT_IN: u32 = 0x81;
+const ENDPOINT_OUT: u32 = 0x02;
+const INTERFACE: u32 = 0;
+
+/// Echo id of frames received from the bus, as opposed to echoes of our
+/// own transmissions.
+const RX_ECHO_ID: u32 = 0xFFFF_FFFF;
+
+// Flags in a host frame's can_id, as in Linux's struct can_frame
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+const CAN_EFF_MASK: u32 = 0x1FFF_FFFF;
+
+/// `struct gs_host_frame` without the optional hardware timestamp.
+const HOST_FRAME_LEN: usize = 20;
+
+/// Sample point aimed for when picking bit timing, in tenths of a percent.
+const SAMPLE_POINT: u32 = 875;
+
+#[repr(C)]
+struct CtrlTransfer {
+    request_type: u8,
+    request: u8,
+    value: u16,
+    index: u16,
+    length: u16,
+    timeout: u32,
+    data: *mut libc::c_void,
+}
+
+#[repr(C)]
+struct BulkTransfer {
+    endpoint: u32,
+    length: u32,
+    timeout: u32,
+    data: *mut libc::c_void,
+}
+
+#[repr(C)]
+struct UsbIoctl {
+    interface: libc::c_int,
+    code: libc::c_int,
+    data: *mut libc::c_void,
+}
+
+/// Linux's `_IOC` encoding for usbdevfs requests.
+const fn ioc(dir: u64, nr: u64, size: usize) -> u64 {
+    (dir << 30) | ((size as u64) << 16) | ((b'U' as u64) << 8) | nr
+}
+
+const USBDEVFS_CONTROL: u64 = ioc(3, 0, std::mem::size_of::<CtrlTransfer>());
+const USBDEVFS_BULK: u64 = ioc(3, 2, std::mem::size_of::<BulkTransfer>());
+const USBDEVFS_CLAIMINTERFACE: u64 = ioc(2, 15, std::mem::size_of::<libc::c_uint>());
+const USBDEVFS_RELEASEINTERFACE: u64 = ioc(2, 16, std::mem::size_of::<libc::c_uint>());
+const USBDEVFS_IOCTL: u64 = ioc(3, 18, std::mem::size_of::<UsbIoctl>());
+const USBDEVFS_DISCONNECT: u64 = ioc(0, 22, 0);
+const USBDEVFS_CONNECT: u64 = ioc(0, 23, 0);
+
+fn ioctl<T>(dev: &File, request: u64, arg: &mut T) -> std::io::Result<libc::c_int> {
+    // SAFETY: every request above is paired with the struct it expects,
+    // and any buffers those structs point to outlive the call
+    match unsafe { libc::ioctl(dev.as_raw_fd(), request as _, arg as *mut T) } {
+        -1 => Err(std::io::Error::last_os_error()),
+        n => Ok(n),
+    }
+}
+
+/// Detach or reattach the kernel driver bound to our interface.
+fn driver_ioctl(dev: &File, code: u64) -> std::io::Result<libc::c_int> {
+    let mut req = UsbIoctl {
+        interface: INTERFACE as _,
+        code: code as _,
+        data: std::ptr::null_mut(),
+    };
+    ioctl(dev, USBDEVFS_IOCTL, &mut req)
+}
+
+fn control(
+    dev: &File,
+    request_type: u8,
+    request: u8,
+    value: u16,
+    buf: &mut [u8],
+) -> std::io::Result<()> {
+    let mut ctrl = CtrlTransfer {
+        request_type,
+        request,
+        value,
+        index: INTERFACE as u16,
+        length: buf.len() as u16,
+        timeout: 1000,
+        data: buf.as_mut_ptr().cast(),
+    };
+    ioctl(dev, USBDEVFS_CONTROL, &mut ctrl).map(|_| ())
+}
+
+/// Bulk transfer on `endpoint`; a timeout of zero waits forever. Returns
+/// the number of bytes moved, or None on timeout.
+fn bulk(dev: &File, endpoint: u32, buf: &mut [u8], timeout: u32) -> std::io::Result<Option<usize>> {
+    let mut xfer = BulkTransfer {
+        endpoint,
+        length: buf.len() as u32,
+        timeout,
+        data: buf.as_mut_ptr().cast(),
+    };
+    match ioctl(dev, USBDEVFS_BULK, &mut xfer) {
+        Ok(n) => Ok(Some(n as usize)),
+        Err(e) if e.raw_os_error() == Some(libc::ETIMEDOUT) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn le_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn le_words(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+/// `struct gs_device_bittiming` (prop_seg, phase_seg1, phase_seg2, sjw,
+/// brp) for `bitrate`, from the limits in `struct gs_device_bt_const`.
+fn bit_timing(bt_const: &[u8], bitrate: u32) -> Option<[u32; 5]> {
+    let [fclk, tseg1_min, tseg1_max, tseg2_min, tseg2_max, _sjw_max, brp_min, brp_max, brp_inc] =
+        [4, 8, 12, 16, 20, 24, 28, 32, 36].map(|at| le_u32(bt_const, at));
+
+    if bitrate == 0 {
+        return None;
+    }
+
+    // Timing for one prescaler, if the segments fit the limits
+    let timing = |brp: u32| -> Option<[u32; 5]> {
+        let tq = bitrate.checked_mul(brp)?;
+        if fclk % tq != 0 {
+            return None;
+        }
+        let total = fclk / tq;
+        let tseg2 = total
+            .checked_mul(1000 - SAMPLE_POINT)?
+            .div_ceil(1000)
+            .max(tseg2_min);
+        let tseg1 = total.checked_sub(tseg2.checked_add(1)?)?;
+        ((tseg1_min..=tseg1_max).contains(&tseg1) && tseg1 > 0 && tseg2 <= tseg2_max)
+            .then(|| [1, tseg1 - 1, tseg2, 1, brp])
+    };
+
+    let mut brp = brp_min.max(1);
+    while brp <= brp_max {
+        if let Some(timing) = timing(brp) {
+            return Some(timing);
+        }
+        brp = brp.checked_add(brp_inc.max(1))?;
+    }
+    None
+}
+
+fn parse_frame(buf: &[u8]) -> Option<PyCanMessage> {
+    if buf.len() < HOST_FRAME_LEN || le_u32(buf, 0) != RX_ECHO_ID {
+        return None;
+    }
+
+    let can_id = le_u32(buf, 4);
+    let extended = can_id & CAN_EFF_FLAG != 0;
+    let id = CanId::new(can_id & CAN_EFF_MASK, extended).ok()?;
+    let dlc = buf[8];
+
+    let mut msg = PyCanMessage::new(id, &[]);
+    msg.dlc = Some(dlc);
+    msg.is_remote_frame = can_id & CAN_RTR_FLAG != 0;
+    msg.is_error_frame = can_id & CAN_ERR_FLAG != 0;
+    if !msg.is_remote_frame {
+        msg.data = Some(buf[12..12 + usize::from(dlc.min(8))].to_vec());
+    }
+    Some(msg)
+}
+
+/// A gs_usb adapter opened without python-can. The channel is stopped and
+/// the kernel driver reattached on drop.
+pub struct GsusbBus {
+    dev: File,
+    channel: u8,
+    echo_id: AtomicU32,
+    /// Serializes reads, so a frame is never split between callers.
+    rx: Mutex<()>,
+    name: Arc<str>,
+}
+
+impl GsusbBus {
+    /// Open the adapter described by a gs_usb bus type and start the
+    /// channel at its bitrate.
+    pub fn open(kind: &PyCanBusType) -> Result<Self, PyCanError> {
+        let PyCanBusType::Gsusb {
+            bitrate,
+            usb_channel,
+            usb_bus,
+            usb_address,
+        } = kind
+        else {
+            return Err(PyCanError::Unsupported(format!("native gs_usb on {kind}")));
+        };
+        let fail = |why: String| PyCanError::FailedToCreateInterface(why);
+
+        let channel: u8 = usb_channel
+            .parse()
+            .map_err(|_| fail(format!("invalid gs_usb channel {usb_channel}")))?;
+
+        let path = format!("/dev/bus/usb/{usb_bus:03}/{usb_address:03}");
+        let dev = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|e| PyCanError::open_failed(&path, e))?;
+
+        // Fails harmlessly if no kernel driver is bound
+        let _ = driver_ioctl(&dev, USBDEVFS_DISCONNECT);
+
+        let mut interface = INTERFACE as libc::c_uint;
+        ioctl(&dev, USBDEVFS_CLAIMINTERFACE, &mut interface)
+            .map_err(|e| PyCanError::open_failed(&format!("claiming {path}"), e))?;
+
+        let setup = || -> Result<(), String> {
+            let value = u16::from(channel);
+            control(
+                &dev,
+                REQ_TYPE_OUT,
+                BREQ_HOST_FORMAT,
+                1,
+                &mut le_words(&[0x0000_beef]),
+            )
+            .map_err(|e| e.to_string())?;
+            control(
+                &dev,
+                REQ_TYPE_OUT,
+                BREQ_MODE,
+                value,
+                &mut le_words(&[MODE_RESET, 0]),
+            )
+            .map_err(|e| e.to_string())?;
+
+            let mut bt_const = [0; 40];
+            control(&dev, REQ_TYPE_IN, BREQ_BT_CONST, value, &mut bt_const)
+                .map_err(|e| e.to_string())?;
+            let timing = bit_timing(&bt_const, *bitrate)
+                .ok_or_else(|| format!("unsupported gs_usb bitrate {bitrate}"))?;
+
+            control(
+                &dev,
+                REQ_TYPE_OUT,
+                BREQ_BITTIMING,
+                value,
+                &mut le_words(&timing),
+            )
+            .map_err(|e| e.to_string())?;
+            control(
+                &dev,
+                REQ_TYPE_OUT,
+                BREQ_MODE,
+                value,
+                &mut le_words(&[MODE_START, 0]),
+            )
+            .map_err(|e| e.to_string())
+        };
+        setup().map_err(fail)?;
+
+        Ok(Self {
+            dev,
+            channel,
+            echo_id: AtomicU32::new(0),
+            rx: Mutex::new(()),
+            name: format!("gs_usb:{usb_bus}:{usb_address}:{channel}").into(),
+        })
+    }
+}
+
+impl CanBus for GsusbBus {
+    fn send_frame(&self, msg: &PyCanMessage) -> Result<(), PyCanError> {
+        let data = msg.data.as_deref().unwrap_or_default();
+        if msg.is_fd || data.len() > 8 {
+            return Err(PyCanError::Unsupported(
+                "CAN FD frames over native gs_usb".into(),
+            ));
+        }
+
+        let id = msg.arbitration_id;
+        let mut can_id = id.raw();
+        if id.is_extended() {
+            can_id |= CAN_EFF_FLAG;
+        }
+        if msg.is_remote_frame {
+            can_id |= CAN_RTR_FLAG;
+        }
+
+        // Anything but RX_ECHO_ID; the adapter echoes it back once sent
+        let echo_id = self.echo_id.fetch_add(1, Ordering::Relaxed) % RX_ECHO_ID;
+        let mut frame = [0; HOST_FRAME_LEN];
+        frame[0..4].copy_from_slice(&echo_id.to_le_bytes());
+        frame[4..8].copy_from_slice(&can_id.to_le_bytes());
+        frame[8] = msg.dlc.or(len_to_dlc(data.len())).unwrap_or(0);
+        frame[9] = self.channel;
+        frame[12..12 + data.len()].copy_from_slice(data);
+
+        match bulk(&self.dev, ENDPOINT_OUT, &mut frame, 1000) {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => Err(PyCanError::FailedToSend("timed out".into())),
+            Err(e) => Err(PyCanError::FailedToSend(e.to_string())),
+        }
+    }
+
+    fn recv_frame(&self, timeout: Option<Duration>) -> Result<Option<PyCanMessage>, PyCanError> {
+        let _rx = self.rx.lock().unwrap();
+        // A timeout too long to represent waits forever
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+
+        loop {
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if remaining == Some(Duration::ZERO) {
+                return Ok(None);
+            }
+            let ms = remaining.map_or(0, |t| t.as_millis().clamp(1, u32::MAX as u128) as u32);
+
+            let mut buf = [0; 64];
+            let n = bulk(&self.dev, ENDPOINT_IN, &mut buf, ms)
+                .map_err(|e| PyCanError::FailedToReceive(e.to_string()))?;
+            let Some(n) = n else {
+                return Ok(None);
+            };
+
+            // Echoes of our own frames and other channels are skipped
+            if buf[9] != self.channel {
+                continue;
+            }
+            if let Some(mut msg) = parse_frame(&buf[..n]) {
+                msg.iface_name = Some(self.name.clone());
+                return Ok(Some(msg));
+            }
+        }
+    }
+}
+
+impl Drop for GsusbBus {
+    fn drop(&mut self) {
+        let _ = control(
+            &self.dev,
+            REQ_TYPE_OUT,
+            BREQ_MODE,
+            u16::from(self.channel),
+            &mut le_words(&[MODE_RESET, 0]),
+        );
+        let mut interface = INTERFACE as libc::c_uint;
+        let _ = ioctl(&self.dev, USBDEVFS_RELEASEINTERFACE, &mut interface);
+        let _ = driver_ioctl(&self.dev, USBDEVFS_CONNECT);
+    }
+}
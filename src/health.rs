@@ -0,0 +1,96 @@
+//! Liveness summary for supervisors, e.g. a systemd watchdog or a
+//! Kubernetes probe deciding whether to restart the process.
+
+use std::{sync::Mutex, time::Duration};
+
+use pyo3::{types::PyModule, Py, PyAny, PyResult, Python};
+
+use crate::{describe_py_err, notifier::notifier_alive, BusState, PyCanError, PyCanInterface};
+
+/// A listener that only records when the last frame arrived, kept in
+/// Python so it costs no round-trip into Rust per frame.
+const RX_CLOCK: &str = r#"
+import time
+import can
+
+class RxClock(can.Listener):
+    last_rx = None
+
+    def on_message_received(self, msg):
+        self.last_rx = time.monotonic()
+
+    def age(self):
+        return None if self.last_rx is None else time.monotonic() - self.last_rx
+"#;
+
+#[derive(Clone, Debug)]
+pub struct HealthReport {
+    pub notifier_alive: bool,
+    /// Time since the last frame was received, or None if none has been.
+    pub last_rx_age: Option<Duration>,
+    /// Whether the last send succeeded, or None if nothing was sent yet.
+    pub last_tx_ok: Option<bool>,
+    /// Whether the Python interpreter could run code.
+    pub python_alive: bool,
+    /// None if the state couldn't be read.
+    pub state: Option<BusState>,
+}
+
+/// Health tracking state owned by the interface.
+pub(crate) struct Health {
+    rx_clock: Py<PyAny>,
+    last_tx_ok: Mutex<Option<bool>>,
+}
+
+impl Health {
+    /// Create the rx clock and add it to `notifier`.
+    pub(crate) fn new(py: Python, notifier: &Py<PyAny>) -> Result<Self, PyCanError> {
+        let rx_clock = PyModule::from_code(py, RX_CLOCK, "pycanrs_health.py", "pycanrs_health")
+            .and_then(|module| module.getattr("RxClock")?.call0())
+            .map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))?;
+
+        notifier
+            .call_method1(py, "add_listener", (rx_clock,))
+            .map_err(|e| PyCanError::FailedToAddListener(describe_py_err(&e)))?;
+
+        Ok(Self {
+            rx_clock: rx_clock.into(),
+            last_tx_ok: Mutex::new(None),
+        })
+    }
+
+    pub(crate) fn record_tx(&self, ok: bool) {
+        *self.last_tx_ok.lock().unwrap() = Some(ok);
+    }
+}
+
+impl PyCanInterface {
+    pub fn health(&self) -> HealthReport {
+        let (python_alive, notifier_alive, last_rx_age) = Python::with_gil(|py| {
+            let python_alive = py
+                .eval("True", None, None)
+                .and_then(|v| v.extract())
+                .unwrap_or(false);
+
+            let age: PyResult<Option<f64>> = self
+                .health
+                .rx_clock
+                .call_method0(py, "age")
+                .and_then(|age| age.extract(py));
+
+            (
+                python_alive,
+                notifier_alive(py, &self.notifier),
+                age.ok().flatten().map(Duration::from_secs_f64),
+            )
+        });
+
+        HealthReport {
+            notifier_alive,
+            last_rx_age,
+            last_tx_ok: *self.health.last_tx_ok.lock().unwrap(),
+            python_alive,
+            state: self.state().ok(),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Following a USB adapter as it's unplugged and plugged back in.
+//!
+//! The watcher polls for the adapter: slcan by its serial port,
+//! gs_usb through pyusb. When it comes back, possibly at a new USB
+//! address, the interface is reopened on it with
+//! [`PyCanInterface::reopen`], keeping its subscriptions.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, TryLockError,
+    },
+    time::Duration,
+};
+
+use pyo3::{types::IntoPyDict, PyResult, Python, ToPyObject};
+
+use crate::{
+    platform,
+    usb::{GSUSB_PID, GSUSB_VID},
+    PyCanBusType, PyCanError, PyCanEvent, PyCanInterface,
+};
+
+/// How long to wait, without the GIL, before trying the interface's lock
+/// again.
+const LOCK_RETRY: Duration = Duration::from_millis(10);
+
+/// Where the adapter for `kind` currently is, or None if it's unplugged.
+/// A gs_usb adapter that isn't at its old address is assumed to be the
+/// only one attached.
+fn locate(kind: &PyCanBusType) -> Option<PyCanBusType> {
+    match kind {
+        PyCanBusType::Slcan { serial_port, .. } => {
+            platform::serial_port_present(serial_port).then(|| kind.clone())
+        }
+        PyCanBusType::Gsusb {
+            bitrate,
+            usb_channel,
+            usb_bus,
+            usb_address,
+        } => {
+            let devices = Python::with_gil(|py| -> PyResult<Vec<(u32, u32)>> {
+                let kwargs = [
+                    ("find_all", true.to_object(py)),
+                    ("idVendor", GSUSB_VID.to_object(py)),
+                    ("idProduct", GSUSB_PID.to_object(py)),
+                ]
+                .into_py_dict(py);
+
+                py.import("usb.core")?
+                    .call_method("find", (), Some(kwargs))?
+                    .iter()?
+                    .map(|dev| {
+                        let dev = dev?;
+                        Ok((
+                            dev.getattr("bus")?.extract()?,
+                            dev.getattr("address")?.extract()?,
+                        ))
+                    })
+                    .collect()
+            })
+            .ok()?;
+
+            let (bus, address) = match devices[..] {
+                _ if devices.contains(&(*usb_bus, *usb_address)) => (*usb_bus, *usb_address),
+                [only] => only,
+                _ => return None,
+            };
+
+            Some(PyCanBusType::Gsusb {
+                bitrate: *bitrate,
+                usb_channel: usb_channel.clone(),
+                usb_bus: bus,
+                usb_address: address,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Watches an interface's adapter and reopens the interface when it
+/// reappears. Stops when dropped, or once the interface is dropped.
+pub struct HotplugWatcher {
+    stop: Arc<AtomicBool>,
+}
+
+impl HotplugWatcher {
+    /// Start polling every `interval`. Only slcan and gs_usb interfaces
+    /// can be watched. Reopening holds `iface`'s lock while it runs Python,
+    /// so other threads shouldn't wait for the lock while holding the GIL.
+    pub fn spawn(
+        iface: Arc<Mutex<PyCanInterface>>,
+        interval: Duration,
+    ) -> Result<Self, PyCanError> {
+        let (mut kind, events) = {
+            let iface = iface.lock().unwrap();
+            match iface.bustype {
+                PyCanBusType::Slcan { .. } | PyCanBusType::Gsusb { .. } => {}
+                _ => {
+                    return Err(PyCanError::Unsupported(format!(
+                        "hotplug detection on {}",
+                        iface.bustype
+                    )))
+                }
+            }
+            (iface.bustype.clone(), iface.events.clone())
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let iface = Arc::downgrade(&iface);
+
+        std::thread::spawn(move || {
+            let mut present = true;
+            let mut attempt = 0;
+
+            while !stopped.load(Ordering::Relaxed) && iface.strong_count() > 0 {
+                std::thread::sleep(interval);
+
+                match (present, locate(&kind)) {
+                    (true, None) => {
+                        present = false;
+                        events.emit(PyCanEvent::AdapterDetached);
+                    }
+                    (false, Some(found)) => {
+                        attempt += 1;
+                        events.emit(PyCanEvent::ReconnectAttempt { attempt });
+
+                        let Some(iface) = iface.upgrade() else {
+                            return;
+                        };
+                        if reopen(&iface, &found, &stopped) {
+                            present = true;
+                            attempt = 0;
+                            kind = found;
+                            events.emit(PyCanEvent::AdapterAttached);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        Ok(Self { stop })
+    }
+}
+
+/// Reopen `iface` on `kind`. The lock is taken with the GIL held, as
+/// notifier callbacks do, and never waited on with it held: a thread
+/// holding the lock may be waiting for the GIL.
+fn reopen(iface: &Mutex<PyCanInterface>, kind: &PyCanBusType, stopped: &AtomicBool) -> bool {
+    while !stopped.load(Ordering::Relaxed) {
+        let reopened = Python::with_gil(|_| match iface.try_lock() {
+            Ok(mut iface) => Some(iface.reopen(kind.clone()).is_ok()),
+            Err(TryLockError::Poisoned(_)) => Some(false),
+            Err(TryLockError::WouldBlock) => None,
+        });
+        match reopened {
+            Some(reopened) => return reopened,
+            None => std::thread::sleep(LOCK_RETRY),
+        }
+    }
+    false
+}
+
+impl Drop for HotplugWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
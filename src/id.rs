@@ -0,0 +1,91 @@
+use std::{fmt::Display, str::FromStr};
+use thiserror::Error;
+
+/// Largest 11-bit identifier.
+pub const STANDARD_ID_MAX: u16 = 0x7FF;
+/// Largest 29-bit identifier.
+pub const EXTENDED_ID_MAX: u32 = 0x1FFF_FFFF;
+
+/// A CAN arbitration ID, tagged with whether it's an 11-bit standard
+/// or 29-bit extended identifier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CanId {
+    Standard(u16),
+    Extended(u32),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CanIdError {
+    #[error("ID `{0:#X}` does not fit in 11 bits")]
+    StandardOutOfRange(u32),
+    #[error("ID `{0:#X}` does not fit in 29 bits")]
+    ExtendedOutOfRange(u32),
+    #[error("Invalid CAN ID `{0}` - expected 3 (standard) or 8 (extended) hex digits")]
+    InvalidFormat(String),
+}
+
+impl CanId {
+    pub fn standard(id: u16) -> Result<Self, CanIdError> {
+        if id > STANDARD_ID_MAX {
+            return Err(CanIdError::StandardOutOfRange(id.into()));
+        }
+        Ok(Self::Standard(id))
+    }
+
+    pub fn extended(id: u32) -> Result<Self, CanIdError> {
+        if id > EXTENDED_ID_MAX {
+            return Err(CanIdError::ExtendedOutOfRange(id));
+        }
+        Ok(Self::Extended(id))
+    }
+
+    /// Build an ID from a raw value and python-can's `is_extended_id` flag.
+    pub fn new(raw: u32, extended: bool) -> Result<Self, CanIdError> {
+        if extended {
+            Self::extended(raw)
+        } else {
+            let id = u16::try_from(raw).map_err(|_| CanIdError::StandardOutOfRange(raw))?;
+            Self::standard(id)
+        }
+    }
+
+    /// The numeric identifier, without the standard/extended distinction.
+    pub fn raw(&self) -> u32 {
+        match *self {
+            Self::Standard(id) => id.into(),
+            Self::Extended(id) => id,
+        }
+    }
+
+    pub fn is_extended(&self) -> bool {
+        matches!(self, Self::Extended(_))
+    }
+}
+
+/// candump notation: 3 hex digits for standard IDs, 8 for extended.
+impl Display for CanId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Standard(id) => write!(f, "{id:03X}"),
+            Self::Extended(id) => write!(f, "{id:08X}"),
+        }
+    }
+}
+
+impl FromStr for CanId {
+    type Err = CanIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CanIdError::InvalidFormat(s.into());
+
+        if !s.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        match s.len() {
+            1..=3 => Self::new(u32::from_str_radix(s, 16).map_err(|_| invalid())?, false),
+            8 => Self::new(u32::from_str_radix(s, 16).map_err(|_| invalid())?, true),
+            _ => Err(invalid()),
+        }
+    }
+}
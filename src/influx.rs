@@ -0,0 +1,178 @@
+//! InfluxDB sink for signal values, using line protocol over plain HTTP.
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::PyCanError;
+
+/// How long connecting, and each read or write, may take before a write
+/// is given up on.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where and how to write points.
+#[derive(Clone, Debug)]
+pub struct InfluxConfig {
+    /// `host:port` of the InfluxDB HTTP API.
+    pub addr: String,
+    /// Write endpoint including its query, e.g. `/write?db=telemetry` (1.x)
+    /// or `/api/v2/write?org=o&bucket=b&precision=ns` (2.x).
+    pub path: String,
+    /// Sent as `Authorization: Token <token>` if set.
+    pub token: Option<String>,
+    /// Measurement name the signals are written under.
+    pub measurement: String,
+    /// Number of points to buffer before writing.
+    pub batch_size: usize,
+}
+
+/// Buffers signal values and writes them to InfluxDB.
+///
+/// Each signal can be downsampled with [`InfluxSink::downsample`], in which
+/// case samples closer than the given interval to the last kept sample are
+/// dropped. Buffered points are written on drop.
+pub struct InfluxSink {
+    config: InfluxConfig,
+    min_interval: HashMap<String, f64>,
+    last_kept: HashMap<String, f64>,
+    lines: String,
+    pending: usize,
+}
+
+/// Escape a line protocol measurement name or field key.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+impl InfluxSink {
+    pub fn new(config: InfluxConfig) -> Self {
+        Self {
+            config,
+            min_interval: HashMap::new(),
+            last_kept: HashMap::new(),
+            lines: String::new(),
+            pending: 0,
+        }
+    }
+
+    /// Keep at most one sample of `signal` per `interval`.
+    pub fn downsample(&mut self, signal: &str, interval: Duration) {
+        self.min_interval
+            .insert(signal.into(), interval.as_secs_f64());
+    }
+
+    /// Record a value of `signal` at `timestamp` (seconds since the epoch,
+    /// as in python-can message timestamps). Line protocol can't represent
+    /// NaN or infinity, so such values are skipped.
+    pub fn record(&mut self, signal: &str, value: f64, timestamp: f64) -> Result<(), PyCanError> {
+        if !value.is_finite() || !timestamp.is_finite() {
+            return Ok(());
+        }
+
+        if let Some(interval) = self.min_interval.get(signal) {
+            if let Some(last) = self.last_kept.get(signal) {
+                if timestamp - last < *interval {
+                    return Ok(());
+                }
+            }
+            self.last_kept.insert(signal.into(), timestamp);
+        }
+
+        let ns = (timestamp * 1e9) as i64;
+        let _ = writeln!(
+            self.lines,
+            "{} {}={value} {ns}",
+            escape(&self.config.measurement),
+            escape(signal)
+        );
+        self.pending += 1;
+
+        if self.pending >= self.config.batch_size {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Write out any buffered points.
+    pub fn flush(&mut self) -> Result<(), PyCanError> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+
+        let body = std::mem::take(&mut self.lines);
+        self.pending = 0;
+
+        self.post(&body)
+            .map_err(|e| PyCanError::CaptureFailed(format!("InfluxDB write failed: {e}")))
+    }
+
+    fn post(&self, body: &str) -> Result<(), String> {
+        let host = self
+            .config
+            .addr
+            .rsplit_once(':')
+            .map_or(self.config.addr.as_str(), |(h, _)| h);
+
+        let mut req = format!(
+            "POST {} HTTP/1.1\r\nHost: {host}\r\nContent-Type: text/plain; charset=utf-8\r\n\
+             Content-Length: {}\r\nConnection: close\r\n",
+            self.config.path,
+            body.len()
+        );
+        if let Some(token) = &self.config.token {
+            let _ = write!(req, "Authorization: Token {token}\r\n");
+        }
+        req += "\r\n";
+        req += body;
+
+        let mut stream = self.connect().map_err(|e| e.to_string())?;
+        stream
+            .write_all(req.as_bytes())
+            .map_err(|e| e.to_string())?;
+
+        let mut status = String::new();
+        BufReader::new(stream)
+            .read_line(&mut status)
+            .map_err(|e| e.to_string())?;
+
+        // e.g. `HTTP/1.1 204 No Content`
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(status.trim().into()),
+        }
+    }
+
+    fn connect(&self) -> std::io::Result<TcpStream> {
+        let mut last_err = None;
+        for addr in self.config.addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, IO_TIMEOUT) {
+                Ok(stream) => {
+                    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+                    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+                    return Ok(stream);
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "address resolved to nothing",
+            )
+        }))
+    }
+}
+
+impl Drop for InfluxSink {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,149 @@
+//! Middleware run on every received and sent frame.
+//!
+//! Interceptors run in the order they were added. Each can modify the
+//! frame, drop it, or hold it back for a while before passing it on.
+//! Received frames go through the chain once, before any callback sees
+//! them; sent frames go through it in `send`/`send_with_retry`, before
+//! the frame reaches python-can. Periodic tasks are transmitted by
+//! python-can and bypass the chain.
+
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use pyo3::Python;
+
+use crate::{
+    validate::DlcPolicy, CanId, EventHub, Filter, PyCanEvent, PyCanInterface, PyCanMessage,
+};
+
+/// What to do with a frame after an interceptor has seen it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Pass,
+    Drop,
+    /// Wait, then pass the frame on. Delaying a received frame holds up
+    /// every frame behind it.
+    Delay(Duration),
+}
+
+pub trait FrameInterceptor: Send + Sync {
+    fn on_rx(&self, _msg: &mut PyCanMessage) -> Action {
+        Action::Pass
+    }
+
+    fn on_tx(&self, _msg: &mut PyCanMessage) -> Action {
+        Action::Pass
+    }
+}
+
+/// Drops received frames that don't match. Sent frames are unaffected.
+impl FrameInterceptor for Filter {
+    fn on_rx(&self, msg: &mut PyCanMessage) -> Action {
+        if msg.matches(self) {
+            Action::Pass
+        } else {
+            Action::Drop
+        }
+    }
+}
+
+/// Applies a [`DlcPolicy`] to received frames and reports mismatches.
+pub(crate) struct DlcValidator {
+    pub(crate) policy: DlcPolicy,
+    pub(crate) events: EventHub,
+}
+
+impl FrameInterceptor for DlcValidator {
+    fn on_rx(&self, msg: &mut PyCanMessage) -> Action {
+        let (mismatch, deliver) = self.policy.apply(msg);
+        if let Some(dlc) = mismatch {
+            self.events.emit(PyCanEvent::DlcMismatch {
+                id: msg.arbitration_id,
+                dlc,
+                len: msg.data.as_ref().map_or(0, Vec::len),
+                rejected: !deliver,
+            });
+        }
+
+        if deliver {
+            Action::Pass
+        } else {
+            Action::Drop
+        }
+    }
+}
+
+/// An interface's interceptors. Cheap to clone into listener shims.
+#[derive(Clone, Default)]
+pub(crate) struct Chain(Arc<RwLock<Vec<Arc<dyn FrameInterceptor>>>>);
+
+impl Chain {
+    pub(crate) fn push(&self, interceptor: Arc<dyn FrameInterceptor>) {
+        self.0.write().unwrap().push(interceptor);
+    }
+
+    pub(crate) fn remove(&self, interceptor: &Arc<dyn FrameInterceptor>) -> bool {
+        let mut chain = self.0.write().unwrap();
+        let Some(idx) = chain.iter().position(|i| Arc::ptr_eq(i, interceptor)) else {
+            return false;
+        };
+        chain.remove(idx);
+        true
+    }
+
+    /// Returns whether the frame should go on.
+    fn run(
+        &self,
+        msg: &mut PyCanMessage,
+        hook: impl Fn(&dyn FrameInterceptor, &mut PyCanMessage) -> Action,
+    ) -> bool {
+        // Clone the list so a delay doesn't block adding interceptors
+        let chain = self.0.read().unwrap().clone();
+        for interceptor in chain {
+            match hook(interceptor.as_ref(), msg) {
+                Action::Pass => {}
+                Action::Drop => return false,
+                // The rx path holds the GIL, so release it while we wait
+                Action::Delay(delay) => {
+                    Python::with_gil(|py| py.allow_threads(|| std::thread::sleep(delay)))
+                }
+            }
+        }
+        true
+    }
+
+    pub(crate) fn rx(&self, msg: &mut PyCanMessage) -> bool {
+        self.run(msg, |i, msg| i.on_rx(msg))
+    }
+
+    pub(crate) fn tx(&self, msg: &mut PyCanMessage) -> bool {
+        self.run(msg, |i, msg| i.on_tx(msg))
+    }
+}
+
+impl PyCanInterface {
+    /// Append an interceptor to this interface's chain.
+    pub fn add_interceptor(&self, interceptor: Arc<dyn FrameInterceptor>) {
+        self.interceptors.push(interceptor);
+    }
+
+    /// Remove an interceptor added with [`Self::add_interceptor`]. Returns
+    /// false if it isn't in the chain.
+    pub fn remove_interceptor(&self, interceptor: &Arc<dyn FrameInterceptor>) -> bool {
+        self.interceptors.remove(interceptor)
+    }
+
+    /// Run a frame about to be sent through the chain. Returns the ID and
+    /// payload to send, or None if it was dropped.
+    pub(crate) fn intercept_tx(&self, id: CanId, data: &[u8]) -> Option<(CanId, Vec<u8>)> {
+        let mut msg = PyCanMessage::new(id, data);
+        msg.is_rx = false;
+        msg.iface_name = Some(self.name.clone());
+
+        self.interceptors
+            .tx(&mut msg)
+            .then(|| (msg.arbitration_id, msg.data.unwrap_or_default()))
+    }
+}
@@ -0,0 +1,58 @@
+//! J1939 helpers. J1939 uses 29-bit IDs laid out as
+//! priority (3) | EDP (1) | DP (1) | PF (8) | PS (8) | SA (8).
+
+use pyo3::PyErr;
+
+use crate::{CanId, ListenerId, PyCanError, PyCanInterface, PyCanMessage};
+
+/// Parameter group number of a J1939 ID. For PDU1 formats (PF < 240) the
+/// PS field is a destination address and isn't part of the PGN.
+pub fn pgn(id: CanId) -> Option<u32> {
+    let CanId::Extended(id) = id else {
+        return None;
+    };
+
+    let pgn = (id >> 8) & 0x3_FFFF;
+    let pf = (pgn >> 8) & 0xFF;
+    Some(if pf < 240 { pgn & 0x3_FF00 } else { pgn })
+}
+
+/// Source address of a J1939 ID.
+pub fn source_address(id: CanId) -> Option<u8> {
+    match id {
+        CanId::Extended(id) => Some((id & 0xFF) as u8),
+        CanId::Standard(_) => None,
+    }
+}
+
+/// Priority of a J1939 ID.
+pub fn priority(id: CanId) -> Option<u8> {
+    match id {
+        CanId::Extended(id) => Some(((id >> 26) & 0x7) as u8),
+        CanId::Standard(_) => None,
+    }
+}
+
+impl PyCanInterface {
+    /// Register a callback for frames carrying parameter group `pgn`, from
+    /// any source address and at any priority.
+    pub fn subscribe_pgn<R, E>(
+        &self,
+        pgn: u32,
+        on_rx: R,
+        on_error: E,
+    ) -> Result<ListenerId, PyCanError>
+    where
+        R: Fn(&PyCanMessage) + Send + 'static,
+        E: Fn(&PyErr) + Send + 'static,
+    {
+        self.register_rx_callback(
+            move |msg| {
+                if self::pgn(msg.arbitration_id) == Some(pgn) {
+                    on_rx(msg)
+                }
+            },
+            on_error,
+        )
+    }
+}
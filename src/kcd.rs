@@ -0,0 +1,155 @@
+//! KCD (Kayak) database loading.
+
+use std::{path::Path, time::Duration};
+
+use crate::{
+    decode::{read_xml, DatabaseError},
+    xml::Element,
+    ByteOrder, CanId, Decoder, MessageDef, Multiplexing, Signal,
+};
+
+fn invalid(what: String) -> DatabaseError {
+    DatabaseError::Invalid(what)
+}
+
+fn number<T: std::str::FromStr>(el: &Element, attr: &str, default: T) -> Result<T, DatabaseError> {
+    match el.attr(attr) {
+        None => Ok(default),
+        Some(v) => v
+            .parse()
+            .map_err(|_| invalid(format!("<{}> has invalid {attr} `{v}`", el.name))),
+    }
+}
+
+fn notes(el: &Element) -> Option<String> {
+    el.text_at(&["Notes"]).map(Into::into)
+}
+
+/// A `<Signal>` or `<Multiplex>` element.
+fn parse_signal(el: &Element, multiplexing: Multiplexing) -> Result<Signal, DatabaseError> {
+    let name = el
+        .attr("name")
+        .ok_or_else(|| invalid("<Signal> without a name".into()))?;
+    let offset: u32 = number(el, "offset", 0)?;
+    let length = number(el, "length", 1)?;
+
+    // Big-endian offsets count bits MSB first within each byte
+    let (byte_order, start_bit) = match el.attr("endianess") {
+        Some("big") => (ByteOrder::BigEndian, 8 * (offset / 8) + 7 - offset % 8),
+        _ => (ByteOrder::LittleEndian, offset),
+    };
+
+    let mut signal = Signal {
+        byte_order,
+        multiplexing,
+        comment: notes(el),
+        ..Signal::new(name, start_bit, length)
+    };
+
+    if let Some(value) = el.child("Value") {
+        signal.signed = value.attr("type") == Some("signed");
+        signal.factor = number(value, "slope", 1.0)?;
+        signal.offset = number(value, "intercept", 0.0)?;
+        signal.unit = value.attr("unit").unwrap_or_default().into();
+    }
+
+    for label in el
+        .find(&["LabelSet"])
+        .into_iter()
+        .flat_map(|s| s.children("Label"))
+    {
+        let value = label
+            .attr("value")
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| invalid(format!("<Label> in {name} without a valid value")))?;
+        signal
+            .values
+            .insert(value, label.attr("name").unwrap_or_default().into());
+    }
+
+    Ok(signal)
+}
+
+fn parse_message(el: &Element) -> Result<MessageDef, DatabaseError> {
+    let name = el.attr("name").unwrap_or_default();
+    let raw = el
+        .attr("id")
+        .and_then(|id| u32::from_str_radix(id.trim_start_matches("0x"), 16).ok())
+        .ok_or_else(|| invalid(format!("message {name} without a valid id")))?;
+    let id = CanId::new(raw, el.attr("format") == Some("extended"))
+        .map_err(|e| invalid(format!("message {name}: {e}")))?;
+
+    let mut signals = el
+        .children("Signal")
+        .map(|s| parse_signal(s, Multiplexing::None))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for mux in el.children("Multiplex") {
+        signals.push(parse_signal(mux, Multiplexing::Multiplexor)?);
+        for group in mux.children("MuxGroup") {
+            let count = number(group, "count", 0)?;
+            for signal in group.children("Signal") {
+                signals.push(parse_signal(signal, Multiplexing::Multiplexed(count))?);
+            }
+        }
+    }
+
+    let needed = signals.iter().try_fold(0, |needed, signal| {
+        signal.bytes_needed().map(|n| needed.max(n)).ok_or_else(|| {
+            invalid(format!(
+                "signal {} in {name} has an invalid layout",
+                signal.name
+            ))
+        })
+    })?;
+    // `length` may also be "auto", sized to fit the signals
+    let length = el
+        .attr("length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(needed.clamp(8, 64));
+    if needed > length {
+        return Err(invalid(format!(
+            "signals in {name} don't fit in its {length} bytes"
+        )));
+    }
+    let interval: u64 = number(el, "interval", 0)?;
+
+    Ok(MessageDef {
+        id,
+        name: name.into(),
+        length,
+        cycle_time: (interval > 0).then(|| Duration::from_millis(interval)),
+        signals,
+        comment: notes(el),
+    })
+}
+
+impl Decoder {
+    /// Parse a KCD database. Messages from every `<Bus>` are loaded.
+    pub fn from_kcd(text: &str) -> Result<Self, DatabaseError> {
+        let root = crate::xml::parse(text).map_err(DatabaseError::Xml)?;
+        Self::kcd(&root)
+    }
+
+    /// Read and parse a KCD file.
+    pub fn load_kcd(path: impl AsRef<Path>) -> Result<Self, DatabaseError> {
+        Self::kcd(&read_xml(path.as_ref())?)
+    }
+
+    fn kcd(root: &Element) -> Result<Self, DatabaseError> {
+        if root.name != "NetworkDefinition" {
+            return Err(invalid(format!(
+                "expected <NetworkDefinition>, got <{}>",
+                root.name
+            )));
+        }
+
+        let mut decoder = Self::new();
+        for bus in root.children("Bus") {
+            for message in bus.children("Message") {
+                decoder.add(parse_message(message)?);
+            }
+        }
+        Ok(decoder)
+    }
+}
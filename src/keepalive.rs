@@ -0,0 +1,214 @@
+//! Tester-present style keep-alive frames.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use pyo3::Python;
+
+use crate::{Action, CanId, FrameInterceptor, PyCanInterface, PyCanMessage};
+
+/// How long to wait for a response before assuming it was lost, reset
+/// by each response-pending (NRC 0x78) reply. ISO 14229's P2*server max.
+const P2_STAR_MAX: Duration = Duration::from_secs(5);
+
+/// UDS negative response SID, and the requestCorrectlyReceived-
+/// ResponsePending code.
+const NEGATIVE_RESPONSE: u8 = 0x7f;
+const RESPONSE_PENDING: u8 = 0x78;
+
+struct State {
+    /// When the keep-alive or a request was last sent.
+    last_tx: Instant,
+    /// When a request will be given up on, if one is in flight.
+    in_flight_until: Option<Instant>,
+    stopped: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+    payload: Vec<u8>,
+    failures: AtomicU64,
+}
+
+/// Sends a keep-alive frame, e.g. UDS TesterPresent, every `period`
+/// until dropped.
+///
+/// With [`KeepAlive::watch`], diagnostic requests sent on the interface
+/// restart the period, and no keep-alive is sent while a request is
+/// waiting for its response, as ISO 14229's S3 timer expects.
+///
+/// ```ignore
+/// let keepalive = KeepAlive::start(&iface, CanId::Standard(0x7df), &[0x02, 0x3e, 0x80], Duration::from_secs(2))
+///     .watch(CanId::Standard(0x7e0), CanId::Standard(0x7e8));
+/// ```
+pub struct KeepAlive {
+    shared: Arc<Shared>,
+    iface: Arc<PyCanInterface>,
+    watcher: Option<Arc<dyn FrameInterceptor>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl KeepAlive {
+    pub fn start(iface: &Arc<PyCanInterface>, id: CanId, payload: &[u8], period: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                // Send the first one straight away
+                last_tx: Instant::now()
+                    .checked_sub(period)
+                    .unwrap_or_else(Instant::now),
+                in_flight_until: None,
+                stopped: false,
+            }),
+            wake: Condvar::new(),
+            payload: payload.to_vec(),
+            failures: AtomicU64::new(0),
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            let iface = iface.clone();
+            std::thread::spawn(move || run(&iface, id, period, &shared))
+        };
+
+        Self {
+            shared,
+            iface: iface.clone(),
+            watcher: None,
+            thread: Some(thread),
+        }
+    }
+
+    /// Follow the ISO-TP exchange on `request` and `response`, pausing
+    /// while a request is in flight and restarting the period after each
+    /// one. Requests are only seen if they're sent through this library.
+    pub fn watch(mut self, request: CanId, response: CanId) -> Self {
+        let watcher: Arc<dyn FrameInterceptor> = Arc::new(Watcher {
+            shared: self.shared.clone(),
+            request,
+            response,
+        });
+        if let Some(old) = self.watcher.replace(watcher.clone()) {
+            self.iface.remove_interceptor(&old);
+        }
+        self.iface.add_interceptor(watcher);
+        self
+    }
+
+    /// Keep-alive frames that couldn't be sent.
+    pub fn failures(&self) -> u64 {
+        self.shared.failures.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stopped = true;
+        self.shared.wake.notify_all();
+        if let Some(watcher) = self.watcher.take() {
+            self.iface.remove_interceptor(&watcher);
+        }
+        // The thread needs the GIL to finish a send, so don't hold it
+        // while waiting
+        if let Some(thread) = self.thread.take() {
+            let _ = Python::with_gil(|py| py.allow_threads(|| thread.join()));
+        }
+    }
+}
+
+fn run(iface: &PyCanInterface, id: CanId, period: Duration, shared: &Shared) {
+    let mut state = shared.state.lock().unwrap();
+    loop {
+        if state.stopped {
+            return;
+        }
+
+        let now = Instant::now();
+        let wait_until = match state.in_flight_until {
+            Some(until) if until > now => until,
+            _ => {
+                state.in_flight_until = None;
+                state.last_tx + period
+            }
+        };
+        if wait_until > now {
+            state = shared.wake.wait_timeout(state, wait_until - now).unwrap().0;
+            continue;
+        }
+
+        state.last_tx = now;
+        drop(state);
+        let sent = iface
+            .intercept_tx(id, &shared.payload)
+            .is_none_or(|(id, data)| {
+                Python::with_gil(|py| iface.send_once(py, id, &data, None)).is_ok()
+            });
+        if !sent {
+            shared.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        state = shared.state.lock().unwrap();
+    }
+}
+
+/// The UDS service ID of an ISO-TP single or first frame.
+fn service_id(data: &[u8]) -> Option<(u8, &[u8])> {
+    match data.first()? >> 4 {
+        0 => Some((*data.get(1)?, data.get(2..)?)),
+        1 => Some((*data.get(2)?, data.get(3..)?)),
+        _ => None,
+    }
+}
+
+/// Tracks requests and responses for a [`KeepAlive`].
+struct Watcher {
+    shared: Arc<Shared>,
+    request: CanId,
+    response: CanId,
+}
+
+impl FrameInterceptor for Watcher {
+    fn on_tx(&self, msg: &mut PyCanMessage) -> Action {
+        let data = msg.data.as_deref().unwrap_or_default();
+        if msg.arbitration_id != self.request
+            || data == self.shared.payload
+            || service_id(data).is_none()
+        {
+            return Action::Pass;
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        state.last_tx = Instant::now();
+        state.in_flight_until = Some(state.last_tx + P2_STAR_MAX);
+        Action::Pass
+    }
+
+    fn on_rx(&self, msg: &mut PyCanMessage) -> Action {
+        if msg.arbitration_id != self.response {
+            return Action::Pass;
+        }
+        let Some((sid, rest)) = service_id(msg.data.as_deref().unwrap_or_default()) else {
+            return Action::Pass;
+        };
+
+        let mut state = self.shared.state.lock().unwrap();
+        if state.in_flight_until.is_none() {
+            return Action::Pass;
+        }
+        let now = Instant::now();
+        state.in_flight_until = match (sid, rest.get(1)) {
+            (NEGATIVE_RESPONSE, Some(&RESPONSE_PENDING)) => Some(now + P2_STAR_MAX),
+            _ => None,
+        };
+        // S3 runs from the end of the exchange
+        state.last_tx = now;
+        drop(state);
+        self.shared.wake.notify_all();
+        Action::Pass
+    }
+}
@@ -0,0 +1,119 @@
+//! Round-trip latency measurement with probe frames.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{CanId, PyCanError, PyCanInterface};
+
+/// What to send and what to wait for.
+///
+/// Each probe carries a sequence number in its first 4 bytes. A probe is
+/// answered by a frame on `response_id` carrying the same sequence number:
+/// a responder on the bus echoing the payload, or, with `response_id` equal
+/// to `probe_id` and TX confirmations enabled on the builder, the backend's
+/// own echo of the probe.
+#[derive(Clone, Debug)]
+pub struct LatencyProbe {
+    pub probe_id: CanId,
+    pub response_id: CanId,
+    pub count: u32,
+    /// Delay between probes.
+    pub interval: Duration,
+    /// How long to wait for responses after the last probe.
+    pub timeout: Duration,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LatencyReport {
+    pub sent: u32,
+    pub received: u32,
+    pub min: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencyReport {
+    fn from_samples(sent: u32, mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+
+        let percentile = |p: usize| {
+            samples
+                .get((samples.len() * p / 100).min(samples.len().saturating_sub(1)))
+                .copied()
+                .unwrap_or_default()
+        };
+
+        Self {
+            sent,
+            received: samples.len() as u32,
+            min: samples.first().copied().unwrap_or_default(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: samples.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+impl PyCanInterface {
+    /// Send `probe.count` probes and report the latency of their responses.
+    pub fn measure_latency(&self, probe: &LatencyProbe) -> Result<LatencyReport, PyCanError> {
+        let in_flight = Arc::new(Mutex::new(HashMap::<u32, Instant>::new()));
+        let samples = Arc::new(Mutex::new(Vec::new()));
+
+        let listener = {
+            let in_flight = in_flight.clone();
+            let samples = samples.clone();
+            let response_id = probe.response_id;
+
+            self.register_rx_callback(
+                move |msg| {
+                    let now = Instant::now();
+                    if msg.arbitration_id != response_id {
+                        return;
+                    }
+                    let Some(seq) = msg
+                        .data
+                        .as_deref()
+                        .and_then(|d| d.get(..4))
+                        .map(|d| u32::from_le_bytes(d.try_into().unwrap()))
+                    else {
+                        return;
+                    };
+
+                    if let Some(sent) = in_flight.lock().unwrap().remove(&seq) {
+                        samples.lock().unwrap().push(now - sent);
+                    }
+                },
+                |_| {},
+            )?
+        };
+
+        let mut sent = 0;
+        let res = (0..probe.count).try_for_each(|seq| {
+            let mut data = [0; 8];
+            data[..4].copy_from_slice(&seq.to_le_bytes());
+
+            in_flight.lock().unwrap().insert(seq, Instant::now());
+            self.send_with_retry(probe.probe_id, &data, &Default::default())?;
+            sent += 1;
+
+            std::thread::sleep(probe.interval);
+            Ok(())
+        });
+
+        if res.is_ok() {
+            std::thread::sleep(probe.timeout);
+        }
+        self.remove_listener(listener)?;
+        res?;
+
+        let samples = std::mem::take(&mut *samples.lock().unwrap());
+        Ok(LatencyReport::from_samples(sent, samples))
+    }
+}
@@ -1,13 +1,266 @@
 use pyo3::{
+    exceptions::{PyOSError, PyTypeError},
     intern,
     types::{IntoPyDict, PyCFunction, PyDict, PyTuple},
-    Py, PyAny, PyErr, Python, ToPyObject,
+    FromPyObject, Py, PyAny, PyErr, PyResult, Python, ToPyObject,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock, Weak,
+    },
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 
+pub mod anomaly;
+pub use anomaly::{Anomaly, AnomalyConfig, AnomalyMonitor};
+
+mod arxml;
+
+pub mod broadcast;
+pub use broadcast::{Broadcast, BroadcastError, LagPolicy, Subscriber};
+
+pub mod builder;
+pub use builder::PyCanInterfaceBuilder;
+
+pub mod bus;
+pub use bus::CanBus;
+
+mod cache;
+use cache::LastFrames;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+pub mod collision;
+pub use collision::{CollisionReport, IdCollision, TxPlan};
+
+pub mod context;
+pub use context::Context;
+
+pub mod cycle;
+pub use cycle::CycleStats;
+use cycle::CycleTimes;
+
+pub mod dbc;
+pub use dbc::DbcError;
+
+pub mod decode;
+pub use decode::{
+    ByteOrder, DatabaseError, Decoder, DecoratedMessage, MessageDef, Multiplexing, Signal,
+};
+
+pub mod deferred;
+pub use deferred::DeferredTx;
+use deferred::TxWheel;
+
+pub mod demux;
+pub use demux::{Demuxer, IdField};
+
+pub mod device;
+pub use device::{Device, DeviceBuilder};
+
+mod dispatch;
+pub use dispatch::DeliveryOrder;
+
+pub mod doctor;
+pub use doctor::{doctor, DoctorReport};
+
+mod drift;
+use dispatch::Dispatcher;
+use drift::DriftEstimator;
+
+pub mod errframe;
+pub use errframe::{
+    ControllerProblem, ProtocolError, ProtocolLocation, ProtocolViolation, SocketcanError,
+    TransceiverStatus, WireStatus,
+};
+
+pub mod events;
+use events::EventHub;
+pub use events::{EventSink, ListenerId, PyCanEvent};
+
+pub mod filter;
+pub use filter::Filter;
+
+pub mod fmt;
+pub use fmt::{FrameFormatter, FrameStyle};
+
+pub mod frame;
+pub use frame::{parse_hex, FrameError, HexError};
+
+pub mod gateway;
+pub use gateway::{Gateway, GatewayError, GatewayRules};
+
+pub mod gil;
+use gil::GilMeter;
+pub use gil::{GilHold, GilOp, GilStats};
+
+pub mod gsusb;
+pub use gsusb::GsusbExt;
+
+#[cfg(all(feature = "native-gsusb", target_os = "linux"))]
+mod gsusb_native;
+#[cfg(all(feature = "native-gsusb", target_os = "linux"))]
+pub use gsusb_native::GsusbBus;
+
+pub mod graph;
+pub use graph::{NodeId, NodeStats, Pipeline};
+
+pub mod health;
+use health::Health;
+pub use health::HealthReport;
+
+pub mod hotplug;
+pub use hotplug::HotplugWatcher;
+
+pub mod id;
+pub use id::CanId;
+
+#[cfg(feature = "influxdb")]
+pub mod influx;
+#[cfg(feature = "influxdb")]
+pub use influx::{InfluxConfig, InfluxSink};
+
+pub mod intercept;
+pub use intercept::{Action, FrameInterceptor};
+use intercept::{Chain, DlcValidator};
+
+pub mod j1939;
+
+pub mod keepalive;
+pub use keepalive::KeepAlive;
+
+mod kcd;
+
+pub mod latency;
+pub use latency::{LatencyProbe, LatencyReport};
+
+pub mod listeners;
+pub use listeners::{PythonListenerKind, PythonSource};
+
+pub mod logfile;
+pub use logfile::{CsvReader, CsvWriter, LogError, NdjsonWriter, TextReader, TrcReader, TrcWriter};
+
+#[cfg(feature = "managed-python")]
+pub mod managed;
+#[cfg(feature = "managed-python")]
+pub use managed::{use_managed_python, ManagedPython};
+
+pub mod merge;
+pub use merge::{ClockOffset, LogMerger};
+
 pub mod message;
 pub use message::PyCanMessage;
 
+mod named;
+use named::NamedTx;
+
+pub mod notifier;
+pub use notifier::NotifierDied;
+
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "parquet")]
+pub use parquet::ParquetSink;
+
+pub mod periodic;
+use periodic::TaskInner;
+pub use periodic::{PeriodicTask, RestartPolicy, TaskGroup};
+
+pub mod pipeline;
+pub use pipeline::{FrameSink, FrameSource, FrameSourceExt, InterfaceSource, PipeError};
+
+mod platform;
+
+#[cfg(feature = "python-module")]
+pub mod python;
+
+pub mod recorder;
+pub use recorder::{Compression, Recorder, RecorderConfig};
+
+mod registry;
+use registry::ChannelClaim;
+
+mod reopen;
+
+pub mod replay;
+pub use replay::{looped, LoopSource, ReplayEdit, ReplaySource, ReplayTrigger, TriggeredSource};
+
+pub mod report;
+pub use report::{ErrorBurst, IdSummary, LoadBucket, ReportConfig, SessionReport, SessionReporter};
+
+pub mod responder;
+pub use responder::{Responder, ResponderError, ResponderScript};
+
+pub mod scan;
+pub use scan::{ScanConfig, ScanReport, ScannedId, UdsResponder};
+
+pub mod scheduler;
+pub use scheduler::{ScheduleEntry, Scheduler};
+
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "scripting")]
+pub use script::{FrameScript, ScriptError, ScriptSource};
+
+pub mod seedkey;
+pub use seedkey::{ExternalKeyTool, PythonKeyFunction, SeedKeyError, SeedKeyProvider, XorKey};
+
+pub mod sim;
+pub use sim::{SimBus, SimNode};
+
+#[cfg(all(feature = "native-slcan", unix))]
+pub mod slcan;
+#[cfg(all(feature = "native-slcan", unix))]
+pub use slcan::SlcanBus;
+
+#[cfg(feature = "native-socketcand")]
+pub mod socketcand;
+#[cfg(feature = "native-socketcand")]
+pub use socketcand::{SocketcandBus, SocketcandTasks};
+
+pub mod state;
+pub use state::BusState;
+
+pub mod tee;
+pub use tee::BusTee;
+
+pub mod test_support;
+
+#[cfg(feature = "trace")]
+pub mod trace;
+#[cfg(feature = "trace")]
+pub use trace::FrameTracer;
+
+pub mod tx;
+use tx::{PendingTx, TxPacer};
+pub use tx::{RetryPolicy, TxToken};
+
+pub mod uri;
+pub use uri::BusUriError;
+
+pub mod validate;
+pub use validate::DlcPolicy;
+
+mod usb;
+
+pub mod vector;
+pub use vector::{VectorExt, VectorTimestampMode};
+
+pub mod wakeup;
+pub use wakeup::{SleepMonitor, WakeUpPattern};
+
+pub mod wire;
+pub use wire::{decode_batch, BatchEncoder, WireError};
+
+mod xml;
+
+mod yaml;
+
+#[derive(Clone, Debug)]
 pub enum PyCanBusType {
     Gsusb {
         bitrate: u32,
@@ -27,13 +280,98 @@ pub enum PyCanBusType {
         channel: String,
         port: u16,
     },
+    Vector {
+        /// Application name configured in Vector Hardware Config.
+        /// If None, `channel` is a global channel index.
+        app_name: Option<String>,
+        channel: String,
+        bitrate: u32,
+    },
+    /// python-can's in-process virtual bus. Interfaces opened on the same
+    /// channel see each other's frames.
+    Virtual {
+        channel: String,
+    },
+}
+
+impl PyCanBusType {
+    /// The channel this bus is attached to (serial port, netdev, etc.)
+    pub fn channel(&self) -> &str {
+        match self {
+            Self::Gsusb { usb_channel, .. } => usb_channel,
+            Self::Slcan { serial_port, .. } => serial_port,
+            Self::Socketcan { channel } => channel,
+            Self::Socketcand { channel, .. } => channel,
+            Self::Vector { channel, .. } => channel,
+            Self::Virtual { channel } => channel,
+        }
+    }
+
+    /// The same bus configuration on a different channel of the device.
+    pub fn with_channel(&self, channel: &str) -> Self {
+        let mut bus = self.clone();
+        match &mut bus {
+            Self::Gsusb { usb_channel, .. } => *usb_channel = channel.into(),
+            Self::Slcan { serial_port, .. } => *serial_port = channel.into(),
+            Self::Socketcan { channel: c } => *c = channel.into(),
+            Self::Socketcand { channel: c, .. } => *c = channel.into(),
+            Self::Vector { channel: c, .. } => *c = channel.into(),
+            Self::Virtual { channel: c } => *c = channel.into(),
+        }
+        bus
+    }
 }
 
 pub struct PyCanInterface {
     pub bustype: PyCanBusType,
+    name: Arc<str>,
     iface: Py<PyAny>,
+    /// The bus `watch_bus_state` threads poll, switched over on reopen.
+    state_bus: Arc<Mutex<Py<PyAny>>>,
     notifier: Py<PyAny>,
     pycan: Py<PyAny>,
+    events: EventHub,
+    listeners: Mutex<Vec<(ListenerId, Py<PyAny>)>>,
+    /// Cleared when the interface is dropped, to stop background threads.
+    alive: Arc<AtomicBool>,
+    /// Sent frames awaiting their echo, if TX confirmations are enabled.
+    pending_tx: Option<PendingTx>,
+    /// Rust callbacks, if batched delivery is enabled.
+    dispatcher: Option<Dispatcher>,
+    drift: Option<DriftEstimator>,
+    interceptors: Chain,
+    /// The frame the notifier's listeners are being called with.
+    prepared: Arc<Prepared>,
+    last_frames: Option<LastFrames>,
+    cycle_times: Option<CycleTimes>,
+    health: Health,
+    notifier_timeout: Duration,
+    /// Periodic tasks started on this interface, moved to the new bus on
+    /// reopen.
+    tasks: Mutex<Vec<Weak<TaskInner>>>,
+    decoder: Option<Arc<Decoder>>,
+    /// Last payload sent with `send_named`, by ID.
+    named_tx: Mutex<HashMap<CanId, NamedTx>>,
+    /// Minimum inter-frame gap, if set.
+    pacer: Option<TxPacer>,
+    gil: GilMeter,
+    /// Frames waiting for `send_at` deadlines, started on first use.
+    tx_wheel: OnceLock<Arc<TxWheel>>,
+    #[cfg(feature = "trace")]
+    tracer: Option<Arc<FrameTracer>>,
+    /// Released after the bus is shut down, since fields drop after `drop`.
+    claim: ChannelClaim,
+}
+
+/// A Python exception as `Type: message`, followed by the traceback if it
+/// has one, so errors show where in python-can (or the backend) they came
+/// from.
+pub(crate) fn describe_py_err(err: &PyErr) -> String {
+    let traceback = Python::with_gil(|py| err.traceback(py).and_then(|tb| tb.format().ok()));
+    match traceback {
+        Some(tb) => format!("{err}\n{}", tb.trim_end()),
+        None => err.to_string(),
+    }
 }
 
 /// pyo3 dict entry.
@@ -44,183 +382,772 @@ macro_rules! py_dict_entry {
     };
 }
 
-#[derive(Debug, Error)]
-pub enum PyCanError {
-    #[error("Failed to import python-can - is it installed? :: `{0}`")]
-    PythonCanImportFailed(String),
-    #[error("Failed to create python-can interface :: `{0}`")]
-    FailedToCreateInterface(String),
-    #[error("Failed to create notifier :: `{0}`")]
-    FailedToCreateNotifier(String),
-    #[error("Failed to add listener :: `{0}")]
-    FailedToAddListener(String),
-}
+/// Build a python-can Message.
+pub(crate) fn make_message(
+    py: Python,
+    pycan: &Py<PyAny>,
+    id: CanId,
+    data: &[u8],
+) -> PyResult<Py<PyAny>> {
+    let kwargs = [
+        py_dict_entry!(py, "arbitration_id", id.raw()),
+        py_dict_entry!(py, "is_extended_id", id.is_extended()),
+        py_dict_entry!(py, "data", data),
+        py_dict_entry!(py, "dlc", data.len()),
+    ]
+    .into_py_dict(py);
 
-impl PyCanInterface {
-    pub fn new(kind: PyCanBusType) -> Result<Self, PyCanError> {
-        // Import python-can
-        let pycan = Python::with_gil(|py| -> Result<Py<PyAny>, PyCanError> {
-            Ok(py
-                .import("can")
-                .map_err(|e| PyCanError::PythonCanImportFailed(e.to_string()))?
-                .to_object(py))
-        })?;
+    pycan.call_method(py, "Message", (), Some(kwargs))
+}
 
-        // Set up interface
-        let iface = match &kind {
-            PyCanBusType::Gsusb {
+impl PyCanBusType {
+    /// Keyword arguments to `can.Bus` for this bus.
+    fn bus_kwargs<'py>(&self, py: Python<'py>) -> &'py PyDict {
+        match self {
+            Self::Gsusb {
                 bitrate,
                 usb_channel,
                 usb_bus,
                 usb_address,
-            } => Python::with_gil(|py| -> Result<Py<PyAny>, PyCanError> {
-                // Note: issues finding libusb on Mac - see:
-                // https://github.com/pyusb/pyusb/issues/355#issuecomment-1214444040
-                // We might have to manually look up libusb to help
-
-                let args = [
+            } => {
+                // libusb is looked up for pyusb when opening; see usb.rs
+                [
                     py_dict_entry!(py, "bustype", "gs_usb"),
                     py_dict_entry!(py, "bitrate", bitrate),
                     py_dict_entry!(py, "channel", usb_channel),
                     py_dict_entry!(py, "bus", usb_bus),
                     py_dict_entry!(py, "address", usb_address),
                 ]
-                .into_py_dict(py);
-
-                let iface = pycan
-                    .call_method(py, "Bus", (), Some(args))
-                    .map_err(|e| PyCanError::FailedToCreateInterface(e.to_string()))?;
-
-                Ok(iface)
-            }),
-            PyCanBusType::Slcan {
+                .into_py_dict(py)
+            }
+            Self::Slcan {
                 bitrate,
                 serial_port,
-            } => Python::with_gil(|py| -> Result<Py<PyAny>, PyCanError> {
+            } => {
                 let args = [
                     py_dict_entry!(py, "bustype", "slcan"),
-                    py_dict_entry!(py, "channel", serial_port),
+                    py_dict_entry!(py, "channel", platform::slcan_port(serial_port)),
                     py_dict_entry!(py, "bitrate", bitrate),
                 ]
                 .into_py_dict(py);
-
-                let iface = pycan
-                    .call_method(py, "Bus", (), Some(args))
-                    .map_err(|e| PyCanError::FailedToCreateInterface(e.to_string()))?;
-
-                Ok(iface)
-            }),
-            PyCanBusType::Socketcan { channel } => {
-                Python::with_gil(|py| -> Result<Py<PyAny>, PyCanError> {
-                    let args = [
-                        py_dict_entry!(py, "bustype", "socketcan"),
-                        py_dict_entry!(py, "channel", channel),
-                    ]
-                    .into_py_dict(py);
-
-                    let iface = pycan
-                        .call_method(py, "Bus", (), Some(args))
-                        .map_err(|e| PyCanError::FailedToCreateInterface(e.to_string()))?;
-
-                    Ok(iface)
-                })
+                if let Some(timeout) = platform::SLCAN_TIMEOUT {
+                    // Only fails if the key can't be hashed
+                    args.set_item(intern!(py, "timeout"), timeout).unwrap();
+                }
+                args
             }
-            PyCanBusType::Socketcand {
+            Self::Socketcan { channel } => [
+                py_dict_entry!(py, "bustype", "socketcan"),
+                py_dict_entry!(py, "channel", channel),
+            ]
+            .into_py_dict(py),
+            Self::Socketcand {
                 host,
                 channel,
                 port,
-            } => Python::with_gil(|py| -> Result<Py<PyAny>, PyCanError> {
-                let args = [
-                    py_dict_entry!(py, "bustype", "socketcand"),
-                    py_dict_entry!(py, "host", host),
-                    py_dict_entry!(py, "channel", channel),
-                    py_dict_entry!(py, "port", port),
-                ]
-                .into_py_dict(py);
+            } => [
+                py_dict_entry!(py, "bustype", "socketcand"),
+                py_dict_entry!(py, "host", host),
+                py_dict_entry!(py, "channel", channel),
+                py_dict_entry!(py, "port", port),
+            ]
+            .into_py_dict(py),
+            Self::Vector {
+                app_name,
+                channel,
+                bitrate,
+            } => [
+                py_dict_entry!(py, "bustype", "vector"),
+                py_dict_entry!(py, "app_name", app_name),
+                py_dict_entry!(py, "channel", channel),
+                py_dict_entry!(py, "bitrate", bitrate),
+            ]
+            .into_py_dict(py),
+            Self::Virtual { channel } => [
+                py_dict_entry!(py, "bustype", "virtual"),
+                py_dict_entry!(py, "channel", channel),
+            ]
+            .into_py_dict(py),
+        }
+    }
+}
 
-                let iface = pycan
-                    .call_method(py, "Bus", (), Some(args))
-                    .map_err(|e| PyCanError::FailedToCreateInterface(e.to_string()))?;
+/// Open a python-can Bus for `kind`. With `receive_own`, the backend is
+/// asked to echo transmitted frames.
+fn open_bus(
+    py: Python,
+    pycan: &Py<PyAny>,
+    kind: &PyCanBusType,
+    receive_own: bool,
+) -> Result<Py<PyAny>, PyCanError> {
+    platform::check_supported(kind)?;
 
-                Ok(iface)
-            }),
-        }?;
+    let args = kind.bus_kwargs(py);
+    if receive_own {
+        args.set_item(intern!(py, "receive_own_messages"), true)
+            .map_err(|e| PyCanError::FailedToCreateInterface(describe_py_err(&e)))?;
+    }
 
-        // Set up notifier thread
-        let notifier = Python::with_gil(|py| -> Result<_, PyCanError> {
-            let args = [
-                py_dict_entry!(py, "bus", iface.clone()),
-                py_dict_entry!(py, "listeners", PyTuple::empty(py)), // no listeners to start
-            ]
-            .into_py_dict(py);
+    pycan.call_method(py, "Bus", (), Some(args)).map_err(|e| {
+        if is_unavailable(py, pycan, &e) {
+            PyCanError::InterfaceUnavailable(describe_py_err(&e))
+        } else {
+            PyCanError::FailedToCreateInterface(describe_py_err(&e))
+        }
+    })
+}
+
+/// Whether an error creating a bus means the device is busy or missing,
+/// rather than the backend being unavailable or the arguments invalid:
+/// python-can's CanInitializationError (but not
+/// CanInterfaceNotImplementedError), or an OSError such as ENODEV or
+/// EBUSY.
+fn is_unavailable(py: Python, pycan: &Py<PyAny>, err: &PyErr) -> bool {
+    let pycan = pycan.as_ref(py);
+    let is = |name: &str| {
+        pycan
+            .getattr(name)
+            .is_ok_and(|cls| err.is_instance(py, cls))
+    };
+    if is("CanInterfaceNotImplementedError") {
+        return false;
+    }
+    if is("CanInitializationError") {
+        return true;
+    }
+
+    if !err.is_instance_of::<PyOSError>(py) {
+        return false;
+    }
+    let errno: Option<i64> = err
+        .value(py)
+        .getattr("errno")
+        .and_then(PyAny::extract)
+        .ok()
+        .flatten();
+    let Ok(codes) = py.import("errno") else {
+        return false;
+    };
+    [
+        "ENOENT",
+        "ENODEV",
+        "ENXIO",
+        "EBUSY",
+        "EAGAIN",
+        "ECONNREFUSED",
+        "ETIMEDOUT",
+        "ENETDOWN",
+        "EHOSTUNREACH",
+    ]
+    .iter()
+    .filter_map(|name| codes.getattr(*name).ok()?.extract::<i64>().ok())
+    .any(|code| Some(code) == errno)
+}
+
+/// Start a notifier thread reading `bus`.
+fn open_notifier(
+    py: Python,
+    pycan: &Py<PyAny>,
+    bus: &Py<PyAny>,
+    timeout: Duration,
+) -> Result<Py<PyAny>, PyCanError> {
+    let args = [
+        py_dict_entry!(py, "bus", bus.clone()),
+        py_dict_entry!(py, "listeners", PyTuple::empty(py)), // no listeners to start
+        py_dict_entry!(py, "timeout", timeout.as_secs_f64()),
+    ]
+    .into_py_dict(py);
+
+    // Register the notifier
+    pycan
+        .call_method(py, "Notifier", (), Some(args))
+        .map_err(|e| PyCanError::FailedToCreateNotifier(describe_py_err(&e)))
+}
+
+/// The exception python-can passed to a listener's `on_error`.
+fn notifier_exception(args: &PyTuple) -> PyErr {
+    match args.get_item(0) {
+        Ok(exc) => PyErr::from_value(exc),
+        // Not called as python-can does, so report that instead
+        Err(e) => e,
+    }
+}
+
+/// Error reported when a received object can't be extracted as a PyCanMessage.
+fn extraction_error(obj: Option<&PyAny>, e: PyErr) -> PyErr {
+    let repr = obj
+        .and_then(|obj| obj.repr().ok())
+        .map(|r| r.to_string())
+        .unwrap_or_else(|| "<unavailable>".into());
+
+    PyTypeError::new_err(format!("failed to extract PyCanMessage from {repr} :: {e}"))
+}
 
-            // Register the notifier
-            pycan
-                .call_method(py, "Notifier", (), Some(args))
-                .map_err(|e| PyCanError::FailedToCreateNotifier(e.to_string()))
+/// Which received frames a listener is given.
+#[derive(Clone, Copy)]
+enum Frames {
+    Data,
+    Error,
+    All,
+}
+
+impl Frames {
+    fn accepts(&self, msg: &PyCanMessage) -> bool {
+        match self {
+            Self::Data => !msg.is_error_frame,
+            Self::Error => msg.is_error_frame,
+            Self::All => true,
+        }
+    }
+}
+
+/// Make a `Send` callback `Sync` by locking it, so it never runs
+/// concurrently with itself.
+fn serialized<R>(callback: R) -> impl Fn(&PyCanMessage) + Send + Sync
+where
+    R: Fn(&PyCanMessage) + Send,
+{
+    let callback = Mutex::new(callback);
+    move |msg| (callback.lock().unwrap())(msg)
+}
+
+/// The result of preparing the frame python-can's notifier is passing to
+/// its listeners. The notifier calls every listener with the same
+/// `can.Message`, so listener shims share the first one's result rather
+/// than each running the interceptors. The message is kept alive so its
+/// address can't be reused by a later one while it's the key.
+#[derive(Default)]
+struct Prepared(Mutex<Option<(Py<PyAny>, Option<PyCanMessage>)>>);
+
+/// Per-interface processing applied to every received frame before it
+/// reaches callbacks. Cheap to clone into listener shims.
+#[derive(Clone)]
+struct Inbound {
+    name: Arc<str>,
+    drift: Option<DriftEstimator>,
+    interceptors: Chain,
+    prepared: Arc<Prepared>,
+    gil: GilMeter,
+    #[cfg(feature = "trace")]
+    tracer: Option<Arc<FrameTracer>>,
+}
+
+impl Inbound {
+    /// Returns None if the frame should be dropped.
+    fn prepare(&self, mut msg: PyCanMessage) -> Option<PyCanMessage> {
+        msg.iface_name = Some(self.name.clone());
+
+        if let Some(drift) = &self.drift {
+            drift.correct(&mut msg);
+        }
+
+        if !self.interceptors.rx(&mut msg) {
+            return None;
+        }
+
+        #[cfg(feature = "trace")]
+        if let Some(tracer) = &self.tracer {
+            tracer.trace(&msg);
+        }
+
+        Some(msg)
+    }
+
+    /// Extract and prepare `obj`, a frame from python-can's notifier, once
+    /// however many listeners it's passed to. Returns None if it should
+    /// be dropped.
+    fn prepare_notified(&self, obj: &PyAny) -> PyResult<Option<PyCanMessage>> {
+        if let Some((last, msg)) = &*self.prepared.0.lock().unwrap() {
+            if last.is(obj) {
+                return Ok(msg.clone());
+            }
+        }
+
+        // Not locked while preparing, as interceptors may run Python
+        let msg = self.prepare(obj.extract()?);
+        *self.prepared.0.lock().unwrap() = Some((obj.into(), msg.clone()));
+        Ok(msg)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PyCanError {
+    #[error("Failed to import python-can - is it installed? :: `{0}`")]
+    PythonCanImportFailed(String),
+    #[error("Failed to create python-can interface :: `{0}`")]
+    FailedToCreateInterface(String),
+    #[error("Failed to create notifier :: `{0}`")]
+    FailedToCreateNotifier(String),
+    #[error("Failed to create listener :: `{0}`")]
+    FailedToCreateListener(String),
+    #[error("Failed to add listener :: `{0}")]
+    FailedToAddListener(String),
+    #[error("Failed to remove listener :: `{0}`")]
+    FailedToRemoveListener(String),
+    #[error("Not supported :: `{0}`")]
+    Unsupported(String),
+    #[error("Backend request failed :: `{0}`")]
+    BackendRequestFailed(String),
+    #[error("Failed to send :: `{0}`")]
+    FailedToSend(String),
+    #[error("Transmit queue is full :: `{0}`")]
+    TxQueueFull(String),
+    #[error("Failed to receive :: `{0}`")]
+    FailedToReceive(String),
+    #[error("Failed to get bus state :: `{0}`")]
+    FailedToGetState(String),
+    #[error("Periodic task error :: `{0}`")]
+    PeriodicTaskFailed(String),
+    #[error("Failed to write capture :: `{0}`")]
+    CaptureFailed(String),
+    #[error("Channel is already open :: `{0}`")]
+    ChannelAlreadyOpen(String),
+    #[error("Interface is busy or not present :: `{0}`")]
+    InterfaceUnavailable(String),
+    #[error("Unknown signal :: `{0}`")]
+    UnknownSignal(String),
+    #[error("Signal is defined in more than one message :: `{0}`")]
+    AmbiguousSignal(String),
+    #[error("Unknown message :: `{0}`")]
+    UnknownMessage(String),
+    #[error("Signal doesn't fit its message :: `{0}`")]
+    InvalidSignal(String),
+    #[error("Duration out of range :: `{0}`")]
+    InvalidDuration(String),
+}
+
+impl PyCanError {
+    /// Whether the operation might succeed if tried again later, e.g. the
+    /// adapter was busy or not plugged in yet. Errors such as python-can or
+    /// the backend not being installed, or invalid arguments, are fatal.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::InterfaceUnavailable(_) | Self::ChannelAlreadyOpen(_)
+        )
+    }
+
+    /// An error opening a native driver's device or connection. The
+    /// ResourceBusy and *Unreachable kinds need Rust 1.83; errno values
+    /// that std doesn't categorize, such as a device that was unplugged,
+    /// are checked as well.
+    #[cfg(any(
+        all(feature = "native-gsusb", target_os = "linux"),
+        all(feature = "native-slcan", unix),
+        feature = "native-socketcand"
+    ))]
+    pub(crate) fn open_failed(what: &str, err: std::io::Error) -> Self {
+        use std::io::ErrorKind::*;
+
+        let why = format!("{what}: {err}");
+        // ENXIO, EBUSY and ENODEV
+        #[cfg(unix)]
+        const UNAVAILABLE: &[i32] = &[6, 16, 19];
+        #[cfg(not(unix))]
+        const UNAVAILABLE: &[i32] = &[];
+
+        match err.kind() {
+            NotFound | ResourceBusy | ConnectionRefused | TimedOut | HostUnreachable
+            | NetworkUnreachable => Self::InterfaceUnavailable(why),
+            _ if err
+                .raw_os_error()
+                .is_some_and(|code| UNAVAILABLE.contains(&code)) =>
+            {
+                Self::InterfaceUnavailable(why)
+            }
+            _ => Self::FailedToCreateInterface(why),
+        }
+    }
+}
+
+impl PyCanInterface {
+    pub fn new(kind: PyCanBusType) -> Result<Self, PyCanError> {
+        Self::builder(kind).build()
+    }
+
+    pub fn builder(kind: PyCanBusType) -> PyCanInterfaceBuilder {
+        PyCanInterfaceBuilder::new(kind)
+    }
+
+    fn open(builder: PyCanInterfaceBuilder) -> Result<Self, PyCanError> {
+        if let PyCanBusType::Gsusb { .. } = builder.kind {
+            Python::with_gil(|py| usb::configure_libusb(py, builder.libusb_path.as_deref()))?;
+        }
+
+        let kind = match &builder.usb_serial {
+            Some(serial) => Python::with_gil(|py| usb::resolve_serial(py, &builder.kind, serial))?,
+            None => builder.kind,
+        };
+
+        // Import python-can
+        let pycan = Python::with_gil(|py| -> Result<Py<PyAny>, PyCanError> {
+            #[cfg(feature = "managed-python")]
+            managed::ensure(py)?;
+
+            Ok(py
+                .import("can")
+                .map_err(|e| PyCanError::PythonCanImportFailed(describe_py_err(&e)))?
+                .to_object(py))
         })?;
 
-        Ok(Self {
+        let claim = ChannelClaim::acquire(&kind)?;
+
+        // Set up interface and notifier thread
+        let iface = Python::with_gil(|py| open_bus(py, &pycan, &kind, builder.tx_confirmations))?;
+        let notifier =
+            Python::with_gil(|py| open_notifier(py, &pycan, &iface, builder.notifier_timeout))?;
+
+        let health = Python::with_gil(|py| Health::new(py, &notifier))?;
+        let state_bus = Python::with_gil(|py| Arc::new(Mutex::new(iface.clone_ref(py))));
+
+        let name: Arc<str> = builder.name.as_deref().unwrap_or(kind.channel()).into();
+
+        let events = EventHub::new(name.clone());
+        events.emit(PyCanEvent::BusOpened);
+        let gil = GilMeter::new(builder.gil_budget, events.clone());
+
+        let iface = Self {
             bustype: kind,
+            name,
             iface,
+            state_bus,
             notifier,
             pycan,
-        })
+            events,
+            listeners: Mutex::new(Vec::new()),
+            alive: Arc::new(AtomicBool::new(true)),
+            pending_tx: builder.tx_confirmations.then(PendingTx::default),
+            dispatcher: builder.batched_delivery.then(Dispatcher::default),
+            drift: builder.drift_correction.then(DriftEstimator::default),
+            interceptors: Chain::default(),
+            prepared: Arc::default(),
+            last_frames: builder.last_frame_cache.then(LastFrames::default),
+            cycle_times: builder.cycle_stats.then(CycleTimes::default),
+            health,
+            notifier_timeout: builder.notifier_timeout,
+            tasks: Mutex::new(Vec::new()),
+            decoder: builder.decoder.map(Arc::new),
+            named_tx: Mutex::new(HashMap::new()),
+            pacer: builder.tx_gap.map(TxPacer::new),
+            gil,
+            tx_wheel: OnceLock::new(),
+            #[cfg(feature = "trace")]
+            tracer: builder.tracer.map(Arc::new),
+            claim,
+        };
+
+        iface.watch_notifier();
+
+        if let Some(policy) = builder.dlc_policy {
+            let events = iface.events.clone();
+            iface.add_interceptor(Arc::new(DlcValidator { policy, events }));
+        }
+
+        if let Some(dispatcher) = &iface.dispatcher {
+            iface.start_dispatcher(dispatcher, builder.callback_workers, builder.max_batch)?;
+        }
+
+        if let Some(pending) = iface.pending_tx.clone() {
+            let events = iface.events.clone();
+            iface.register_listener(
+                Frames::Data,
+                DeliveryOrder::PerId,
+                move |msg| {
+                    if let Some(token) = pending.confirm(msg) {
+                        events.emit(PyCanEvent::TxConfirmation {
+                            token,
+                            id: msg.arbitration_id,
+                        });
+                    }
+                },
+                |_| {},
+            )?;
+        }
+
+        if let Some(cache) = iface.last_frames.clone() {
+            iface.register_listener(
+                Frames::Data,
+                DeliveryOrder::PerId,
+                move |msg| cache.record(msg),
+                |_| {},
+            )?;
+        }
+
+        if let Some(cycles) = iface.cycle_times.clone() {
+            iface.register_listener(
+                Frames::Data,
+                DeliveryOrder::PerId,
+                move |msg| cycles.record(msg),
+                |_| {},
+            )?;
+        }
+
+        Ok(iface)
     }
 
+    /// Open several channels of one device, e.g. both channels of a dual
+    /// channel adapter. Channels are opened in order; if any fails, the ones
+    /// already opened are closed again and the error is returned.
+    pub fn open_channels(kind: &PyCanBusType, channels: &[&str]) -> Result<Vec<Self>, PyCanError> {
+        channels
+            .iter()
+            .map(|channel| Self::new(kind.with_channel(channel)))
+            .collect()
+    }
+
+    /// The interface's label, set with [`PyCanInterfaceBuilder::name`].
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set the sink receiving lifecycle events for this interface.
+    /// Events are also delivered to the global sink, if one is set.
+    pub fn set_event_sink(&self, sink: Arc<dyn EventSink>) {
+        self.events.set_sink(Some(sink));
+    }
+
+    /// Block until a frame is received. Panics if receiving fails; see
+    /// [`Self::recv_checked`].
+    #[cfg(feature = "panicking-api")]
     pub fn recv(&self) -> PyCanMessage {
-        Python::with_gil(|py| -> _ {
-            self.iface
-                .call_method0(py, intern!(py, "recv"))
-                .unwrap()
-                .extract(py)
-                .unwrap()
-        })
+        self.recv_checked().unwrap()
     }
 
-    pub fn send(&self, id: u32, data: &[u8]) {
-        Python::with_gil(|py| {
-            let kwargs = [
-                py_dict_entry!(py, "arbitration_id", id),
-                py_dict_entry!(py, "data", data),
-                py_dict_entry!(py, "dlc", data.len()),
-            ]
-            .into_py_dict(py);
+    /// Block until a frame is received.
+    pub fn recv_checked(&self) -> Result<PyCanMessage, PyCanError> {
+        Python::with_gil(|py| self.recv_prepared(py, None))?
+            .ok_or_else(|| PyCanError::FailedToReceive("no message".into()))
+    }
+
+    /// Receive the next frame that isn't dropped by the interceptors, as
+    /// the listener paths would deliver it. Returns None once `timeout`
+    /// passes without one.
+    pub(crate) fn recv_prepared(
+        &self,
+        py: Python,
+        timeout: Option<Duration>,
+    ) -> Result<Option<PyCanMessage>, PyCanError> {
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        let inbound = self.inbound();
 
+        loop {
+            // A timeout too long to represent waits forever
+            let remaining =
+                deadline.map(|d| d.saturating_duration_since(Instant::now()).as_secs_f64());
             let msg = self
-                .pycan
-                .call_method(py, "Message", (), Some(kwargs))
-                .unwrap();
+                .iface
+                .call_method1(py, intern!(py, "recv"), (remaining,))
+                .and_then(|msg| {
+                    self.gil
+                        .time(GilOp::Recv, || msg.extract::<Option<PyCanMessage>>(py))
+                })
+                .map_err(|e| PyCanError::FailedToReceive(describe_py_err(&e)))?;
+
+            match msg {
+                Some(msg) => {
+                    if let Some(msg) = inbound.prepare(msg) {
+                        return Ok(Some(msg));
+                    }
+                }
+                None if timeout.is_some() => return Ok(None),
+                None => {}
+            }
+
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Call a backend-specific method of the python-can Bus, e.g.
+    /// `flash` or `get_serial_number`, and convert its return value.
+    /// Fails with Unsupported if the bus has no such method.
+    pub fn call_bus_method<T>(
+        &self,
+        name: &str,
+        kwargs: &[(&str, &dyn ToPyObject)],
+    ) -> Result<T, PyCanError>
+    where
+        T: for<'py> FromPyObject<'py>,
+    {
+        Python::with_gil(|py| {
+            let bus = self.iface.as_ref(py);
+            if !bus.hasattr(name).unwrap_or(false) {
+                return Err(PyCanError::Unsupported(format!(
+                    "`{name}` on {}",
+                    self.bustype
+                )));
+            }
+
+            let kwargs = kwargs
+                .iter()
+                .map(|(k, v)| (*k, v.to_object(py)))
+                .into_py_dict(py);
+
+            let ret = bus
+                .call_method(name, (), Some(kwargs))
+                .map_err(|e| PyCanError::BackendRequestFailed(describe_py_err(&e)))?;
 
-            self.iface
-                .call_method1(py, "send", PyTuple::new(py, [msg]))
-                .unwrap();
+            ret.extract().map_err(|e| {
+                PyCanError::BackendRequestFailed(format!("unexpected return from `{name}`: {e}"))
+            })
         })
     }
 
+    /// Send a frame. If TX confirmations are enabled on the builder, a
+    /// [`PyCanEvent::TxConfirmation`] with the returned token is emitted once
+    /// the backend reports the frame was transmitted. Frames dropped by an
+    /// interceptor are never confirmed. Panics if sending fails; see
+    /// [`Self::send_checked`].
+    #[cfg(feature = "panicking-api")]
+    pub fn send(&self, id: CanId, data: &[u8]) -> TxToken {
+        self.send_checked(id, data).unwrap()
+    }
+
+    /// Send a frame, blocking until the backend accepts it. Like
+    /// [`Self::send`], but failures are returned.
+    pub fn send_checked(&self, id: CanId, data: &[u8]) -> Result<TxToken, PyCanError> {
+        let Some((id, data)) = self.intercept_tx(id, data) else {
+            return Ok(TxToken::next());
+        };
+        let token = self.tx_token(id, &data);
+        let res = Python::with_gil(|py| self.send_once(py, id, &data, None))
+            .map_err(|e| PyCanError::FailedToSend(describe_py_err(&e)));
+        if let (Err(_), Some(pending)) = (&res, &self.pending_tx) {
+            pending.cancel(token);
+        }
+
+        res.map(|_| token)
+    }
+
     /// Register the provided callback to be called on future recieved messages
-    /// on this interface.
-    pub fn register_rx_callback<R, E>(&self, on_rx: R, on_error: E) -> Result<(), PyCanError>
+    /// on this interface. Error frames are not delivered; see
+    /// `register_error_frame_callback` and `register_rx_callback_all`.
+    pub fn register_rx_callback<R, E>(
+        &self,
+        on_rx: R,
+        on_error: E,
+    ) -> Result<ListenerId, PyCanError>
     where
         R: Fn(&PyCanMessage) + Send + 'static,
         E: Fn(&PyErr) + Send + 'static,
     {
-        Python::with_gil(|py| -> Result<(), PyCanError> {
-            // Make a shim to extract the PyCanMessage and call the actual callback
+        self.register_listener(
+            Frames::Data,
+            DeliveryOrder::PerId,
+            serialized(on_rx),
+            on_error,
+        )
+    }
+
+    /// Register a callback for received error frames only.
+    pub fn register_error_frame_callback<R, E>(
+        &self,
+        on_error_frame: R,
+        on_error: E,
+    ) -> Result<ListenerId, PyCanError>
+    where
+        R: Fn(&PyCanMessage) + Send + 'static,
+        E: Fn(&PyErr) + Send + 'static,
+    {
+        self.register_listener(
+            Frames::Error,
+            DeliveryOrder::PerId,
+            serialized(on_error_frame),
+            on_error,
+        )
+    }
+
+    /// Like `register_rx_callback`, but error frames are delivered too.
+    pub fn register_rx_callback_all<R, E>(
+        &self,
+        on_rx: R,
+        on_error: E,
+    ) -> Result<ListenerId, PyCanError>
+    where
+        R: Fn(&PyCanMessage) + Send + 'static,
+        E: Fn(&PyErr) + Send + 'static,
+    {
+        self.register_listener(
+            Frames::All,
+            DeliveryOrder::PerId,
+            serialized(on_rx),
+            on_error,
+        )
+    }
+
+    /// Like `register_rx_callback`, with an explicit ordering guarantee for
+    /// when callbacks run on worker threads. The callback must be `Sync`
+    /// since [`DeliveryOrder::Unordered`] may run it on several workers at
+    /// once. Ignored without batched delivery, where python-can's notifier
+    /// calls every listener in receive order.
+    pub fn register_rx_callback_ordered<R, E>(
+        &self,
+        order: DeliveryOrder,
+        on_rx: R,
+        on_error: E,
+    ) -> Result<ListenerId, PyCanError>
+    where
+        R: Fn(&PyCanMessage) + Send + Sync + 'static,
+        E: Fn(&PyErr) + Send + 'static,
+    {
+        self.register_listener(Frames::Data, order, on_rx, on_error)
+    }
+
+    fn register_listener<R, E>(
+        &self,
+        frames: Frames,
+        order: DeliveryOrder,
+        on_rx: R,
+        on_error: E,
+    ) -> Result<ListenerId, PyCanError>
+    where
+        R: Fn(&PyCanMessage) + Send + Sync + 'static,
+        E: Fn(&PyErr) + Send + 'static,
+    {
+        if let Some(dispatcher) = &self.dispatcher {
+            let id = dispatcher.add(frames, order, on_rx, on_error);
+            self.events.emit(PyCanEvent::ListenerAdded(id));
+            return Ok(id);
+        }
+
+        let events = self.events.clone();
+        let inbound = self.inbound();
+
+        // Both shims report errors through on_error
+        let on_error = Arc::new(Mutex::new(on_error));
+        let rx_on_error = on_error.clone();
+
+        Python::with_gil(|py| -> Result<ListenerId, PyCanError> {
+            // Make a shim to extract the PyCanMessage and call the actual callback.
+            // If extraction fails, report it through on_error rather than raising,
+            // since an exception here would take down the notifier.
             let rx_shim = PyCFunction::new_closure(
                 py,
                 None,
                 None,
                 move |args: &PyTuple, _kwargs: Option<&PyDict>| {
-                    let (msg,) = args.extract::<(PyCanMessage,)>().expect(
-                        "PyCanMessage should always be extractable from \
-                          argument to python-can listener callback",
-                    );
-
-                    on_rx(&msg);
+                    // The notifier holds the GIL for the whole call
+                    inbound.gil.time(GilOp::Callback, || {
+                        let prepared = args
+                            .get_item(0)
+                            .and_then(|obj| inbound.prepare_notified(obj));
+                        match prepared {
+                            Ok(Some(msg)) => {
+                                if frames.accepts(&msg) {
+                                    on_rx(&msg)
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                let err = extraction_error(args.get_item(0).ok(), e);
+                                (rx_on_error.lock().unwrap())(&err);
+                            }
+                        }
+                    })
                 },
             )
-            .expect("creation of listener rx callback shim should always succeed");
+            .map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))?;
 
             // And another shim for on_error
             let error_shim = PyCFunction::new_closure(
@@ -228,43 +1155,12 @@ impl PyCanInterface {
                 None,
                 None,
                 move |args: &PyTuple, _kwargs: Option<&PyDict>| {
-                    let err = PyErr::from_value(
-                        args.get_item(0)
-                            .expect("python-can should have passed exception as arg to on_error"),
-                    );
-
-                    on_error(&err);
+                    let err = notifier_exception(args);
+                    events.emit(PyCanEvent::NotifierError(err.to_string()));
+                    (on_error.lock().unwrap())(&err);
                 },
             )
-            .expect("creation of listener on_error callback shim should always succeed");
-
-            // Use type() to make an instance of a class inheriting can.Listener
-            // Equivalent Python is like:
-            // ```
-            //     listener = type("PyCanRsListener", (can.Listener,), {
-            //         "on_message_received": rx_shim,
-            //         "on_error": error_shim
-            //     })
-            //     listener = listener()
-            // ```
-            // So we're doing:
-            // ```
-            //     base = (can.Listener,)
-            //     methods = {"on_message_received": rx_shim, "on_error": error_shim}
-            //     listener = type("PyCanRsListener", base, methods)()
-            // ```
-
-            let type_builtin = py
-                .import("builtins")
-                .expect("should be able to import builtins")
-                .getattr("type")
-                .expect("builtins should have type()");
-
-            let base = (self
-                .pycan
-                .getattr(py, "Listener")
-                .expect("python-can should have Listener"),)
-                .to_object(py);
+            .map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))?;
 
             let methods = [
                 py_dict_entry!(py, "on_message_received", rx_shim),
@@ -272,25 +1168,129 @@ impl PyCanInterface {
             ]
             .into_py_dict(py);
 
-            let type_args = (
-                "PyCanRsListener".to_object(py),
-                base.to_object(py),
-                methods.to_object(py),
-            );
-
-            // call type() and then call the result of that
-            let listener = type_builtin
-                .call1(type_args)
-                .expect("should be able to create class deriving Listener")
-                .call0()
-                .unwrap();
+            let listener = self
+                .make_listener(py, "Listener", methods)
+                .map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))?;
 
             // Register the listener
+            self.add_listener(py, listener)
+        })
+    }
+
+    fn inbound(&self) -> Inbound {
+        Inbound {
+            name: self.name.clone(),
+            drift: self.drift.clone(),
+            interceptors: self.interceptors.clone(),
+            prepared: self.prepared.clone(),
+            gil: self.gil.clone(),
+            #[cfg(feature = "trace")]
+            tracer: self.tracer.clone(),
+        }
+    }
+
+    /// Instantiate a subclass of python-can's `base` listener class with the
+    /// given methods.
+    fn make_listener<'py>(
+        &self,
+        py: Python<'py>,
+        base: &str,
+        methods: &PyDict,
+    ) -> PyResult<&'py PyAny> {
+        // Use type() to make an instance of a class inheriting can.Listener
+        // Equivalent Python is like:
+        // ```
+        //     listener = type("PyCanRsListener", (can.Listener,), {
+        //         "on_message_received": rx_shim,
+        //         "on_error": error_shim
+        //     })
+        //     listener = listener()
+        // ```
+        // So we're doing:
+        // ```
+        //     base = (can.Listener,)
+        //     methods = {"on_message_received": rx_shim, "on_error": error_shim}
+        //     listener = type("PyCanRsListener", base, methods)()
+        // ```
+
+        let type_builtin = py.import("builtins")?.getattr("type")?;
+        let base = (self.pycan.getattr(py, base)?,).to_object(py);
+
+        let type_args = (
+            "PyCanRsListener".to_object(py),
+            base.to_object(py),
+            methods.to_object(py),
+        );
+
+        // call type() and then call the result of that
+        type_builtin.call1(type_args)?.call0()
+    }
+
+    /// Add a python-can Listener to the notifier and track it.
+    fn add_listener(&self, py: Python, listener: &PyAny) -> Result<ListenerId, PyCanError> {
+        self.notifier
+            .call_method1(py, "add_listener", (listener,))
+            .map_err(|e| PyCanError::FailedToAddListener(describe_py_err(&e)))?;
+
+        let id = ListenerId::next();
+        self.listeners
+            .lock()
+            .unwrap()
+            .push((id, listener.to_object(py)));
+        self.events.emit(PyCanEvent::ListenerAdded(id));
+
+        Ok(id)
+    }
+
+    /// Remove a listener previously registered with `register_rx_callback`
+    /// or `attach_python_listener`.
+    pub fn remove_listener(&self, id: ListenerId) -> Result<(), PyCanError> {
+        if self.dispatcher.as_ref().is_some_and(|d| d.remove(id)) {
+            self.events.emit(PyCanEvent::ListenerRemoved(id));
+            return Ok(());
+        }
+
+        // Take it out of the list before calling into Python, which may
+        // hand the GIL to a thread that's waiting for the list lock
+        let entry = {
+            let mut listeners = self.listeners.lock().unwrap();
+            let Some(idx) = listeners.iter().position(|(l, _)| *l == id) else {
+                return Err(PyCanError::FailedToRemoveListener(format!(
+                    "no listener with id {id:?}"
+                )));
+            };
+            listeners.remove(idx)
+        };
+
+        let removed = Python::with_gil(|py| {
             self.notifier
-                .call_method1(py, "add_listener", (listener,))
-                .map_err(|e| PyCanError::FailedToAddListener(e.to_string()))?;
+                .call_method1(py, "remove_listener", (&entry.1,))
+                .map_err(|e| PyCanError::FailedToRemoveListener(describe_py_err(&e)))
+        });
+        if let Err(e) = removed {
+            self.listeners.lock().unwrap().push(entry);
+            return Err(e);
+        }
+        self.events.emit(PyCanEvent::ListenerRemoved(id));
 
-            Ok(())
-        })
+        Ok(())
+    }
+}
+
+impl Drop for PyCanInterface {
+    fn drop(&mut self) {
+        self.alive.store(false, Ordering::Relaxed);
+        if let Some(wheel) = self.tx_wheel.get() {
+            wheel.stop();
+        }
+
+        // Stop the notifier thread before shutting down the bus it reads from.
+        // Errors here are not actionable, so they're ignored.
+        Python::with_gil(|py| {
+            let _ = self.notifier.call_method0(py, intern!(py, "stop"));
+            let _ = self.iface.call_method0(py, intern!(py, "shutdown"));
+        });
+
+        self.events.emit(PyCanEvent::BusClosed);
     }
 }
@@ -0,0 +1,232 @@
+use pyo3::{
+    types::{IntoPyDict, PyModule},
+    Py, PyAny, PyResult, Python, ToPyObject,
+};
+
+use crate::{
+    describe_py_err, DeliveryOrder, Frames, ListenerId, PyCanError, PyCanEvent, PyCanInterface,
+    PyCanMessage,
+};
+
+/// Table used by `log_to_sqlite`. Matches python-can's SqliteWriter default.
+const SQLITE_TABLE: &str = "messages";
+
+/// Listeners that ship with python-can.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PythonListenerKind {
+    /// `can.Printer` - prints messages to stdout, or to `file` if given.
+    Printer,
+    /// `can.CSVWriter` - takes `file` and optionally `append`.
+    CsvWriter,
+    /// `can.SqliteWriter` - takes `file` and optionally `table_name`.
+    SqliteWriter,
+    /// `can.MF4Writer` - takes `file`. Needs the `asammdf` package.
+    Mf4Writer,
+}
+
+/// Where to find a user-supplied `can.Listener` subclass.
+#[derive(Clone, Copy, Debug)]
+pub enum PythonSource<'a> {
+    /// Python source code, run as a fresh module.
+    Code(&'a str),
+    /// Dotted path of an importable module, e.g. `mytool.listeners`.
+    Module(&'a str),
+}
+
+impl PythonSource<'_> {
+    pub(crate) fn load<'py>(&self, py: Python<'py>) -> PyResult<&'py PyModule> {
+        match self {
+            Self::Code(code) => {
+                PyModule::from_code(py, code, "pycanrs_listener.py", "pycanrs_listener")
+            }
+            Self::Module(path) => py.import(*path),
+        }
+    }
+}
+
+impl PythonListenerKind {
+    fn class_name(&self) -> &'static str {
+        match self {
+            Self::Printer => "Printer",
+            Self::CsvWriter => "CSVWriter",
+            Self::SqliteWriter => "SqliteWriter",
+            Self::Mf4Writer => "MF4Writer",
+        }
+    }
+}
+
+impl PyCanInterface {
+    /// Construct one of python-can's built-in listeners with the given
+    /// keyword arguments and add it to this interface's notifier.
+    pub fn attach_python_listener(
+        &self,
+        kind: PythonListenerKind,
+        args: &[(&str, &dyn ToPyObject)],
+    ) -> Result<ListenerId, PyCanError> {
+        Python::with_gil(|py| {
+            let kwargs = args
+                .iter()
+                .map(|(k, v)| (*k, v.to_object(py)))
+                .into_py_dict(py);
+
+            let listener = self
+                .pycan
+                .as_ref(py)
+                .getattr(kind.class_name())
+                .and_then(|class| class.call((), Some(kwargs)))
+                .map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))?;
+
+            self.add_listener(py, listener)
+        })
+    }
+
+    /// Instantiate the `can.Listener` subclass `class` defined in `source`
+    /// with the given keyword arguments and add it to this interface's
+    /// notifier, e.g. to run an existing Python analysis tool alongside
+    /// Rust callbacks.
+    pub fn attach_custom_listener(
+        &self,
+        source: PythonSource,
+        class: &str,
+        args: &[(&str, &dyn ToPyObject)],
+    ) -> Result<ListenerId, PyCanError> {
+        Python::with_gil(|py| {
+            let kwargs = args
+                .iter()
+                .map(|(k, v)| (*k, v.to_object(py)))
+                .into_py_dict(py);
+
+            let class = source
+                .load(py)
+                .and_then(|module| module.getattr(class))
+                .map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))?;
+
+            let is_listener = self
+                .pycan
+                .as_ref(py)
+                .getattr("Listener")
+                .and_then(|base| {
+                    py.import("builtins")?
+                        .call_method1("issubclass", (class, base))
+                })
+                .and_then(PyAny::extract::<bool>)
+                .map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))?;
+            if !is_listener {
+                return Err(PyCanError::FailedToCreateListener(format!(
+                    "{class} is not a can.Listener subclass"
+                )));
+            }
+
+            let listener = class
+                .call((), Some(kwargs))
+                .map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))?;
+
+            self.add_listener(py, listener)
+        })
+    }
+
+    /// Log all received messages to the SQLite database at `path`, using
+    /// python-can's SqliteWriter.
+    ///
+    /// The table is created up front (with SqliteWriter's schema) so that
+    /// `arbitration_id` and `ts` can be indexed for later queries.
+    pub fn log_to_sqlite(&self, path: &str) -> Result<ListenerId, PyCanError> {
+        Python::with_gil(|py| -> Result<(), PyCanError> {
+            let statements = [
+                format!(
+                    "CREATE TABLE IF NOT EXISTS {SQLITE_TABLE} (ts REAL, arbitration_id INTEGER, \
+                     extended INTEGER, remote INTEGER, error INTEGER, dlc INTEGER, data BLOB)"
+                ),
+                format!("CREATE INDEX IF NOT EXISTS {SQLITE_TABLE}_id ON {SQLITE_TABLE} (arbitration_id)"),
+                format!("CREATE INDEX IF NOT EXISTS {SQLITE_TABLE}_ts ON {SQLITE_TABLE} (ts)"),
+            ];
+
+            let conn = py
+                .import("sqlite3")
+                .and_then(|sqlite3| sqlite3.call_method1("connect", (path,)))
+                .map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))?;
+
+            let res = statements
+                .iter()
+                .try_for_each(|s| conn.call_method1("execute", (s,)).map(|_| ()))
+                .and_then(|_| conn.call_method0("commit").map(|_| ()));
+            let _ = conn.call_method0("close");
+
+            res.map_err(|e| PyCanError::FailedToCreateListener(describe_py_err(&e)))
+        })?;
+
+        self.attach_python_listener(
+            PythonListenerKind::SqliteWriter,
+            &[("file", &path), ("table_name", &SQLITE_TABLE)],
+        )
+    }
+
+    /// Log all received messages to an ASAM MDF4 file at `path`, using
+    /// python-can's MF4Writer. Requires `asammdf`.
+    ///
+    /// The file is only complete once the writer is stopped, which happens
+    /// when the interface is dropped.
+    pub fn log_to_mf4(&self, path: &str) -> Result<ListenerId, PyCanError> {
+        self.attach_python_listener(PythonListenerKind::Mf4Writer, &[("file", &path)])
+    }
+
+    /// Call the Python callable `callback` with a `can.Message` for every
+    /// received frame, error frames included, so analysis code written in
+    /// Python can share this interface's bus. Frames have been through
+    /// the interceptors and drift correction, like those given to Rust
+    /// callbacks. Exceptions raised by `callback` are reported as
+    /// [`PyCanEvent::NotifierError`].
+    pub fn register_rx_callback_py(&self, callback: Py<PyAny>) -> Result<ListenerId, PyCanError> {
+        let callable = Python::with_gil(|py| callback.as_ref(py).is_callable());
+        if !callable {
+            return Err(PyCanError::FailedToCreateListener(
+                "callback is not callable".into(),
+            ));
+        }
+
+        let pycan = self.pycan.clone();
+        let events = self.events.clone();
+        let on_rx = move |msg: &PyCanMessage| {
+            let res = Python::with_gil(|py| {
+                let msg = to_py_message(py, &pycan, msg)?;
+                callback.call1(py, (msg,)).map(|_| ())
+            });
+            if let Err(e) = res {
+                events.emit(PyCanEvent::NotifierError(describe_py_err(&e)));
+            }
+        };
+        self.register_listener(Frames::All, DeliveryOrder::PerId, on_rx, |_| {})
+    }
+}
+
+/// Build a `can.Message` carrying everything in `msg`.
+fn to_py_message(py: Python, pycan: &Py<PyAny>, msg: &PyCanMessage) -> PyResult<Py<PyAny>> {
+    let kwargs = [
+        ("timestamp", msg.timestamp.unwrap_or_default().to_object(py)),
+        ("arbitration_id", msg.arbitration_id.raw().to_object(py)),
+        (
+            "is_extended_id",
+            msg.arbitration_id.is_extended().to_object(py),
+        ),
+        ("is_remote_frame", msg.is_remote_frame.to_object(py)),
+        ("is_error_frame", msg.is_error_frame.to_object(py)),
+        ("channel", msg.iface_name.as_deref().to_object(py)),
+        ("dlc", msg.data_length().to_object(py)),
+        (
+            "data",
+            msg.data.as_deref().unwrap_or_default().to_object(py),
+        ),
+        ("is_fd", msg.is_fd.to_object(py)),
+        ("bitrate_switch", msg.bitrate_switch.to_object(py)),
+        (
+            "error_state_indicator",
+            msg.error_state_indicator.to_object(py),
+        ),
+    ]
+    .into_py_dict(py);
+
+    let message = pycan.call_method(py, "Message", (), Some(kwargs))?;
+    // Not a constructor argument before python-can 4.0
+    message.setattr(py, "is_rx", msg.is_rx)?;
+    Ok(message)
+}
@@ -0,0 +1,1011 @@
+//! Reading and writing capture files in PEAK's TRC format and python-can's
+//! CSV format, writing frames as newline-delimited JSON, and reading frames
+//! as text from pipelines.
+//!
+//! TRC files are written as version 2.1, the format PCAN-View saves by
+//! default. Versions 1.1 and 2.x can be read.
+//!
+//! CSV files use python-can's `CSVWriter` schema, so they can be read back
+//! with `can.CSVReader`:
+//!
+//! ```text
+//! timestamp,arbitration_id,extended,remote,error,dlc,data
+//! 1483389946.197,0x123,0,0,0,4,3q2+7w==
+//! ```
+//!
+//! `arbitration_id` is hex with a `0x` prefix, the flags are `0` or `1`,
+//! and `data` is base64. CAN FD flags aren't stored; frames with more than
+//! 8 bytes of data are read back as FD frames.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Lines, Write},
+    iter::Peekable,
+    path::Path,
+    str::Chars,
+    sync::Arc,
+};
+
+use thiserror::Error;
+
+use crate::{
+    message::{dlc_to_len, len_to_dlc},
+    CanId, PyCanMessage,
+};
+
+#[derive(Debug, Error)]
+pub enum LogError {
+    #[error("Failed to access log file :: `{0}`")]
+    Io(String),
+    #[error("Log file syntax error on line {0} :: `{1}`")]
+    Syntax(usize, String),
+}
+
+impl From<std::io::Error> for LogError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, LogError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| LogError::Io(format!("{}: {e}", path.display())))
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>, LogError> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|e| LogError::Io(format!("{}: {e}", path.display())))
+}
+
+fn hex_bytes<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<Vec<u8>, String> {
+    tokens
+        .map(|b| u8::from_str_radix(b, 16).map_err(|_| format!("invalid data byte `{b}`")))
+        .collect()
+}
+
+/// Days between 1899-12-30, the epoch of TRC start times, and 1970-01-01.
+const OLE_UNIX_DAYS: f64 = 25569.0;
+
+/// Writes frames to a TRC file.
+///
+/// Time offsets are relative to the file's start time, which is the first
+/// frame's timestamp unless set with `start_time`. The header is written
+/// with the first frame, or on drop if there are none.
+pub struct TrcWriter<W: Write> {
+    out: W,
+    /// Bus number written in the `B` column.
+    bus: u8,
+    /// Listed in the header.
+    bus_names: Vec<(u8, String)>,
+    count: u64,
+    start: Option<f64>,
+    header_written: bool,
+}
+
+impl TrcWriter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, LogError> {
+        Ok(Self::new(create(path.as_ref())?))
+    }
+}
+
+impl<W: Write> TrcWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            bus: 1,
+            bus_names: Vec::new(),
+            count: 0,
+            start: None,
+            header_written: false,
+        }
+    }
+
+    /// Set the bus number written for each frame. Defaults to 1.
+    pub fn bus(mut self, bus: u8) -> Self {
+        self.bus = bus;
+        self
+    }
+
+    /// Name a bus number in the header, e.g. with its interface's name.
+    pub fn bus_name(mut self, bus: u8, name: &str) -> Self {
+        self.bus_names.push((bus, name.into()));
+        self
+    }
+
+    /// Set the file's start time, as Unix time.
+    pub fn start_time(mut self, start: f64) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    fn header(&mut self, start: f64) -> Result<(), LogError> {
+        self.start = Some(start);
+        self.header_written = true;
+        writeln!(self.out, ";$FILEVERSION=2.1")?;
+        writeln!(self.out, ";$STARTTIME={}", start / 86400.0 + OLE_UNIX_DAYS)?;
+        writeln!(self.out, ";$COLUMNS=N,O,T,B,I,d,R,L,D")?;
+        writeln!(self.out, ";")?;
+        writeln!(self.out, ";   Generated by pycanrs")?;
+        for (bus, name) in &self.bus_names {
+            writeln!(self.out, ";   Bus {bus}: {name}")?;
+        }
+        writeln!(self.out, ";")?;
+        Ok(())
+    }
+
+    pub fn write(&mut self, msg: &PyCanMessage) -> Result<(), LogError> {
+        self.write_on_bus(msg, self.bus)
+    }
+
+    /// Write a frame with the given bus number rather than the default.
+    pub fn write_on_bus(&mut self, msg: &PyCanMessage, bus: u8) -> Result<(), LogError> {
+        let timestamp = msg.timestamp.unwrap_or_default();
+        if !self.header_written {
+            self.header(self.start.unwrap_or(timestamp))?;
+        }
+        let start = self.start.unwrap_or_default();
+        self.count += 1;
+
+        let kind = if msg.is_error_frame {
+            "ER"
+        } else if msg.is_remote_frame {
+            "RR"
+        } else if msg.is_fd {
+            match (msg.bitrate_switch, msg.error_state_indicator) {
+                (false, false) => "FD",
+                (true, false) => "FB",
+                (false, true) => "FE",
+                (true, true) => "BI",
+            }
+        } else {
+            "DT"
+        };
+        let id = match msg.arbitration_id {
+            CanId::Standard(id) => format!("{id:04X}"),
+            CanId::Extended(id) => format!("{id:08X}"),
+        };
+        let direction = if msg.is_rx { "Rx" } else { "Tx" };
+        let data = msg.data.as_deref().unwrap_or_default();
+        let dlc = msg.dlc.or_else(|| len_to_dlc(data.len())).unwrap_or(0);
+        let bytes = if msg.is_remote_frame {
+            String::new()
+        } else {
+            data.iter().map(|b| format!(" {b:02X}")).collect()
+        };
+
+        writeln!(
+            self.out,
+            "{:>7} {:>13.3} {kind} {:>2} {id:>8} {direction} - {dlc:>2}   {bytes}",
+            self.count,
+            (timestamp - start) * 1000.0,
+            bus,
+        )?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), LogError> {
+        Ok(self.out.flush()?)
+    }
+}
+
+impl<W: Write> Drop for TrcWriter<W> {
+    fn drop(&mut self) {
+        if !self.header_written {
+            let _ = self.header(self.start.unwrap_or_default());
+        }
+        let _ = self.flush();
+    }
+}
+
+/// Reads frames from a TRC file. Timestamps are the file's start time
+/// plus each frame's offset. If the header names buses, as multi-bus
+/// captures from [`crate::BusTee`] do, `iface_name` is the frame's bus
+/// name, or its number if that bus isn't named. Status and other
+/// non-frame records are skipped.
+pub struct TrcReader<R: BufRead> {
+    lines: Lines<R>,
+    line: usize,
+    version: u8,
+    columns: Vec<char>,
+    bus_names: HashMap<String, Arc<str>>,
+    /// Unix time of the start of the capture.
+    start: f64,
+}
+
+impl TrcReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, LogError> {
+        Ok(Self::new(open(path.as_ref())?))
+    }
+}
+
+impl<R: BufRead> TrcReader<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            lines: input.lines(),
+            line: 0,
+            version: 1,
+            columns: Vec::new(),
+            bus_names: HashMap::new(),
+            start: 0.0,
+        }
+    }
+
+    fn header(&mut self, line: &str) {
+        if let Some(version) = line.strip_prefix(";$FILEVERSION=") {
+            self.version = version
+                .trim()
+                .chars()
+                .next()
+                .map_or(1, |c| c.to_digit(10).map_or(1, |d| d as u8));
+            if self.version == 2 && self.columns.is_empty() {
+                // 2.0 has fixed columns
+                self.columns = "NOTIdlD".chars().collect();
+            }
+        } else if let Some(start) = line.strip_prefix(";$STARTTIME=") {
+            if let Ok(days) = start.trim().parse::<f64>() {
+                self.start = (days - OLE_UNIX_DAYS) * 86400.0;
+            }
+        } else if let Some(columns) = line.strip_prefix(";$COLUMNS=") {
+            self.columns = columns
+                .split(',')
+                .filter_map(|c| c.trim().chars().next())
+                .collect();
+        } else if let Some((bus, name)) = line
+            .strip_prefix(';')
+            .and_then(|l| l.trim_start().strip_prefix("Bus "))
+            .and_then(|l| l.split_once(':'))
+        {
+            self.bus_names.insert(bus.trim().into(), name.trim().into());
+        }
+    }
+
+    /// A version 1.x record: `N) offset [type] ID DLC data`.
+    fn record_v1(&self, line: &str) -> Result<Option<PyCanMessage>, String> {
+        let mut tokens = line.split_whitespace().skip(1);
+        let offset = tokens.next().ok_or("missing time offset")?;
+        let mut next = tokens.next().ok_or("missing ID")?;
+
+        let mut is_rx = true;
+        let mut is_error_frame = false;
+        match next {
+            "Rx" | "Tx" => is_rx = next == "Rx",
+            "Error" => is_error_frame = true,
+            "Warng" => return Ok(None),
+            _ => {}
+        }
+        if matches!(next, "Rx" | "Tx" | "Error") {
+            next = tokens.next().ok_or("missing ID")?;
+        }
+
+        let id = parse_id(next)?;
+        let dlc: u8 = tokens
+            .next()
+            .and_then(|d| d.parse().ok())
+            .ok_or("missing DLC")?;
+        let rest: Vec<&str> = tokens.collect();
+        let is_remote_frame = rest.first() == Some(&"RTR");
+        let data = if is_remote_frame {
+            Vec::new()
+        } else {
+            hex_bytes(rest.into_iter())?
+        };
+
+        let mut msg = PyCanMessage::new(id, &data);
+        msg.dlc = Some(dlc);
+        msg.is_rx = is_rx;
+        msg.is_remote_frame = is_remote_frame;
+        msg.is_error_frame = is_error_frame;
+        msg.timestamp = Some(self.start + parse_offset(offset)?);
+        Ok(Some(msg))
+    }
+
+    /// A version 2.x record, laid out by `$COLUMNS`.
+    fn record_v2(&self, line: &str) -> Result<Option<PyCanMessage>, String> {
+        let mut tokens = line.split_whitespace();
+        let mut msg = PyCanMessage::new(CanId::Standard(0), &[]);
+        let mut kind = "DT";
+        let mut length = None;
+        let mut dlc = None;
+        let mut data = Vec::new();
+
+        for column in &self.columns {
+            if *column == 'D' {
+                data = hex_bytes(tokens.by_ref())?;
+                break;
+            }
+            let token = tokens
+                .next()
+                .ok_or_else(|| format!("missing column `{column}`"))?;
+            match column {
+                'O' => msg.timestamp = Some(self.start + parse_offset(token)?),
+                'T' => kind = token,
+                'B' if !self.bus_names.is_empty() => {
+                    msg.iface_name = Some(
+                        self.bus_names
+                            .get(token)
+                            .cloned()
+                            .unwrap_or_else(|| token.into()),
+                    );
+                }
+                'I' if token != "-" => msg.arbitration_id = parse_id(token)?,
+                'd' => msg.is_rx = token == "Rx",
+                'l' => length = token.parse().ok(),
+                'L' => dlc = token.parse().ok(),
+                _ => {}
+            }
+        }
+
+        match kind {
+            "DT" => {}
+            "FD" | "FB" | "FE" | "BI" => {
+                msg.is_fd = true;
+                msg.bitrate_switch = matches!(kind, "FB" | "BI");
+                msg.error_state_indicator = matches!(kind, "FE" | "BI");
+            }
+            "RR" => msg.is_remote_frame = true,
+            "ER" => msg.is_error_frame = true,
+            // Status, overrun and other events
+            _ => return Ok(None),
+        }
+
+        let dlc = dlc.or_else(|| len_to_dlc(length.unwrap_or(data.len())));
+        if !msg.is_remote_frame {
+            let len = dlc.map_or(data.len(), |dlc| {
+                if msg.is_fd {
+                    dlc_to_len(dlc)
+                } else {
+                    usize::from(dlc.min(8))
+                }
+            });
+            data.truncate(len);
+        }
+        msg.dlc = dlc;
+        msg.data = Some(data);
+        Ok(Some(msg))
+    }
+}
+
+/// An ID: 4 or fewer hex digits for standard frames, 8 for extended.
+fn parse_id(s: &str) -> Result<CanId, String> {
+    let raw = u32::from_str_radix(s, 16).map_err(|_| format!("invalid ID `{s}`"))?;
+    let extended = s.len() > 4 || raw > u32::from(crate::id::STANDARD_ID_MAX);
+    CanId::new(raw, extended).map_err(|e| e.to_string())
+}
+
+/// A time offset in milliseconds, as seconds.
+fn parse_offset(s: &str) -> Result<f64, String> {
+    s.parse::<f64>()
+        .map(|ms| ms / 1000.0)
+        .map_err(|_| format!("invalid time offset `{s}`"))
+}
+
+impl<R: BufRead> Iterator for TrcReader<R> {
+    type Item = Result<PyCanMessage, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            self.line += 1;
+
+            let line = line.trim();
+            if line.starts_with(';') {
+                self.header(line);
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            let record = if self.version >= 2 {
+                self.record_v2(line)
+            } else {
+                self.record_v1(line)
+            };
+            match record {
+                Ok(Some(msg)) => return Some(Ok(msg)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(LogError::Syntax(self.line, e))),
+            }
+        }
+    }
+}
+
+const CSV_HEADER: &str = "timestamp,arbitration_id,extended,remote,error,dlc,data";
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let digits = s
+        .trim_end_matches('=')
+        .bytes()
+        .map(|c| BASE64.iter().position(|&b| b == c).map(|d| d as u32))
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut out = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, &d)| n | d << (18 - 6 * i));
+        for i in 0..chunk.len() - 1 {
+            out.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// Writes frames in python-can's CSV schema.
+pub struct CsvWriter<W: Write> {
+    out: W,
+}
+
+impl CsvWriter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, LogError> {
+        Self::new(create(path.as_ref())?)
+    }
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Writes the header line straight away.
+    pub fn new(mut out: W) -> Result<Self, LogError> {
+        writeln!(out, "{CSV_HEADER}")?;
+        Ok(Self { out })
+    }
+
+    pub fn write(&mut self, msg: &PyCanMessage) -> Result<(), LogError> {
+        let data = msg.data.as_deref().unwrap_or_default();
+        // python-can's `dlc` is a byte count
+        let dlc = if msg.is_fd {
+            msg.data_length()
+        } else {
+            usize::from(msg.dlc.or_else(|| len_to_dlc(data.len())).unwrap_or(0))
+        };
+        writeln!(
+            self.out,
+            "{},{:#x},{},{},{},{},{}",
+            msg.timestamp.unwrap_or_default(),
+            msg.arbitration_id.raw(),
+            u8::from(msg.arbitration_id.is_extended()),
+            u8::from(msg.is_remote_frame),
+            u8::from(msg.is_error_frame),
+            dlc,
+            base64_encode(data),
+        )?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), LogError> {
+        Ok(self.out.flush()?)
+    }
+}
+
+impl<W: Write> Drop for CsvWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+/// Reads frames in python-can's CSV schema.
+pub struct CsvReader<R: BufRead> {
+    lines: Lines<R>,
+    line: usize,
+}
+
+impl CsvReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, LogError> {
+        Ok(Self::new(open(path.as_ref())?))
+    }
+}
+
+impl<R: BufRead> CsvReader<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            lines: input.lines(),
+            line: 0,
+        }
+    }
+
+    fn record(line: &str) -> Result<PyCanMessage, String> {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [timestamp, id, extended, remote, error, dlc, data] = fields[..] else {
+            return Err(format!("expected 7 fields, got {}", fields.len()));
+        };
+
+        let flag = |s: &str| match s {
+            "0" => Ok(false),
+            "1" => Ok(true),
+            _ => Err(format!("invalid flag `{s}`")),
+        };
+        let raw = u32::from_str_radix(id.trim_start_matches("0x"), 16)
+            .map_err(|_| format!("invalid ID `{id}`"))?;
+        let id = CanId::new(raw, flag(extended)?).map_err(|e| e.to_string())?;
+        let data = base64_decode(data).ok_or_else(|| format!("invalid data `{data}`"))?;
+
+        let mut msg = PyCanMessage::new(id, &data);
+        msg.timestamp = Some(
+            timestamp
+                .parse()
+                .map_err(|_| format!("invalid timestamp `{timestamp}`"))?,
+        );
+        msg.is_remote_frame = flag(remote)?;
+        msg.is_error_frame = flag(error)?;
+        let len: usize = dlc.parse().map_err(|_| format!("invalid DLC `{dlc}`"))?;
+        msg.dlc = Some(if msg.is_fd {
+            len_to_dlc(len).unwrap_or(15)
+        } else {
+            len.min(15) as u8
+        });
+        Ok(msg)
+    }
+}
+
+impl<R: BufRead> Iterator for CsvReader<R> {
+    type Item = Result<PyCanMessage, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            self.line += 1;
+
+            let line = line.trim();
+            if line.is_empty() || line == CSV_HEADER {
+                continue;
+            }
+            return Some(Self::record(line).map_err(|e| LogError::Syntax(self.line, e)));
+        }
+    }
+}
+
+/// Writes frames as JSON, one object per line, for piping into `jq` and
+/// the like:
+///
+/// ```text
+/// {"timestamp": 1483389946.197000, "iface": "can0", "id": 291, "extended": false, "dlc": 4, "data": "DEADBEEF", "is_rx": true, "is_error_frame": false, "is_remote_frame": false, "is_fd": false, "bitrate_switch": false}
+/// ```
+///
+/// `data` is hex and `dlc` a byte count, as in python-can. `timestamp` and `iface` are null if the frame has none.
+/// There is no reader; this is for looking at frames, not storing them.
+pub struct NdjsonWriter<W: Write> {
+    out: W,
+}
+
+impl NdjsonWriter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, LogError> {
+        Ok(Self::new(create(path.as_ref())?))
+    }
+}
+
+impl NdjsonWriter<std::io::Stdout> {
+    /// Stdout is line buffered, so each frame is passed on as it's written.
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn write(&mut self, msg: &PyCanMessage) -> Result<(), LogError> {
+        // JSON has no NaN or infinity
+        let timestamp = msg
+            .timestamp
+            .filter(|t| t.is_finite())
+            .map_or("null".to_string(), |t| format!("{t:.6}"));
+        let iface = msg
+            .iface_name
+            .as_deref()
+            .map_or("null".to_string(), json_string);
+        let data: String = msg
+            .data
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect();
+        writeln!(
+            self.out,
+            "{{\"timestamp\": {timestamp}, \"iface\": {iface}, \"id\": {}, \"extended\": {}, \
+             \"dlc\": {}, \"data\": \"{data}\", \"is_rx\": {}, \"is_error_frame\": {}, \
+             \"is_remote_frame\": {}, \"is_fd\": {}, \"bitrate_switch\": {}}}",
+            msg.arbitration_id.raw(),
+            msg.arbitration_id.is_extended(),
+            msg.data_length(),
+            msg.is_rx,
+            msg.is_error_frame,
+            msg.is_remote_frame,
+            msg.is_fd,
+            msg.bitrate_switch,
+        )?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), LogError> {
+        Ok(self.out.flush()?)
+    }
+}
+
+impl<W: Write> Drop for NdjsonWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+/// `s` as a quoted JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if u32::from(c) < 0x20 => out.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Reads frames written one per line as text, e.g. from a shell pipeline.
+/// Each line can be any of:
+///
+/// ```text
+/// (1483389946.197000) can0 123#DEADBEEF   candump log, as `candump -L` writes
+/// 123#DE.AD.BE.EF                         cansend
+///  (1483389946.197000)  can0  123   [4]  DE AD BE EF
+///                                         candump's screen output
+/// {"id": 291, "data": "DEADBEEF"}         JSON, as NdjsonWriter writes
+/// ```
+///
+/// Formats can be mixed. In the log and cansend forms `123#R` is a remote
+/// frame and `123##1DEAD` an FD frame with flags `1`. In JSON only `id` is
+/// required, as a number or in candump notation; other fields take
+/// [`PyCanMessage::new`]'s defaults, and `extended` defaults to whether the
+/// ID needs 29 bits. Blank lines and lines starting with `#` are skipped.
+pub struct TextReader<R: BufRead> {
+    lines: Lines<R>,
+    line: usize,
+}
+
+impl TextReader<std::io::StdinLock<'static>> {
+    pub fn stdin() -> Self {
+        Self::new(std::io::stdin().lock())
+    }
+}
+
+impl<R: BufRead> TextReader<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            lines: input.lines(),
+            line: 0,
+        }
+    }
+
+    fn record(line: &str) -> Result<PyCanMessage, String> {
+        if line.starts_with('{') {
+            return json_frame(line);
+        }
+
+        let mut tokens: Vec<&str> = line.split_whitespace().collect();
+        // A timestamp, then in screen output maybe a delta time
+        let mut timestamp = None;
+        while let Some(t) = tokens.first().and_then(|t| t.strip_prefix('(')) {
+            let t = t.trim_end_matches(')');
+            let t: f64 = t.parse().map_err(|_| format!("invalid timestamp `{t}`"))?;
+            timestamp = timestamp.or(Some(t));
+            tokens.remove(0);
+        }
+
+        let mut msg = match tokens.iter().position(|t| t.contains('#')) {
+            Some(i) => {
+                let mut msg = compact_frame(tokens[i])?;
+                if i > 0 {
+                    msg.iface_name = Some(tokens[0].into());
+                }
+                msg
+            }
+            None => screen_frame(&tokens)?,
+        };
+        msg.timestamp = timestamp;
+        Ok(msg)
+    }
+}
+
+impl<R: BufRead> Iterator for TextReader<R> {
+    type Item = Result<PyCanMessage, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            self.line += 1;
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            return Some(Self::record(line).map_err(|e| LogError::Syntax(self.line, e)));
+        }
+    }
+}
+
+/// An ID in candump notation. Error frames have bit 29 set.
+fn text_id(s: &str) -> Result<(CanId, bool), String> {
+    let invalid = || format!("invalid ID `{s}`");
+    let raw = u32::from_str_radix(s, 16).map_err(|_| invalid())?;
+    if s.len() == 8 && raw & 0x2000_0000 != 0 {
+        let raw = raw & 0x1FFF_FFFF;
+        let id = CanId::new(raw, raw > 0x7FF).map_err(|_| invalid())?;
+        return Ok((id, true));
+    }
+    Ok((s.parse().map_err(|_| invalid())?, false))
+}
+
+/// `ID#DATA`, `ID#R`, `ID#R<len>` or `ID##<flags><data>`.
+fn compact_frame(frame: &str) -> Result<PyCanMessage, String> {
+    let (id, rest) = frame.split_once('#').unwrap_or((frame, ""));
+    let (id, is_error_frame) = text_id(id)?;
+    let hex = |s: &str| crate::parse_hex(s).map_err(|e| e.to_string());
+
+    let mut msg = if let Some(fd) = rest.strip_prefix('#') {
+        let mut chars = fd.chars();
+        let flags = chars
+            .next()
+            .and_then(|c| c.to_digit(16))
+            .ok_or_else(|| format!("invalid FD flags `{fd}`"))?;
+        let mut msg = PyCanMessage::new(id, &hex(chars.as_str())?);
+        msg.is_fd = true;
+        msg.dlc = len_to_dlc(msg.data_length());
+        msg.bitrate_switch = flags & 1 != 0;
+        msg.error_state_indicator = flags & 2 != 0;
+        msg
+    } else if let Some(len) = rest.strip_prefix(['R', 'r']) {
+        let mut msg = PyCanMessage::new(id, &[]);
+        msg.is_remote_frame = true;
+        msg.dlc = Some(match len {
+            "" => 0,
+            len => len.parse().map_err(|_| format!("invalid length `{len}`"))?,
+        });
+        msg
+    } else {
+        PyCanMessage::new(id, &hex(rest)?)
+    };
+    msg.is_error_frame = is_error_frame;
+    Ok(msg)
+}
+
+/// `[iface] ID [len] DATA`, optionally ending in `ERRORFRAME`, or
+/// `[iface] ID [len] remote request`.
+fn screen_frame(tokens: &[&str]) -> Result<PyCanMessage, String> {
+    let len_at = tokens
+        .iter()
+        .position(|t| t.starts_with('['))
+        .filter(|&i| i == 1 || i == 2)
+        .ok_or("expected `ID [len] DATA` or `ID#DATA`")?;
+    let (id, is_error_frame) = text_id(tokens[len_at - 1])?;
+    let len_token = tokens[len_at].trim_matches(['[', ']']);
+    let len: u8 = len_token
+        .parse()
+        .map_err(|_| format!("invalid length `{}`", tokens[len_at]))?;
+
+    let rest = &tokens[len_at + 1..];
+    let mut msg = if rest == ["remote", "request"] {
+        let mut msg = PyCanMessage::new(id, &[]);
+        msg.is_remote_frame = true;
+        msg.dlc = Some(len);
+        msg
+    } else {
+        let rest = rest.strip_suffix(&["ERRORFRAME"]).unwrap_or(rest);
+        let data = crate::parse_hex(&rest.concat()).map_err(|e| e.to_string())?;
+        if data.len() != usize::from(len) {
+            return Err(format!("length {len} but {} data bytes", data.len()));
+        }
+        let mut msg = PyCanMessage::new(id, &data);
+        // candump pads FD lengths to two digits
+        msg.is_fd |= len_token.len() == 2;
+        msg
+    };
+    msg.is_error_frame = is_error_frame;
+    if len_at == 2 {
+        msg.iface_name = Some(tokens[0].into());
+    }
+    Ok(msg)
+}
+
+enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+/// A frame as a JSON object. Only flat objects are understood.
+fn json_frame(line: &str) -> Result<PyCanMessage, String> {
+    let fields = json_object(line)?;
+    let field = |name: &str| fields.get(name).filter(|v| !matches!(v, JsonValue::Null));
+    let flag = |name: &str| match field(name) {
+        None => Ok(None),
+        Some(JsonValue::Bool(b)) => Ok(Some(*b)),
+        Some(_) => Err(format!("`{name}` must be true or false")),
+    };
+
+    let extended = flag("extended")?;
+    let id = match field("id") {
+        Some(JsonValue::Number(n)) if n.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(n) => {
+            let raw = *n as u32;
+            CanId::new(raw, extended.unwrap_or(raw > 0x7FF)).map_err(|e| e.to_string())?
+        }
+        Some(JsonValue::String(s)) => match s.strip_prefix("0x") {
+            Some(hex) => {
+                let raw = u32::from_str_radix(hex, 16).map_err(|_| format!("invalid ID `{s}`"))?;
+                CanId::new(raw, extended.unwrap_or(raw > 0x7FF)).map_err(|e| e.to_string())?
+            }
+            None => s.parse().map_err(|_| format!("invalid ID `{s}`"))?,
+        },
+        Some(_) => return Err("`id` must be a number or a string".into()),
+        None => return Err("missing `id`".into()),
+    };
+    let data = match field("data") {
+        Some(JsonValue::String(s)) => crate::parse_hex(s).map_err(|e| e.to_string())?,
+        Some(_) => return Err("`data` must be a hex string".into()),
+        None => Vec::new(),
+    };
+
+    let mut msg = PyCanMessage::new(id, &data);
+    msg.is_fd = flag("is_fd")?.unwrap_or(msg.is_fd);
+    msg.is_error_frame = flag("is_error_frame")?.unwrap_or_default();
+    msg.is_remote_frame = flag("is_remote_frame")?.unwrap_or_default();
+    msg.bitrate_switch = flag("bitrate_switch")?.unwrap_or_default();
+    msg.error_state_indicator = flag("error_state_indicator")?.unwrap_or_default();
+    msg.is_rx = flag("is_rx")?.unwrap_or(msg.is_rx);
+    match field("timestamp") {
+        Some(JsonValue::Number(t)) => msg.timestamp = Some(*t),
+        Some(_) => return Err("`timestamp` must be a number".into()),
+        None => {}
+    }
+    match field("iface") {
+        Some(JsonValue::String(s)) => msg.iface_name = Some(s.as_str().into()),
+        Some(_) => return Err("`iface` must be a string".into()),
+        None => {}
+    }
+    // As in python-can, `dlc` is a byte count
+    match field("dlc") {
+        Some(JsonValue::Number(n)) if n.fract() == 0.0 && (0.0..=64.0).contains(n) => {
+            let len = *n as usize;
+            msg.dlc = Some(if msg.is_fd {
+                len_to_dlc(len).unwrap_or(15)
+            } else {
+                len.min(15) as u8
+            });
+        }
+        Some(_) => return Err("`dlc` must be a byte count".into()),
+        None => {}
+    }
+    Ok(msg)
+}
+
+fn json_object(text: &str) -> Result<HashMap<String, JsonValue>, String> {
+    let mut chars = text.trim().chars().peekable();
+    let mut fields = HashMap::new();
+    let skip_ws =
+        |chars: &mut Peekable<Chars>| while chars.next_if(|c| c.is_whitespace()).is_some() {};
+
+    if chars.next() != Some('{') {
+        return Err("expected `{`".into());
+    }
+    skip_ws(&mut chars);
+    if chars.next_if_eq(&'}').is_some() {
+        return Ok(fields);
+    }
+    loop {
+        skip_ws(&mut chars);
+        if chars.next() != Some('"') {
+            return Err("expected a quoted key".into());
+        }
+        let key = json_string_body(&mut chars)?;
+        skip_ws(&mut chars);
+        if chars.next() != Some(':') {
+            return Err(format!("expected `:` after `{key}`"));
+        }
+        skip_ws(&mut chars);
+
+        let value = match chars.peek() {
+            Some('"') => {
+                chars.next();
+                JsonValue::String(json_string_body(&mut chars)?)
+            }
+            Some('{' | '[') => return Err(format!("`{key}` is nested, which isn't supported")),
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !matches!(c, ',' | '}') && !c.is_whitespace())
+                {
+                    word.push(c);
+                }
+                match word.as_str() {
+                    "null" => JsonValue::Null,
+                    "true" => JsonValue::Bool(true),
+                    "false" => JsonValue::Bool(false),
+                    n => JsonValue::Number(
+                        n.parse()
+                            .ok()
+                            .filter(|n: &f64| n.is_finite())
+                            .ok_or_else(|| format!("invalid value `{n}` for `{key}`"))?,
+                    ),
+                }
+            }
+        };
+        fields.insert(key, value);
+
+        skip_ws(&mut chars);
+        match chars.next() {
+            Some(',') => {}
+            Some('}') => break,
+            _ => return Err("expected `,` or `}`".into()),
+        }
+    }
+    skip_ws(&mut chars);
+    match chars.next() {
+        None => Ok(fields),
+        Some(_) => Err("trailing characters after the object".into()),
+    }
+}
+
+/// The rest of a JSON string whose opening quote has been read.
+fn json_string_body(chars: &mut impl Iterator<Item = char>) -> Result<String, String> {
+    let mut out = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(out),
+            '\\' => out.push(match chars.next().ok_or("unterminated string")? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| format!("invalid escape `\\u{hex}`"))?
+                }
+                c => c,
+            }),
+            c => out.push(c),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! A private, pinned python-can install, so deployed binaries don't
+//! depend on what the host has installed.
+//!
+//! On first use, a virtualenv is created in the configured directory with
+//! the host's Python and the pinned python-can is pip-installed into it.
+//! Its site-packages directory is then put ahead of the host's on
+//! `sys.path`. Later runs reuse the virtualenv.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
+
+use pyo3::{PyAny, PyErr, Python};
+
+use crate::{describe_py_err, PyCanError};
+
+/// python-can version installed unless another is configured.
+pub const PINNED_PYTHON_CAN: &str = "4.3.1";
+
+#[derive(Clone, Debug)]
+pub struct ManagedPython {
+    dir: PathBuf,
+    python_can_version: String,
+}
+
+impl ManagedPython {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            python_can_version: PINNED_PYTHON_CAN.into(),
+        }
+    }
+
+    pub fn python_can_version(mut self, version: impl Into<String>) -> Self {
+        self.python_can_version = version.into();
+        self
+    }
+
+    /// Marks a completed install of this version.
+    fn marker(&self) -> PathBuf {
+        self.dir
+            .join(format!(".pycanrs-python-can-{}", self.python_can_version))
+    }
+
+    fn venv_python(&self) -> PathBuf {
+        if cfg!(windows) {
+            self.dir.join("Scripts").join("python.exe")
+        } else {
+            self.dir.join("bin").join("python")
+        }
+    }
+
+    fn install(&self, host_python: &Path) -> Result<(), PyCanError> {
+        run(Command::new(host_python)
+            .arg("-m")
+            .arg("venv")
+            .arg(&self.dir))?;
+        run(Command::new(self.venv_python()).args([
+            "-m",
+            "pip",
+            "install",
+            "--quiet",
+            &format!("python-can=={}", self.python_can_version),
+        ]))?;
+
+        std::fs::write(self.marker(), "")
+            .map_err(|e| PyCanError::PythonCanImportFailed(e.to_string()))
+    }
+
+    fn site_packages(&self) -> Result<String, PyCanError> {
+        let out = run(Command::new(self.venv_python()).args([
+            "-c",
+            "import sysconfig; print(sysconfig.get_paths()['purelib'])",
+        ]))?;
+        Ok(out.trim().into())
+    }
+}
+
+fn run(cmd: &mut Command) -> Result<String, PyCanError> {
+    let desc = format!("{cmd:?}");
+    let fail = |why: String| PyCanError::PythonCanImportFailed(format!("{desc}: {why}"));
+
+    let out = cmd.output().map_err(|e| fail(e.to_string()))?;
+    if !out.status.success() {
+        return Err(fail(String::from_utf8_lossy(&out.stderr).into_owned()));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// The configured install, and whether it's been set up in this process.
+static MANAGED: Mutex<(Option<ManagedPython>, bool)> = Mutex::new((None, false));
+
+/// Held while installing, so two threads don't install at once. Only taken
+/// with the GIL released.
+static INSTALLING: Mutex<()> = Mutex::new(());
+
+/// Use a private python-can install for every interface opened after this
+/// call.
+pub fn use_managed_python(config: ManagedPython) {
+    *MANAGED.lock().unwrap() = (Some(config), false);
+}
+
+/// Set up the configured install, if any, and add it to `sys.path`. Fails
+/// if python-can has already been imported from somewhere else, since the
+/// install couldn't take effect.
+pub(crate) fn ensure(py: Python) -> Result<(), PyCanError> {
+    let config = match &*MANAGED.lock().unwrap() {
+        (Some(config), false) => config.clone(),
+        _ => return Ok(()),
+    };
+    let fail = |e: PyErr| PyCanError::PythonCanImportFailed(describe_py_err(&e));
+
+    let sys = py.import("sys").map_err(fail)?;
+    if let Some(can) = sys
+        .getattr("modules")
+        .and_then(|modules| modules.call_method1("get", ("can",)))
+        .map_err(fail)?
+        .extract::<Option<&PyAny>>()
+        .map_err(fail)?
+    {
+        let origin = can
+            .getattr("__file__")
+            .map_or_else(|_| "an unknown location".into(), |f| f.to_string());
+        return Err(PyCanError::PythonCanImportFailed(format!(
+            "python-can was already imported from {origin}, so the managed install can't be used"
+        )));
+    }
+
+    // The embedded interpreter's sys.executable is our own binary, so
+    // find the real one from its installation prefix.
+    let prefix: PathBuf = sys
+        .getattr("base_prefix")
+        .and_then(|p| p.extract())
+        .map_err(fail)?;
+    let host_python = if cfg!(windows) {
+        prefix.join("python.exe")
+    } else {
+        prefix.join("bin").join("python3")
+    };
+
+    // Installing takes a while, so let other threads run Python meanwhile
+    let site_packages = py.allow_threads(|| {
+        let _installing = INSTALLING.lock().unwrap();
+        if !config.marker().exists() {
+            config.install(&host_python)?;
+        }
+        config.site_packages()
+    })?;
+
+    // Claim the sys.path change before making it, without holding the
+    // lock across the Python call
+    if std::mem::replace(&mut MANAGED.lock().unwrap().1, true) {
+        return Ok(());
+    }
+    sys.getattr("path")
+        .and_then(|path| path.call_method1("insert", (0, site_packages)))
+        .map_err(|e| {
+            MANAGED.lock().unwrap().1 = false;
+            fail(e)
+        })?;
+    Ok(())
+}
@@ -0,0 +1,179 @@
+//! Merging captures from several buses or files into one time-ordered
+//! stream.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::{CanId, LogError, PyCanMessage};
+
+/// How a source's timestamps are shifted onto the merged timebase.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ClockOffset {
+    /// The source's clock is already on the shared timebase.
+    #[default]
+    None,
+    /// Add this many seconds to every timestamp.
+    Fixed(f64),
+    /// Shift timestamps so the source's first frame with this ID lines up
+    /// with the first source's first frame with it, e.g. a sync message
+    /// gatewayed onto both buses. Left unshifted if either source never
+    /// has the frame.
+    SyncFrame(CanId),
+}
+
+type Frames = Box<dyn Iterator<Item = Result<PyCanMessage, LogError>> + Send>;
+
+struct Source {
+    tag: Arc<str>,
+    frames: Frames,
+    /// Frames read ahead while looking for a sync frame.
+    buffered: VecDeque<Result<PyCanMessage, LogError>>,
+    offset: ClockOffset,
+    /// The next frame, already shifted and tagged.
+    head: Option<Result<PyCanMessage, LogError>>,
+}
+
+impl Source {
+    fn pull(&mut self) -> Option<Result<PyCanMessage, LogError>> {
+        self.buffered.pop_front().or_else(|| self.frames.next())
+    }
+
+    /// Timestamp of the first frame with `id`, reading ahead as needed.
+    fn find(&mut self, id: CanId) -> Option<f64> {
+        let timestamp = |frame: &Result<PyCanMessage, LogError>| {
+            frame
+                .as_ref()
+                .ok()
+                .filter(|msg| msg.arbitration_id == id)
+                .and_then(|msg| msg.timestamp)
+        };
+        if let Some(t) = self.buffered.iter().find_map(timestamp) {
+            return Some(t);
+        }
+        for frame in self.frames.by_ref() {
+            let t = timestamp(&frame);
+            self.buffered.push_back(frame);
+            if t.is_some() {
+                return t;
+            }
+        }
+        None
+    }
+
+    fn advance(&mut self) {
+        let offset = match self.offset {
+            ClockOffset::Fixed(offset) => offset,
+            _ => 0.0,
+        };
+        let tag = self.tag.clone();
+        self.head = self.pull().map(|frame| {
+            frame.map(|mut msg| {
+                msg.timestamp = msg.timestamp.map(|t| t + offset);
+                msg.iface_name = Some(tag);
+                msg
+            })
+        });
+    }
+
+    /// Sort key of the head: errors first, then by timestamp.
+    fn key(&self) -> Option<f64> {
+        match self.head.as_ref()? {
+            Ok(msg) => Some(msg.timestamp.unwrap_or_default()),
+            Err(_) => Some(f64::NEG_INFINITY),
+        }
+    }
+}
+
+/// Merges frames from several sources into one stream ordered by
+/// timestamp. Each frame's `iface_name` is set to its source's tag.
+///
+/// Sources are read lazily, and are each expected to be in time order
+/// already, as captures are. Read errors are passed through as soon as
+/// they're hit; the source carries on after them.
+///
+/// ```ignore
+/// let merged = LogMerger::new()
+///     .add("powertrain", TrcReader::open("pt.trc")?)
+///     .add_with_offset("body", CsvReader::open("body.csv")?, ClockOffset::Fixed(0.0125));
+/// for frame in merged { ... }
+/// ```
+#[derive(Default)]
+pub struct LogMerger {
+    sources: Vec<Source>,
+    started: bool,
+}
+
+impl LogMerger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a source whose clock is already on the shared timebase.
+    pub fn add<I>(self, tag: &str, frames: I) -> Self
+    where
+        I: IntoIterator<Item = Result<PyCanMessage, LogError>>,
+        I::IntoIter: Send + 'static,
+    {
+        self.add_with_offset(tag, frames, ClockOffset::None)
+    }
+
+    pub fn add_with_offset<I>(mut self, tag: &str, frames: I, offset: ClockOffset) -> Self
+    where
+        I: IntoIterator<Item = Result<PyCanMessage, LogError>>,
+        I::IntoIter: Send + 'static,
+    {
+        self.sources.push(Source {
+            tag: tag.into(),
+            frames: Box::new(frames.into_iter()),
+            buffered: VecDeque::new(),
+            offset,
+            head: None,
+        });
+        self
+    }
+
+    /// Work out sync frame offsets and read each source's first frame.
+    fn start(&mut self) {
+        for i in 0..self.sources.len() {
+            let ClockOffset::SyncFrame(id) = self.sources[i].offset else {
+                continue;
+            };
+            let reference = self.sources[0].find(id).map(|t| {
+                t + match self.sources[0].offset {
+                    ClockOffset::Fixed(offset) => offset,
+                    _ => 0.0,
+                }
+            });
+            let own = self.sources[i].find(id);
+            self.sources[i].offset = match (reference, own) {
+                (Some(reference), Some(own)) => ClockOffset::Fixed(reference - own),
+                _ => ClockOffset::None,
+            };
+        }
+
+        for source in &mut self.sources {
+            source.advance();
+        }
+    }
+}
+
+impl Iterator for LogMerger {
+    type Item = Result<PyCanMessage, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            self.start();
+        }
+
+        // Ties go to the source added first
+        let source = self
+            .sources
+            .iter_mut()
+            .filter_map(|s| Some((s.key()?, s)))
+            .reduce(|a, b| if b.0 < a.0 { b } else { a })?
+            .1;
+        let frame = source.head.take();
+        source.advance();
+        frame
+    }
+}
@@ -1,13 +1,183 @@
-use pyo3::prelude::*;
-use std::fmt::{Debug, Display};
+use pyo3::{
+    exceptions::{PyAttributeError, PyValueError},
+    prelude::*,
+};
+use std::{
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
 
-#[derive(Clone, Debug, FromPyObject)]
+use crate::{CanId, Context, Filter};
+
+#[derive(Clone, Debug)]
 pub struct PyCanMessage {
-    pub arbitration_id: u32,
+    pub arbitration_id: CanId,
     pub data: Option<Vec<u8>>,
     pub dlc: Option<u8>,
     pub is_error_frame: bool,
     pub timestamp: Option<f64>,
+    /// `timestamp` rebased onto the local clock, if drift correction is
+    /// enabled on the interface.
+    pub corrected_timestamp: Option<f64>,
+    pub is_remote_frame: bool,
+    pub is_fd: bool,
+    pub bitrate_switch: bool,
+    pub error_state_indicator: bool,
+    pub is_rx: bool,
+    /// Name of the interface that delivered this message, if any.
+    pub iface_name: Option<Arc<str>>,
+    /// Annotations added after the frame was received, e.g. by decoders.
+    /// Not part of the frame: ignored by comparisons.
+    pub context: Context,
+}
+
+/// Payload lengths for each CAN FD DLC code.
+const FD_LENGTHS: [usize; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64];
+
+/// Payload length in bytes for a CAN FD DLC code. Codes above 15 are
+/// treated as 15.
+pub fn dlc_to_len(dlc: u8) -> usize {
+    FD_LENGTHS[usize::from(dlc.min(15))]
+}
+
+/// Smallest DLC code whose payload fits `len` bytes, or None if `len`
+/// exceeds 64.
+pub fn len_to_dlc(len: usize) -> Option<u8> {
+    FD_LENGTHS
+        .iter()
+        .position(|&l| l >= len)
+        .map(|code| code as u8)
+}
+
+impl PyCanMessage {
+    /// A data frame with no timestamp. Payloads over 8 bytes make it a
+    /// CAN FD frame.
+    pub fn new(id: CanId, data: &[u8]) -> Self {
+        let is_fd = data.len() > 8;
+        Self {
+            arbitration_id: id,
+            data: Some(data.to_vec()),
+            dlc: len_to_dlc(data.len()),
+            is_error_frame: false,
+            timestamp: None,
+            corrected_timestamp: None,
+            is_remote_frame: false,
+            is_fd,
+            bitrate_switch: false,
+            error_state_indicator: false,
+            is_rx: true,
+            iface_name: None,
+            context: Context::default(),
+        }
+    }
+
+    /// Payload length in bytes, as implied by the DLC.
+    /// Classic CAN frames carry at most 8 bytes regardless of DLC.
+    pub fn data_length(&self) -> usize {
+        match self.dlc {
+            Some(dlc) if self.is_fd => dlc_to_len(dlc),
+            Some(dlc) => usize::from(dlc.min(8)),
+            None => self.data.as_ref().map_or(0, Vec::len),
+        }
+    }
+
+    pub fn matches(&self, filter: &Filter) -> bool {
+        filter.matches(self.arbitration_id)
+    }
+
+    /// The fields compared by `PartialEq` and `Hash`: what went on the wire,
+    /// but not when or where it was received.
+    fn key(&self) -> (CanId, Option<u8>, &Option<Vec<u8>>, [bool; 5]) {
+        (
+            self.arbitration_id,
+            self.dlc,
+            &self.data,
+            [
+                self.is_error_frame,
+                self.is_remote_frame,
+                self.is_fd,
+                self.bitrate_switch,
+                self.error_state_indicator,
+            ],
+        )
+    }
+}
+
+/// Frames compare by ID, DLC, data and frame flags. Timestamps, direction
+/// (`is_rx`), the interface name and context are ignored.
+impl PartialEq for PyCanMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for PyCanMessage {}
+
+impl Hash for PyCanMessage {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+/// Extract the first of `names` present on `obj`, or `default` if none are.
+/// python-can has added and renamed Message attributes across releases,
+/// so we accept every spelling we know about.
+fn attr_or<'a, T: FromPyObject<'a>>(obj: &'a PyAny, names: &[&str], default: T) -> PyResult<T> {
+    for name in names {
+        if obj.hasattr(*name)? {
+            let attr = obj.getattr(*name)?;
+            if attr.is_none() {
+                return Ok(default);
+            }
+            return attr.extract();
+        }
+    }
+
+    Ok(default)
+}
+
+impl<'source> FromPyObject<'source> for PyCanMessage {
+    fn extract(obj: &'source PyAny) -> PyResult<Self> {
+        if !obj.hasattr("arbitration_id")? {
+            return Err(PyAttributeError::new_err(
+                "python-can Message has no arbitration_id",
+            ));
+        }
+
+        // python-can reports `dlc` as a byte count; we keep the raw code.
+        let is_fd = attr_or(obj, &["is_fd"], false)?;
+        let len: Option<usize> = attr_or(obj, &["dlc"], None)?;
+        let dlc = len.map(|len| {
+            if is_fd {
+                len_to_dlc(len).unwrap_or(15)
+            } else {
+                len.min(15) as u8
+            }
+        });
+
+        // 2.x: `id_type`/`extended_id`, 3.x+: `is_extended_id`
+        let extended = attr_or(obj, &["is_extended_id", "extended_id", "id_type"], false)?;
+        let arbitration_id = CanId::new(obj.getattr("arbitration_id")?.extract()?, extended)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Ok(Self {
+            arbitration_id,
+            data: attr_or(obj, &["data"], None)?,
+            dlc,
+            is_error_frame: attr_or(obj, &["is_error_frame"], false)?,
+            timestamp: attr_or(obj, &["timestamp"], None)?,
+            corrected_timestamp: None,
+            is_remote_frame: attr_or(obj, &["is_remote_frame"], false)?,
+            is_fd,
+            bitrate_switch: attr_or(obj, &["bitrate_switch"], false)?,
+            error_state_indicator: attr_or(obj, &["error_state_indicator"], false)?,
+            // Added in 4.x. Older releases only hand us received frames.
+            is_rx: attr_or(obj, &["is_rx"], true)?,
+            iface_name: None,
+            context: Context::default(),
+        })
+    }
 }
 
 fn option_to_str<T: Debug>(o: &Option<T>) -> String {
@@ -24,12 +194,17 @@ impl Display for PyCanMessage {
         let dlc = option_to_str(&self.dlc);
         let timestamp = option_to_str(&self.timestamp);
 
+        write!(f, "PyCanMessage")?;
+        if let Some(name) = &self.iface_name {
+            write!(f, "[{name}]")?;
+        }
+
         if self.is_error_frame {
-            write!(f, "PyCanMessage: @{timestamp} ERROR FRAME")
+            write!(f, ": @{timestamp} ERROR FRAME")
         } else {
             write!(
                 f,
-                "PyCanMessage: @{timestamp} | id=0x{:03X} | dlc={dlc} | data={data}",
+                ": @{timestamp} | id=0x{} | dlc={dlc} | data={data}",
                 self.arbitration_id
             )
         }
@@ -0,0 +1,99 @@
+//! Sending messages from the loaded database by name.
+
+use std::sync::Weak;
+
+use crate::{periodic::TaskInner, PeriodicTask, PyCanError, PyCanInterface, TxToken};
+
+/// Signal values for [`PyCanInterface::send_named`]:
+///
+/// ```ignore
+/// iface.send_named("BMS_Status", signals! { "soc" => 87.5, "state" => 2.0 })?;
+/// ```
+#[macro_export]
+macro_rules! signals {
+    ($($name:expr => $value:expr),* $(,)?) => {
+        &[$(($name, $value as f64)),*][..]
+    };
+}
+
+/// The payload last sent for a message, and its periodic task if any.
+#[derive(Default)]
+pub(crate) struct NamedTx {
+    data: Vec<u8>,
+    task: Weak<TaskInner>,
+}
+
+impl PyCanInterface {
+    /// Encode `signals` into the payload last sent for `message` and send
+    /// it. Signals not given keep their previous value, or zero on the
+    /// first send. If the message is also being sent periodically with
+    /// [`Self::send_named_periodic`], the task's payload is updated too.
+    pub fn send_named(
+        &self,
+        message: &str,
+        signals: &[(&str, f64)],
+    ) -> Result<TxToken, PyCanError> {
+        let (id, data, task) = self.encode_named(message, signals)?;
+        if let Some(task) = task.upgrade() {
+            pyo3::Python::with_gil(|py| task.modify_data(py, &data))?;
+        }
+        self.send_checked(id, &data)
+    }
+
+    /// Like [`Self::send_named`], but start sending the message every
+    /// cycle time given in the database instead. Later `send_named` calls
+    /// update the task's payload.
+    pub fn send_named_periodic(
+        &self,
+        message: &str,
+        signals: &[(&str, f64)],
+    ) -> Result<PeriodicTask, PyCanError> {
+        let cycle_time = self.named_message(message)?.cycle_time.ok_or_else(|| {
+            PyCanError::PeriodicTaskFailed(format!("`{message}` has no cycle time"))
+        })?;
+        let (id, data, _) = self.encode_named(message, signals)?;
+
+        let task = self.send_periodic(id, &data, cycle_time)?;
+        if let Some(named) = self.named_tx.lock().unwrap().get_mut(&id) {
+            named.task = task.downgrade();
+        }
+        Ok(task)
+    }
+
+    fn named_message(&self, name: &str) -> Result<&crate::MessageDef, PyCanError> {
+        self.decoder
+            .iter()
+            .flat_map(|d| d.messages())
+            .find(|m| m.name == name)
+            .ok_or_else(|| PyCanError::UnknownMessage(name.into()))
+    }
+
+    /// Update and return the stored payload for `message`.
+    fn encode_named(
+        &self,
+        message: &str,
+        signals: &[(&str, f64)],
+    ) -> Result<(crate::CanId, Vec<u8>, Weak<TaskInner>), PyCanError> {
+        let def = self.named_message(message)?;
+
+        let mut named_tx = self.named_tx.lock().unwrap();
+        let named = named_tx.entry(def.id).or_default();
+        // Start from zeros, or resize if the database was changed
+        named.data.resize(def.length, 0);
+
+        let mut data = named.data.clone();
+        for (name, value) in signals {
+            let signal = def
+                .signals
+                .iter()
+                .find(|s| s.name == *name)
+                .ok_or_else(|| PyCanError::UnknownSignal(format!("{message}.{name}")))?;
+            signal
+                .encode(*value, &mut data)
+                .ok_or_else(|| PyCanError::InvalidSignal(format!("{message}.{name}")))?;
+        }
+        named.data.clone_from(&data);
+
+        Ok((def.id, data, named.task.clone()))
+    }
+}
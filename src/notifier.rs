@@ -0,0 +1,112 @@
+//! Detecting a dead python-can Notifier.
+//!
+//! python-can's Notifier reads the bus on a thread. If the bus raises
+//! (e.g. a serial adapter is unplugged) the thread exits and frames
+//! silently stop arriving. A probe thread checks the reader threads and,
+//! once one has died, reports it to every listener's `on_error` as a
+//! [`NotifierDied`] exception and emits [`PyCanEvent::NotifierDied`].
+
+// pyo3's create_exception! expands to cfgs newer compilers don't know about
+#![allow(unexpected_cfgs)]
+
+use std::{sync::atomic::Ordering, time::Duration};
+
+use pyo3::{create_exception, exceptions::PyException, Py, PyAny, PyErr, PyResult, Python};
+
+use crate::{describe_py_err, PyCanEvent, PyCanInterface};
+
+create_exception!(
+    pycanrs,
+    NotifierDied,
+    PyException,
+    "The python-can Notifier stopped reading from the bus."
+);
+
+const PROBE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether every reader thread of `notifier` is still running. Readers
+/// that are file descriptors polled by an asyncio loop can't be observed
+/// and count as alive.
+pub(crate) fn notifier_alive(py: Python, notifier: &Py<PyAny>) -> bool {
+    let readers = || -> PyResult<bool> {
+        for reader in notifier.as_ref(py).getattr("_readers")?.iter()? {
+            let reader = reader?;
+            if reader.hasattr("is_alive")? && !reader.call_method0("is_alive")?.extract()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    };
+
+    readers().unwrap_or(true)
+}
+
+/// False once the notifier has been stopped.
+fn notifier_running(py: Python, notifier: &Py<PyAny>) -> bool {
+    notifier
+        .as_ref(py)
+        .getattr("_running")
+        .and_then(|running| running.extract())
+        .unwrap_or(true)
+}
+
+/// The exception raised by the notifier's reader, if python-can recorded it.
+fn notifier_exception(py: Python, notifier: &Py<PyAny>) -> Option<String> {
+    notifier
+        .as_ref(py)
+        .getattr("exception")
+        .ok()
+        .filter(|e| !e.is_none())
+        .map(|e| describe_py_err(&PyErr::from_value(e)))
+}
+
+impl PyCanInterface {
+    pub(crate) fn watch_notifier(&self) {
+        let notifier = self.notifier.clone();
+        let alive = self.alive.clone();
+        let events = self.events.clone();
+        let name = self.name.clone();
+
+        std::thread::spawn(move || loop {
+            std::thread::sleep(PROBE_INTERVAL);
+
+            // Checked under the GIL, since drop and `reopen` stop the
+            // notifier while holding it.
+            let (running, died) = Python::with_gil(|py| {
+                let running = alive.load(Ordering::Relaxed) && notifier_running(py, &notifier);
+                if !running || notifier_alive(py, &notifier) {
+                    return (running, None);
+                }
+
+                let reason = notifier_exception(py, &notifier)
+                    .unwrap_or_else(|| "reader thread exited".into());
+                let err = NotifierDied::new_err(format!("{name}: {reason}"));
+                report(py, &notifier, &err);
+                (running, Some(reason))
+            });
+
+            if let Some(reason) = died {
+                events.emit(PyCanEvent::NotifierDied(reason));
+                return;
+            }
+            if !running {
+                return;
+            }
+        });
+    }
+}
+
+/// Call `on_error` on every listener, including our own shims.
+fn report(py: Python, notifier: &Py<PyAny>, err: &PyErr) {
+    let Ok(listeners) = notifier.as_ref(py).getattr("listeners") else {
+        return;
+    };
+    let Ok(listeners) = listeners.iter() else {
+        return;
+    };
+
+    let exc = err.value(py);
+    for listener in listeners.flatten() {
+        let _ = listener.call_method1("on_error", (exc,));
+    }
+}
@@ -0,0 +1,159 @@
+//! Parquet capture sink, via pyarrow.
+
+use pyo3::{
+    types::{IntoPyDict, PyBytes},
+    Py, PyAny, PyResult, Python, ToPyObject,
+};
+
+use crate::{describe_py_err, PyCanError, PyCanMessage};
+
+/// Buffered row of a capture.
+struct Row {
+    timestamp: Option<f64>,
+    id: u32,
+    is_extended_id: bool,
+    is_remote_frame: bool,
+    is_error_frame: bool,
+    is_fd: bool,
+    payload: Vec<u8>,
+}
+
+/// Writes frames to a Parquet file in record batches of `batch_size` rows.
+///
+/// Columns are `timestamp`, `arbitration_id`, `is_extended_id`,
+/// `is_remote_frame`, `is_error_frame`, `is_fd` and `payload`.
+/// Requires `pyarrow` to be installed. The file is finalized on drop.
+pub struct ParquetSink {
+    pa: Py<PyAny>,
+    schema: Py<PyAny>,
+    writer: Py<PyAny>,
+    rows: Vec<Row>,
+    batch_size: usize,
+}
+
+impl ParquetSink {
+    pub fn create(path: &str, batch_size: usize) -> Result<Self, PyCanError> {
+        Python::with_gil(|py| -> PyResult<_> {
+            let pa = py.import("pyarrow")?;
+            let pq = py.import("pyarrow.parquet")?;
+
+            let fields = [
+                ("timestamp", pa.call_method0("float64")?),
+                ("arbitration_id", pa.call_method0("uint32")?),
+                ("is_extended_id", pa.call_method0("bool_")?),
+                ("is_remote_frame", pa.call_method0("bool_")?),
+                ("is_error_frame", pa.call_method0("bool_")?),
+                ("is_fd", pa.call_method0("bool_")?),
+                ("payload", pa.call_method0("binary")?),
+            ];
+            let schema = pa.call_method1("schema", (fields.to_vec(),))?;
+            let writer = pq.call_method1("ParquetWriter", (path, schema))?;
+
+            Ok(Self {
+                pa: pa.into(),
+                schema: schema.into(),
+                writer: writer.into(),
+                rows: Vec::with_capacity(batch_size),
+                batch_size: batch_size.max(1),
+            })
+        })
+        .map_err(|e| PyCanError::CaptureFailed(describe_py_err(&e)))
+    }
+
+    /// Buffer a frame, writing out a batch once `batch_size` frames are held.
+    pub fn write(&mut self, msg: &PyCanMessage) -> Result<(), PyCanError> {
+        self.rows.push(Row {
+            timestamp: msg.timestamp,
+            id: msg.arbitration_id.raw(),
+            is_extended_id: msg.arbitration_id.is_extended(),
+            is_remote_frame: msg.is_remote_frame,
+            is_error_frame: msg.is_error_frame,
+            is_fd: msg.is_fd,
+            payload: msg.data.clone().unwrap_or_default(),
+        });
+
+        if self.rows.len() >= self.batch_size {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Write out any buffered frames as a (possibly short) batch.
+    pub fn flush(&mut self) -> Result<(), PyCanError> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+
+        let rows = std::mem::take(&mut self.rows);
+
+        Python::with_gil(|py| -> PyResult<()> {
+            let columns = [
+                (
+                    "timestamp",
+                    rows.iter()
+                        .map(|r| r.timestamp)
+                        .collect::<Vec<_>>()
+                        .to_object(py),
+                ),
+                (
+                    "arbitration_id",
+                    rows.iter().map(|r| r.id).collect::<Vec<_>>().to_object(py),
+                ),
+                (
+                    "is_extended_id",
+                    rows.iter()
+                        .map(|r| r.is_extended_id)
+                        .collect::<Vec<_>>()
+                        .to_object(py),
+                ),
+                (
+                    "is_remote_frame",
+                    rows.iter()
+                        .map(|r| r.is_remote_frame)
+                        .collect::<Vec<_>>()
+                        .to_object(py),
+                ),
+                (
+                    "is_error_frame",
+                    rows.iter()
+                        .map(|r| r.is_error_frame)
+                        .collect::<Vec<_>>()
+                        .to_object(py),
+                ),
+                (
+                    "is_fd",
+                    rows.iter()
+                        .map(|r| r.is_fd)
+                        .collect::<Vec<_>>()
+                        .to_object(py),
+                ),
+                (
+                    "payload",
+                    rows.iter()
+                        .map(|r| PyBytes::new(py, &r.payload))
+                        .collect::<Vec<_>>()
+                        .to_object(py),
+                ),
+            ]
+            .into_py_dict(py);
+
+            let kwargs = [("schema", &self.schema)].into_py_dict(py);
+            let table = self.pa.call_method(py, "table", (columns,), Some(kwargs))?;
+
+            self.writer.call_method1(py, "write_table", (table,))?;
+            Ok(())
+        })
+        .map_err(|e| PyCanError::CaptureFailed(describe_py_err(&e)))
+    }
+}
+
+impl Drop for ParquetSink {
+    fn drop(&mut self) {
+        // Nothing useful to do with errors while dropping
+        let _ = self.flush();
+        Python::with_gil(|py| {
+            let _ = self.writer.call_method0(py, "close");
+        });
+    }
+}
@@ -0,0 +1,520 @@
+//! Periodic transmission, backed by python-can's `send_periodic`.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+use pyo3::{types::IntoPyDict, Py, PyAny, PyResult, Python};
+
+use crate::{describe_py_err, make_message, CanId, PyCanError, PyCanEvent, PyCanInterface};
+
+/// A python-can cyclic send task. Stopped on drop.
+pub struct PeriodicTask {
+    inner: Arc<TaskInner>,
+}
+
+pub(crate) struct TaskInner {
+    /// Replaced when the interface is reopened on a new bus.
+    task: Mutex<Py<PyAny>>,
+    pycan: Py<PyAny>,
+    id: CanId,
+    /// Current payload, for recreating the task.
+    data: Mutex<Vec<u8>>,
+    period: Duration,
+    /// Whether we expect the task to be running, i.e. it hasn't been
+    /// deliberately stopped.
+    running: AtomicBool,
+    /// Bus to recreate the task on when it's next started, if it wasn't
+    /// running when the interface was reopened.
+    moved_to: Mutex<Option<Py<PyAny>>>,
+}
+
+/// How a supervised periodic task is restarted after it stops unexpectedly.
+#[derive(Clone, Debug)]
+pub struct RestartPolicy {
+    /// How often to check whether the task is still running.
+    pub check_interval: Duration,
+    /// Delay before the first restart attempt. Doubles on each
+    /// consecutive failure, up to `max_backoff`.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up after this many consecutive failed restarts.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_millis(100),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            max_attempts: None,
+        }
+    }
+}
+
+impl PyCanInterface {
+    /// Start sending `data` on `id` every `period`.
+    pub fn send_periodic(
+        &self,
+        id: CanId,
+        data: &[u8],
+        period: Duration,
+    ) -> Result<PeriodicTask, PyCanError> {
+        let task =
+            Python::with_gil(|py| start_task(py, &self.pycan, &self.iface, id, data, period))?;
+
+        let inner = Arc::new(TaskInner {
+            task: Mutex::new(task),
+            pycan: self.pycan.clone(),
+            id,
+            data: Mutex::new(data.to_vec()),
+            period,
+            running: AtomicBool::new(true),
+            moved_to: Mutex::new(None),
+        });
+        self.tasks.lock().unwrap().push(Arc::downgrade(&inner));
+
+        Ok(PeriodicTask { inner })
+    }
+
+    /// Move every live periodic task to `bus`. Called when the interface
+    /// is reopened.
+    ///
+    /// Running tasks are recreated on `bus`. Stopped tasks aren't sent,
+    /// and are recreated there when next started. The move is all or
+    /// nothing: if a running task can't be recreated, none of them are
+    /// left running, and each is recreated on `bus` when next started.
+    pub(crate) fn move_tasks(&self, py: Python, bus: &Py<PyAny>) -> Result<(), PyCanError> {
+        let tasks: Vec<_> = {
+            let mut tasks = self.tasks.lock().unwrap();
+            tasks.retain(|task| task.strong_count() > 0);
+            tasks.iter().filter_map(Weak::upgrade).collect()
+        };
+        let (running, stopped): (Vec<_>, Vec<_>) = tasks
+            .into_iter()
+            .partition(|task| task.running.load(Ordering::Relaxed));
+
+        let mut started = Vec::new();
+        for task in &running {
+            let data = task.data.lock().unwrap().clone();
+            match start_task(py, &task.pycan, bus, task.id, &data, task.period) {
+                Ok(new) => started.push(new),
+                Err(e) => {
+                    for new in started {
+                        let _ = new.call_method0(py, "stop");
+                    }
+                    for task in running.iter().chain(&stopped) {
+                        task.running.store(false, Ordering::Relaxed);
+                        *task.moved_to.lock().unwrap() = Some(bus.clone_ref(py));
+                    }
+                    return Err(PyCanError::PeriodicTaskFailed(format!(
+                        "no periodic tasks were moved to the new bus, as the task for {} \
+                         couldn't be restarted: {e}",
+                        task.id
+                    )));
+                }
+            }
+        }
+
+        for (task, new) in running.iter().zip(started) {
+            let old = std::mem::replace(&mut *task.task.lock().unwrap(), new);
+            let _ = old.call_method0(py, "stop");
+        }
+        for task in &stopped {
+            *task.moved_to.lock().unwrap() = Some(bus.clone_ref(py));
+        }
+        Ok(())
+    }
+
+    /// Watch `task` and restart it if it stops without being asked to,
+    /// e.g. because the backend hit a bus error. Stops and restarts are
+    /// reported through this interface's event hooks.
+    ///
+    /// Only python-can's thread-based tasks can be observed stopping;
+    /// tasks run natively by the backend are assumed to be alive.
+    /// Supervision ends when the task is dropped.
+    pub fn supervise_periodic(&self, task: &PeriodicTask, policy: RestartPolicy) {
+        let weak = Arc::downgrade(&task.inner);
+        let events = self.events.clone();
+
+        std::thread::spawn(move || {
+            let mut attempt = 0;
+            let mut backoff = policy.initial_backoff;
+
+            loop {
+                std::thread::sleep(policy.check_interval);
+
+                let Some(task) = weak.upgrade() else {
+                    return;
+                };
+
+                if !task.running.load(Ordering::Relaxed) || Python::with_gil(|py| task.is_alive(py))
+                {
+                    attempt = 0;
+                    backoff = policy.initial_backoff;
+                    continue;
+                }
+
+                if attempt == 0 {
+                    events.emit(PyCanEvent::PeriodicTaskStopped { id: task.id });
+                }
+
+                if policy.max_attempts.is_some_and(|max| attempt >= max) {
+                    continue;
+                }
+
+                // Don't hold the task alive while we wait
+                drop(task);
+                std::thread::sleep(backoff);
+                let Some(task) = weak.upgrade() else {
+                    return;
+                };
+
+                attempt += 1;
+                backoff = backoff.saturating_mul(2).min(policy.max_backoff);
+
+                // Stopping takes the GIL too, so a stop during the backoff
+                // is seen here. One that lands while Python runs `start`
+                // is caught by checking again afterwards.
+                let restarted = Python::with_gil(|py| {
+                    if !task.running.load(Ordering::Relaxed) {
+                        return None;
+                    }
+                    let res = task.start(py);
+                    if res.is_ok() && !task.running.load(Ordering::Relaxed) {
+                        let _ = task.call(py, "stop");
+                        return None;
+                    }
+                    Some(res)
+                });
+                match restarted {
+                    Some(Ok(())) => events.emit(PyCanEvent::PeriodicTaskRestarted {
+                        id: task.id,
+                        attempt,
+                    }),
+                    Some(Err(e)) => events.emit(PyCanEvent::PeriodicTaskRestartFailed {
+                        id: task.id,
+                        attempt,
+                        reason: e.to_string(),
+                    }),
+                    None => {}
+                }
+            }
+        });
+    }
+}
+
+fn start_task(
+    py: Python,
+    pycan: &Py<PyAny>,
+    bus: &Py<PyAny>,
+    id: CanId,
+    data: &[u8],
+    period: Duration,
+) -> Result<Py<PyAny>, PyCanError> {
+    (|| -> PyResult<_> {
+        let msg = make_message(py, pycan, id, data)?;
+        // python-can keeps its own reference to tasks by default;
+        // we own ours so it stops when dropped.
+        let kwargs = [("store_task", false)].into_py_dict(py);
+        bus.call_method(
+            py,
+            "send_periodic",
+            (msg, period.as_secs_f64()),
+            Some(kwargs),
+        )
+    })()
+    .map_err(|e| PyCanError::PeriodicTaskFailed(describe_py_err(&e)))
+}
+
+impl TaskInner {
+    /// The python-can task. Cloned out so the lock isn't held while
+    /// Python runs, which may hand the GIL to another thread.
+    fn handle(&self, py: Python) -> Py<PyAny> {
+        self.task.lock().unwrap().clone_ref(py)
+    }
+
+    fn call(&self, py: Python, method: &str) -> Result<(), PyCanError> {
+        self.handle(py)
+            .call_method0(py, method)
+            .map(|_| ())
+            .map_err(|e| PyCanError::PeriodicTaskFailed(describe_py_err(&e)))
+    }
+
+    fn start(&self, py: Python) -> Result<(), PyCanError> {
+        let Some(bus) = self.moved_to.lock().unwrap().take() else {
+            return self.call(py, "start");
+        };
+        let data = self.data.lock().unwrap().clone();
+        match start_task(py, &self.pycan, &bus, self.id, &data, self.period) {
+            Ok(new) => {
+                let old = std::mem::replace(&mut *self.task.lock().unwrap(), new);
+                let _ = old.call_method0(py, "stop");
+                Ok(())
+            }
+            Err(e) => {
+                *self.moved_to.lock().unwrap() = Some(bus);
+                Err(e)
+            }
+        }
+    }
+
+    pub(crate) fn modify_data(&self, py: Python, data: &[u8]) -> Result<(), PyCanError> {
+        let msg = self.message(py, data)?;
+        self.replace_message(py, &msg, data)
+    }
+
+    fn message(&self, py: Python, data: &[u8]) -> Result<Py<PyAny>, PyCanError> {
+        if data.len() > 8 {
+            return Err(PyCanError::PeriodicTaskFailed(format!(
+                "{} byte payload for {} is longer than 8 bytes",
+                data.len(),
+                self.id
+            )));
+        }
+        make_message(py, &self.pycan, self.id, data)
+            .map_err(|e| PyCanError::PeriodicTaskFailed(describe_py_err(&e)))
+    }
+
+    /// Swap in `msg`, built from `data` by [`Self::message`].
+    fn replace_message(&self, py: Python, msg: &Py<PyAny>, data: &[u8]) -> Result<(), PyCanError> {
+        // A task waiting to be moved is recreated with the stored payload
+        if self.moved_to.lock().unwrap().is_none() {
+            self.handle(py)
+                .call_method1(py, "modify_data", (msg,))
+                .map_err(|e| PyCanError::PeriodicTaskFailed(describe_py_err(&e)))?;
+        }
+
+        *self.data.lock().unwrap() = data.to_vec();
+        Ok(())
+    }
+
+    /// The lock python-can's thread-based tasks hold while sending. Tasks
+    /// run by the backend don't have one.
+    fn send_lock<'py>(&self, py: Python<'py>) -> Option<&'py PyAny> {
+        self.handle(py)
+            .into_ref(py)
+            .getattr("send_lock")
+            .ok()
+            .filter(|lock| !lock.is_none())
+    }
+
+    /// Whether python-can's send thread is still running. Tasks without a
+    /// thread are run by the backend and can't be observed.
+    fn is_alive(&self, py: Python) -> bool {
+        let task = self.handle(py);
+        match task.as_ref(py).getattr("thread") {
+            Ok(thread) if !thread.is_none() => thread
+                .call_method0("is_alive")
+                .and_then(|alive| alive.extract())
+                .unwrap_or(true),
+            _ => true,
+        }
+    }
+}
+
+impl Drop for TaskInner {
+    fn drop(&mut self) {
+        let _ = Python::with_gil(|py| self.call(py, "stop"));
+    }
+}
+
+impl PeriodicTask {
+    pub(crate) fn downgrade(&self) -> Weak<TaskInner> {
+        Arc::downgrade(&self.inner)
+    }
+
+    pub fn id(&self) -> CanId {
+        self.inner.id
+    }
+
+    pub fn period(&self) -> Duration {
+        self.inner.period
+    }
+
+    pub fn start(&self) -> Result<(), PyCanError> {
+        Python::with_gil(|py| self.start_with_gil(py))
+    }
+
+    pub fn stop(&self) -> Result<(), PyCanError> {
+        Python::with_gil(|py| self.stop_with_gil(py))
+    }
+
+    /// Replace the payload sent on future cycles.
+    pub fn modify_data(&self, data: &[u8]) -> Result<(), PyCanError> {
+        Python::with_gil(|py| self.modify_data_with_gil(py, data))
+    }
+
+    fn start_with_gil(&self, py: Python) -> Result<(), PyCanError> {
+        self.inner.start(py)?;
+        self.inner.running.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn stop_with_gil(&self, py: Python) -> Result<(), PyCanError> {
+        self.inner.running.store(false, Ordering::Relaxed);
+        self.inner.call(py, "stop")
+    }
+
+    fn modify_data_with_gil(&self, py: Python, data: &[u8]) -> Result<(), PyCanError> {
+        self.inner.modify_data(py, data)
+    }
+}
+
+/// A named set of periodic tasks, e.g. an ECU's TX table.
+///
+/// Tasks can be started and stopped together or individually.
+#[derive(Default)]
+pub struct TaskGroup {
+    tasks: BTreeMap<String, (PeriodicTask, bool)>,
+}
+
+impl TaskGroup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a task and start it. Replaces (and stops) any task already
+    /// registered under `name`.
+    pub fn add(
+        &mut self,
+        iface: &PyCanInterface,
+        name: &str,
+        id: CanId,
+        data: &[u8],
+        period: Duration,
+    ) -> Result<(), PyCanError> {
+        let task = iface.send_periodic(id, data, period)?;
+        self.tasks.insert(name.into(), (task, true));
+        Ok(())
+    }
+
+    /// Stop and remove a task.
+    pub fn remove(&mut self, name: &str) -> Option<PeriodicTask> {
+        self.tasks.remove(name).map(|(task, _)| task)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PeriodicTask> {
+        self.tasks.get(name).map(|(task, _)| task)
+    }
+
+    fn task(&self, name: &str) -> Result<&PeriodicTask, PyCanError> {
+        self.get(name)
+            .ok_or_else(|| PyCanError::PeriodicTaskFailed(format!("no task named `{name}`")))
+    }
+
+    /// Start every enabled task.
+    pub fn start_all(&self) -> Result<(), PyCanError> {
+        Python::with_gil(|py| {
+            self.tasks
+                .values()
+                .filter(|(_, enabled)| *enabled)
+                .try_for_each(|(task, _)| task.start_with_gil(py))
+        })
+    }
+
+    pub fn stop_all(&self) -> Result<(), PyCanError> {
+        Python::with_gil(|py| {
+            self.tasks
+                .values()
+                .try_for_each(|(task, _)| task.stop_with_gil(py))
+        })
+    }
+
+    /// Enable or disable a task. Disabled tasks are stopped and are skipped
+    /// by `start_all`.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), PyCanError> {
+        self.task(name)?;
+        let (task, state) = self.tasks.get_mut(name).unwrap();
+        if enabled {
+            task.start()?;
+        } else {
+            task.stop()?;
+        }
+        *state = enabled;
+        Ok(())
+    }
+
+    /// Update the payloads of several tasks at once. Every name and
+    /// payload is checked before anything changes, and python-can's send
+    /// threads are held off while the new payloads are swapped in, so no
+    /// cycle goes out with only some of them applied. If a task rejects
+    /// its update, the ones already applied are rolled back.
+    ///
+    /// Tasks run by the backend (e.g. SocketCAN's broadcast manager)
+    /// can't be held off, and may still send a mix for one cycle.
+    pub fn update(&self, payloads: &[(&str, &[u8])]) -> Result<(), PyCanError> {
+        Python::with_gil(|py| {
+            let updates = payloads
+                .iter()
+                .map(|(name, data)| {
+                    let task = &self.task(name)?.inner;
+                    Ok((task, task.message(py, data)?, *data))
+                })
+                .collect::<Result<Vec<_>, PyCanError>>()?;
+
+            let _locks = SendLocks::acquire(py, updates.iter().map(|(task, ..)| *task))?;
+
+            let mut applied: Vec<(&Arc<TaskInner>, Vec<u8>)> = Vec::new();
+            for (task, msg, data) in &updates {
+                let old = task.data.lock().unwrap().clone();
+                if let Err(e) = task.replace_message(py, msg, data) {
+                    for (task, old) in applied.into_iter().rev() {
+                        let _ = task.modify_data(py, &old);
+                    }
+                    return Err(e);
+                }
+                applied.push((*task, old));
+            }
+            Ok(())
+        })
+    }
+
+    /// Supervise every task currently in the group.
+    /// See [`PyCanInterface::supervise_periodic`].
+    pub fn supervise(&self, iface: &PyCanInterface, policy: RestartPolicy) {
+        for (task, _) in self.tasks.values() {
+            iface.supervise_periodic(task, policy.clone());
+        }
+    }
+}
+
+/// python-can send locks held across a group update, released on drop.
+/// Tasks on one bus share a lock, so each is taken once, and in address
+/// order so concurrent updates can't deadlock.
+struct SendLocks<'py>(Vec<&'py PyAny>);
+
+impl<'py> SendLocks<'py> {
+    fn acquire<'a>(
+        py: Python<'py>,
+        tasks: impl Iterator<Item = &'a Arc<TaskInner>>,
+    ) -> Result<Self, PyCanError> {
+        let mut locks: Vec<_> = tasks.filter_map(|task| task.send_lock(py)).collect();
+        locks.sort_by_key(|lock| *lock as *const PyAny as usize);
+        locks.dedup_by_key(|lock| *lock as *const PyAny as usize);
+
+        let mut held = Self(Vec::with_capacity(locks.len()));
+        for lock in locks {
+            // Blocking in acquire() releases the GIL, so a task thread
+            // holding the lock can finish its send
+            lock.call_method0("acquire")
+                .map_err(|e| PyCanError::PeriodicTaskFailed(describe_py_err(&e)))?;
+            held.0.push(lock);
+        }
+        Ok(held)
+    }
+}
+
+impl Drop for SendLocks<'_> {
+    fn drop(&mut self) {
+        for lock in self.0.iter().rev() {
+            let _ = lock.call_method0("release");
+        }
+    }
+}
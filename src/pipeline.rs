@@ -0,0 +1,485 @@
+//! Pull-based frame sources and sinks, for snapping captures, interfaces
+//! and loggers together.
+//!
+//! ```ignore
+//! // Replay a capture onto a bus in real time, keeping only 0x100-0x1ff,
+//! // and log what was sent
+//! TrcReader::open("drive.trc")?
+//!     .paced()
+//!     .filter(|msg| (0x100..0x200).contains(&msg.arbitration_id.raw()))
+//!     .tee(CsvWriter::create("sent.csv")?)
+//!     .pump(bus.clone())?;
+//! ```
+
+use std::{
+    sync::{mpsc, Arc},
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+use crate::{
+    BroadcastError, CanBus, CsvReader, CsvWriter, ListenerId, LogError, LogMerger, NdjsonWriter,
+    PyCanError, PyCanInterface, PyCanMessage, Recorder, ReplayEdit, ReplaySource, ReplayTrigger,
+    Subscriber, TextReader, TrcReader, TrcWriter, TriggeredSource,
+};
+
+#[derive(Debug, Error)]
+pub enum PipeError {
+    #[error(transparent)]
+    Interface(#[from] PyCanError),
+    #[error(transparent)]
+    Log(#[from] LogError),
+    #[error("Pipeline stage disconnected")]
+    Disconnected,
+    #[error("Invalid pipeline graph :: `{0}`")]
+    InvalidGraph(String),
+    #[error("Invalid configuration :: `{0}`")]
+    InvalidConfig(String),
+    #[error("Frame timestamp can't be paced :: `{0}`")]
+    InvalidTimestamp(f64),
+    /// No frame arrived within the timeout given to
+    /// [`FrameSource::next_frame_timeout`].
+    #[error("Timed out waiting for a frame")]
+    Timeout,
+    #[cfg(feature = "scripting")]
+    #[error(transparent)]
+    Script(#[from] crate::ScriptError),
+}
+
+/// Produces frames, e.g. a capture being read or an interface's receive
+/// queue.
+pub trait FrameSource: Send {
+    /// The next frame, blocking if need be. None once the source is
+    /// exhausted.
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>>;
+
+    /// Like [`Self::next_frame`], but gives up after about `timeout` with
+    /// [`PipeError::Timeout`]. Sources that may block indefinitely, like
+    /// an interface, implement this so their reader can be stopped; for
+    /// the rest it just calls `next_frame`.
+    fn next_frame_timeout(
+        &mut self,
+        _timeout: Duration,
+    ) -> Option<Result<PyCanMessage, PipeError>> {
+        self.next_frame()
+    }
+}
+
+/// What's left of `timeout`, which started when `deadline` was taken.
+fn remaining(deadline: Option<Instant>, timeout: Duration) -> Duration {
+    deadline.map_or(timeout, |d| d.saturating_duration_since(Instant::now()))
+}
+
+/// Consumes frames, e.g. a log writer or an interface to send on.
+pub trait FrameSink: Send {
+    fn write_frame(&mut self, msg: &PyCanMessage) -> Result<(), PipeError>;
+
+    fn flush_frames(&mut self) -> Result<(), PipeError> {
+        Ok(())
+    }
+}
+
+impl<S: FrameSource + ?Sized> FrameSource for Box<S> {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        (**self).next_frame()
+    }
+
+    fn next_frame_timeout(&mut self, timeout: Duration) -> Option<Result<PyCanMessage, PipeError>> {
+        (**self).next_frame_timeout(timeout)
+    }
+}
+
+impl<S: FrameSink + ?Sized> FrameSink for Box<S> {
+    fn write_frame(&mut self, msg: &PyCanMessage) -> Result<(), PipeError> {
+        (**self).write_frame(msg)
+    }
+
+    fn flush_frames(&mut self) -> Result<(), PipeError> {
+        (**self).flush_frames()
+    }
+}
+
+impl<S: FrameSink + ?Sized> FrameSink for &mut S {
+    fn write_frame(&mut self, msg: &PyCanMessage) -> Result<(), PipeError> {
+        (**self).write_frame(msg)
+    }
+
+    fn flush_frames(&mut self) -> Result<(), PipeError> {
+        (**self).flush_frames()
+    }
+}
+
+/// Combinators on every [`FrameSource`].
+pub trait FrameSourceExt: FrameSource + Sized {
+    /// Keep only frames for which `keep` returns true.
+    fn filter<F>(self, keep: F) -> FilterSource<Self, F>
+    where
+        F: FnMut(&PyCanMessage) -> bool + Send,
+    {
+        FilterSource { source: self, keep }
+    }
+
+    /// Rewrite each frame, or drop it by returning None.
+    fn map<F>(self, f: F) -> MapSource<Self, F>
+    where
+        F: FnMut(PyCanMessage) -> Option<PyCanMessage> + Send,
+    {
+        MapSource { source: self, f }
+    }
+
+    /// Also write every frame to `sink` on the way through.
+    fn tee<K: FrameSink>(self, sink: K) -> TeeSource<Self, K> {
+        TeeSource { source: self, sink }
+    }
+
+    /// Hand frames out no faster than their timestamps say they were
+    /// captured, e.g. to replay a log onto a bus.
+    fn paced(self) -> PacedSource<Self> {
+        PacedSource {
+            source: self,
+            start: None,
+        }
+    }
+
+    /// Select, slice and edit frames as `edit` says, e.g. before
+    /// [`paced`](Self::paced) to replay part of a capture.
+    fn replay(self, edit: ReplayEdit) -> ReplaySource<Self> {
+        ReplaySource::new(self, edit)
+    }
+
+    /// Hold the first frame back until `trigger` fires. Put it before
+    /// [`paced`](Self::paced) so pacing starts from the trigger.
+    fn start_on(self, trigger: ReplayTrigger) -> TriggeredSource<Self> {
+        TriggeredSource::new(self, trigger)
+    }
+
+    /// Copy every frame into `sink` until the source is exhausted, then
+    /// flush it. Returns the number of frames copied. Stops at the first
+    /// error.
+    fn pump<K: FrameSink>(mut self, mut sink: K) -> Result<u64, PipeError> {
+        let mut count = 0;
+        while let Some(msg) = self.next_frame() {
+            sink.write_frame(&msg?)?;
+            count += 1;
+        }
+        sink.flush_frames()?;
+        Ok(count)
+    }
+}
+
+impl<S: FrameSource> FrameSourceExt for S {}
+
+pub struct FilterSource<S, F> {
+    source: S,
+    keep: F,
+}
+
+impl<S, F> FrameSource for FilterSource<S, F>
+where
+    S: FrameSource,
+    F: FnMut(&PyCanMessage) -> bool + Send,
+{
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        loop {
+            match self.source.next_frame()? {
+                Ok(msg) if !(self.keep)(&msg) => continue,
+                frame => return Some(frame),
+            }
+        }
+    }
+
+    fn next_frame_timeout(&mut self, timeout: Duration) -> Option<Result<PyCanMessage, PipeError>> {
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            match self
+                .source
+                .next_frame_timeout(remaining(deadline, timeout))?
+            {
+                Ok(msg) if !(self.keep)(&msg) => {
+                    if remaining(deadline, timeout).is_zero() {
+                        return Some(Err(PipeError::Timeout));
+                    }
+                }
+                frame => return Some(frame),
+            }
+        }
+    }
+}
+
+pub struct MapSource<S, F> {
+    source: S,
+    f: F,
+}
+
+impl<S, F> FrameSource for MapSource<S, F>
+where
+    S: FrameSource,
+    F: FnMut(PyCanMessage) -> Option<PyCanMessage> + Send,
+{
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        loop {
+            match self.source.next_frame()? {
+                Ok(msg) => match (self.f)(msg) {
+                    Some(msg) => return Some(Ok(msg)),
+                    None => continue,
+                },
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    fn next_frame_timeout(&mut self, timeout: Duration) -> Option<Result<PyCanMessage, PipeError>> {
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            match self
+                .source
+                .next_frame_timeout(remaining(deadline, timeout))?
+            {
+                Ok(msg) => match (self.f)(msg) {
+                    Some(msg) => return Some(Ok(msg)),
+                    None if remaining(deadline, timeout).is_zero() => {
+                        return Some(Err(PipeError::Timeout))
+                    }
+                    None => continue,
+                },
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+pub struct TeeSource<S, K> {
+    source: S,
+    sink: K,
+}
+
+impl<S: FrameSource, K: FrameSink> FrameSource for TeeSource<S, K> {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        let frame = self.source.next_frame();
+        self.write(frame)
+    }
+
+    fn next_frame_timeout(&mut self, timeout: Duration) -> Option<Result<PyCanMessage, PipeError>> {
+        let frame = self.source.next_frame_timeout(timeout);
+        self.write(frame)
+    }
+}
+
+impl<S, K: FrameSink> TeeSource<S, K> {
+    fn write(
+        &mut self,
+        frame: Option<Result<PyCanMessage, PipeError>>,
+    ) -> Option<Result<PyCanMessage, PipeError>> {
+        // The sink is flushed once the source runs dry
+        let Some(frame) = frame else {
+            return self.sink.flush_frames().err().map(Err);
+        };
+        Some(frame.and_then(|msg| self.sink.write_frame(&msg).map(|_| msg)))
+    }
+}
+
+pub struct PacedSource<S> {
+    source: S,
+    /// Wall clock and capture time of the first frame.
+    start: Option<(Instant, f64)>,
+}
+
+impl<S: FrameSource> FrameSource for PacedSource<S> {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        let frame = self.source.next_frame()?;
+        if let Ok(Some(timestamp)) = frame.as_ref().map(|msg| msg.timestamp) {
+            if !timestamp.is_finite() {
+                return Some(Err(PipeError::InvalidTimestamp(timestamp)));
+            }
+            let (wall, first) = *self.start.get_or_insert((Instant::now(), timestamp));
+            let due = Duration::try_from_secs_f64((timestamp - first).max(0.0))
+                .ok()
+                .and_then(|offset| wall.checked_add(offset));
+            let Some(due) = due else {
+                return Some(Err(PipeError::InvalidTimestamp(timestamp)));
+            };
+            if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                std::thread::sleep(wait);
+            }
+        }
+        Some(frame)
+    }
+}
+
+/// Frames received on an interface, from
+/// [`PyCanInterface::frame_source`]. Stops receiving on drop.
+pub struct InterfaceSource {
+    iface: Arc<PyCanInterface>,
+    listener: ListenerId,
+    rx: mpsc::Receiver<PyCanMessage>,
+}
+
+impl PyCanInterface {
+    /// Receive frames as a [`FrameSource`], including error frames. The
+    /// source never ends while the interface is open.
+    pub fn frame_source(self: &Arc<Self>) -> Result<InterfaceSource, PyCanError> {
+        let (tx, rx) = mpsc::channel();
+        let listener = self.register_rx_callback_all(
+            move |msg| {
+                let _ = tx.send(msg.clone());
+            },
+            |_| {},
+        )?;
+        Ok(InterfaceSource {
+            iface: self.clone(),
+            listener,
+            rx,
+        })
+    }
+}
+
+impl FrameSource for InterfaceSource {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        self.rx.next_frame()
+    }
+
+    fn next_frame_timeout(&mut self, timeout: Duration) -> Option<Result<PyCanMessage, PipeError>> {
+        self.rx.next_frame_timeout(timeout)
+    }
+}
+
+impl Drop for InterfaceSource {
+    fn drop(&mut self) {
+        let _ = self.iface.remove_listener(self.listener);
+    }
+}
+
+/// Sends each frame on the interface.
+impl FrameSink for Arc<PyCanInterface> {
+    fn write_frame(&mut self, msg: &PyCanMessage) -> Result<(), PipeError> {
+        Ok(self.send_frame(msg)?)
+    }
+}
+
+/// Frames missed by falling behind are skipped; see
+/// [`crate::Subscriber::missed`]. Receive errors are passed on. Ends when
+/// the subscriber is disconnected or the broadcast closed.
+impl FrameSource for Subscriber {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        loop {
+            match self.recv() {
+                Ok(msg) => return Some(Ok(msg)),
+                Err(BroadcastError::Lagged(_)) => continue,
+                Err(BroadcastError::ReceiveFailed(e)) => {
+                    return Some(Err(PyCanError::FailedToReceive(e).into()))
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+
+    fn next_frame_timeout(&mut self, timeout: Duration) -> Option<Result<PyCanMessage, PipeError>> {
+        loop {
+            match self.recv_timeout(timeout) {
+                Ok(msg) => return Some(Ok(msg)),
+                Err(BroadcastError::Lagged(_)) => continue,
+                Err(BroadcastError::Timeout) => return Some(Err(PipeError::Timeout)),
+                Err(BroadcastError::ReceiveFailed(e)) => {
+                    return Some(Err(PyCanError::FailedToReceive(e).into()))
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+impl FrameSource for mpsc::Receiver<PyCanMessage> {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        self.recv().ok().map(Ok)
+    }
+
+    fn next_frame_timeout(&mut self, timeout: Duration) -> Option<Result<PyCanMessage, PipeError>> {
+        match self.recv_timeout(timeout) {
+            Ok(msg) => Some(Ok(msg)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Some(Err(PipeError::Timeout)),
+            Err(mpsc::RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+impl FrameSink for mpsc::Sender<PyCanMessage> {
+    fn write_frame(&mut self, msg: &PyCanMessage) -> Result<(), PipeError> {
+        self.send(msg.clone()).map_err(|_| PipeError::Disconnected)
+    }
+}
+
+impl<R: std::io::BufRead + Send> FrameSource for TrcReader<R> {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        self.next().map(|frame| frame.map_err(PipeError::from))
+    }
+}
+
+impl<R: std::io::BufRead + Send> FrameSource for CsvReader<R> {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        self.next().map(|frame| frame.map_err(PipeError::from))
+    }
+}
+
+impl<R: std::io::BufRead + Send> FrameSource for TextReader<R> {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        self.next().map(|frame| frame.map_err(PipeError::from))
+    }
+}
+
+impl FrameSource for LogMerger {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        self.next().map(|frame| frame.map_err(PipeError::from))
+    }
+}
+
+impl<W: std::io::Write + Send> FrameSink for TrcWriter<W> {
+    fn write_frame(&mut self, msg: &PyCanMessage) -> Result<(), PipeError> {
+        Ok(self.write(msg)?)
+    }
+
+    fn flush_frames(&mut self) -> Result<(), PipeError> {
+        Ok(self.flush()?)
+    }
+}
+
+impl<W: std::io::Write + Send> FrameSink for CsvWriter<W> {
+    fn write_frame(&mut self, msg: &PyCanMessage) -> Result<(), PipeError> {
+        Ok(self.write(msg)?)
+    }
+
+    fn flush_frames(&mut self) -> Result<(), PipeError> {
+        Ok(self.flush()?)
+    }
+}
+
+impl<W: std::io::Write + Send> FrameSink for NdjsonWriter<W> {
+    fn write_frame(&mut self, msg: &PyCanMessage) -> Result<(), PipeError> {
+        Ok(self.write(msg)?)
+    }
+
+    fn flush_frames(&mut self) -> Result<(), PipeError> {
+        Ok(self.flush()?)
+    }
+}
+
+impl FrameSink for Recorder {
+    fn write_frame(&mut self, msg: &PyCanMessage) -> Result<(), PipeError> {
+        Ok(self.write(msg)?)
+    }
+
+    fn flush_frames(&mut self) -> Result<(), PipeError> {
+        Ok(self.flush()?)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl FrameSink for crate::ParquetSink {
+    fn write_frame(&mut self, msg: &PyCanMessage) -> Result<(), PipeError> {
+        Ok(self.write(msg)?)
+    }
+
+    fn flush_frames(&mut self) -> Result<(), PipeError> {
+        Ok(self.flush()?)
+    }
+}
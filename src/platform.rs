@@ -0,0 +1,76 @@
+//! Platform-specific handling of bus arguments, so unusable configurations
+//! fail with a clear error instead of a python-can traceback.
+
+use std::path::Path;
+
+use pyo3::{PyResult, Python};
+
+use crate::{PyCanBusType, PyCanError};
+
+/// Serial read timeout for slcan, in seconds. USB serial drivers on
+/// Windows batch reads for up to 16ms, so python-can's default of 1ms
+/// mostly times out there.
+#[cfg(windows)]
+pub(crate) const SLCAN_TIMEOUT: Option<f64> = Some(0.05);
+#[cfg(not(windows))]
+pub(crate) const SLCAN_TIMEOUT: Option<f64> = None;
+
+/// Fail early for bus types that can't work on this OS.
+pub(crate) fn check_supported(kind: &PyCanBusType) -> Result<(), PyCanError> {
+    let unsupported = |why: &str| Err(PyCanError::Unsupported(format!("{kind}: {why}")));
+
+    match kind {
+        PyCanBusType::Socketcan { .. } if !cfg!(target_os = "linux") => {
+            unsupported("SocketCAN is only available on Linux")
+        }
+        PyCanBusType::Vector { .. } if !cfg!(windows) => {
+            unsupported("the Vector XL driver is only available on Windows")
+        }
+        PyCanBusType::Slcan { serial_port, .. }
+            if cfg!(windows) && serial_port.starts_with("/dev/") =>
+        {
+            unsupported("serial ports are named COMn on Windows")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The slcan port as pyserial expects it. On Windows, `com3` becomes
+/// `COM3`; other names (including `\\.\COM12`) are passed through.
+pub(crate) fn slcan_port(port: &str) -> String {
+    let is_com = |p: &str| {
+        p.len() > 3
+            && p.get(..3).is_some_and(|s| s.eq_ignore_ascii_case("com"))
+            && p[3..].bytes().all(|b| b.is_ascii_digit())
+    };
+
+    if cfg!(windows) && is_com(port) {
+        port.to_ascii_uppercase()
+    } else {
+        port.into()
+    }
+}
+
+/// Whether the serial port exists. COM ports have no path on Windows, so
+/// ask pyserial there.
+pub(crate) fn serial_port_present(port: &str) -> bool {
+    if !cfg!(windows) {
+        return Path::new(port).exists();
+    }
+
+    let port = slcan_port(port);
+    Python::with_gil(|py| -> PyResult<bool> {
+        for info in py
+            .import("serial.tools.list_ports")?
+            .call_method0("comports")?
+            .iter()?
+        {
+            let device: String = info?.getattr("device")?.extract()?;
+            if port.ends_with(&device) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    })
+    .unwrap_or(false)
+}
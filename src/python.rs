@@ -0,0 +1,236 @@
+//! The crate's frame processing, as the contents of a Python extension
+//! module called `pycanrs`. The module itself is built by the crate in
+//! `python/`, which links it as an extension so this crate's binaries can
+//! still link libpython:
+//!
+//! ```text
+//! cargo build --release --manifest-path python/Cargo.toml
+//! ```
+//!
+//! and the resulting library renamed to `pycanrs.so` (`pycanrs.pyd` on
+//! Windows). Functions take python-can `Message`s, or anything with the
+//! same attributes:
+//!
+//! ```text
+//! import pycanrs
+//!
+//! wanted = pycanrs.Filter(0x100, 0x700)
+//! cycles = pycanrs.CycleTimes()
+//! for msg in wanted.filter(bus_messages):
+//!     cycles.record(msg)
+//! print(cycles.all())
+//! ```
+
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+    types::{PyDict, PyList},
+};
+
+use crate::{
+    cycle::CycleTimes, CanId, CycleStats, Filter, PyCanMessage, ReportConfig, SessionReport,
+    SessionReporter, SocketcanError,
+};
+
+fn can_id(raw: u32, extended: bool) -> PyResult<CanId> {
+    CanId::new(raw, extended).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// `can_filters` style ID filter.
+#[pyclass(name = "Filter")]
+struct PyFilter(Filter);
+
+#[pymethods]
+impl PyFilter {
+    #[new]
+    #[pyo3(signature = (can_id, can_mask = 0x1FFF_FFFF, extended = None))]
+    fn new(can_id: u32, can_mask: u32, extended: Option<bool>) -> Self {
+        Self(Filter {
+            can_id,
+            can_mask,
+            extended,
+        })
+    }
+
+    fn matches(&self, msg: PyCanMessage) -> bool {
+        msg.matches(&self.0)
+    }
+
+    /// The messages in `msgs` that match, as a list.
+    fn filter<'py>(&self, py: Python<'py>, msgs: &'py PyAny) -> PyResult<&'py PyList> {
+        let matched = PyList::empty(py);
+        for obj in msgs.iter()? {
+            let obj = obj?;
+            if obj.extract::<PyCanMessage>()?.matches(&self.0) {
+                matched.append(obj)?;
+            }
+        }
+        Ok(matched)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Filter(can_id=0x{:X}, can_mask=0x{:X}, extended={:?})",
+            self.0.can_id, self.0.can_mask, self.0.extended
+        )
+    }
+}
+
+/// Interval statistics for each ID recorded.
+#[pyclass(name = "CycleTimes")]
+#[derive(Default)]
+struct PyCycleTimes(CycleTimes);
+
+fn cycle_dict(py: Python, stats: &CycleStats) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("arbitration_id", stats.id.raw())?;
+    dict.set_item("is_extended_id", stats.id.is_extended())?;
+    dict.set_item("frames", stats.frames)?;
+    dict.set_item("min", stats.min.as_secs_f64())?;
+    dict.set_item("mean", stats.mean.as_secs_f64())?;
+    dict.set_item("max", stats.max.as_secs_f64())?;
+    dict.set_item("jitter", stats.jitter.as_secs_f64())?;
+    Ok(dict.into())
+}
+
+#[pymethods]
+impl PyCycleTimes {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a received message. Sent ones (`is_rx` false) are ignored.
+    fn record(&self, msg: PyCanMessage) {
+        self.0.record(&msg);
+    }
+
+    /// Stats for one ID as a dict of seconds, or None if it wasn't seen.
+    #[pyo3(signature = (arbitration_id, is_extended_id = false))]
+    fn stats(
+        &self,
+        py: Python,
+        arbitration_id: u32,
+        is_extended_id: bool,
+    ) -> PyResult<Option<Py<PyDict>>> {
+        let id = can_id(arbitration_id, is_extended_id)?;
+        self.0.get(id).map(|s| cycle_dict(py, &s)).transpose()
+    }
+
+    /// Stats for every ID, in ID order.
+    fn all(&self, py: Python) -> PyResult<Vec<Py<PyDict>>> {
+        self.0.all().iter().map(|s| cycle_dict(py, s)).collect()
+    }
+
+    fn reset(&self) {
+        self.0.clear();
+    }
+}
+
+/// Builds a session report from recorded messages.
+#[pyclass(name = "SessionReporter")]
+struct PySessionReporter(Option<SessionReporter>);
+
+impl PySessionReporter {
+    fn reporter(&mut self) -> PyResult<&mut SessionReporter> {
+        self.0
+            .as_mut()
+            .ok_or_else(|| PyRuntimeError::new_err("report already finished"))
+    }
+}
+
+#[pymethods]
+impl PySessionReporter {
+    #[new]
+    #[pyo3(signature = (bitrate = 500_000, bucket = 1.0, burst_gap = 0.1))]
+    fn new(bitrate: u32, bucket: f64, burst_gap: f64) -> PyResult<Self> {
+        let duration = |secs: f64| {
+            std::time::Duration::try_from_secs_f64(secs)
+                .map_err(|e| PyValueError::new_err(e.to_string()))
+        };
+        let reporter = SessionReporter::new(ReportConfig {
+            bitrate,
+            bucket: duration(bucket)?,
+            burst_gap: duration(burst_gap)?,
+        })
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self(Some(reporter)))
+    }
+
+    fn record(&mut self, msg: PyCanMessage) -> PyResult<()> {
+        self.reporter()?.record(&msg);
+        Ok(())
+    }
+
+    /// Record every message in `msgs`.
+    fn record_all(&mut self, msgs: &PyAny) -> PyResult<()> {
+        let reporter = self.reporter()?;
+        for msg in msgs.iter()? {
+            reporter.record(&msg?.extract()?);
+        }
+        Ok(())
+    }
+
+    /// The report. Nothing more can be recorded afterwards.
+    fn finish(&mut self) -> PyResult<PySessionReport> {
+        let reporter = self
+            .0
+            .take()
+            .ok_or_else(|| PyRuntimeError::new_err("report already finished"))?;
+        Ok(PySessionReport(reporter.finish()))
+    }
+}
+
+#[pyclass(name = "SessionReport")]
+struct PySessionReport(SessionReport);
+
+#[pymethods]
+impl PySessionReport {
+    #[getter]
+    fn frames(&self) -> u64 {
+        self.0.frames
+    }
+
+    #[getter]
+    fn error_frames(&self) -> u64 {
+        self.0.error_frames
+    }
+
+    /// Seconds between the first and last timestamped message.
+    #[getter]
+    fn duration(&self) -> f64 {
+        self.0.duration().as_secs_f64()
+    }
+
+    fn to_json(&self) -> String {
+        self.0.to_json()
+    }
+
+    fn ids_csv(&self) -> String {
+        self.0.ids_csv()
+    }
+
+    fn error_bursts_csv(&self) -> String {
+        self.0.error_bursts_csv()
+    }
+
+    fn load_csv(&self) -> String {
+        self.0.load_csv()
+    }
+}
+
+/// Describe a SocketCAN error frame, or None if `msg` isn't one.
+#[pyfunction]
+fn decode_error_frame(msg: PyCanMessage) -> Option<String> {
+    SocketcanError::decode(&msg).map(|e| e.to_string())
+}
+
+/// Add the module's classes and functions to `m`.
+pub fn init(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyFilter>()?;
+    m.add_class::<PyCycleTimes>()?;
+    m.add_class::<PySessionReporter>()?;
+    m.add_class::<PySessionReport>()?;
+    m.add_function(wrap_pyfunction!(decode_error_frame, m)?)?;
+    Ok(())
+}
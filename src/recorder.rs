@@ -0,0 +1,299 @@
+//! Long-running capture to rotated candump log files.
+//!
+//! Frames are written to a segment file until it reaches a size or age
+//! limit, then a new segment is started. Finished segments are compressed
+//! in the background, and the oldest segments are deleted whenever the
+//! recording's total size exceeds its disk budget.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use pyo3::{PyResult, Python};
+
+use crate::{PyCanError, PyCanMessage};
+
+/// How finished segments are compressed. Compression runs through
+/// Python: `gzip` is in the standard library, and zstd needs Python 3.14
+/// (`compression.zstd`) or the `zstandard` package.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Gzip => ".gz",
+            Self::Zstd => ".zst",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RecorderConfig {
+    /// Segment file names are `<prefix>-<unix time in ms>-<sequence>.log`.
+    pub prefix: String,
+    /// Start a new segment once the current one is this big.
+    pub max_segment_bytes: Option<u64>,
+    /// Start a new segment once the current one is this old.
+    pub max_segment_age: Option<Duration>,
+    pub compression: Compression,
+    /// Delete the oldest segments while the recording takes more than
+    /// this many bytes. The segment being written is never deleted.
+    pub disk_budget: Option<u64>,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            prefix: "can".into(),
+            max_segment_bytes: Some(64 * 1024 * 1024),
+            max_segment_age: Some(Duration::from_secs(3600)),
+            compression: Compression::None,
+            disk_budget: None,
+        }
+    }
+}
+
+struct Segment {
+    path: PathBuf,
+    file: BufWriter<File>,
+    bytes: u64,
+    opened: Instant,
+}
+
+/// Writes frames in candump log format (`(timestamp) iface ID#DATA`) to
+/// rotated segment files in a directory. The last segment is closed and
+/// compressed on drop.
+pub struct Recorder {
+    dir: PathBuf,
+    config: RecorderConfig,
+    segment: Option<Segment>,
+    sequence: u64,
+    /// Sent to the housekeeping thread on each rotation, with the finished
+    /// segment if it needs compressing. The thread compresses segments
+    /// and enforces the disk budget off the write path.
+    jobs: Option<Sender<Option<PathBuf>>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+fn capture_error(e: impl ToString) -> PyCanError {
+    PyCanError::CaptureFailed(e.to_string())
+}
+
+impl Recorder {
+    /// Start recording into `dir`, creating it if needed.
+    pub fn create(dir: impl AsRef<Path>, config: RecorderConfig) -> Result<Self, PyCanError> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir).map_err(capture_error)?;
+
+        let (jobs, rx) = mpsc::channel::<Option<PathBuf>>();
+        let worker = {
+            let (dir, config) = (dir.clone(), config.clone());
+            std::thread::spawn(move || {
+                for finished in rx {
+                    if let Some(path) = finished {
+                        // A segment that fails to compress is kept as it is
+                        if compress(&path, config.compression).is_ok() {
+                            let _ = std::fs::remove_file(&path);
+                        }
+                    }
+                    enforce_budget(&dir, &config);
+                }
+            })
+        };
+
+        let mut recorder = Self {
+            dir,
+            config,
+            segment: None,
+            sequence: 0,
+            jobs: Some(jobs),
+            worker: Some(worker),
+        };
+        recorder.rotate()?;
+        Ok(recorder)
+    }
+
+    /// The segment currently being written.
+    pub fn current_segment(&self) -> Option<&Path> {
+        self.segment.as_ref().map(|s| s.path.as_path())
+    }
+
+    pub fn write(&mut self, msg: &PyCanMessage) -> Result<(), PyCanError> {
+        let due = self.segment.as_ref().is_none_or(|s| {
+            self.config
+                .max_segment_bytes
+                .is_some_and(|max| s.bytes >= max)
+                || self
+                    .config
+                    .max_segment_age
+                    .is_some_and(|max| s.opened.elapsed() >= max)
+        });
+        if due {
+            self.rotate()?;
+        }
+
+        let line = candump_line(msg);
+        let segment = self.segment.as_mut().unwrap();
+        segment
+            .file
+            .write_all(line.as_bytes())
+            .map_err(capture_error)?;
+        segment.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), PyCanError> {
+        match &mut self.segment {
+            Some(segment) => segment.file.flush().map_err(capture_error),
+            None => Ok(()),
+        }
+    }
+
+    /// Finish the current segment and start a new one.
+    pub fn rotate(&mut self) -> Result<(), PyCanError> {
+        let finished = self.finish_segment()?;
+
+        let ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        // The sequence number orders segments opened in the same millisecond
+        let path = self.dir.join(format!(
+            "{}-{ms}-{:06}.log",
+            self.config.prefix, self.sequence
+        ));
+        self.sequence += 1;
+
+        let file = File::create(&path).map_err(capture_error)?;
+        self.send_job(finished);
+        self.segment = Some(Segment {
+            path,
+            file: BufWriter::new(file),
+            bytes: 0,
+            opened: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Close the current segment. Returns its path if it needs
+    /// compressing.
+    fn finish_segment(&mut self) -> Result<Option<PathBuf>, PyCanError> {
+        let Some(mut segment) = self.segment.take() else {
+            return Ok(None);
+        };
+        segment.file.flush().map_err(capture_error)?;
+        Ok((self.config.compression != Compression::None).then_some(segment.path))
+    }
+
+    fn send_job(&self, finished: Option<PathBuf>) {
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send(finished);
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Ok(finished) = self.finish_segment() {
+            self.send_job(finished);
+        }
+        // Wait for the last segments to be compressed
+        self.jobs = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn candump_line(msg: &PyCanMessage) -> String {
+    let timestamp = msg.timestamp.unwrap_or_default();
+    let iface = msg.iface_name.as_deref().unwrap_or("can0");
+    let data: String = msg
+        .data
+        .iter()
+        .flatten()
+        .map(|b| format!("{b:02X}"))
+        .collect();
+
+    let frame = if msg.is_error_frame {
+        // The error class is carried in the ID, with the error flag set
+        format!("{:08X}#{data}", 0x2000_0000 | msg.arbitration_id.raw())
+    } else if msg.is_remote_frame {
+        // As candump, which leaves out a zero length
+        match msg.dlc.unwrap_or_default() {
+            0 => format!("{}#R", msg.arbitration_id),
+            dlc => format!("{}#R{dlc}", msg.arbitration_id),
+        }
+    } else if msg.is_fd {
+        let flags = u8::from(msg.bitrate_switch) | u8::from(msg.error_state_indicator) << 1;
+        format!("{}##{flags:X}{data}", msg.arbitration_id)
+    } else {
+        format!("{}#{data}", msg.arbitration_id)
+    };
+    format!("({timestamp:.6}) {iface} {frame}\n")
+}
+
+/// Compress `path` to `path` plus the compression's extension.
+fn compress(path: &Path, compression: Compression) -> PyResult<()> {
+    let mut out = path.as_os_str().to_owned();
+    out.push(compression.extension());
+
+    Python::with_gil(|py| {
+        let module = match compression {
+            Compression::None => return Ok(()),
+            Compression::Gzip => py.import("gzip")?,
+            Compression::Zstd => py
+                .import("compression.zstd")
+                .or_else(|_| py.import("zstandard"))?,
+        };
+        let src = py.import("io")?.call_method1("open", (path, "rb"))?;
+        let dst = module.call_method1("open", (out, "wb"))?;
+        let copied = py.import("shutil")?.call_method1("copyfileobj", (src, dst));
+        src.call_method0("close")?;
+        dst.call_method0("close")?;
+        copied.map(|_| ())
+    })
+}
+
+/// Delete the oldest segments until the recording fits its budget. The
+/// newest segment, which is the one being written, is never deleted.
+fn enforce_budget(dir: &Path, config: &RecorderConfig) {
+    let Some(budget) = config.disk_budget else {
+        return;
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    let prefix = format!("{}-", config.prefix);
+    let mut segments: Vec<(PathBuf, u64)> = entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+        .filter_map(|e| Some((e.path(), e.metadata().ok()?.len())))
+        .collect();
+    // Names start with the time the segment was opened
+    segments.sort();
+
+    let mut total: u64 = segments.iter().map(|(_, len)| len).sum();
+    segments.pop();
+    for (path, len) in segments {
+        if total <= budget {
+            break;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            total -= len;
+        }
+    }
+}
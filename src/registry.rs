@@ -0,0 +1,60 @@
+//! Process-wide record of open channels.
+//!
+//! Opening the same physical channel twice through python-can has
+//! backend-specific (usually broken) results, so it's refused with
+//! [`PyCanError::ChannelAlreadyOpen`]. Virtual and SocketCAN channels are
+//! made to be opened several times and aren't tracked.
+
+use std::{collections::BTreeSet, sync::Mutex};
+
+use crate::{PyCanBusType, PyCanError};
+
+static OPEN: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// What identifies the hardware behind `kind`, if it's tracked.
+fn key(kind: &PyCanBusType) -> Option<String> {
+    match kind {
+        PyCanBusType::Gsusb {
+            usb_channel,
+            usb_bus,
+            usb_address,
+            ..
+        } => Some(format!("gsusb:{usb_bus}:{usb_address}/{usb_channel}")),
+        PyCanBusType::Slcan { serial_port, .. } => Some(format!("slcan:{serial_port}")),
+        PyCanBusType::Socketcand {
+            host,
+            port,
+            channel,
+        } => Some(format!("socketcand:{host}:{port}/{channel}")),
+        PyCanBusType::Vector { channel, .. } => Some(format!("vector:{channel}")),
+        PyCanBusType::Socketcan { .. } | PyCanBusType::Virtual { .. } => None,
+    }
+}
+
+/// Marks a channel open until dropped.
+pub(crate) struct ChannelClaim(Option<String>);
+
+impl ChannelClaim {
+    pub(crate) fn acquire(kind: &PyCanBusType) -> Result<Self, PyCanError> {
+        let key = key(kind);
+        if let Some(key) = &key {
+            if !OPEN.lock().unwrap().insert(key.clone()) {
+                return Err(PyCanError::ChannelAlreadyOpen(kind.to_string()));
+            }
+        }
+        Ok(Self(key))
+    }
+
+    /// Whether this claim already covers `kind`'s channel.
+    pub(crate) fn covers(&self, kind: &PyCanBusType) -> bool {
+        self.0 == key(kind)
+    }
+}
+
+impl Drop for ChannelClaim {
+    fn drop(&mut self) {
+        if let Some(key) = &self.0 {
+            OPEN.lock().unwrap().remove(key);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Replacing the python-can Bus under a live interface, e.g. when a USB
+//! adapter re-enumerates on a different port.
+
+use pyo3::{intern, PyResult, Python};
+
+use crate::{
+    describe_py_err, open_bus, open_notifier, ChannelClaim, PyCanBusType, PyCanError, PyCanEvent,
+    PyCanInterface,
+};
+
+impl PyCanInterface {
+    /// Replace the python-can Bus and Notifier with ones for `kind`,
+    /// keeping every listener and Rust callback, interceptor, and periodic
+    /// task.
+    ///
+    /// The new bus is opened before the old one is closed; if that fails,
+    /// the interface is left as it was. Stopped periodic tasks stay
+    /// stopped. If a running task can't be restarted on the new bus, the
+    /// error is reported after the switch and no task is left running;
+    /// each is restarted on the new bus when next started. Frames
+    /// arriving during the switch may be lost. Threads started by
+    /// `watch_bus_state` poll the new bus from then on.
+    pub fn reopen(&mut self, kind: PyCanBusType) -> Result<(), PyCanError> {
+        let claim = if self.claim.covers(&kind) {
+            None
+        } else {
+            Some(ChannelClaim::acquire(&kind)?)
+        };
+
+        Python::with_gil(|py| -> Result<(), PyCanError> {
+            let receive_own = self.pending_tx.is_some();
+            let bus = open_bus(py, &self.pycan, &kind, receive_own)?;
+            let notifier = match open_notifier(py, &self.pycan, &bus, self.notifier_timeout) {
+                Ok(notifier) => notifier,
+                Err(e) => {
+                    let _ = bus.call_method0(py, intern!(py, "shutdown"));
+                    return Err(e);
+                }
+            };
+
+            // Hand every listener, ours and the user's, to the new notifier
+            let moved = (|| -> PyResult<()> {
+                let old = self.notifier.as_ref(py);
+                let listeners = old.getattr("listeners")?.call_method0("copy")?;
+                old.call_method0(intern!(py, "stop"))?;
+
+                let new = notifier.as_ref(py);
+                for listener in listeners.iter()? {
+                    new.call_method1("add_listener", (listener?,))?;
+                }
+                Ok(())
+            })();
+            if let Err(e) = moved {
+                let _ = notifier.call_method0(py, intern!(py, "stop"));
+                let _ = bus.call_method0(py, intern!(py, "shutdown"));
+                return Err(PyCanError::FailedToAddListener(describe_py_err(&e)));
+            }
+
+            let _ = self.iface.call_method0(py, intern!(py, "shutdown"));
+            self.events.emit(PyCanEvent::BusClosed);
+
+            *self.state_bus.lock().unwrap() = bus.clone_ref(py);
+            self.iface = bus;
+            self.notifier = notifier;
+            self.bustype = kind;
+            if let Some(claim) = claim {
+                self.claim = claim;
+            }
+            Ok(())
+        })?;
+
+        self.events.emit(PyCanEvent::BusOpened);
+        self.watch_notifier();
+
+        Python::with_gil(|py| self.move_tasks(py, &self.iface.clone_ref(py)))
+    }
+}
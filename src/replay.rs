@@ -0,0 +1,420 @@
+//! Selecting and editing frames on their way out of a capture, e.g. to
+//! replay part of a drive with some IDs moved and the VIN blanked, and
+//! controlling when and how often replays run.
+
+use std::{
+    collections::HashMap,
+    ops::Range,
+    sync::{Arc, Barrier, Condvar, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    CanId, Filter, FrameSource, ListenerId, PipeError, PyCanError, PyCanInterface, PyCanMessage,
+};
+
+/// What to replay from a capture and how to change it. Apply it to any
+/// [`FrameSource`] with [`crate::FrameSourceExt::replay`].
+///
+/// Frames are selected and edited by the ID they were captured with,
+/// then moved to their new ID.
+///
+/// ```ignore
+/// let edit = ReplayEdit::new()
+///     .only(Filter::new(0x100, 0x700))
+///     .slice(Duration::from_secs(30), Some(Duration::from_secs(90)))
+///     .remap(CanId::Standard(0x123), CanId::Standard(0x523))
+///     .scrub(Filter::id(CanId::Standard(0x3e0)), 1..8, 0);
+/// TrcReader::open("drive.trc")?.replay(edit).paced().pump(bus.clone())?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ReplayEdit {
+    only: Vec<Filter>,
+    skip: Vec<Filter>,
+    from: Duration,
+    to: Option<Duration>,
+    remap: HashMap<CanId, CanId>,
+    patches: Vec<(Filter, Patch)>,
+}
+
+#[derive(Clone, Debug)]
+enum Patch {
+    Fill(Range<usize>, u8),
+    Replace(usize, Vec<u8>),
+}
+
+impl ReplayEdit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only replay frames matching `filter`. Frames matching any of the
+    /// filters given are kept.
+    pub fn only(mut self, filter: Filter) -> Self {
+        self.only.push(filter);
+        self
+    }
+
+    /// Don't replay frames matching `filter`.
+    pub fn skip(mut self, filter: Filter) -> Self {
+        self.skip.push(filter);
+        self
+    }
+
+    /// Only replay frames captured between `from` and `to` after the
+    /// first frame in the capture. The replay ends once past `to`, so the
+    /// capture must be in time order.
+    pub fn slice(mut self, from: Duration, to: Option<Duration>) -> Self {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    /// Send frames captured on `from` on `to` instead.
+    pub fn remap(mut self, from: CanId, to: CanId) -> Self {
+        self.remap.insert(from, to);
+        self
+    }
+
+    /// Overwrite payload bytes `range` with `fill` on frames matching
+    /// `filter`. Bytes past the end of a payload are left alone.
+    pub fn scrub(mut self, filter: Filter, range: Range<usize>, fill: u8) -> Self {
+        self.patches.push((filter, Patch::Fill(range, fill)));
+        self
+    }
+
+    /// Overwrite payload bytes from `offset` with `bytes` on frames
+    /// matching `filter`. Bytes past the end of a payload are left alone.
+    pub fn replace(mut self, filter: Filter, offset: usize, bytes: &[u8]) -> Self {
+        self.patches
+            .push((filter, Patch::Replace(offset, bytes.to_vec())));
+        self
+    }
+
+    fn keeps(&self, id: CanId) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|f| f.matches(id)))
+            && !self.skip.iter().any(|f| f.matches(id))
+    }
+
+    fn apply(&self, mut msg: PyCanMessage) -> PyCanMessage {
+        let id = msg.arbitration_id;
+        if let Some(data) = msg.data.as_mut() {
+            for (_, patch) in self.patches.iter().filter(|(f, _)| f.matches(id)) {
+                match patch {
+                    Patch::Fill(range, fill) => {
+                        let end = range.end.min(data.len());
+                        if let Some(bytes) = data.get_mut(range.start.min(end)..end) {
+                            bytes.fill(*fill);
+                        }
+                    }
+                    Patch::Replace(offset, bytes) => {
+                        for (dst, src) in data.iter_mut().skip(*offset).zip(bytes) {
+                            *dst = *src;
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(to) = self.remap.get(&id) {
+            msg.arbitration_id = *to;
+        }
+        msg
+    }
+}
+
+pub struct ReplaySource<S> {
+    source: S,
+    edit: ReplayEdit,
+    /// Capture time of the first frame.
+    first: Option<f64>,
+    done: bool,
+}
+
+impl<S> ReplaySource<S> {
+    pub(crate) fn new(source: S, edit: ReplayEdit) -> Self {
+        Self {
+            source,
+            edit,
+            first: None,
+            done: false,
+        }
+    }
+}
+
+impl<S: FrameSource> FrameSource for ReplaySource<S> {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        while !self.done {
+            let msg = match self.source.next_frame()? {
+                Ok(msg) => msg,
+                Err(e) => return Some(Err(e)),
+            };
+
+            // Frames without timestamps can't be sliced, so they pass
+            if let Some(timestamp) = msg.timestamp {
+                let first = *self.first.get_or_insert(timestamp);
+                let offset = (timestamp - first).max(0.0);
+                if self.edit.to.is_some_and(|to| offset > to.as_secs_f64()) {
+                    self.done = true;
+                    break;
+                }
+                if offset < self.edit.from.as_secs_f64() {
+                    continue;
+                }
+            }
+
+            if self.edit.keeps(msg.arbitration_id) {
+                return Some(Ok(self.edit.apply(msg)));
+            }
+        }
+        None
+    }
+}
+
+/// Replays a capture over and over, reopening it with `open` for each
+/// pass. Timestamps are shifted so each pass follows on from the last,
+/// which keeps [`crate::FrameSourceExt::paced`] pacing across the seams.
+/// Loops forever unless limited with [`times`](LoopSource::times).
+///
+/// Apply any [`ReplayEdit`] inside `open`, so its slice restarts with
+/// each pass.
+///
+/// ```ignore
+/// looped(|| Ok(TrcReader::open("idle.trc")?.replay(edit.clone())))
+///     .gap(Duration::from_millis(10))
+///     .paced()
+///     .pump(bus.clone())?;
+/// ```
+pub fn looped<S, F>(open: F) -> LoopSource<S, F>
+where
+    S: FrameSource,
+    F: FnMut() -> Result<S, PipeError> + Send,
+{
+    LoopSource {
+        open,
+        current: None,
+        passes: 0,
+        limit: None,
+        gap: Duration::ZERO,
+        offset: 0.0,
+        last: None,
+        pass_frames: 0,
+    }
+}
+
+pub struct LoopSource<S, F> {
+    open: F,
+    current: Option<S>,
+    passes: u64,
+    limit: Option<u64>,
+    gap: Duration,
+    /// Added to this pass's timestamps.
+    offset: f64,
+    /// Shifted timestamp of the last frame handed out.
+    last: Option<f64>,
+    pass_frames: u64,
+}
+
+impl<S, F> LoopSource<S, F> {
+    /// Stop after `passes` passes.
+    pub fn times(mut self, passes: u64) -> Self {
+        self.limit = Some(passes);
+        self
+    }
+
+    /// Time between the last frame of a pass and the first of the next.
+    pub fn gap(mut self, gap: Duration) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    /// Passes started so far.
+    pub fn passes(&self) -> u64 {
+        self.passes
+    }
+}
+
+impl<S, F> FrameSource for LoopSource<S, F>
+where
+    S: FrameSource,
+    F: FnMut() -> Result<S, PipeError> + Send,
+{
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        loop {
+            let source = match &mut self.current {
+                Some(source) => source,
+                None => {
+                    if self.limit.is_some_and(|limit| self.passes >= limit) {
+                        return None;
+                    }
+                    let source = match (self.open)() {
+                        Ok(source) => source,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    self.passes += 1;
+                    self.pass_frames = 0;
+                    self.current.insert(source)
+                }
+            };
+
+            match source.next_frame() {
+                Some(Ok(mut msg)) => {
+                    if let Some(timestamp) = msg.timestamp {
+                        if self.pass_frames == 0 {
+                            self.offset = self
+                                .last
+                                .map_or(0.0, |last| last + self.gap.as_secs_f64() - timestamp);
+                        }
+                        msg.timestamp = Some(timestamp + self.offset);
+                        self.last = msg.timestamp;
+                    }
+                    self.pass_frames += 1;
+                    return Some(Ok(msg));
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.current = None;
+                    // An empty capture would otherwise spin forever
+                    if self.pass_frames == 0 {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Holds replays back until something happens. Clones share the same
+/// trigger, so every replay waiting on them starts together.
+#[derive(Clone)]
+pub struct ReplayTrigger {
+    inner: Arc<TriggerInner>,
+}
+
+struct TriggerInner {
+    kind: TriggerKind,
+    signal: Arc<Signal>,
+}
+
+/// Shared with the frame listener, which mustn't own the trigger: the
+/// listener can't be removed from inside its own callback.
+#[derive(Default)]
+struct Signal {
+    fired: Mutex<bool>,
+    wake: Condvar,
+}
+
+enum TriggerKind {
+    Manual,
+    Frame {
+        iface: Arc<PyCanInterface>,
+        listener: Mutex<Option<ListenerId>>,
+    },
+    Barrier(Barrier),
+}
+
+impl ReplayTrigger {
+    /// Starts when [`fire`](Self::fire) is called.
+    pub fn manual() -> Self {
+        Self::new(TriggerKind::Manual)
+    }
+
+    /// Starts when `iface` receives a frame matching `filter`.
+    pub fn on_frame(iface: &Arc<PyCanInterface>, filter: Filter) -> Result<Self, PyCanError> {
+        let trigger = Self::new(TriggerKind::Frame {
+            iface: iface.clone(),
+            listener: Mutex::new(None),
+        });
+
+        let signal = trigger.inner.signal.clone();
+        let listener = iface.register_rx_callback(
+            move |msg| {
+                if msg.is_rx && filter.matches(msg.arbitration_id) {
+                    signal.fire();
+                }
+            },
+            |_| {},
+        )?;
+        if let TriggerKind::Frame { listener: id, .. } = &trigger.inner.kind {
+            *id.lock().unwrap() = Some(listener);
+        }
+        Ok(trigger)
+    }
+
+    /// Starts once `replays` replays are all waiting on it, e.g. one per
+    /// interface of a multi-bus scenario.
+    pub fn sync(replays: usize) -> Self {
+        Self::new(TriggerKind::Barrier(Barrier::new(replays)))
+    }
+
+    fn new(kind: TriggerKind) -> Self {
+        Self {
+            inner: Arc::new(TriggerInner {
+                kind,
+                signal: Arc::default(),
+            }),
+        }
+    }
+
+    /// Start every replay waiting on this trigger now.
+    pub fn fire(&self) {
+        self.inner.signal.fire();
+    }
+
+    pub fn has_fired(&self) -> bool {
+        *self.inner.signal.fired.lock().unwrap()
+    }
+
+    /// Block until the trigger fires.
+    pub fn wait(&self) {
+        if let TriggerKind::Barrier(barrier) = &self.inner.kind {
+            if !self.has_fired() {
+                barrier.wait();
+                self.fire();
+            }
+            return;
+        }
+        let signal = &self.inner.signal;
+        let fired = signal.fired.lock().unwrap();
+        drop(signal.wake.wait_while(fired, |fired| !*fired).unwrap());
+    }
+}
+
+impl Signal {
+    fn fire(&self) {
+        *self.fired.lock().unwrap() = true;
+        self.wake.notify_all();
+    }
+}
+
+impl Drop for TriggerInner {
+    fn drop(&mut self) {
+        if let TriggerKind::Frame { iface, listener } = &self.kind {
+            if let Some(listener) = listener.lock().unwrap().take() {
+                let _ = iface.remove_listener(listener);
+            }
+        }
+    }
+}
+
+pub struct TriggeredSource<S> {
+    source: S,
+    trigger: Option<ReplayTrigger>,
+}
+
+impl<S> TriggeredSource<S> {
+    pub(crate) fn new(source: S, trigger: ReplayTrigger) -> Self {
+        Self {
+            source,
+            trigger: Some(trigger),
+        }
+    }
+}
+
+impl<S: FrameSource> FrameSource for TriggeredSource<S> {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        if let Some(trigger) = self.trigger.take() {
+            trigger.wait();
+        }
+        self.source.next_frame()
+    }
+}
@@ -0,0 +1,336 @@
+//! Summaries of capture sessions, for test reports.
+
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+use crate::{CanId, FrameSink, FrameSource, PipeError, PyCanMessage};
+
+/// How [`SessionReport`]s are built.
+#[derive(Clone, Debug)]
+pub struct ReportConfig {
+    /// Nominal bitrate, for bus load.
+    pub bitrate: u32,
+    /// Width of the bus load buckets. Must be more than zero.
+    pub bucket: Duration,
+    /// Error frames closer together than this are one burst.
+    pub burst_gap: Duration,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            bitrate: 500_000,
+            bucket: Duration::from_secs(1),
+            burst_gap: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Traffic on one ID over the session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IdSummary {
+    pub id: CanId,
+    pub frames: u64,
+    pub first_seen: f64,
+    pub last_seen: f64,
+    /// Longest time between two consecutive frames.
+    pub longest_silence: Duration,
+    /// When that silence began.
+    pub silence_start: f64,
+}
+
+/// Error frames with less than [`ReportConfig::burst_gap`] between them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorBurst {
+    pub start: f64,
+    pub end: f64,
+    pub frames: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadBucket {
+    /// Timestamp the bucket starts at.
+    pub start: f64,
+    pub frames: u64,
+    /// Fraction of the bucket the bus was busy, from 0 to 1.
+    pub load: f64,
+}
+
+/// What happened over a capture session. Frames without timestamps are
+/// counted but otherwise left out.
+#[derive(Clone, Debug, Default)]
+pub struct SessionReport {
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    pub frames: u64,
+    pub error_frames: u64,
+    /// Longest time without any frame.
+    pub longest_silence: Duration,
+    /// In ID order.
+    pub ids: Vec<IdSummary>,
+    pub error_bursts: Vec<ErrorBurst>,
+    /// In time order. Buckets without frames are left out.
+    pub load: Vec<LoadBucket>,
+}
+
+/// Frame length on the wire in bits, without stuffing. FD data phases are
+/// counted at the nominal bitrate, so FD load is overstated.
+fn frame_bits(msg: &PyCanMessage) -> u64 {
+    let overhead = if msg.arbitration_id.is_extended() {
+        67
+    } else {
+        47
+    };
+    let len = if msg.is_remote_frame {
+        0
+    } else {
+        msg.data.as_ref().map_or(0, Vec::len)
+    };
+    overhead + 8 * len as u64
+}
+
+/// Time from `from` to `to`, or zero if `to` is earlier. Saturates
+/// rather than panicking on gaps too long for a Duration.
+fn gap(from: f64, to: f64) -> Duration {
+    Duration::try_from_secs_f64((to - from).max(0.0)).unwrap_or(Duration::MAX)
+}
+
+/// Builds a [`SessionReport`] from frames as they're written, e.g. teed off
+/// a capture while it's being logged.
+///
+/// ```ignore
+/// let mut reporter = SessionReporter::new(ReportConfig::default())?;
+/// bus.frame_source()?.tee(&mut reporter).pump(TrcWriter::create("run.trc")?)?;
+/// std::fs::write("run.json", reporter.finish().to_json())?;
+/// ```
+pub struct SessionReporter {
+    config: ReportConfig,
+    report: SessionReport,
+    ids: BTreeMap<CanId, IdSummary>,
+    /// Frames and bits seen in each bucket that has any.
+    bits: BTreeMap<u64, (u64, u64)>,
+}
+
+impl SessionReporter {
+    pub fn new(config: ReportConfig) -> Result<Self, PipeError> {
+        if config.bucket.is_zero() {
+            return Err(PipeError::InvalidConfig(
+                "load bucket width must be more than zero".into(),
+            ));
+        }
+
+        Ok(Self {
+            config,
+            report: SessionReport::default(),
+            ids: BTreeMap::new(),
+            bits: BTreeMap::new(),
+        })
+    }
+
+    pub fn record(&mut self, msg: &PyCanMessage) {
+        let report = &mut self.report;
+        report.frames += 1;
+        if msg.is_error_frame {
+            report.error_frames += 1;
+        }
+        // Frames without a usable time only count towards the totals
+        let Some(time) = msg.timestamp.filter(|t| t.is_finite()) else {
+            return;
+        };
+
+        let start = *report.start.get_or_insert(time);
+        if let Some(end) = report.end {
+            report.longest_silence = report.longest_silence.max(gap(end, time));
+        }
+        report.end = Some(report.end.map_or(time, |end| end.max(time)));
+
+        let bucket = ((time - start).max(0.0) / self.config.bucket.as_secs_f64()) as u64;
+        let bits = self.bits.entry(bucket).or_default();
+        bits.0 += 1;
+        bits.1 += frame_bits(msg);
+
+        if msg.is_error_frame {
+            let gap = self.config.burst_gap.as_secs_f64();
+            match report.error_bursts.last_mut() {
+                Some(burst) if time - burst.end < gap => {
+                    burst.end = time;
+                    burst.frames += 1;
+                }
+                _ => report.error_bursts.push(ErrorBurst {
+                    start: time,
+                    end: time,
+                    frames: 1,
+                }),
+            }
+            return;
+        }
+
+        let id = msg.arbitration_id;
+        let summary = self.ids.entry(id).or_insert(IdSummary {
+            id,
+            frames: 0,
+            first_seen: time,
+            last_seen: time,
+            longest_silence: Duration::ZERO,
+            silence_start: time,
+        });
+        let silence = gap(summary.last_seen, time);
+        if silence > summary.longest_silence {
+            summary.longest_silence = silence;
+            summary.silence_start = summary.last_seen;
+        }
+        summary.frames += 1;
+        summary.last_seen = time;
+    }
+
+    pub fn finish(mut self) -> SessionReport {
+        let start = self.report.start.unwrap_or_default();
+        let bucket = self.config.bucket.as_secs_f64();
+        let capacity = f64::from(self.config.bitrate) * bucket;
+        self.report.load = self
+            .bits
+            .iter()
+            .map(|(&i, (frames, bits))| LoadBucket {
+                start: start + i as f64 * bucket,
+                frames: *frames,
+                load: (*bits as f64 / capacity).min(1.0),
+            })
+            .collect();
+        self.report.ids = self.ids.into_values().collect();
+        self.report
+    }
+}
+
+impl FrameSink for SessionReporter {
+    fn write_frame(&mut self, msg: &PyCanMessage) -> Result<(), PipeError> {
+        self.record(msg);
+        Ok(())
+    }
+}
+
+impl SessionReport {
+    /// Report on every frame in `source`, e.g. a capture file.
+    pub fn from_source(
+        mut source: impl FrameSource,
+        config: ReportConfig,
+    ) -> Result<Self, PipeError> {
+        let mut reporter = SessionReporter::new(config)?;
+        while let Some(msg) = source.next_frame() {
+            reporter.record(&msg?);
+        }
+        Ok(reporter.finish())
+    }
+
+    /// Time from the first frame to the last.
+    pub fn duration(&self) -> Duration {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => gap(start, end),
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let opt = |v: Option<f64>| v.map_or("null".to_string(), |v| format!("{v:.6}"));
+
+        let mut out = String::from("{\n");
+        let _ = writeln!(out, "  \"start\": {},", opt(self.start));
+        let _ = writeln!(out, "  \"end\": {},", opt(self.end));
+        let _ = writeln!(out, "  \"frames\": {},", self.frames);
+        let _ = writeln!(out, "  \"error_frames\": {},", self.error_frames);
+        let _ = writeln!(
+            out,
+            "  \"longest_silence\": {:.6},",
+            self.longest_silence.as_secs_f64()
+        );
+
+        let ids: Vec<_> = self
+            .ids
+            .iter()
+            .map(|s| {
+                format!(
+                    "    {{\"id\": \"{}\", \"extended\": {}, \"frames\": {}, \"first_seen\": {:.6}, \
+                     \"last_seen\": {:.6}, \"longest_silence\": {:.6}, \"silence_start\": {:.6}}}",
+                    s.id,
+                    s.id.is_extended(),
+                    s.frames,
+                    s.first_seen,
+                    s.last_seen,
+                    s.longest_silence.as_secs_f64(),
+                    s.silence_start
+                )
+            })
+            .collect();
+        let bursts: Vec<_> = self
+            .error_bursts
+            .iter()
+            .map(|b| {
+                format!(
+                    "    {{\"start\": {:.6}, \"end\": {:.6}, \"frames\": {}}}",
+                    b.start, b.end, b.frames
+                )
+            })
+            .collect();
+        let load: Vec<_> = self
+            .load
+            .iter()
+            .map(|b| {
+                format!(
+                    "    {{\"start\": {:.6}, \"frames\": {}, \"load\": {:.4}}}",
+                    b.start, b.frames, b.load
+                )
+            })
+            .collect();
+
+        for (name, items, last) in [
+            ("ids", ids, false),
+            ("error_bursts", bursts, false),
+            ("load", load, true),
+        ] {
+            let comma = if last { "" } else { "," };
+            if items.is_empty() {
+                let _ = writeln!(out, "  \"{name}\": []{comma}");
+            } else {
+                let _ = writeln!(out, "  \"{name}\": [\n{}\n  ]{comma}", items.join(",\n"));
+            }
+        }
+        out.push('}');
+        out
+    }
+
+    /// [`ids`](Self::ids) as CSV, with a header row.
+    pub fn ids_csv(&self) -> String {
+        let mut out =
+            String::from("id,extended,frames,first_seen,last_seen,longest_silence,silence_start\n");
+        for s in &self.ids {
+            let _ = writeln!(
+                out,
+                "0x{:X},{},{},{:.6},{:.6},{:.6},{:.6}",
+                s.id.raw(),
+                u8::from(s.id.is_extended()),
+                s.frames,
+                s.first_seen,
+                s.last_seen,
+                s.longest_silence.as_secs_f64(),
+                s.silence_start
+            );
+        }
+        out
+    }
+
+    /// [`error_bursts`](Self::error_bursts) as CSV, with a header row.
+    pub fn error_bursts_csv(&self) -> String {
+        let mut out = String::from("start,end,frames\n");
+        for b in &self.error_bursts {
+            let _ = writeln!(out, "{:.6},{:.6},{}", b.start, b.end, b.frames);
+        }
+        out
+    }
+
+    /// [`load`](Self::load) as CSV, with a header row.
+    pub fn load_csv(&self) -> String {
+        let mut out = String::from("start,frames,load\n");
+        for b in &self.load {
+            let _ = writeln!(out, "{:.6},{},{:.4}", b.start, b.frames, b.load);
+        }
+        out
+    }
+}
@@ -0,0 +1,359 @@
+//! Faking simple ECU behaviour from a script of request/response rules.
+//!
+//! Scripts are YAML. Each received frame is checked against the rules in
+//! order, and the first one that matches sends its replies:
+//!
+//! ```text
+//! initial: locked
+//! rules:
+//!   # Answer a security access seed request, once
+//!   - name: seed
+//!     match: 7E0
+//!     data: "02 27 01"
+//!     state: locked
+//!     next: seed_sent
+//!     reply:
+//!       id: 7E8
+//!       data: "04 67 01 12 34"
+//!       delay: 20ms
+//!
+//!   # Echo byte 1 of any 0x100-0x10F frame back with a rolling counter
+//!   - name: echo
+//!     match: 100:7F0
+//!     counter_mod: 16
+//!     reply:
+//!       - id: 101
+//!         data: "{rx1} {counter} 00"
+//!       - id: 102
+//!         data: "AA"
+//!         delay: 5ms
+//! ```
+//!
+//! `match` takes an ID in candump notation, optionally with a mask
+//! (`id:mask`). `data` is a payload prefix in hex, with `??` matching any
+//! byte. With `state`, a rule only matches while the responder is in
+//! that state, and `next` moves it to another state once the rule fires;
+//! the first state is `initial`, if given.
+//!
+//! Reply payloads are hex bytes and templates: `{rxN}` copies byte `N` of
+//! the received frame (0 if it's too short), and `{counter}` is how many
+//! times the rule has fired before, modulo `counter_mod` (at most 256, the
+//! default). `delay` takes `us`, `ms` or `s`.
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use thiserror::Error;
+
+use crate::{
+    gateway::{parse_duration, parse_match},
+    yaml::{self, Value},
+    CanId, Filter, ListenerId, PyCanError, PyCanEvent, PyCanInterface, PyCanMessage,
+};
+
+#[derive(Debug, Error)]
+pub enum ResponderError {
+    #[error("Failed to read responder script :: `{0}`")]
+    Io(String),
+    #[error("Responder script syntax error on line {0} :: `{1}`")]
+    Syntax(usize, String),
+    #[error("Invalid responder rule :: `{0}`")]
+    InvalidRule(String),
+    #[error(transparent)]
+    Interface(#[from] PyCanError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    Byte(u8),
+    Rx(usize),
+    Counter,
+}
+
+#[derive(Clone, Debug)]
+struct Reply {
+    id: CanId,
+    data: Vec<Token>,
+    delay: Duration,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    name: String,
+    filter: Filter,
+    /// Payload prefix, None for wildcard bytes.
+    data: Vec<Option<u8>>,
+    state: Option<String>,
+    next: Option<String>,
+    counter_mod: u32,
+    replies: Vec<Reply>,
+}
+
+impl Rule {
+    fn matches(&self, msg: &PyCanMessage, state: Option<&str>) -> bool {
+        let payload = msg.data.as_deref().unwrap_or_default();
+        self.filter.matches(msg.arbitration_id)
+            && self
+                .state
+                .as_deref()
+                .is_none_or(|wanted| state == Some(wanted))
+            && payload.len() >= self.data.len()
+            && self
+                .data
+                .iter()
+                .zip(payload)
+                .all(|(want, got)| want.is_none_or(|want| want == *got))
+    }
+}
+
+fn hex_byte(s: &str) -> Result<u8, String> {
+    u8::from_str_radix(s, 16).map_err(|_| format!("invalid byte `{s}`"))
+}
+
+fn parse_pattern(s: &str) -> Result<Vec<Option<u8>>, String> {
+    s.split_whitespace()
+        .map(|b| match b {
+            "??" => Ok(None),
+            b => hex_byte(b).map(Some),
+        })
+        .collect()
+}
+
+fn parse_template(s: &str) -> Result<Vec<Token>, String> {
+    s.split_whitespace()
+        .map(
+            |t| match t.strip_prefix('{').and_then(|t| t.strip_suffix('}')) {
+                Some("counter") => Ok(Token::Counter),
+                Some(inner) => inner
+                    .strip_prefix("rx")
+                    .and_then(|i| i.parse().ok())
+                    .map(Token::Rx)
+                    .ok_or_else(|| format!("unknown template `{t}`")),
+                None => hex_byte(t).map(Token::Byte),
+            },
+        )
+        .collect()
+}
+
+/// A parsed responder script. See the [module docs](self) for the format.
+#[derive(Clone, Debug, Default)]
+pub struct ResponderScript {
+    initial: Option<String>,
+    rules: Vec<Rule>,
+}
+
+impl ResponderScript {
+    pub fn parse(text: &str) -> Result<Self, ResponderError> {
+        let doc = yaml::parse(text).map_err(|(line, e)| ResponderError::Syntax(line, e))?;
+
+        let initial = doc.get("initial").map(scalar).transpose()?;
+        let rules = doc
+            .get("rules")
+            .map(Value::items)
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| parse_rule(i, rule))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { initial, rules })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ResponderError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ResponderError::Io(format!("{}: {e}", path.display())))?;
+        Self::parse(&text)
+    }
+}
+
+fn scalar(value: &Value) -> Result<String, ResponderError> {
+    value
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| ResponderError::InvalidRule(format!("expected a value, got {value:?}")))
+}
+
+fn parse_rule(index: usize, value: &Value) -> Result<Rule, ResponderError> {
+    let name = value
+        .get("name")
+        .and_then(Value::as_str)
+        .map_or_else(|| format!("#{}", index + 1), String::from);
+    let invalid = |e: String| ResponderError::InvalidRule(format!("{name}: {e}"));
+    let text = |key: &str| -> Result<Option<&str>, ResponderError> {
+        value
+            .get(key)
+            .map(|v| {
+                v.as_str()
+                    .ok_or_else(|| invalid(format!("`{key}` must be a value")))
+            })
+            .transpose()
+    };
+
+    let filter = parse_match(text("match")?.ok_or_else(|| invalid("no `match`".into()))?)
+        .map_err(invalid)?;
+    let data = text("data")?
+        .map(parse_pattern)
+        .transpose()
+        .map_err(invalid)?
+        .unwrap_or_default();
+    let counter_mod = text("counter_mod")?
+        .map(|m| m.parse().ok().filter(|m| (1..=256).contains(m)))
+        .map(|m| m.ok_or_else(|| invalid("`counter_mod` must be from 1 to 256".into())))
+        .transpose()?
+        .unwrap_or(256);
+
+    let replies = value
+        .get("reply")
+        .map(Value::items)
+        .unwrap_or_default()
+        .iter()
+        .map(|reply| {
+            let field = |key: &str| reply.get(key).and_then(Value::as_str);
+            let id = field("id")
+                .ok_or_else(|| invalid("reply has no `id`".into()))?
+                .parse()
+                .map_err(|e| invalid(format!("{e}")))?;
+            let data = parse_template(field("data").unwrap_or_default()).map_err(invalid)?;
+            let delay = field("delay")
+                .map(parse_duration)
+                .transpose()
+                .map_err(invalid)?
+                .unwrap_or_default();
+            Ok(Reply { id, data, delay })
+        })
+        .collect::<Result<_, ResponderError>>()?;
+
+    Ok(Rule {
+        filter,
+        data,
+        state: text("state")?.map(String::from),
+        next: text("next")?.map(String::from),
+        counter_mod,
+        replies,
+        name,
+    })
+}
+
+struct RunState {
+    state: Option<String>,
+    /// Times each rule has fired.
+    hits: Vec<u64>,
+}
+
+/// Answers received frames as a [`ResponderScript`] says. Replies are sent
+/// from the interface's deferred TX thread (see
+/// [`PyCanInterface::send_at`]). Stops on drop.
+pub struct Responder {
+    iface: Arc<PyCanInterface>,
+    listener: ListenerId,
+    rules: Arc<Vec<Rule>>,
+    run: Arc<Mutex<RunState>>,
+}
+
+impl Responder {
+    pub fn start(iface: &Arc<PyCanInterface>, script: ResponderScript) -> Result<Self, PyCanError> {
+        let rules = Arc::new(script.rules);
+        let run = Arc::new(Mutex::new(RunState {
+            state: script.initial,
+            hits: vec![0; rules.len()],
+        }));
+
+        let listener = {
+            let rules = rules.clone();
+            let run = run.clone();
+            // Weak, as the interface owns its listeners
+            let weak: Weak<PyCanInterface> = Arc::downgrade(iface);
+            iface.register_rx_callback(
+                move |msg| {
+                    if !msg.is_rx {
+                        return;
+                    }
+                    let Some(iface) = weak.upgrade() else {
+                        return;
+                    };
+                    for (id, data, delay) in respond(&rules, &run, msg) {
+                        if let Err(e) = iface.send_after(id, &data, delay) {
+                            iface.events.emit(PyCanEvent::DeferredTxFailed {
+                                id,
+                                reason: e.to_string(),
+                            });
+                        }
+                    }
+                },
+                |_| {},
+            )?
+        };
+
+        Ok(Self {
+            iface: iface.clone(),
+            listener,
+            rules,
+            run,
+        })
+    }
+
+    /// The current state, if the script uses states.
+    pub fn state(&self) -> Option<String> {
+        self.run.lock().unwrap().state.clone()
+    }
+
+    /// Move to `state`, e.g. to reset the simulated ECU between tests.
+    pub fn set_state(&self, state: Option<&str>) {
+        self.run.lock().unwrap().state = state.map(String::from);
+    }
+
+    /// Times the rule called `name` has fired.
+    pub fn hits(&self, name: &str) -> Option<u64> {
+        let index = self.rules.iter().position(|r| r.name == name)?;
+        Some(self.run.lock().unwrap().hits[index])
+    }
+}
+
+impl Drop for Responder {
+    fn drop(&mut self) {
+        let _ = self.iface.remove_listener(self.listener);
+    }
+}
+
+/// The replies to `msg`, from the first rule it matches.
+fn respond(
+    rules: &[Rule],
+    run: &Mutex<RunState>,
+    msg: &PyCanMessage,
+) -> Vec<(CanId, Vec<u8>, Duration)> {
+    let mut run = run.lock().unwrap();
+    let Some(index) = rules
+        .iter()
+        .position(|r| r.matches(msg, run.state.as_deref()))
+    else {
+        return Vec::new();
+    };
+    let rule = &rules[index];
+
+    let counter = (run.hits[index] % u64::from(rule.counter_mod)) as u8;
+    run.hits[index] += 1;
+    if let Some(next) = &rule.next {
+        run.state = Some(next.clone());
+    }
+
+    let payload = msg.data.as_deref().unwrap_or_default();
+    rule.replies
+        .iter()
+        .map(|reply| {
+            let data = reply
+                .data
+                .iter()
+                .map(|token| match token {
+                    Token::Byte(b) => *b,
+                    Token::Rx(i) => payload.get(*i).copied().unwrap_or_default(),
+                    Token::Counter => counter,
+                })
+                .collect();
+            (reply.id, data, reply.delay)
+        })
+        .collect()
+}
@@ -0,0 +1,219 @@
+//! Discovering what's on an unknown bus.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use pyo3::{intern, types::IntoPyDict, IntoPy, Python};
+
+use crate::{describe_py_err, CanId, PyCanError, PyCanInterface, PyCanMessage};
+
+/// OBD/UDS functional request IDs: 11-bit, and 29-bit normal fixed
+/// addressing from tester 0xF1.
+const UDS_FUNCTIONAL: [CanId; 2] = [CanId::Standard(0x7df), CanId::Extended(0x18db_33f1)];
+
+/// TesterPresent, with a response requested.
+const TESTER_PRESENT: [u8; 8] = [0x02, 0x3e, 0x00, 0, 0, 0, 0, 0];
+
+/// What [`PyCanInterface::scan`] does.
+#[derive(Clone, Debug)]
+pub struct ScanConfig {
+    /// How long to listen passively.
+    pub window: Duration,
+    /// IDs to send remote frames on after listening, to find nodes that
+    /// answer RTRs. IDs already seen while listening are skipped.
+    pub rtr_ids: Vec<CanId>,
+    /// Send UDS TesterPresent on the functional addresses to find
+    /// diagnostic responders.
+    pub uds_probe: bool,
+    /// How long to wait for answers to each probe.
+    pub response_timeout: Duration,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(5),
+            rtr_ids: Vec::new(),
+            uds_probe: false,
+            response_timeout: Duration::from_millis(200),
+        }
+    }
+}
+
+/// Traffic seen on one ID while listening.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScannedId {
+    pub id: CanId,
+    pub frames: u64,
+    /// Average frames per second over the window.
+    pub rate_hz: f64,
+    /// Distinct payload lengths seen.
+    pub lengths: Vec<usize>,
+    pub is_fd: bool,
+    pub remote_frames: u64,
+    /// Payload of the last data frame.
+    pub last_data: Vec<u8>,
+}
+
+/// A node that answered a UDS functional request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UdsResponder {
+    pub request_id: CanId,
+    pub response_id: CanId,
+    /// Whether it answered positively rather than with a negative
+    /// response.
+    pub positive: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ScanReport {
+    /// Every ID seen while listening, in ID order.
+    pub ids: Vec<ScannedId>,
+    pub error_frames: u64,
+    /// Probed IDs that answered a remote frame with data.
+    pub rtr_responders: Vec<CanId>,
+    pub uds_responders: Vec<UdsResponder>,
+}
+
+#[derive(Default)]
+struct Seen {
+    frames: u64,
+    lengths: BTreeSet<usize>,
+    is_fd: bool,
+    remote_frames: u64,
+    last_data: Vec<u8>,
+}
+
+impl PyCanInterface {
+    /// List every ID seen over `config.window`, with its rate and payload
+    /// lengths, then optionally probe for nodes answering remote frames
+    /// and UDS functional requests. Frames this interface sent itself are
+    /// ignored.
+    pub fn scan(&self, config: &ScanConfig) -> Result<ScanReport, PyCanError> {
+        let (tx, rx) = mpsc::channel();
+        let listener = self.register_rx_callback_all(
+            move |msg| {
+                if msg.is_rx {
+                    let _ = tx.send(msg.clone());
+                }
+            },
+            |_| {},
+        )?;
+
+        let res = self.run_scan(config, &rx);
+        self.remove_listener(listener)?;
+        res
+    }
+
+    fn run_scan(
+        &self,
+        config: &ScanConfig,
+        rx: &mpsc::Receiver<PyCanMessage>,
+    ) -> Result<ScanReport, PyCanError> {
+        let mut report = ScanReport::default();
+        let mut seen = BTreeMap::<CanId, Seen>::new();
+
+        for msg in frames_until(rx, deadline(config.window)?) {
+            if msg.is_error_frame {
+                report.error_frames += 1;
+                continue;
+            }
+            let entry = seen.entry(msg.arbitration_id).or_default();
+            entry.frames += 1;
+            entry.is_fd |= msg.is_fd;
+            if msg.is_remote_frame {
+                entry.remote_frames += 1;
+            } else {
+                let data = msg.data.unwrap_or_default();
+                entry.lengths.insert(data.len());
+                entry.last_data = data;
+            }
+        }
+
+        let window = config.window.as_secs_f64().max(f64::EPSILON);
+        report.ids = seen
+            .iter()
+            .map(|(id, s)| ScannedId {
+                id: *id,
+                frames: s.frames,
+                rate_hz: s.frames as f64 / window,
+                lengths: s.lengths.iter().copied().collect(),
+                is_fd: s.is_fd,
+                remote_frames: s.remote_frames,
+                last_data: s.last_data.clone(),
+            })
+            .collect();
+
+        for id in config.rtr_ids.iter().filter(|id| !seen.contains_key(id)) {
+            self.send_remote(*id)?;
+            let answered = frames_until(rx, deadline(config.response_timeout)?)
+                .any(|msg| msg.arbitration_id == *id && !msg.is_remote_frame);
+            if answered {
+                report.rtr_responders.push(*id);
+            }
+        }
+
+        if config.uds_probe {
+            for request_id in UDS_FUNCTIONAL {
+                Python::with_gil(|py| self.send_once(py, request_id, &TESTER_PRESENT, None))
+                    .map_err(|e| PyCanError::FailedToSend(describe_py_err(&e)))?;
+
+                for msg in frames_until(rx, deadline(config.response_timeout)?) {
+                    let data = msg.data.as_deref().unwrap_or_default();
+                    let positive = match data {
+                        [_, 0x7e, ..] => true,
+                        [_, 0x7f, 0x3e, ..] => false,
+                        _ => continue,
+                    };
+                    let responder = UdsResponder {
+                        request_id,
+                        response_id: msg.arbitration_id,
+                        positive,
+                    };
+                    if !report.uds_responders.contains(&responder) {
+                        report.uds_responders.push(responder);
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Send a remote frame requesting 8 bytes.
+    fn send_remote(&self, id: CanId) -> Result<(), PyCanError> {
+        Python::with_gil(|py| {
+            let kwargs = [
+                ("arbitration_id", id.raw().into_py(py)),
+                ("is_extended_id", id.is_extended().into_py(py)),
+                ("is_remote_frame", true.into_py(py)),
+                ("dlc", 8.into_py(py)),
+            ]
+            .into_py_dict(py);
+            let msg = self.pycan.call_method(py, "Message", (), Some(kwargs))?;
+            self.iface.call_method1(py, intern!(py, "send"), (msg,))
+        })
+        .map(|_| ())
+        .map_err(|e| PyCanError::FailedToSend(describe_py_err(&e)))
+    }
+}
+
+/// Frames received until `deadline`.
+fn deadline(timeout: Duration) -> Result<Instant, PyCanError> {
+    Instant::now()
+        .checked_add(timeout)
+        .ok_or_else(|| PyCanError::InvalidDuration(format!("{timeout:?} from now")))
+}
+
+fn frames_until(
+    rx: &mpsc::Receiver<PyCanMessage>,
+    deadline: Instant,
+) -> impl Iterator<Item = PyCanMessage> + '_ {
+    std::iter::from_fn(move || {
+        let timeout = deadline.checked_duration_since(Instant::now())?;
+        rx.recv_timeout(timeout).ok()
+    })
+}
@@ -0,0 +1,152 @@
+//! Phase-aligned transmission in fixed slots of a repeating cycle.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use pyo3::Python;
+
+use crate::{CanId, PyCanInterface};
+
+/// A frame sent `offset` into every cycle.
+#[derive(Clone, Debug)]
+pub struct ScheduleEntry {
+    pub offset: Duration,
+    pub id: CanId,
+    pub data: Vec<u8>,
+}
+
+/// Lateness of transmissions relative to their slots.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JitterStats {
+    pub samples: u64,
+    pub mean: Duration,
+    pub max: Duration,
+    /// Cycles that started late because the previous one overran.
+    pub overruns: u64,
+    pub send_errors: u64,
+}
+
+/// Transmits a table of frames at fixed offsets within a repeating cycle,
+/// e.g. a 10ms major frame. Cycles are scheduled at a fixed rate, so
+/// lateness in one cycle doesn't shift the next. Stops on drop.
+pub struct Scheduler {
+    entries: Arc<Mutex<Vec<ScheduleEntry>>>,
+    stats: Arc<Mutex<JitterStats>>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    /// Start transmitting. Entries with offsets beyond `cycle` are sent at
+    /// the end of the cycle.
+    pub fn start(
+        iface: Arc<PyCanInterface>,
+        cycle: Duration,
+        mut entries: Vec<ScheduleEntry>,
+    ) -> Self {
+        entries.sort_by_key(|e| e.offset);
+
+        let entries = Arc::new(Mutex::new(entries));
+        let stats = Arc::new(Mutex::new(JitterStats::default()));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread = {
+            let entries = entries.clone();
+            let stats = stats.clone();
+            let running = running.clone();
+            std::thread::spawn(move || run(&iface, cycle, &entries, &stats, &running))
+        };
+
+        Self {
+            entries,
+            stats,
+            running,
+            thread: Some(thread),
+        }
+    }
+
+    /// Replace the payload of the `index`th entry (in offset order) from the
+    /// next transmission on.
+    pub fn update(&self, index: usize, data: &[u8]) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(index) {
+            entry.data = data.to_vec();
+        }
+    }
+
+    pub fn jitter(&self) -> JitterStats {
+        *self.stats.lock().unwrap()
+    }
+
+    pub fn stop(mut self) {
+        self.halt();
+    }
+
+    fn halt(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.halt();
+    }
+}
+
+fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline > now {
+        std::thread::sleep(deadline - now);
+    }
+}
+
+fn run(
+    iface: &PyCanInterface,
+    cycle: Duration,
+    entries: &Mutex<Vec<ScheduleEntry>>,
+    stats: &Mutex<JitterStats>,
+    running: &AtomicBool,
+) {
+    let mut cycle_start = Instant::now();
+    let mut total_lateness = Duration::ZERO;
+
+    while running.load(Ordering::Relaxed) {
+        let slots = entries.lock().unwrap().clone();
+
+        for entry in slots {
+            let target = cycle_start + entry.offset.min(cycle);
+            sleep_until(target);
+            let lateness = Instant::now().saturating_duration_since(target);
+
+            let sent = Python::with_gil(|py| iface.send_once(py, entry.id, &entry.data, None));
+
+            let mut stats = stats.lock().unwrap();
+            stats.samples += 1;
+            total_lateness += lateness;
+            stats.mean = Duration::from_nanos(
+                (total_lateness.as_nanos() / u128::from(stats.samples)) as u64,
+            );
+            stats.max = stats.max.max(lateness);
+            if sent.is_err() {
+                stats.send_errors += 1;
+            }
+        }
+
+        cycle_start += cycle;
+        let now = Instant::now();
+        if now > cycle_start + cycle {
+            // Overran a whole cycle - resynchronize rather than bursting to catch up
+            stats.lock().unwrap().overruns += 1;
+            cycle_start = now;
+        }
+        sleep_until(cycle_start);
+    }
+}
@@ -0,0 +1,203 @@
+//! Frame processing scripts, run by the embedded Python interpreter.
+//!
+//! A script is a Python function taking one frame and returning what to
+//! pass on in its place:
+//!
+//! ```text
+//! from types import SimpleNamespace
+//!
+//! def process(frame):
+//!     if frame.arbitration_id == 0x7DF:
+//!         return None                          # drop it
+//!     if frame.arbitration_id == 0x100:
+//!         frame.data[0] ^= 0xFF                # modify it
+//!     if frame.arbitration_id == 0x200:
+//!         ack = SimpleNamespace(arbitration_id=0x201, data=b"\x01")
+//!         return [frame, ack]                  # inject another frame
+//!     return frame
+//! ```
+//!
+//! Frames are namespaces with python-can's `Message` attributes, and
+//! `data` is a mutable `bytearray`. Anything with an `arbitration_id` can
+//! be returned, including a `can.Message`; missing attributes take their
+//! defaults.
+
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use pyo3::{
+    types::{IntoPyDict, PyByteArray, PyList},
+    IntoPy, Py, PyAny, PyResult, Python,
+};
+use thiserror::Error;
+
+use crate::{
+    describe_py_err, message::len_to_dlc, Action, FrameInterceptor, FrameSource, PipeError,
+    PyCanMessage, PythonSource,
+};
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("Failed to load frame script :: `{0}`")]
+    Load(String),
+    #[error("Frame script failed :: `{0}`")]
+    Run(String),
+}
+
+/// A Python function that drops, modifies or adds to frames. See the
+/// [module docs](self).
+pub struct FrameScript {
+    function: Py<PyAny>,
+    errors: AtomicU64,
+}
+
+impl FrameScript {
+    /// Load `function` from `source`.
+    pub fn load(source: PythonSource, function: &str) -> Result<Self, ScriptError> {
+        Python::with_gil(|py| {
+            let function = source
+                .load(py)
+                .and_then(|module| module.getattr(function))
+                .map_err(|e| ScriptError::Load(describe_py_err(&e)))?;
+            if !function.is_callable() {
+                return Err(ScriptError::Load(format!("{function} is not callable")));
+            }
+            Ok(Self {
+                function: function.into(),
+                errors: AtomicU64::new(0),
+            })
+        })
+    }
+
+    /// Run the script on `msg`. An empty result means the frame was
+    /// dropped.
+    pub fn run(&self, msg: &PyCanMessage) -> Result<Vec<PyCanMessage>, ScriptError> {
+        Python::with_gil(|py| {
+            let frame = to_frame(py, msg)?;
+            let result = self.function.as_ref(py).call1((frame,))?;
+            if result.is_none() {
+                Ok(Vec::new())
+            } else if let Ok(list) = result.downcast::<PyList>() {
+                list.iter().map(|item| from_frame(item, msg)).collect()
+            } else {
+                Ok(vec![from_frame(result, msg)?])
+            }
+        })
+        .map_err(|e| ScriptError::Run(describe_py_err(&e)))
+    }
+
+    /// Times the script has raised, or returned something that isn't a
+    /// frame or more than one frame, while running as an interceptor.
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Run the script on every frame `source` produces.
+    pub fn wrap<S: FrameSource>(self, source: S) -> ScriptSource<S> {
+        ScriptSource {
+            script: self,
+            source,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn intercept(&self, msg: &mut PyCanMessage) -> Action {
+        match self.run(msg).as_deref() {
+            Ok([]) => Action::Drop,
+            Ok([frame]) => {
+                *msg = frame.clone();
+                Action::Pass
+            }
+            // An interceptor can't add frames, and silently losing them
+            // would be worse than leaving the frame alone
+            _ => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                Action::Pass
+            }
+        }
+    }
+}
+
+/// Runs the script on received and sent frames. An interceptor can only
+/// replace or drop a frame, so returning several frames is an error; use
+/// [`FrameScript::wrap`] in a pipeline to inject frames. If the script
+/// fails, the frame passes unchanged and [`FrameScript::errors`] goes up.
+impl FrameInterceptor for FrameScript {
+    fn on_rx(&self, msg: &mut PyCanMessage) -> Action {
+        self.intercept(msg)
+    }
+
+    fn on_tx(&self, msg: &mut PyCanMessage) -> Action {
+        self.intercept(msg)
+    }
+}
+
+fn to_frame<'py>(py: Python<'py>, msg: &PyCanMessage) -> PyResult<&'py PyAny> {
+    let data = PyByteArray::new(py, msg.data.as_deref().unwrap_or_default());
+    let kwargs = [
+        ("arbitration_id", msg.arbitration_id.raw().into_py(py)),
+        (
+            "is_extended_id",
+            msg.arbitration_id.is_extended().into_py(py),
+        ),
+        ("data", data.into_py(py)),
+        ("dlc", msg.data_length().into_py(py)),
+        ("timestamp", msg.timestamp.into_py(py)),
+        ("is_error_frame", msg.is_error_frame.into_py(py)),
+        ("is_remote_frame", msg.is_remote_frame.into_py(py)),
+        ("is_fd", msg.is_fd.into_py(py)),
+        ("bitrate_switch", msg.bitrate_switch.into_py(py)),
+        (
+            "error_state_indicator",
+            msg.error_state_indicator.into_py(py),
+        ),
+        ("is_rx", msg.is_rx.into_py(py)),
+    ]
+    .into_py_dict(py);
+    py.import("types")?
+        .getattr("SimpleNamespace")?
+        .call((), Some(kwargs))
+}
+
+/// Extract a returned frame. The payload may have been edited without
+/// updating `dlc`, so the length is taken from `data`; the frame keeps
+/// the interface name and context of the frame it came from.
+fn from_frame(obj: &PyAny, original: &PyCanMessage) -> PyResult<PyCanMessage> {
+    let mut msg: PyCanMessage = obj.extract()?;
+    let len = msg.data.as_ref().map_or(0, Vec::len);
+    if msg.dlc.is_none() || msg.data_length() != len {
+        msg.dlc = len_to_dlc(len);
+        msg.is_fd |= len > 8;
+    }
+    msg.iface_name = original.iface_name.clone();
+    msg.context = original.context.clone();
+    Ok(msg)
+}
+
+/// A [`FrameSource`] with a [`FrameScript`] run on each of its frames.
+pub struct ScriptSource<S> {
+    script: FrameScript,
+    source: S,
+    /// Frames the script returned that haven't been handed out yet.
+    pending: VecDeque<PyCanMessage>,
+}
+
+impl<S: FrameSource> FrameSource for ScriptSource<S> {
+    fn next_frame(&mut self) -> Option<Result<PyCanMessage, PipeError>> {
+        loop {
+            if let Some(msg) = self.pending.pop_front() {
+                return Some(Ok(msg));
+            }
+            let msg = match self.source.next_frame()? {
+                Ok(msg) => msg,
+                Err(e) => return Some(Err(e)),
+            };
+            match self.script.run(&msg) {
+                Ok(frames) => self.pending.extend(frames),
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
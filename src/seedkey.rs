@@ -0,0 +1,219 @@
+//! Key algorithms for UDS SecurityAccess (service 0x27).
+
+use std::{
+    ffi::OsString,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use pyo3::{types::PyBytes, Py, PyAny, Python};
+use thiserror::Error;
+
+use crate::{describe_py_err, PythonSource};
+
+#[derive(Debug, Error)]
+pub enum SeedKeyError {
+    #[error("Failed to run key tool :: `{0}`")]
+    Tool(String),
+    #[error("Python key function failed :: `{0}`")]
+    Python(String),
+    #[error("Key algorithm returned an invalid key :: `{0}`")]
+    InvalidKey(String),
+}
+
+/// Computes the key for a SecurityAccess seed. `level` is the odd
+/// requestSeed sub-function, e.g. 0x01.
+///
+/// Implement this for OEM-specific algorithms, or use one of the
+/// built-in providers.
+pub trait SeedKeyProvider: Send + Sync {
+    fn key(&self, level: u8, seed: &[u8]) -> Result<Vec<u8>, SeedKeyError>;
+}
+
+impl<F> SeedKeyProvider for F
+where
+    F: Fn(u8, &[u8]) -> Result<Vec<u8>, SeedKeyError> + Send + Sync,
+{
+    fn key(&self, level: u8, seed: &[u8]) -> Result<Vec<u8>, SeedKeyError> {
+        self(level, seed)
+    }
+}
+
+/// XORs the seed with a fixed mask, repeated to the seed's length.
+#[derive(Clone, Debug)]
+pub struct XorKey {
+    mask: Vec<u8>,
+}
+
+impl XorKey {
+    pub fn new(mask: &[u8]) -> Self {
+        Self {
+            mask: mask.to_vec(),
+        }
+    }
+}
+
+impl SeedKeyProvider for XorKey {
+    fn key(&self, _level: u8, seed: &[u8]) -> Result<Vec<u8>, SeedKeyError> {
+        if self.mask.is_empty() {
+            return Err(SeedKeyError::InvalidKey("empty XOR mask".into()));
+        }
+        Ok(seed
+            .iter()
+            .zip(self.mask.iter().cycle())
+            .map(|(s, m)| s ^ m)
+            .collect())
+    }
+}
+
+/// Runs an external program, e.g. a vendor's key generator, as
+/// `program [args...] <level> <seed>`, with the level and seed as hex.
+/// The key is read from its stdout as hex, whitespace ignored.
+///
+/// Arguments containing `{level}` or `{seed}` have those replaced
+/// instead, and the level and seed aren't appended. With
+/// [`ExternalKeyTool::seed_on_stdin`], the raw seed is written to the
+/// program's stdin instead.
+#[derive(Clone, Debug)]
+pub struct ExternalKeyTool {
+    program: OsString,
+    args: Vec<String>,
+    seed_on_stdin: bool,
+}
+
+impl ExternalKeyTool {
+    pub fn new(program: impl Into<OsString>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            seed_on_stdin: false,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Write the seed's bytes to the program's stdin rather than passing
+    /// it as an argument.
+    pub fn seed_on_stdin(mut self, enabled: bool) -> Self {
+        self.seed_on_stdin = enabled;
+        self
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, SeedKeyError> {
+    let digits: Vec<u8> = s.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(SeedKeyError::InvalidKey(format!(
+            "odd number of hex digits in `{s}`"
+        )));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| SeedKeyError::InvalidKey(format!("not hex: `{s}`")))
+        })
+        .collect()
+}
+
+impl SeedKeyProvider for ExternalKeyTool {
+    fn key(&self, level: u8, seed: &[u8]) -> Result<Vec<u8>, SeedKeyError> {
+        let level_hex = format!("{level:02X}");
+        let seed_hex = to_hex(seed);
+
+        let templated = self
+            .args
+            .iter()
+            .any(|a| a.contains("{level}") || a.contains("{seed}"));
+        let mut command = Command::new(&self.program);
+        for arg in &self.args {
+            command.arg(
+                arg.replace("{level}", &level_hex)
+                    .replace("{seed}", &seed_hex),
+            );
+        }
+        if !templated {
+            command.arg(&level_hex);
+            if !self.seed_on_stdin {
+                command.arg(&seed_hex);
+            }
+        }
+
+        let mut child = command
+            .stdin(if self.seed_on_stdin {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| SeedKeyError::Tool(format!("{:?}: {e}", self.program)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(seed)
+                .map_err(|e| SeedKeyError::Tool(e.to_string()))?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| SeedKeyError::Tool(e.to_string()))?;
+        if !output.status.success() {
+            let mut msg = format!("{:?} exited with {}", self.program, output.status);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if !stderr.trim().is_empty() {
+                msg = format!("{msg}: {}", stderr.trim());
+            }
+            return Err(SeedKeyError::Tool(msg));
+        }
+
+        from_hex(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// Calls a Python function as `function(level, seed)`, with the seed as
+/// `bytes`. It must return the key as `bytes`, `bytearray` or a list of
+/// ints.
+pub struct PythonKeyFunction {
+    function: Py<PyAny>,
+}
+
+impl PythonKeyFunction {
+    /// Look up `function` in `source`.
+    pub fn load(source: PythonSource, function: &str) -> Result<Self, SeedKeyError> {
+        Python::with_gil(|py| {
+            let function = source
+                .load(py)
+                .and_then(|module| module.getattr(function))
+                .map_err(|e| SeedKeyError::Python(describe_py_err(&e)))?;
+            if !function.is_callable() {
+                return Err(SeedKeyError::Python(format!("{function} is not callable")));
+            }
+            Ok(Self {
+                function: function.into(),
+            })
+        })
+    }
+}
+
+impl SeedKeyProvider for PythonKeyFunction {
+    fn key(&self, level: u8, seed: &[u8]) -> Result<Vec<u8>, SeedKeyError> {
+        Python::with_gil(|py| {
+            let key = self
+                .function
+                .call1(py, (level, PyBytes::new(py, seed)))
+                .map_err(|e| SeedKeyError::Python(describe_py_err(&e)))?;
+            key.extract::<Vec<u8>>(py)
+                .map_err(|e| SeedKeyError::InvalidKey(describe_py_err(&e)))
+        })
+    }
+}
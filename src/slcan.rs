@@ -0,0 +1,258 @@
+//! Native slcan driver.
+//!
+//! slcan is a line-based ASCII protocol over a serial port, so it doesn't
+//! need python-can at all. This driver talks to the tty directly, saving
+//! the Python round-trip on every frame. Unix only.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    os::fd::AsRawFd,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{tx::TxPacer, CanBus, CanId, PyCanBusType, PyCanError, PyCanMessage};
+
+/// `S<n>` setup codes, by bitrate.
+const BITRATES: [(u32, u8); 9] = [
+    (10_000, 0),
+    (20_000, 1),
+    (50_000, 2),
+    (100_000, 3),
+    (125_000, 4),
+    (250_000, 5),
+    (500_000, 6),
+    (800_000, 7),
+    (1_000_000, 8),
+];
+
+/// An slcan adapter opened without python-can. Closed on drop.
+pub struct SlcanBus {
+    port: Mutex<File>,
+    /// Bytes read but not yet parsed into a frame.
+    rx: Mutex<(File, Vec<u8>)>,
+    name: Arc<str>,
+    pacer: Option<TxPacer>,
+}
+
+fn io_error(e: std::io::Error) -> PyCanError {
+    PyCanError::BackendRequestFailed(e.to_string())
+}
+
+/// Put the tty in raw mode. USB slcan adapters ignore the baud rate, but
+/// serial ones don't, so 115200 is set like python-can does.
+fn configure_tty(port: &File) -> std::io::Result<()> {
+    let fd = port.as_raw_fd();
+    // SAFETY: termios is plain data and fd is a valid open descriptor
+    unsafe {
+        let mut tio: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut tio) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        libc::cfmakeraw(&mut tio);
+        libc::cfsetspeed(&mut tio, libc::B115200);
+        if libc::tcsetattr(fd, libc::TCSANOW, &tio) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Wait until `port` is readable or `timeout` passes.
+fn wait_readable(port: &File, timeout: Option<Duration>) -> std::io::Result<bool> {
+    let mut fds = libc::pollfd {
+        fd: port.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let ms = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+
+    // SAFETY: fds points to one valid pollfd
+    match unsafe { libc::poll(&mut fds, 1, ms) } {
+        -1 => Err(std::io::Error::last_os_error()),
+        n => Ok(n > 0),
+    }
+}
+
+fn hex(s: &[u8]) -> Option<u32> {
+    u32::from_str_radix(std::str::from_utf8(s).ok()?, 16).ok()
+}
+
+/// Parse one line (without the trailing `\r`). Returns None for anything
+/// that isn't a frame, e.g. acknowledgements.
+fn parse_frame(line: &[u8]) -> Option<PyCanMessage> {
+    let (kind, rest) = line.split_first()?;
+    let (id_len, extended, remote) = match kind {
+        b't' => (3, false, false),
+        b'T' => (8, true, false),
+        b'r' => (3, false, true),
+        b'R' => (8, true, true),
+        _ => return None,
+    };
+
+    let id = CanId::new(hex(rest.get(..id_len)?)?, extended).ok()?;
+    let dlc = hex(rest.get(id_len..id_len + 1)?)? as u8;
+    let len = usize::from(dlc.min(8));
+
+    let mut msg = PyCanMessage::new(id, &[]);
+    msg.dlc = Some(dlc);
+    msg.is_remote_frame = remote;
+    if !remote {
+        let data = rest.get(id_len + 1..id_len + 1 + 2 * len)?;
+        msg.data = Some(
+            data.chunks(2)
+                .map(|pair| hex(pair).map(|b| b as u8))
+                .collect::<Option<_>>()?,
+        );
+    }
+    Some(msg)
+}
+
+fn format_frame(msg: &PyCanMessage) -> Result<String, PyCanError> {
+    let data = msg.data.as_deref().unwrap_or_default();
+    if msg.is_fd || data.len() > 8 {
+        return Err(PyCanError::Unsupported("CAN FD frames over slcan".into()));
+    }
+
+    let id = msg.arbitration_id;
+    let kind = match (id.is_extended(), msg.is_remote_frame) {
+        (false, false) => 't',
+        (true, false) => 'T',
+        (false, true) => 'r',
+        (true, true) => 'R',
+    };
+    // The DLC digit is all a remote frame carries; a data frame's has to
+    // agree with its payload, or the adapter reads the wrong bytes
+    let dlc = match msg.dlc {
+        Some(dlc) if msg.is_remote_frame && dlc > 8 => {
+            return Err(PyCanError::FailedToSend(format!(
+                "DLC {dlc} of remote frame {id} is over 8"
+            )))
+        }
+        Some(dlc) if !msg.is_remote_frame && usize::from(dlc) != data.len() => {
+            return Err(PyCanError::FailedToSend(format!(
+                "DLC {dlc} of {id} doesn't match its {} data bytes",
+                data.len()
+            )))
+        }
+        Some(dlc) => dlc,
+        None => data.len() as u8,
+    };
+
+    let mut line = format!("{kind}{id}{dlc:X}");
+    if !msg.is_remote_frame {
+        line.extend(data.iter().map(|b| format!("{b:02X}")));
+    }
+    line.push('\r');
+    Ok(line)
+}
+
+impl SlcanBus {
+    /// Open the adapter described by an slcan bus type and start the
+    /// channel at its bitrate.
+    pub fn open(kind: &PyCanBusType) -> Result<Self, PyCanError> {
+        let PyCanBusType::Slcan {
+            bitrate,
+            serial_port,
+        } = kind
+        else {
+            return Err(PyCanError::Unsupported(format!("native slcan on {kind}")));
+        };
+
+        let code = BITRATES
+            .iter()
+            .find(|(rate, _)| rate == bitrate)
+            .map(|(_, code)| code)
+            .ok_or_else(|| {
+                PyCanError::FailedToCreateInterface(format!("unsupported slcan bitrate {bitrate}"))
+            })?;
+
+        let open = || -> std::io::Result<(File, File)> {
+            let port = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(serial_port)?;
+            configure_tty(&port)?;
+            let reader = port.try_clone()?;
+            Ok((port, reader))
+        };
+        let (mut port, reader) = open().map_err(|e| PyCanError::open_failed(serial_port, e))?;
+
+        // Close first in case the channel was left open
+        port.write_all(format!("C\rS{code}\rO\r").as_bytes())
+            .map_err(|e| PyCanError::FailedToCreateInterface(e.to_string()))?;
+
+        Ok(Self {
+            port: Mutex::new(port),
+            rx: Mutex::new((reader, Vec::new())),
+            name: serial_port.as_str().into(),
+            pacer: None,
+        })
+    }
+
+    /// Leave at least `gap` between frames sent, as
+    /// [`crate::PyCanInterfaceBuilder::tx_gap`] does for python-can buses.
+    pub fn tx_gap(mut self, gap: Duration) -> Self {
+        self.pacer = Some(TxPacer::new(gap));
+        self
+    }
+}
+
+impl CanBus for SlcanBus {
+    fn send_frame(&self, msg: &PyCanMessage) -> Result<(), PyCanError> {
+        let line = format_frame(msg)?;
+        if let Some(pacer) = &self.pacer {
+            pacer.wait();
+        }
+        self.port
+            .lock()
+            .unwrap()
+            .write_all(line.as_bytes())
+            .map_err(|e| PyCanError::FailedToSend(e.to_string()))
+    }
+
+    fn recv_frame(&self, timeout: Option<Duration>) -> Result<Option<PyCanMessage>, PyCanError> {
+        // A timeout too long to represent waits forever
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        let mut rx = self.rx.lock().unwrap();
+        let (port, buf) = &mut *rx;
+
+        loop {
+            // Handle everything already buffered before reading more
+            while let Some(end) = buf.iter().position(|&b| b == b'\r' || b == 0x07) {
+                let line: Vec<u8> = buf.drain(..=end).collect();
+                if let Some(mut msg) = parse_frame(&line[..end]) {
+                    msg.iface_name = Some(self.name.clone());
+                    return Ok(Some(msg));
+                }
+            }
+
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if remaining == Some(Duration::ZERO)
+                || !wait_readable(port, remaining).map_err(io_error)?
+            {
+                return Ok(None);
+            }
+
+            let mut chunk = [0; 256];
+            // Readable with nothing to read means the device went away
+            let n = match port.read(&mut chunk).map_err(io_error)? {
+                0 => {
+                    return Err(io_error(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "serial port closed",
+                    )))
+                }
+                n => n,
+            };
+            buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+}
+
+impl Drop for SlcanBus {
+    fn drop(&mut self) {
+        let _ = self.port.lock().unwrap().write_all(b"C\r");
+    }
+}
@@ -0,0 +1,354 @@
+//! Native socketcand client.
+//!
+//! socketcand exposes a remote SocketCAN interface over TCP with an ASCII
+//! protocol of `< command args >` elements. This client puts the
+//! connection in raw mode, where every frame on the bus is forwarded,
+//! and talks it directly instead of through python-can.
+//!
+//! Raw mode has no server-side filtering, so filters are applied as
+//! frames arrive.
+//!
+//! [`SocketcandTasks`] uses a second connection in socketcand's default
+//! broadcast manager mode to run cyclic transmissions on the server.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{CanBus, CanId, Filter, PyCanBusType, PyCanError, PyCanInterface, PyCanMessage};
+
+/// How long to wait for each reply while connecting.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for an error after a broadcast manager command.
+/// socketcand only replies to commands that fail.
+const COMMAND_ERROR_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A socketcand channel opened without python-can.
+pub struct SocketcandBus {
+    tx: Mutex<TcpStream>,
+    /// Bytes read but not yet parsed into an element.
+    rx: Mutex<(TcpStream, Vec<u8>)>,
+    filters: Mutex<Vec<Filter>>,
+    name: Arc<str>,
+}
+
+fn io_error(e: std::io::Error) -> PyCanError {
+    PyCanError::BackendRequestFailed(e.to_string())
+}
+
+/// Take the next complete `< ... >` element out of `buf`, without its
+/// brackets.
+fn next_element(buf: &mut Vec<u8>) -> Option<String> {
+    let start = buf.iter().position(|&b| b == b'<')?;
+    let end = start + buf[start..].iter().position(|&b| b == b'>')?;
+    let element = String::from_utf8_lossy(&buf[start + 1..end])
+        .trim()
+        .to_owned();
+    buf.drain(..=end);
+    Some(element)
+}
+
+/// Parse a raw mode `frame` or `error` element. Returns None for anything
+/// else.
+fn parse_element(element: &str) -> Option<PyCanMessage> {
+    let mut fields = element.split_whitespace();
+    let kind = fields.next()?;
+    let id = fields.next()?;
+    let timestamp: f64 = fields.next()?.parse().ok()?;
+
+    let raw = u32::from_str_radix(id, 16).ok()?;
+    let mut msg = match kind {
+        "frame" => {
+            let data = fields.next().unwrap_or_default();
+            let data = (0..data.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(data.get(i..i + 2)?, 16).ok())
+                .collect::<Option<Vec<u8>>>()?;
+            // Extended IDs are always sent as 8 digits
+            PyCanMessage::new(CanId::new(raw, id.len() == 8).ok()?, &data)
+        }
+        "error" => {
+            let mut msg = PyCanMessage::new(CanId::new(raw, raw > 0x7FF).ok()?, &[]);
+            msg.is_error_frame = true;
+            msg
+        }
+        _ => return None,
+    };
+    msg.timestamp = Some(timestamp);
+    Some(msg)
+}
+
+impl SocketcandBus {
+    /// Connect to the server described by a socketcand bus type and open
+    /// its channel in raw mode.
+    pub fn open(kind: &PyCanBusType) -> Result<Self, PyCanError> {
+        let bus = Self::connect(kind)?;
+        bus.command("rawmode")
+            .and_then(|_| bus.expect("ok"))
+            .map_err(|why| PyCanError::FailedToCreateInterface(format!("{}: {why}", bus.name)))?;
+        Ok(bus)
+    }
+
+    /// Connect and open the channel, leaving the connection in broadcast
+    /// manager mode.
+    fn connect(kind: &PyCanBusType) -> Result<Self, PyCanError> {
+        let PyCanBusType::Socketcand {
+            host,
+            channel,
+            port,
+        } = kind
+        else {
+            return Err(PyCanError::Unsupported(format!(
+                "native socketcand on {kind}"
+            )));
+        };
+        let fail =
+            |why: String| PyCanError::FailedToCreateInterface(format!("{host}:{port}: {why}"));
+
+        let stream = TcpStream::connect((host.as_str(), *port))
+            .map_err(|e| PyCanError::open_failed(&format!("{host}:{port}"), e))?;
+        stream.set_nodelay(true).map_err(|e| fail(e.to_string()))?;
+        let bus = Self {
+            tx: Mutex::new(stream.try_clone().map_err(|e| fail(e.to_string()))?),
+            rx: Mutex::new((stream, Vec::new())),
+            filters: Mutex::new(Vec::new()),
+            name: format!("{host}:{port}/{channel}").into(),
+        };
+
+        bus.expect("hi").map_err(fail)?;
+        bus.command(&format!("open {channel}")).map_err(fail)?;
+        bus.expect("ok").map_err(fail)?;
+        Ok(bus)
+    }
+
+    /// Only receive frames matching at least one of `filters`. An empty
+    /// list receives everything. Error frames are always received.
+    pub fn set_filters(&self, filters: Vec<Filter>) {
+        *self.filters.lock().unwrap() = filters;
+    }
+
+    fn command(&self, command: &str) -> Result<(), String> {
+        self.tx
+            .lock()
+            .unwrap()
+            .write_all(format!("< {command} >").as_bytes())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Wait for the next element during the handshake and check it's
+    /// `reply`.
+    fn expect(&self, reply: &str) -> Result<(), String> {
+        match self.next(Some(HANDSHAKE_TIMEOUT)) {
+            Ok(Some(element)) if element == reply => Ok(()),
+            Ok(Some(element)) => Err(format!("expected < {reply} >, got < {element} >")),
+            Ok(None) => Err(format!("timed out waiting for < {reply} >")),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// The next element from the server, waiting up to `timeout`.
+    fn next(&self, timeout: Option<Duration>) -> std::io::Result<Option<String>> {
+        // A timeout too long to represent waits forever
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        let mut rx = self.rx.lock().unwrap();
+        let (stream, buf) = &mut *rx;
+
+        loop {
+            if let Some(element) = next_element(buf) {
+                return Ok(Some(element));
+            }
+
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if remaining == Some(Duration::ZERO) {
+                return Ok(None);
+            }
+            stream.set_read_timeout(remaining)?;
+
+            let mut chunk = [0; 1024];
+            match stream.read(&mut chunk) {
+                Ok(0) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "socketcand closed the connection",
+                    ))
+                }
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl CanBus for SocketcandBus {
+    fn send_frame(&self, msg: &PyCanMessage) -> Result<(), PyCanError> {
+        let data = msg.data.as_deref().unwrap_or_default();
+        if msg.is_fd || msg.is_remote_frame || data.len() > 8 {
+            return Err(PyCanError::Unsupported(
+                "CAN FD and remote frames over socketcand".into(),
+            ));
+        }
+
+        let dlc = data.len();
+        if let Some(given) = msg.dlc.filter(|&d| usize::from(d) != dlc) {
+            return Err(PyCanError::FailedToSend(format!(
+                "DLC {given} of {} doesn't match its {dlc} data bytes",
+                msg.arbitration_id
+            )));
+        }
+        let mut command = format!("send {} {dlc}", msg.arbitration_id);
+        command.extend(data.iter().map(|b| format!(" {b:02X}")));
+        self.command(&command).map_err(PyCanError::FailedToSend)
+    }
+
+    fn recv_frame(&self, timeout: Option<Duration>) -> Result<Option<PyCanMessage>, PyCanError> {
+        // A timeout too long to represent waits forever
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+
+        loop {
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            let Some(element) = self.next(remaining).map_err(io_error)? else {
+                return Ok(None);
+            };
+            let Some(mut msg) = parse_element(&element) else {
+                continue;
+            };
+
+            let filters = self.filters.lock().unwrap();
+            if msg.is_error_frame
+                || filters.is_empty()
+                || filters.iter().any(|f| f.matches(msg.arbitration_id))
+            {
+                msg.iface_name = Some(self.name.clone());
+                return Ok(Some(msg));
+            }
+        }
+    }
+}
+
+/// `id dlc data...` as socketcand's commands take them.
+fn frame_args(id: CanId, data: &[u8]) -> Result<String, PyCanError> {
+    if data.len() > 8 {
+        return Err(PyCanError::Unsupported("CAN FD over socketcand".into()));
+    }
+    let mut args = format!("{id} {}", data.len());
+    args.extend(data.iter().map(|b| format!(" {b:02X}")));
+    Ok(args)
+}
+
+/// Cyclic transmissions run by the socketcand server's broadcast manager,
+/// so their timing doesn't depend on this process being scheduled.
+///
+/// socketcand ties the tasks to this connection: they all stop when it's
+/// closed, including when it's dropped or the process exits.
+pub struct SocketcandTasks {
+    conn: SocketcandBus,
+    /// Period of each running task.
+    tasks: Mutex<HashMap<CanId, Duration>>,
+}
+
+impl SocketcandTasks {
+    /// Open a broadcast manager connection to the server described by a
+    /// socketcand bus type.
+    pub fn open(kind: &PyCanBusType) -> Result<Self, PyCanError> {
+        Ok(Self {
+            conn: SocketcandBus::connect(kind)?,
+            tasks: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Start sending `data` on `id` every `period`. There's one task per ID;
+    /// adding one for an ID that already has a task replaces it. Fails if
+    /// the server rejects the task, e.g. for a channel it can't send on.
+    pub fn add(&self, id: CanId, data: &[u8], period: Duration) -> Result<(), PyCanError> {
+        if period.is_zero() {
+            return Err(PyCanError::PeriodicTaskFailed(format!(
+                "{id}: period must be non-zero"
+            )));
+        }
+        let command = format!(
+            "add {} {} {}",
+            period.as_secs(),
+            period.subsec_micros(),
+            frame_args(id, data)?
+        );
+        self.run(&command)?;
+        self.tasks.lock().unwrap().insert(id, period);
+        Ok(())
+    }
+
+    /// Change the payload of the task on `id`, from its next cycle.
+    pub fn update(&self, id: CanId, data: &[u8]) -> Result<(), PyCanError> {
+        self.task(id)?;
+        self.run(&format!("update {}", frame_args(id, data)?))
+    }
+
+    /// Stop the task on `id`.
+    pub fn delete(&self, id: CanId) -> Result<(), PyCanError> {
+        self.task(id)?;
+        self.run(&format!("delete {id}"))?;
+        self.tasks.lock().unwrap().remove(&id);
+        Ok(())
+    }
+
+    /// IDs with a running task and their periods.
+    pub fn tasks(&self) -> Vec<(CanId, Duration)> {
+        let mut tasks: Vec<_> = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, period)| (*id, *period))
+            .collect();
+        tasks.sort();
+        tasks
+    }
+
+    fn task(&self, id: CanId) -> Result<(), PyCanError> {
+        if self.tasks.lock().unwrap().contains_key(&id) {
+            Ok(())
+        } else {
+            Err(PyCanError::PeriodicTaskFailed(format!("no task on {id}")))
+        }
+    }
+
+    /// Send a command and check the server didn't reject it.
+    fn run(&self, command: &str) -> Result<(), PyCanError> {
+        self.conn
+            .command(command)
+            .map_err(PyCanError::PeriodicTaskFailed)?;
+
+        let deadline = Instant::now() + COMMAND_ERROR_TIMEOUT;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.conn.next(Some(remaining)) {
+                Ok(Some(element)) if element.starts_with("error") => {
+                    return Err(PyCanError::PeriodicTaskFailed(format!(
+                        "< {command} >: < {element} >"
+                    )))
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => return Ok(()),
+                Err(e) => return Err(PyCanError::PeriodicTaskFailed(e.to_string())),
+            }
+        }
+    }
+}
+
+impl PyCanInterface {
+    /// Open a connection for running cyclic transmissions on this
+    /// interface's socketcand server. Fails with Unsupported for other bus
+    /// types.
+    pub fn socketcand_tasks(&self) -> Result<SocketcandTasks, PyCanError> {
+        SocketcandTasks::open(&self.bustype)
+    }
+}
@@ -0,0 +1,73 @@
+use std::{sync::atomic::Ordering, time::Duration};
+
+use pyo3::{intern, Py, PyAny, Python};
+
+use crate::{describe_py_err, PyCanError, PyCanEvent, PyCanInterface};
+
+/// Mirrors python-can's `can.BusState`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BusState {
+    Active,
+    Passive,
+    /// Bus-off.
+    Error,
+}
+
+impl BusState {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ACTIVE" => Some(Self::Active),
+            "PASSIVE" => Some(Self::Passive),
+            "ERROR" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+fn read_state(py: Python, iface: &Py<PyAny>) -> Result<BusState, PyCanError> {
+    let name: String = iface
+        .getattr(py, intern!(py, "state"))
+        .and_then(|state| state.getattr(py, intern!(py, "name")))
+        .and_then(|name| name.extract(py))
+        .map_err(|e| PyCanError::FailedToGetState(describe_py_err(&e)))?;
+
+    BusState::from_name(&name)
+        .ok_or_else(|| PyCanError::FailedToGetState(format!("unknown bus state {name}")))
+}
+
+impl PyCanInterface {
+    /// Current bus state, as reported by the backend. Backends that don't
+    /// track state always report `Active`.
+    pub fn state(&self) -> Result<BusState, PyCanError> {
+        Python::with_gil(|py| read_state(py, &self.iface))
+    }
+
+    /// Poll the bus state every `interval` in the background and report
+    /// transitions as [`PyCanEvent::BusStateChanged`] events. Polling stops
+    /// when the interface is dropped.
+    pub fn watch_bus_state(&self, interval: Duration) -> Result<(), PyCanError> {
+        let mut last = self.state()?;
+        let bus = self.state_bus.clone();
+        let events = self.events.clone();
+        let alive = self.alive.clone();
+
+        std::thread::spawn(move || {
+            while alive.load(Ordering::Relaxed) {
+                std::thread::sleep(interval);
+
+                // Looked up each time, as `reopen` may have replaced it
+                let state = Python::with_gil(|py| {
+                    let iface = bus.lock().unwrap().clone_ref(py);
+                    read_state(py, &iface)
+                });
+
+                if let Some(new) = state.ok().filter(|s| *s != last) {
+                    events.emit(PyCanEvent::BusStateChanged { old: last, new });
+                    last = new;
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
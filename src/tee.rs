@@ -0,0 +1,104 @@
+//! Logging several interfaces into one multi-channel capture.
+
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{ListenerId, LogError, PyCanError, PyCanInterface, TrcWriter};
+
+struct Log {
+    writer: TrcWriter<BufWriter<File>>,
+    /// First write error since the last flush.
+    error: Option<LogError>,
+}
+
+/// Writes every frame received on a set of interfaces to one TRC file,
+/// with each interface in its own bus column. Stops on drop.
+///
+/// Frames are logged with `corrected_timestamp` where the interface has
+/// drift correction enabled, so buses whose adapters have their own
+/// clocks share the host's timebase.
+pub struct BusTee {
+    log: Arc<Mutex<Log>>,
+    listeners: Vec<(Arc<PyCanInterface>, ListenerId)>,
+}
+
+impl BusTee {
+    /// Start logging `buses` to `path`. Bus numbers follow the order of
+    /// `buses`, starting at 1, and are named after each interface in the
+    /// file's header. TRC bus numbers are a byte, so at most 255 buses
+    /// can be logged.
+    pub fn create(
+        path: impl AsRef<Path>,
+        buses: &[Arc<PyCanInterface>],
+    ) -> Result<Self, PyCanError> {
+        let capture_error = |e: LogError| PyCanError::CaptureFailed(e.to_string());
+        if buses.len() > usize::from(u8::MAX) {
+            return Err(PyCanError::CaptureFailed(format!(
+                "{} buses, but a TRC file holds at most {}",
+                buses.len(),
+                u8::MAX
+            )));
+        }
+        let start = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+
+        let mut writer = TrcWriter::create(path)
+            .map_err(capture_error)?
+            .start_time(start);
+        for (bus, iface) in (1..=u8::MAX).zip(buses) {
+            writer = writer.bus_name(bus, iface.name());
+        }
+        let log = Arc::new(Mutex::new(Log {
+            writer,
+            error: None,
+        }));
+
+        let mut tee = Self {
+            log,
+            listeners: Vec::new(),
+        };
+        for (bus, iface) in (1..=u8::MAX).zip(buses) {
+            let log = tee.log.clone();
+            let id = iface.register_rx_callback_all(
+                move |msg| {
+                    let mut msg = msg.clone();
+                    msg.timestamp = msg.corrected_timestamp.or(msg.timestamp);
+
+                    let mut log = log.lock().unwrap();
+                    if let Err(e) = log.writer.write_on_bus(&msg, bus) {
+                        log.error.get_or_insert(e);
+                    }
+                },
+                |_| {},
+            )?;
+            tee.listeners.push((iface.clone(), id));
+        }
+        Ok(tee)
+    }
+
+    /// Flush buffered frames to the file. Also reports the first write
+    /// error since the last flush.
+    pub fn flush(&self) -> Result<(), PyCanError> {
+        let mut log = self.log.lock().unwrap();
+        match log.error.take() {
+            Some(e) => Err(e),
+            None => log.writer.flush(),
+        }
+        .map_err(|e| PyCanError::CaptureFailed(e.to_string()))
+    }
+}
+
+impl Drop for BusTee {
+    fn drop(&mut self) {
+        for (iface, id) in &self.listeners {
+            let _ = iface.remove_listener(*id);
+        }
+    }
+}
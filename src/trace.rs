@@ -0,0 +1,98 @@
+//! Built-in trace logging of every frame sent and received.
+//!
+//! Each frame is written as one `key=value` line, after interceptors have
+//! run, so the log shows what callbacks saw and what went to python-can.
+//! A received frame is traced once, however many listeners it's passed
+//! to.
+//! Payloads can be masked before they're written, e.g. to keep security
+//! access seeds and keys out of field logs.
+
+use std::{fmt::Debug, sync::Arc};
+
+use crate::{CanId, PyCanInterface, PyCanMessage};
+
+type Redactor = dyn Fn(&PyCanMessage) -> Option<Vec<u8>> + Send + Sync;
+type Sink = dyn Fn(&str) + Send + Sync;
+
+/// Writes a line per frame, to stderr unless another sink is set.
+#[derive(Clone)]
+pub struct FrameTracer {
+    redact: Option<Arc<Redactor>>,
+    sink: Arc<Sink>,
+}
+
+impl Default for FrameTracer {
+    fn default() -> Self {
+        Self {
+            redact: None,
+            sink: Arc::new(|line| eprintln!("{line}")),
+        }
+    }
+}
+
+impl Debug for FrameTracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameTracer")
+            .field("redact", &self.redact.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl FrameTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Called for every frame before it's logged. Returning a payload logs
+    /// it instead of the frame's own; the frame itself is never changed.
+    /// `is_rx` tells received frames from sent ones.
+    pub fn redact(
+        mut self,
+        redact: impl Fn(&PyCanMessage) -> Option<Vec<u8>> + Send + Sync + 'static,
+    ) -> Self {
+        self.redact = Some(Arc::new(redact));
+        self
+    }
+
+    /// Where lines go, e.g. a `log` or `tracing` macro.
+    pub fn sink(mut self, sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.sink = Arc::new(sink);
+        self
+    }
+
+    pub(crate) fn trace(&self, msg: &PyCanMessage) {
+        let redacted = self.redact.as_ref().and_then(|redact| redact(msg));
+        let data = redacted
+            .as_deref()
+            .or(msg.data.as_deref())
+            .unwrap_or_default();
+
+        let mut line = format!(
+            "TRACE pycanrs: iface={} dir={} id={} dlc={} data=",
+            msg.iface_name.as_deref().unwrap_or("-"),
+            if msg.is_rx { "rx" } else { "tx" },
+            msg.arbitration_id,
+            msg.dlc.map_or("-".into(), |dlc| dlc.to_string()),
+        );
+        line.extend(data.iter().map(|b| format!("{b:02X}")));
+        if redacted.is_some() {
+            line.push_str(" redacted=true");
+        }
+        if let Some(timestamp) = msg.timestamp {
+            line.push_str(&format!(" ts={timestamp:.6}"));
+        }
+        (self.sink)(&line);
+    }
+}
+
+impl PyCanInterface {
+    /// Trace a frame that's about to be sent.
+    pub(crate) fn trace_tx(&self, id: CanId, data: &[u8]) {
+        if let Some(tracer) = &self.tracer {
+            let mut msg = PyCanMessage::new(id, data);
+            msg.is_rx = false;
+            msg.iface_name = Some(self.name.clone());
+            tracer.trace(&msg);
+        }
+    }
+}
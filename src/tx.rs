@@ -0,0 +1,327 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use pyo3::{intern, PyErr, PyResult, Python};
+
+use crate::{
+    describe_py_err, make_message, CanId, GilOp, PyCanError, PyCanInterface, PyCanMessage,
+};
+
+/// Identifies a frame passed to [`crate::PyCanInterface::send`], so its
+/// transmission can be matched to a [`crate::PyCanEvent::TxConfirmation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TxToken(u64);
+
+impl TxToken {
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Oldest unconfirmed frames are forgotten beyond this, in case the
+/// backend drops echoes.
+const MAX_PENDING: usize = 1024;
+
+type Pending = VecDeque<(TxToken, CanId, Vec<u8>)>;
+
+/// Frames sent but not yet echoed back by the backend.
+#[derive(Clone, Default)]
+pub(crate) struct PendingTx(Arc<Mutex<Pending>>);
+
+impl PendingTx {
+    pub(crate) fn push(&self, id: CanId, data: &[u8]) -> TxToken {
+        let token = TxToken::next();
+        let mut pending = self.0.lock().unwrap();
+        if pending.len() >= MAX_PENDING {
+            pending.pop_front();
+        }
+        pending.push_back((token, id, data.to_vec()));
+        token
+    }
+
+    /// If `msg` is the echo of a pending frame, forget it and return its
+    /// token. Echoes of identical frames are matched in send order.
+    pub(crate) fn confirm(&self, msg: &PyCanMessage) -> Option<TxToken> {
+        if msg.is_rx {
+            return None;
+        }
+
+        let data = msg.data.as_deref().unwrap_or_default();
+        let mut pending = self.0.lock().unwrap();
+        let idx = pending
+            .iter()
+            .position(|(_, id, d)| *id == msg.arbitration_id && d == data)?;
+
+        pending.remove(idx).map(|(token, _, _)| token)
+    }
+
+    /// Forget a frame that was never sent.
+    pub(crate) fn cancel(&self, token: TxToken) {
+        self.0.lock().unwrap().retain(|(t, _, _)| *t != token);
+    }
+}
+
+/// Enforces a minimum gap between transmitted frames.
+pub(crate) struct TxPacer {
+    gap: Duration,
+    /// When the last frame was handed to the backend.
+    last: Mutex<Option<Instant>>,
+}
+
+impl TxPacer {
+    pub(crate) fn new(gap: Duration) -> Self {
+        Self {
+            gap,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Wait until the gap since the last frame has passed, then claim the
+    /// next slot. Concurrent senders queue up behind each other.
+    pub(crate) fn wait(&self) {
+        let mut last = self.last.lock().unwrap();
+        if let Some(remaining) = last.and_then(|t| self.gap.checked_sub(t.elapsed())) {
+            std::thread::sleep(remaining);
+        }
+        *last = Some(Instant::now());
+    }
+
+    /// Claim the next slot if the gap has passed, without waiting.
+    fn try_claim(&self) -> Option<TxSlot> {
+        let mut last = self.last.lock().unwrap();
+        if last.is_some_and(|t| t.elapsed() < self.gap) {
+            return None;
+        }
+        let claimed = Instant::now();
+        Some(TxSlot {
+            claimed,
+            previous: last.replace(claimed),
+        })
+    }
+
+    /// Hand back a slot whose frame wasn't sent, unless another frame has
+    /// claimed a later one since.
+    fn refund(&self, slot: TxSlot) {
+        let mut last = self.last.lock().unwrap();
+        if *last == Some(slot.claimed) {
+            *last = slot.previous;
+        }
+    }
+}
+
+/// A slot claimed from a [`TxPacer`].
+struct TxSlot {
+    claimed: Instant,
+    previous: Option<Instant>,
+}
+
+/// How [`crate::PyCanInterface::send_with_retry`] retries failed sends.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first.
+    pub max_attempts: u32,
+    /// Delay before the first retry. Doubles on each retry, up to
+    /// `max_backoff`.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Per-attempt timeout passed to python-can's `send`. None blocks.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(100),
+            timeout: Some(Duration::from_millis(100)),
+        }
+    }
+}
+
+impl PyCanInterface {
+    /// Token for a frame about to be sent, tracked for confirmation if
+    /// enabled.
+    pub(crate) fn tx_token(&self, id: CanId, data: &[u8]) -> TxToken {
+        match &self.pending_tx {
+            Some(pending) => pending.push(id, data),
+            None => TxToken::next(),
+        }
+    }
+
+    /// Send one frame with python-can, with an optional timeout, after
+    /// waiting out the inter-frame gap if one is set.
+    pub(crate) fn send_once(
+        &self,
+        py: Python,
+        id: CanId,
+        data: &[u8],
+        timeout: Option<Duration>,
+    ) -> PyResult<()> {
+        if let Some(pacer) = &self.pacer {
+            py.allow_threads(|| pacer.wait());
+        }
+        self.send_unpaced(py, id, data, timeout)
+    }
+
+    fn send_unpaced(
+        &self,
+        py: Python,
+        id: CanId,
+        data: &[u8],
+        timeout: Option<Duration>,
+    ) -> PyResult<()> {
+        #[cfg(feature = "trace")]
+        self.trace_tx(id, data);
+
+        let timeout = timeout.map(|t| t.as_secs_f64());
+        let res = self.gil.time(GilOp::Send, || {
+            let msg = make_message(py, &self.pycan, id, data)?;
+            self.iface
+                .call_method1(py, intern!(py, "send"), (msg, timeout))
+        });
+        self.health.record_tx(res.is_ok());
+        res.map(|_| ())
+    }
+
+    /// Whether a send error is worth retrying: python-can's
+    /// CanOperationError and CanTimeoutError. python-can 3.x doesn't have
+    /// those, so any CanError is retried there.
+    fn is_transient(&self, py: Python, err: &PyErr) -> bool {
+        let pycan = self.pycan.as_ref(py);
+        let transient = ["CanOperationError", "CanTimeoutError"]
+            .iter()
+            .filter_map(|name| pycan.getattr(*name).ok())
+            .collect::<Vec<_>>();
+
+        if transient.is_empty() {
+            return pycan
+                .getattr("CanError")
+                .is_ok_and(|cls| err.is_instance(py, cls));
+        }
+
+        transient.into_iter().any(|cls| err.is_instance(py, cls))
+    }
+
+    /// Whether a send error means the adapter's transmit queue is full:
+    /// python-can's CanTimeoutError, or an OSError / CanOperationError
+    /// carrying ENOBUFS or EAGAIN.
+    fn is_queue_full(&self, py: Python, err: &PyErr) -> bool {
+        let pycan = self.pycan.as_ref(py);
+        if pycan
+            .getattr("CanTimeoutError")
+            .is_ok_and(|cls| err.is_instance(py, cls))
+        {
+            return true;
+        }
+
+        // socketcan raises CanOperationError("Transmit buffer full") when
+        // the socket doesn't become writable within the timeout
+        let value = err.value(py);
+        if pycan
+            .getattr("CanOperationError")
+            .is_ok_and(|cls| err.is_instance(py, cls))
+            && value
+                .str()
+                .is_ok_and(|s| s.to_string_lossy().contains("buffer full"))
+        {
+            return true;
+        }
+
+        let code = ["errno", "error_code"]
+            .iter()
+            .find_map(|attr| value.getattr(*attr).ok()?.extract::<i64>().ok());
+        let Ok(codes) = py.import("errno") else {
+            return false;
+        };
+        ["ENOBUFS", "EAGAIN"]
+            .iter()
+            .filter_map(|name| codes.getattr(*name).ok()?.extract::<i64>().ok())
+            .any(|c| Some(c) == code)
+    }
+
+    /// Send a frame without blocking. Fails with
+    /// [`PyCanError::TxQueueFull`] straight away if the adapter can't take
+    /// the frame right now, so the caller can drop or defer it.
+    pub fn try_send(&self, id: CanId, data: &[u8]) -> Result<TxToken, PyCanError> {
+        let Some((id, data)) = self.intercept_tx(id, data) else {
+            return Ok(TxToken::next());
+        };
+
+        let slot =
+            match &self.pacer {
+                Some(pacer) => Some(pacer.try_claim().ok_or_else(|| {
+                    PyCanError::TxQueueFull("inter-frame gap hasn't passed".into())
+                })?),
+                None => None,
+            };
+
+        let token = self.tx_token(id, &data);
+        let res = Python::with_gil(|py| {
+            self.send_unpaced(py, id, &data, Some(Duration::ZERO))
+                .map_err(|e| match self.is_queue_full(py, &e) {
+                    true => PyCanError::TxQueueFull(describe_py_err(&e)),
+                    false => PyCanError::FailedToSend(describe_py_err(&e)),
+                })
+        });
+        if res.is_err() {
+            if let Some(pending) = &self.pending_tx {
+                pending.cancel(token);
+            }
+            // Nothing went out, so the next frame needn't wait
+            if let (Some(pacer), Some(slot)) = (&self.pacer, slot) {
+                pacer.refund(slot);
+            }
+        }
+
+        res.map(|_| token)
+    }
+
+    /// Send a frame, retrying transient failures (e.g. lost arbitration,
+    /// full TX buffer, timeouts) according to `policy`.
+    pub fn send_with_retry(
+        &self,
+        id: CanId,
+        data: &[u8],
+        policy: &RetryPolicy,
+    ) -> Result<TxToken, PyCanError> {
+        let Some((id, data)) = self.intercept_tx(id, data) else {
+            return Ok(TxToken::next());
+        };
+        let token = self.tx_token(id, &data);
+
+        let mut backoff = policy.initial_backoff;
+        for attempt in 1..=policy.max_attempts.max(1) {
+            let res = Python::with_gil(|py| {
+                self.send_once(py, id, &data, policy.timeout)
+                    .map_err(|e| (self.is_transient(py, &e), describe_py_err(&e)))
+            });
+
+            match res {
+                Ok(()) => return Ok(token),
+                Err((true, _)) if attempt < policy.max_attempts => {
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(policy.max_backoff);
+                }
+                Err((_, e)) => {
+                    if let Some(pending) = &self.pending_tx {
+                        pending.cancel(token);
+                    }
+                    return Err(PyCanError::FailedToSend(format!(
+                        "{e} (attempt {attempt} of {})",
+                        policy.max_attempts
+                    )));
+                }
+            }
+        }
+
+        unreachable!("the last attempt always returns")
+    }
+}
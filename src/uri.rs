@@ -0,0 +1,205 @@
+//! URI-style bus strings, e.g.:
+//!
+//! - `slcan:///dev/ttyACM0?bitrate=500000`
+//! - `socketcan://can0`
+//! - `socketcand://host:29536/can0`
+//! - `gsusb://<usb bus>:<usb address>/<channel>?bitrate=500000`
+//! - `vector://<channel>?bitrate=500000[&app_name=CANalyzer]`
+//! - `virtual://<channel>`
+
+use std::{fmt::Display, str::FromStr};
+use thiserror::Error;
+
+use crate::PyCanBusType;
+
+/// socketcand's default port.
+pub const SOCKETCAND_DEFAULT_PORT: u16 = 29536;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BusUriError {
+    #[error("Bus string `{0}` is missing a `scheme://`")]
+    MissingScheme(String),
+    #[error("Unknown bus type `{0}`")]
+    UnknownScheme(String),
+    #[error("Bus string is missing `{0}`")]
+    Missing(&'static str),
+    #[error("Invalid value for `{0}` :: `{1}`")]
+    InvalidValue(&'static str, String),
+    #[error("Unexpected query parameter `{0}`")]
+    UnexpectedParam(String),
+}
+
+type Params<'a> = Vec<(&'a str, &'a str)>;
+
+/// Split `rest?a=1&b=2` into `rest` and its query parameters.
+fn split_query(s: &str) -> Result<(&str, Params<'_>), BusUriError> {
+    let Some((path, query)) = s.split_once('?') else {
+        return Ok((s, Vec::new()));
+    };
+
+    let params = query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            p.split_once('=')
+                .ok_or_else(|| BusUriError::InvalidValue("query", p.into()))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((path, params))
+}
+
+fn parse<T: FromStr>(name: &'static str, v: &str) -> Result<T, BusUriError> {
+    v.parse()
+        .map_err(|_| BusUriError::InvalidValue(name, v.into()))
+}
+
+/// Pull `bitrate` out of the query parameters, rejecting anything else.
+fn bitrate(params: &[(&str, &str)]) -> Result<u32, BusUriError> {
+    let mut bitrate = None;
+    for (k, v) in params {
+        match *k {
+            "bitrate" => match parse("bitrate", v)? {
+                0 => return Err(BusUriError::InvalidValue("bitrate", (*v).into())),
+                b => bitrate = Some(b),
+            },
+            _ => return Err(BusUriError::UnexpectedParam((*k).into())),
+        }
+    }
+
+    bitrate.ok_or(BusUriError::Missing("bitrate"))
+}
+
+fn no_params(params: &[(&str, &str)]) -> Result<(), BusUriError> {
+    match params.first() {
+        Some((k, _)) => Err(BusUriError::UnexpectedParam((*k).into())),
+        None => Ok(()),
+    }
+}
+
+fn non_empty(name: &'static str, v: &str) -> Result<String, BusUriError> {
+    if v.is_empty() {
+        return Err(BusUriError::Missing(name));
+    }
+    Ok(v.into())
+}
+
+impl FromStr for PyCanBusType {
+    type Err = BusUriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, rest) = s
+            .split_once("://")
+            .ok_or_else(|| BusUriError::MissingScheme(s.into()))?;
+        let (path, params) = split_query(rest)?;
+
+        match scheme {
+            "slcan" => Ok(Self::Slcan {
+                serial_port: non_empty("serial port", path)?,
+                bitrate: bitrate(&params)?,
+            }),
+            "socketcan" => {
+                no_params(&params)?;
+                Ok(Self::Socketcan {
+                    channel: non_empty("channel", path)?,
+                })
+            }
+            "socketcand" => {
+                no_params(&params)?;
+                let (authority, channel) = path
+                    .split_once('/')
+                    .ok_or(BusUriError::Missing("channel"))?;
+                let (host, port) = match authority.rsplit_once(':') {
+                    Some((host, port)) => (host, parse("port", port)?),
+                    None => (authority, SOCKETCAND_DEFAULT_PORT),
+                };
+
+                Ok(Self::Socketcand {
+                    host: non_empty("host", host)?,
+                    channel: non_empty("channel", channel)?,
+                    port,
+                })
+            }
+            "gsusb" => {
+                let (device, channel) = path
+                    .split_once('/')
+                    .ok_or(BusUriError::Missing("channel"))?;
+                let (bus, address) = device
+                    .split_once(':')
+                    .ok_or(BusUriError::Missing("usb bus:address"))?;
+
+                Ok(Self::Gsusb {
+                    bitrate: bitrate(&params)?,
+                    usb_channel: non_empty("channel", channel)?,
+                    usb_bus: parse("usb bus", bus)?,
+                    usb_address: parse("usb address", address)?,
+                })
+            }
+            "virtual" => {
+                no_params(&params)?;
+                Ok(Self::Virtual {
+                    channel: non_empty("channel", path)?,
+                })
+            }
+            "vector" => {
+                let mut bitrate = None;
+                let mut app_name = None;
+                for (k, v) in params {
+                    match k {
+                        "bitrate" => match parse("bitrate", v)? {
+                            0 => return Err(BusUriError::InvalidValue("bitrate", (*v).into())),
+                            b => bitrate = Some(b),
+                        },
+                        "app_name" => app_name = Some(non_empty("app_name", v)?),
+                        _ => return Err(BusUriError::UnexpectedParam(k.into())),
+                    }
+                }
+
+                Ok(Self::Vector {
+                    app_name,
+                    channel: non_empty("channel", path)?,
+                    bitrate: bitrate.ok_or(BusUriError::Missing("bitrate"))?,
+                })
+            }
+            _ => Err(BusUriError::UnknownScheme(scheme.into())),
+        }
+    }
+}
+
+impl Display for PyCanBusType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Gsusb {
+                bitrate,
+                usb_channel,
+                usb_bus,
+                usb_address,
+            } => write!(
+                f,
+                "gsusb://{usb_bus}:{usb_address}/{usb_channel}?bitrate={bitrate}"
+            ),
+            Self::Slcan {
+                bitrate,
+                serial_port,
+            } => write!(f, "slcan://{serial_port}?bitrate={bitrate}"),
+            Self::Socketcan { channel } => write!(f, "socketcan://{channel}"),
+            Self::Socketcand {
+                host,
+                channel,
+                port,
+            } => write!(f, "socketcand://{host}:{port}/{channel}"),
+            Self::Virtual { channel } => write!(f, "virtual://{channel}"),
+            Self::Vector {
+                app_name,
+                channel,
+                bitrate,
+            } => {
+                write!(f, "vector://{channel}?bitrate={bitrate}")?;
+                if let Some(app_name) = app_name {
+                    write!(f, "&app_name={app_name}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
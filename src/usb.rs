@@ -0,0 +1,142 @@
+//! Finding USB adapters by serial number, so configs don't depend on
+//! enumeration order, tty names or USB addresses, and helping pyusb find
+//! libusb.
+
+use std::path::{Path, PathBuf};
+
+use pyo3::{exceptions::PyOSError, types::IntoPyDict, PyResult, Python, ToPyObject};
+
+use crate::{describe_py_err, PyCanBusType, PyCanError};
+
+/// USB IDs of the candleLight/gs_usb firmware.
+pub(crate) const GSUSB_VID: u16 = 0x1d50;
+pub(crate) const GSUSB_PID: u16 = 0x606f;
+
+/// `kind` with its device location replaced by that of the adapter with
+/// USB serial number `serial`. slcan ports are found with pyserial,
+/// gs_usb devices with pyusb.
+pub(crate) fn resolve_serial(
+    py: Python,
+    kind: &PyCanBusType,
+    serial: &str,
+) -> Result<PyCanBusType, PyCanError> {
+    let not_found =
+        || PyCanError::InterfaceUnavailable(format!("no adapter with serial number `{serial}`"));
+    let lookup_failed = |e: pyo3::PyErr| PyCanError::FailedToCreateInterface(describe_py_err(&e));
+
+    let mut kind = kind.clone();
+    match &mut kind {
+        PyCanBusType::Slcan { serial_port, .. } => {
+            let port = (|| -> PyResult<Option<String>> {
+                for port in py
+                    .import("serial.tools.list_ports")?
+                    .call_method0("comports")?
+                    .iter()?
+                {
+                    let port = port?;
+                    let number: Option<String> = port.getattr("serial_number")?.extract()?;
+                    if number.as_deref() == Some(serial) {
+                        return Ok(Some(port.getattr("device")?.extract()?));
+                    }
+                }
+                Ok(None)
+            })()
+            .map_err(lookup_failed)?;
+
+            *serial_port = port.ok_or_else(not_found)?;
+        }
+        PyCanBusType::Gsusb {
+            usb_bus,
+            usb_address,
+            ..
+        } => {
+            let device = (|| -> PyResult<Option<(u32, u32)>> {
+                let kwargs = [
+                    ("find_all", true.to_object(py)),
+                    ("idVendor", GSUSB_VID.to_object(py)),
+                    ("idProduct", GSUSB_PID.to_object(py)),
+                ]
+                .into_py_dict(py);
+
+                for dev in py
+                    .import("usb.core")?
+                    .call_method("find", (), Some(kwargs))?
+                    .iter()?
+                {
+                    let dev = dev?;
+                    // Reading the string descriptor can fail without
+                    // permission to open the device; skip those.
+                    let number: Option<String> = dev
+                        .getattr("serial_number")
+                        .and_then(|n| n.extract())
+                        .unwrap_or(None);
+                    if number.as_deref() == Some(serial) {
+                        return Ok(Some((
+                            dev.getattr("bus")?.extract()?,
+                            dev.getattr("address")?.extract()?,
+                        )));
+                    }
+                }
+                Ok(None)
+            })()
+            .map_err(lookup_failed)?;
+
+            (*usb_bus, *usb_address) = device.ok_or_else(not_found)?;
+        }
+        _ => {
+            return Err(PyCanError::Unsupported(format!(
+                "selecting by serial number on {kind}"
+            )))
+        }
+    }
+
+    Ok(kind)
+}
+
+/// Where Homebrew (Apple silicon, then Intel) and MacPorts install libusb.
+const LIBUSB_CANDIDATES: [&str; 3] = [
+    "/opt/homebrew/lib/libusb-1.0.dylib",
+    "/usr/local/lib/libusb-1.0.dylib",
+    "/opt/local/lib/libusb-1.0.dylib",
+];
+
+/// Load libusb into pyusb from `path`, or on macOS from the usual package
+/// manager locations, which pyusb doesn't search
+/// (https://github.com/pyusb/pyusb/issues/355). pyusb keeps the first
+/// library it loads, so every later lookup, including python-can's, uses it.
+pub(crate) fn configure_libusb(py: Python, path: Option<&Path>) -> Result<(), PyCanError> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None if cfg!(target_os = "macos") => {
+            match LIBUSB_CANDIDATES
+                .iter()
+                .map(PathBuf::from)
+                .find(|p| p.exists())
+            {
+                Some(path) => path,
+                // Leave it to pyusb; it may still find one
+                None => return Ok(()),
+            }
+        }
+        None => return Ok(()),
+    };
+
+    (|| -> PyResult<()> {
+        let find_library = py
+            .eval("lambda path: (lambda _: path)", None, None)?
+            .call1((path.to_string_lossy(),))?;
+        let kwargs = [("find_library", find_library)].into_py_dict(py);
+
+        let backend =
+            py.import("usb.backend.libusb1")?
+                .call_method("get_backend", (), Some(kwargs))?;
+        if backend.is_none() {
+            return Err(PyOSError::new_err(format!(
+                "couldn't load libusb from {}",
+                path.display()
+            )));
+        }
+        Ok(())
+    })()
+    .map_err(|e| PyCanError::FailedToCreateInterface(describe_py_err(&e)))
+}
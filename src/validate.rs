@@ -0,0 +1,79 @@
+//! Checking received payloads against their DLC.
+//!
+//! Some adapters deliver `data` shorter (or longer) than the DLC says.
+//! With a [`DlcPolicy`] set on the builder, such frames are made
+//! consistent or dropped by the first interceptor in the interface's chain,
+//! and each mismatch is reported as a [`crate::PyCanEvent::DlcMismatch`].
+
+use crate::{
+    message::{dlc_to_len, len_to_dlc},
+    PyCanMessage,
+};
+
+/// What to do with a frame whose payload length doesn't match its DLC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DlcPolicy {
+    /// Keep every byte: zero-pad short payloads up to the DLC, and raise
+    /// the DLC to cover long ones.
+    Pad,
+    /// Never invent bytes: drop payload bytes beyond the DLC, and lower
+    /// the DLC to cover short payloads.
+    Truncate,
+    /// Drop the frame.
+    Reject,
+}
+
+/// Largest valid payload length for the frame type that is at most `len`.
+fn floor_len(len: usize, is_fd: bool) -> usize {
+    if is_fd {
+        (0..=15)
+            .rev()
+            .map(dlc_to_len)
+            .find(|&l| l <= len)
+            .unwrap_or(0)
+    } else {
+        len.min(8)
+    }
+}
+
+/// Smallest valid payload length for the frame type that is at least `len`.
+fn ceil_len(len: usize, is_fd: bool) -> usize {
+    if is_fd {
+        len_to_dlc(len).map_or(64, dlc_to_len)
+    } else {
+        len.min(8)
+    }
+}
+
+impl DlcPolicy {
+    /// Check `msg` and fix it up according to the policy. Returns the
+    /// frame's original DLC code if it was inconsistent, and whether the
+    /// frame should still be delivered.
+    pub(crate) fn apply(self, msg: &mut PyCanMessage) -> (Option<u8>, bool) {
+        // Remote and error frames legitimately carry no payload
+        if msg.is_remote_frame || msg.is_error_frame {
+            return (None, true);
+        }
+        let Some(dlc) = msg.dlc else {
+            return (None, true);
+        };
+        let expected = msg.data_length();
+        let Some(data) = msg.data.as_mut() else {
+            return (None, true);
+        };
+
+        if data.len() == expected {
+            return (None, true);
+        }
+
+        let len = match self {
+            Self::Reject => return (Some(dlc), false),
+            Self::Pad => ceil_len(data.len().max(expected), msg.is_fd),
+            Self::Truncate => floor_len(data.len().min(expected), msg.is_fd),
+        };
+
+        data.resize(len, 0);
+        msg.dlc = len_to_dlc(len);
+        (Some(dlc), true)
+    }
+}
@@ -0,0 +1,76 @@
+//! Vector XL driver timestamp control.
+
+use pyo3::{PyResult, Python};
+
+use crate::{describe_py_err, PyCanBusType, PyCanError, PyCanInterface};
+
+/// How timestamps on received frames relate to the host clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VectorTimestampMode {
+    /// Hardware timestamps offset onto the host's wall clock (python-can's
+    /// default). The offset is computed at open time; see
+    /// [`VectorExt::vector_resync_clock`].
+    HostSynced,
+    /// Raw hardware timestamps, in seconds since the driver's epoch.
+    /// Useful for latency measurements between Vector channels.
+    Hardware,
+}
+
+/// Extra functionality for Vector interfaces. Every method fails with
+/// [`PyCanError::Unsupported`] on other bus types.
+pub trait VectorExt {
+    fn vector_set_timestamp_mode(&self, mode: VectorTimestampMode) -> Result<(), PyCanError>;
+    /// Recompute the offset between the driver clock and the host clock.
+    /// Only meaningful in `HostSynced` mode.
+    fn vector_resync_clock(&self) -> Result<(), PyCanError>;
+}
+
+impl PyCanInterface {
+    fn vector_check(&self) -> Result<(), PyCanError> {
+        match self.bustype {
+            PyCanBusType::Vector { .. } => Ok(()),
+            _ => Err(PyCanError::Unsupported(format!(
+                "Vector requests on {}",
+                self.bustype
+            ))),
+        }
+    }
+}
+
+impl VectorExt for PyCanInterface {
+    fn vector_set_timestamp_mode(&self, mode: VectorTimestampMode) -> Result<(), PyCanError> {
+        self.vector_check()?;
+
+        match mode {
+            VectorTimestampMode::HostSynced => self.vector_resync_clock(),
+            VectorTimestampMode::Hardware => Python::with_gil(|py| {
+                self.iface
+                    .setattr(py, "_time_offset", 0.0)
+                    .map_err(|e| PyCanError::BackendRequestFailed(describe_py_err(&e)))
+            }),
+        }
+    }
+
+    fn vector_resync_clock(&self) -> Result<(), PyCanError> {
+        self.vector_check()?;
+
+        // Mirrors what python-can's VectorBus does at construction.
+        Python::with_gil(|py| -> PyResult<()> {
+            let xldriver = py
+                .import("can.interfaces.vector.canlib")?
+                .getattr("xldriver")?;
+            let xlclass = py.import("can.interfaces.vector.xlclass")?;
+            let time = py.import("time")?;
+
+            let offset = xlclass.call_method0("XLuint64")?;
+            let port_handle = self.iface.getattr(py, "port_handle")?;
+            xldriver.call_method1("xlGetSyncTime", (port_handle, offset))?;
+
+            let now: f64 = time.call_method0("time")?.extract()?;
+            let driver_time: u64 = offset.getattr("value")?.extract()?;
+            self.iface
+                .setattr(py, "_time_offset", now - driver_time as f64 * 1e-9)
+        })
+        .map_err(|e| PyCanError::BackendRequestFailed(describe_py_err(&e)))
+    }
+}
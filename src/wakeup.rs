@@ -0,0 +1,187 @@
+//! Waking a sleeping bus and noticing when it goes to sleep, for partial
+//! networking.
+
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{CanId, ListenerId, PyCanError, PyCanEvent, PyCanInterface};
+
+/// Frames that wake a sleeping network, e.g. an NM message with the
+/// repeat message request bit set, sent `repeat` times.
+///
+/// ```ignore
+/// let wake = WakeUpPattern::new()
+///     .frame(CanId::Standard(0x510), &[0x10, 0x01, 0, 0, 0, 0, 0, 0])
+///     .repeat(3)
+///     .interval(Duration::from_millis(20));
+/// iface.send_wake_up(&wake)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct WakeUpPattern {
+    frames: Vec<(CanId, Vec<u8>)>,
+    repeat: u32,
+    interval: Duration,
+}
+
+impl Default for WakeUpPattern {
+    fn default() -> Self {
+        Self {
+            frames: Vec::new(),
+            repeat: 1,
+            interval: Duration::from_millis(10),
+        }
+    }
+}
+
+impl WakeUpPattern {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a frame to the pattern. Frames are sent in the order added.
+    pub fn frame(mut self, id: CanId, data: &[u8]) -> Self {
+        self.frames.push((id, data.to_vec()));
+        self
+    }
+
+    /// Send the whole pattern `times` times.
+    pub fn repeat(mut self, times: u32) -> Self {
+        self.repeat = times;
+        self
+    }
+
+    /// Time between consecutive frames.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+impl PyCanInterface {
+    /// Send `pattern`, blocking until it has all been sent. Stops at the
+    /// first frame that fails.
+    pub fn send_wake_up(&self, pattern: &WakeUpPattern) -> Result<(), PyCanError> {
+        let frames = (0..pattern.repeat).flat_map(|_| &pattern.frames);
+        for (i, (id, data)) in frames.enumerate() {
+            if i > 0 {
+                std::thread::sleep(pattern.interval);
+            }
+            self.send_checked(*id, data)?;
+        }
+        Ok(())
+    }
+}
+
+struct State {
+    last_rx: Instant,
+    asleep: bool,
+    stopped: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+/// Reports [`PyCanEvent::SleepDetected`] when nothing has been received
+/// for `timeout`, and [`PyCanEvent::WakeDetected`] when traffic resumes.
+/// Stops on drop.
+pub struct SleepMonitor {
+    shared: Arc<Shared>,
+    iface: Arc<PyCanInterface>,
+    listener: ListenerId,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SleepMonitor {
+    pub fn start(iface: &Arc<PyCanInterface>, timeout: Duration) -> Result<Self, PyCanError> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                last_rx: Instant::now(),
+                asleep: false,
+                stopped: false,
+            }),
+            wake: Condvar::new(),
+        });
+
+        let listener = {
+            let shared = shared.clone();
+            let events = iface.events.clone();
+            iface.register_rx_callback_all(
+                move |msg| {
+                    if !msg.is_rx {
+                        return;
+                    }
+                    let mut state = shared.state.lock().unwrap();
+                    state.last_rx = Instant::now();
+                    if state.asleep {
+                        state.asleep = false;
+                        drop(state);
+                        shared.wake.notify_all();
+                        events.emit(PyCanEvent::WakeDetected);
+                    }
+                },
+                |_| {},
+            )?
+        };
+
+        let thread = {
+            let shared = shared.clone();
+            let iface = iface.clone();
+            std::thread::spawn(move || run(&iface, timeout, &shared))
+        };
+
+        Ok(Self {
+            shared,
+            iface: iface.clone(),
+            listener,
+            thread: Some(thread),
+        })
+    }
+
+    /// Whether the bus is currently considered asleep.
+    pub fn is_asleep(&self) -> bool {
+        self.shared.state.lock().unwrap().asleep
+    }
+}
+
+impl Drop for SleepMonitor {
+    fn drop(&mut self) {
+        let _ = self.iface.remove_listener(self.listener);
+        self.shared.state.lock().unwrap().stopped = true;
+        self.shared.wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(iface: &PyCanInterface, timeout: Duration, shared: &Shared) {
+    let mut state = shared.state.lock().unwrap();
+    while !state.stopped {
+        if state.asleep {
+            // Woken by the first frame received
+            state = shared.wake.wait(state).unwrap();
+            continue;
+        }
+
+        let silent_for = state.last_rx.elapsed();
+        if silent_for < timeout {
+            state = shared
+                .wake
+                .wait_timeout(state, timeout - silent_for)
+                .unwrap()
+                .0;
+            continue;
+        }
+
+        state.asleep = true;
+        // Sinks may send or check `is_asleep`, which need the lock
+        drop(state);
+        iface.events.emit(PyCanEvent::SleepDetected { silent_for });
+        state = shared.state.lock().unwrap();
+    }
+}
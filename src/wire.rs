@@ -0,0 +1,236 @@
+//! Compact binary encoding of frame batches, for carrying frames over
+//! remote transports (MQTT, TCP, ...). Batches can optionally be
+//! compressed with zlib (via Python's standard library).
+//!
+//! Packet layout, little-endian:
+//!
+//! ```text
+//! magic "PCRS" | version u8 | flags u8 | count u16 | records...
+//! record: timestamp f64 | id u32 (bit 31 = extended) | flags u8 | dlc u8 | len u8 | data
+//! ```
+//!
+//! If the compressed flag is set, the records are zlib-compressed.
+
+use pyo3::{types::PyBytes, PyResult, Python};
+use thiserror::Error;
+
+use crate::{CanId, PyCanMessage};
+
+const MAGIC: &[u8; 4] = b"PCRS";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 8;
+
+const PACKET_COMPRESSED: u8 = 1 << 0;
+
+const FRAME_REMOTE: u8 = 1 << 0;
+const FRAME_ERROR: u8 = 1 << 1;
+const FRAME_FD: u8 = 1 << 2;
+const FRAME_BRS: u8 = 1 << 3;
+const FRAME_ESI: u8 = 1 << 4;
+const FRAME_HAS_TIMESTAMP: u8 = 1 << 5;
+const FRAME_TX: u8 = 1 << 6;
+
+const ID_EXTENDED: u32 = 1 << 31;
+
+/// Largest payload a record can carry, that of a CAN FD frame.
+const MAX_DATA_LEN: usize = 64;
+/// timestamp, id, flags, dlc and len, then the payload.
+const MAX_RECORD_LEN: usize = 8 + 4 + 3 + MAX_DATA_LEN;
+
+#[derive(Debug, Error)]
+pub enum WireError {
+    #[error("Not a pycanrs packet")]
+    BadMagic,
+    #[error("Unsupported packet version `{0}`")]
+    UnsupportedVersion(u8),
+    #[error("Packet truncated")]
+    Truncated,
+    #[error("Invalid frame in packet :: `{0}`")]
+    InvalidFrame(String),
+    #[error("Compression failed :: `{0}`")]
+    Compression(String),
+}
+
+/// Collects frames into packets of up to `max_frames` frames.
+pub struct BatchEncoder {
+    max_frames: u16,
+    compress: bool,
+    records: Vec<u8>,
+    count: u16,
+}
+
+impl BatchEncoder {
+    pub fn new(max_frames: u16, compress: bool) -> Self {
+        Self {
+            max_frames: max_frames.max(1),
+            compress,
+            records: Vec::new(),
+            count: 0,
+        }
+    }
+
+    /// Add a frame, returning a packet once the batch is full. Frames
+    /// with more than 64 bytes of data are rejected.
+    pub fn push(&mut self, msg: &PyCanMessage) -> Result<Option<Vec<u8>>, WireError> {
+        encode_record(&mut self.records, msg)?;
+        self.count += 1;
+
+        if self.count >= self.max_frames {
+            return self.flush();
+        }
+        Ok(None)
+    }
+
+    /// Packet holding whatever frames are buffered, if any.
+    pub fn flush(&mut self) -> Result<Option<Vec<u8>>, WireError> {
+        if self.count == 0 {
+            return Ok(None);
+        }
+
+        let records = std::mem::take(&mut self.records);
+        let count = std::mem::take(&mut self.count);
+
+        let (flags, body) = if self.compress {
+            (PACKET_COMPRESSED, compress(&records)?)
+        } else {
+            (0, records)
+        };
+
+        let mut packet = Vec::with_capacity(HEADER_LEN + body.len());
+        packet.extend_from_slice(MAGIC);
+        packet.push(VERSION);
+        packet.push(flags);
+        packet.extend_from_slice(&count.to_le_bytes());
+        packet.extend_from_slice(&body);
+
+        Ok(Some(packet))
+    }
+}
+
+fn encode_record(out: &mut Vec<u8>, msg: &PyCanMessage) -> Result<(), WireError> {
+    let data = msg.data.as_deref().unwrap_or_default();
+    if data.len() > MAX_DATA_LEN {
+        return Err(WireError::InvalidFrame(format!(
+            "{} bytes of data on {}, more than {MAX_DATA_LEN}",
+            data.len(),
+            msg.arbitration_id
+        )));
+    }
+
+    let mut flags = 0;
+    for (set, flag) in [
+        (msg.is_remote_frame, FRAME_REMOTE),
+        (msg.is_error_frame, FRAME_ERROR),
+        (msg.is_fd, FRAME_FD),
+        (msg.bitrate_switch, FRAME_BRS),
+        (msg.error_state_indicator, FRAME_ESI),
+        (msg.timestamp.is_some(), FRAME_HAS_TIMESTAMP),
+        (!msg.is_rx, FRAME_TX),
+    ] {
+        if set {
+            flags |= flag;
+        }
+    }
+
+    let mut id = msg.arbitration_id.raw();
+    if msg.arbitration_id.is_extended() {
+        id |= ID_EXTENDED;
+    }
+
+    out.extend_from_slice(&msg.timestamp.unwrap_or_default().to_le_bytes());
+    out.extend_from_slice(&id.to_le_bytes());
+    out.push(flags);
+    out.push(msg.dlc.unwrap_or_default());
+    out.push(data.len() as u8);
+    out.extend_from_slice(data);
+    Ok(())
+}
+
+/// Decode a packet produced by [`BatchEncoder`].
+pub fn decode_batch(packet: &[u8]) -> Result<Vec<PyCanMessage>, WireError> {
+    if packet.len() < HEADER_LEN {
+        return Err(WireError::Truncated);
+    }
+    if &packet[..4] != MAGIC {
+        return Err(WireError::BadMagic);
+    }
+    if packet[4] != VERSION {
+        return Err(WireError::UnsupportedVersion(packet[4]));
+    }
+
+    let flags = packet[5];
+    let count = u16::from_le_bytes([packet[6], packet[7]]);
+
+    let body = if flags & PACKET_COMPRESSED != 0 {
+        // A valid body can't be longer than `count` full records, so don't
+        // inflate past that
+        decompress(&packet[HEADER_LEN..], usize::from(count) * MAX_RECORD_LEN)?
+    } else {
+        packet[HEADER_LEN..].to_vec()
+    };
+
+    let mut rest = body.as_slice();
+    let mut take = |n: usize| -> Result<&[u8], WireError> {
+        if rest.len() < n {
+            return Err(WireError::Truncated);
+        }
+        let (head, tail) = rest.split_at(n);
+        rest = tail;
+        Ok(head)
+    };
+
+    (0..count)
+        .map(|_| {
+            let timestamp = f64::from_le_bytes(take(8)?.try_into().unwrap());
+            let id = u32::from_le_bytes(take(4)?.try_into().unwrap());
+            let [flags, dlc, len] = take(3)?.try_into().unwrap();
+            let data = take(usize::from(len))?;
+
+            let id = CanId::new(id & !ID_EXTENDED, id & ID_EXTENDED != 0)
+                .map_err(|e| WireError::InvalidFrame(e.to_string()))?;
+
+            let mut msg = PyCanMessage::new(id, data);
+            msg.dlc = Some(dlc);
+            msg.timestamp = (flags & FRAME_HAS_TIMESTAMP != 0).then_some(timestamp);
+            msg.is_remote_frame = flags & FRAME_REMOTE != 0;
+            msg.is_error_frame = flags & FRAME_ERROR != 0;
+            msg.is_fd = flags & FRAME_FD != 0;
+            msg.bitrate_switch = flags & FRAME_BRS != 0;
+            msg.error_state_indicator = flags & FRAME_ESI != 0;
+            msg.is_rx = flags & FRAME_TX == 0;
+            Ok(msg)
+        })
+        .collect()
+}
+
+fn compress(data: &[u8]) -> Result<Vec<u8>, WireError> {
+    Python::with_gil(|py| -> PyResult<Vec<u8>> {
+        let out = py
+            .import("zlib")?
+            .call_method1("compress", (PyBytes::new(py, data),))?;
+        Ok(out.downcast::<PyBytes>()?.as_bytes().to_vec())
+    })
+    .map_err(|e| WireError::Compression(e.to_string()))
+}
+
+/// Inflate `data`, failing if it comes to more than `max_len` bytes.
+fn decompress(data: &[u8], max_len: usize) -> Result<Vec<u8>, WireError> {
+    // zlib reads a limit of zero as no limit
+    if max_len == 0 {
+        return Ok(Vec::new());
+    }
+    let (out, overflowed) = Python::with_gil(|py| -> PyResult<(Vec<u8>, bool)> {
+        let inflater = py.import("zlib")?.call_method0("decompressobj")?;
+        let out = inflater.call_method1("decompress", (PyBytes::new(py, data), max_len))?;
+        let overflowed = inflater.getattr("unconsumed_tail")?.len()? > 0;
+        Ok((out.downcast::<PyBytes>()?.as_bytes().to_vec(), overflowed))
+    })
+    .map_err(|e| WireError::Compression(e.to_string()))?;
+
+    if overflowed {
+        return Err(WireError::Compression(format!(
+            "body inflates to more than {max_len} bytes"
+        )));
+    }
+    Ok(out)
+}
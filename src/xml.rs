@@ -0,0 +1,211 @@
+//! Just enough XML to read KCD and ARXML databases: elements, attributes
+//! and text. Namespace prefixes are dropped, and DTDs, processing
+//! instructions and comments are skipped.
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Element {
+    pub(crate) name: String,
+    pub(crate) attrs: Vec<(String, String)>,
+    pub(crate) children: Vec<Element>,
+    pub(crate) text: String,
+}
+
+impl Element {
+    pub(crate) fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub(crate) fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub(crate) fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// The first element along `path` of child names.
+    pub(crate) fn find(&self, path: &[&str]) -> Option<&Element> {
+        path.iter().try_fold(self, |el, name| el.child(name))
+    }
+
+    /// Trimmed text of the first element along `path`.
+    pub(crate) fn text_at(&self, path: &[&str]) -> Option<&str> {
+        Some(self.find(path)?.text.trim())
+    }
+
+    /// Every descendant named `name`, depth first.
+    pub(crate) fn descendants<'a>(&'a self, name: &str, out: &mut Vec<&'a Element>) {
+        for child in &self.children {
+            if child.name == name {
+                out.push(child);
+            }
+            child.descendants(name, out);
+        }
+    }
+}
+
+fn local_name(name: &str) -> String {
+    name.rsplit(':').next().unwrap_or(name).to_owned()
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..semi];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Parse a document and return its root element.
+pub(crate) fn parse(doc: &str) -> Result<Element, String> {
+    // Elements still open, innermost last
+    let mut stack: Vec<Element> = vec![Element::default()];
+    let mut rest = doc;
+
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            stack.last_mut().unwrap().text.push_str(&unescape(rest));
+            break;
+        };
+        stack
+            .last_mut()
+            .unwrap()
+            .text
+            .push_str(&unescape(&rest[..lt]));
+        rest = &rest[lt..];
+
+        let skip_to = |rest: &str, end: &str| -> Result<usize, String> {
+            rest.find(end).map(|i| i + end.len()).ok_or_else(|| {
+                let snippet = rest
+                    .char_indices()
+                    .nth(20)
+                    .map_or(rest, |(i, _)| &rest[..i]);
+                format!("unterminated `{snippet}`")
+            })
+        };
+
+        if rest.starts_with("<!--") {
+            rest = &rest[skip_to(rest, "-->")?..];
+        } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = cdata.find("]]>").ok_or("unterminated CDATA section")?;
+            stack.last_mut().unwrap().text.push_str(&cdata[..end]);
+            rest = &cdata[end + 3..];
+        } else if rest.starts_with("<?") {
+            rest = &rest[skip_to(rest, "?>")?..];
+        } else if rest.starts_with("<!") {
+            rest = &rest[skip_to(rest, ">")?..];
+        } else if let Some(close) = rest.strip_prefix("</") {
+            let end = close.find('>').ok_or("unterminated closing tag")?;
+            let name = local_name(close[..end].trim());
+            let el = stack
+                .pop()
+                .filter(|_| !stack.is_empty())
+                .ok_or("unbalanced tags")?;
+            if el.name != name {
+                return Err(format!("`</{name}>` closes `<{}>`", el.name));
+            }
+            stack.last_mut().unwrap().children.push(el);
+            rest = &close[end + 1..];
+        } else {
+            let (el, self_closing, len) = parse_tag(rest)?;
+            if self_closing {
+                stack.last_mut().unwrap().children.push(el);
+            } else {
+                stack.push(el);
+            }
+            rest = &rest[len..];
+        }
+    }
+
+    if stack.len() != 1 {
+        return Err(format!(
+            "`<{}>` is never closed",
+            stack.last().unwrap().name
+        ));
+    }
+    stack
+        .pop()
+        .unwrap()
+        .children
+        .into_iter()
+        .next()
+        .ok_or_else(|| "no root element".into())
+}
+
+/// Parse an opening tag at the start of `s`. Returns the element, whether
+/// it closes itself, and the tag's length.
+fn parse_tag(s: &str) -> Result<(Element, bool, usize), String> {
+    let mut i = 1;
+    let bytes = s.as_bytes();
+    let name_end = s[i..]
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .ok_or("unterminated tag")?
+        + i;
+    let mut el = Element {
+        name: local_name(&s[i..name_end]),
+        ..Element::default()
+    };
+    i = name_end;
+
+    loop {
+        while bytes.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        match bytes.get(i) {
+            Some(b'>') => return Ok((el, false, i + 1)),
+            Some(b'/') if bytes.get(i + 1) == Some(&b'>') => return Ok((el, true, i + 2)),
+            Some(_) => {
+                let eq = s[i..].find('=').ok_or("attribute without a value")? + i;
+                let name = s[i..eq].trim();
+                let quote = s[eq + 1..]
+                    .find(['"', '\''])
+                    .ok_or("unquoted attribute value")?
+                    + eq
+                    + 1;
+                let q = bytes[quote] as char;
+                let end = s[quote + 1..]
+                    .find(q)
+                    .ok_or("unterminated attribute value")?
+                    + quote
+                    + 1;
+                el.attrs
+                    .push((local_name(name), unescape(&s[quote + 1..end])));
+                i = end + 1;
+            }
+            None => return Err("unterminated tag".into()),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Just enough YAML to read responder scripts: block mappings and
+//! sequences by indentation, plain and quoted scalars, flow sequences of
+//! scalars and comments. Anchors, multi-line strings, flow mappings and
+//! multiple documents aren't supported.
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Scalar(String),
+    List(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Scalar(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The items of a sequence, or a lone value as a sequence of one.
+    pub(crate) fn items(&self) -> &[Value] {
+        match self {
+            Self::List(items) => items,
+            other => std::slice::from_ref(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    text: &'a str,
+}
+
+/// Strip a comment, i.e. `#` at the start or after whitespace, outside
+/// quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '#') if prev.is_whitespace() => return &line[..i],
+            _ => {}
+        }
+        prev = c;
+    }
+    line
+}
+
+/// Parse a document. Errors carry the line they were found on.
+pub(crate) fn parse(doc: &str) -> Result<Value, (usize, String)> {
+    let lines: Vec<_> = doc
+        .lines()
+        .enumerate()
+        .filter_map(|(i, raw)| {
+            let text = strip_comment(raw).trim_end();
+            let trimmed = text.trim_start();
+            (!trimmed.is_empty() && trimmed != "---").then(|| Line {
+                number: i + 1,
+                indent: text.len() - trimmed.len(),
+                text: trimmed,
+            })
+        })
+        .collect();
+
+    let mut parser = Parser { lines, pos: 0 };
+    let Some(first) = parser.lines.first() else {
+        return Ok(Value::Map(Vec::new()));
+    };
+    let value = parser.block(first.indent)?;
+    match parser.lines.get(parser.pos) {
+        None => Ok(value),
+        Some(line) => Err((line.number, "unexpected indentation".into())),
+    }
+}
+
+struct Parser<'a> {
+    lines: Vec<Line<'a>>,
+    pos: usize,
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Split `key: value`, or `key:` with the value on following lines.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    if let Some(key) = text.strip_suffix(':') {
+        return Some((key.trim(), ""));
+    }
+    let (key, value) = text.split_once(": ")?;
+    Some((key.trim(), value.trim()))
+}
+
+impl Parser<'_> {
+    fn block(&mut self, indent: usize) -> Result<Value, (usize, String)> {
+        if is_item(self.lines[self.pos].text) {
+            self.sequence(indent)
+        } else {
+            self.mapping(indent)
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Value, (usize, String)> {
+        let mut items = Vec::new();
+        while let Some(&Line {
+            number,
+            indent: at,
+            text,
+        }) = self.lines.get(self.pos)
+        {
+            if at != indent || !is_item(text) {
+                break;
+            }
+            let rest = text[1..].trim_start();
+            if rest.is_empty() {
+                self.pos += 1;
+                items.push(self.nested(indent)?);
+            } else if split_key(rest).is_some() && !rest.starts_with(['"', '\'', '[']) {
+                // A mapping starting on the item's line: its keys line up
+                // with the first one
+                let column = indent + (text.len() - rest.len());
+                self.lines[self.pos] = Line {
+                    number,
+                    indent: column,
+                    text: rest,
+                };
+                items.push(self.mapping(column)?);
+            } else {
+                self.pos += 1;
+                items.push(scalar(rest).map_err(|e| (number, e))?);
+            }
+        }
+        Ok(Value::List(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Value, (usize, String)> {
+        let mut entries: Vec<(String, Value)> = Vec::new();
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.indent < indent || (line.indent == indent && is_item(line.text)) {
+                break;
+            }
+            let number = line.number;
+            if line.indent > indent {
+                return Err((number, "unexpected indentation".into()));
+            }
+            let (key, value) = split_key(line.text).ok_or_else(|| {
+                (
+                    number,
+                    format!("expected `key: value`, got `{}`", line.text),
+                )
+            })?;
+            let key = unquote(key).map_err(|e| (number, e))?;
+            if entries.iter().any(|(k, _)| *k == key) {
+                return Err((number, format!("duplicate key `{key}`")));
+            }
+            self.pos += 1;
+
+            let value = if value.is_empty() {
+                // A sequence may sit at the key's own indentation
+                match self.lines.get(self.pos) {
+                    Some(next) if next.indent == indent && is_item(next.text) => {
+                        self.sequence(indent)?
+                    }
+                    _ => self.nested(indent)?,
+                }
+            } else {
+                scalar(value).map_err(|e| (number, e))?
+            };
+            entries.push((key, value));
+        }
+        Ok(Value::Map(entries))
+    }
+
+    /// A block indented deeper than `indent`, or an empty scalar if there's
+    /// none.
+    fn nested(&mut self, indent: usize) -> Result<Value, (usize, String)> {
+        match self.lines.get(self.pos) {
+            Some(next) if next.indent > indent => self.block(next.indent),
+            _ => Ok(Value::Scalar(String::new())),
+        }
+    }
+}
+
+fn scalar(text: &str) -> Result<Value, String> {
+    let Some(inner) = text.strip_prefix('[') else {
+        return unquote(text).map(Value::Scalar);
+    };
+    let inner = inner
+        .strip_suffix(']')
+        .ok_or_else(|| format!("unclosed `[` in `{text}`"))?;
+    if inner.trim().is_empty() {
+        return Ok(Value::List(Vec::new()));
+    }
+    inner
+        .split(',')
+        .map(|item| unquote(item.trim()).map(Value::Scalar))
+        .collect::<Result<_, _>>()
+        .map(Value::List)
+}
+
+fn unquote(text: &str) -> Result<String, String> {
+    let unclosed = || format!("unclosed quote in `{text}`");
+    if let Some(inner) = text.strip_prefix('"') {
+        let inner = inner.strip_suffix('"').ok_or_else(unclosed)?;
+        let mut out = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(c @ ('"' | '\\')) => out.push(c),
+                other => return Err(format!("unknown escape `\\{}`", other.unwrap_or(' '))),
+            }
+        }
+        Ok(out)
+    } else if let Some(inner) = text.strip_prefix('\'') {
+        let inner = inner.strip_suffix('\'').ok_or_else(unclosed)?;
+        Ok(inner.replace("''", "'"))
+    } else {
+        Ok(text.into())
+    }
+}
@@ -0,0 +1,102 @@
+//! Message extraction against the installed python-can.
+//!
+//! `scripts/python-can-compat.sh` runs these against each pinned
+//! python-can release in its own virtualenv.
+
+use pycanrs::{CanId, PyCanMessage};
+use pyo3::{
+    types::{IntoPyDict, PyDict},
+    PyAny, PyResult, Python, ToPyObject,
+};
+
+fn message<'py>(py: Python<'py>, kwargs: &'py PyDict) -> PyResult<&'py PyAny> {
+    py.import("can")?.call_method("Message", (), Some(kwargs))
+}
+
+/// Which python-can is being tested, for the test output.
+fn version(py: Python) -> String {
+    py.import("can")
+        .and_then(|can| can.getattr("__version__")?.extract())
+        .unwrap_or_else(|_| "unknown".into())
+}
+
+#[test]
+fn standard_frame() {
+    Python::with_gil(|py| {
+        let kwargs = [
+            ("arbitration_id", 0x123.to_object(py)),
+            ("data", vec![1u8, 2, 3].to_object(py)),
+            ("is_extended_id", false.to_object(py)),
+            ("timestamp", 1.5.to_object(py)),
+        ]
+        .into_py_dict(py);
+        let msg: PyCanMessage = message(py, kwargs).unwrap().extract().unwrap();
+
+        let version = version(py);
+        assert_eq!(msg.arbitration_id, CanId::Standard(0x123), "{version}");
+        assert_eq!(msg.data.as_deref(), Some(&[1, 2, 3][..]), "{version}");
+        assert_eq!(msg.dlc, Some(3), "{version}");
+        assert_eq!(msg.timestamp, Some(1.5), "{version}");
+        assert!(!msg.is_fd && !msg.is_remote_frame && !msg.is_error_frame);
+    })
+}
+
+#[test]
+fn extended_frame() {
+    Python::with_gil(|py| {
+        let kwargs = [
+            ("arbitration_id", 0x18DA_F110.to_object(py)),
+            ("data", vec![0u8; 8].to_object(py)),
+            ("is_extended_id", true.to_object(py)),
+        ]
+        .into_py_dict(py);
+        let msg: PyCanMessage = message(py, kwargs).unwrap().extract().unwrap();
+
+        assert_eq!(
+            msg.arbitration_id,
+            CanId::Extended(0x18DA_F110),
+            "{}",
+            version(py)
+        );
+    })
+}
+
+#[test]
+fn fd_frame() {
+    Python::with_gil(|py| {
+        let kwargs = [
+            ("arbitration_id", 0x100.to_object(py)),
+            ("data", vec![0u8; 12].to_object(py)),
+            ("is_fd", true.to_object(py)),
+            ("bitrate_switch", true.to_object(py)),
+        ]
+        .into_py_dict(py);
+        let msg: PyCanMessage = message(py, kwargs).unwrap().extract().unwrap();
+
+        let version = version(py);
+        assert!(msg.is_fd && msg.bitrate_switch, "{version}");
+        // 12 bytes is DLC 9
+        assert_eq!(msg.dlc, Some(9), "{version}");
+        assert_eq!(msg.data_length(), 12, "{version}");
+    })
+}
+
+/// Releases before 4.0 don't have `is_rx`, and 2.x spells the extended
+/// flag `extended_id`.
+#[test]
+fn missing_and_renamed_attributes() {
+    Python::with_gil(|py| {
+        let msg = py
+            .import("types")
+            .and_then(|types| types.getattr("SimpleNamespace")?.call0())
+            .unwrap();
+        msg.setattr("arbitration_id", 0x7FF_FFFF).unwrap();
+        msg.setattr("extended_id", true).unwrap();
+        msg.setattr("data", vec![0xAAu8]).unwrap();
+
+        let msg: PyCanMessage = msg.extract().unwrap();
+        assert_eq!(msg.arbitration_id, CanId::Extended(0x7FF_FFFF));
+        assert_eq!(msg.dlc, None);
+        assert!(msg.is_rx && !msg.is_fd);
+    })
+}